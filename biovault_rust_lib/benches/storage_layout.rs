@@ -0,0 +1,295 @@
+//! Compares the compact `variants` layout against the original text layout.
+//!
+//! Run with `cargo bench --bench storage_layout`. Set `BIOVAULT_BENCH_VARIANTS`
+//! to change the size of the synthetic genome (default: 600,000 calls, roughly
+//! a 23andMe v5 file).
+
+use rusqlite::{Connection, params};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const DEFAULT_VARIANTS: usize = 600_000;
+const LOOKUPS: usize = 10_000;
+const CHROMOSOMES: &[&str] = &[
+    "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16", "17",
+    "18", "19", "20", "21", "22", "X", "Y", "MT",
+];
+const GENOTYPES: &[&str] = &["AA", "AG", "GG", "CC", "CT", "TT", "AC", "--", "A", "G"];
+
+/// Small deterministic generator so runs are comparable
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+struct Call {
+    rsid: String,
+    chromosome: &'static str,
+    position: u64,
+    genotype: &'static str,
+}
+
+fn synthetic_calls(count: usize) -> Vec<Call> {
+    let mut rng = Lcg(42);
+    let per_chromosome = count / CHROMOSOMES.len() + 1;
+    let mut calls = Vec::with_capacity(count);
+
+    for chromosome in CHROMOSOMES {
+        let mut position = 10_000;
+        for _ in 0..per_chromosome {
+            if calls.len() == count {
+                break;
+            }
+            position += 1 + rng.next() % 5_000;
+            // About 3% of 23andMe probes use internal ids instead of rsIDs
+            let rsid = if rng.next().is_multiple_of(33) {
+                format!("i{}", 3_000_000 + calls.len())
+            } else {
+                format!("rs{}", 1 + rng.next() % 800_000_000)
+            };
+            calls.push(Call {
+                rsid,
+                chromosome,
+                position,
+                genotype: GENOTYPES[(rng.next() % GENOTYPES.len() as u64) as usize],
+            });
+        }
+    }
+
+    calls
+}
+
+fn write_23andme_file(path: &Path, calls: &[Call]) {
+    let mut out = BufWriter::new(fs::File::create(path).unwrap());
+    writeln!(out, "# This data file generated by 23andMe").unwrap();
+    writeln!(out, "# rsid\tchromosome\tposition\tgenotype").unwrap();
+    for call in calls {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            call.rsid, call.chromosome, call.position, call.genotype
+        )
+        .unwrap();
+    }
+}
+
+/// Build a database with the original one-text-row-per-call layout
+fn write_legacy_database(path: &Path, calls: &[Call]) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE genome_metadata (
+            id INTEGER PRIMARY KEY,
+            file_name TEXT NOT NULL,
+            source_format TEXT NOT NULL,
+            total_variants INTEGER NOT NULL,
+            rsid_count INTEGER NOT NULL,
+            assembly TEXT,
+            upload_date TEXT NOT NULL,
+            db_name TEXT NOT NULL
+        );
+        CREATE TABLE variants (
+            id INTEGER PRIMARY KEY,
+            file_id INTEGER NOT NULL,
+            rsid TEXT,
+            chromosome TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            genotype TEXT NOT NULL,
+            source_format TEXT NOT NULL,
+            FOREIGN KEY(file_id) REFERENCES genome_metadata(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_variants_rsid ON variants (rsid);
+        CREATE INDEX idx_variants_chr_pos ON variants (chromosome, position);
+        INSERT INTO genome_metadata
+            (file_name, source_format, total_variants, rsid_count, assembly, upload_date, db_name)
+            VALUES ('bench', '23andMe', 0, 0, 'GRCh37', '', 'bench');",
+    )
+    .unwrap();
+
+    let tx = conn.unchecked_transaction().unwrap();
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO variants (file_id, rsid, chromosome, position, genotype, source_format)
+                 VALUES (1, ?1, ?2, ?3, ?4, '23andMe')",
+            )
+            .unwrap();
+        // Same rows the 23andMe parser keeps: no-calls are dropped
+        for call in calls.iter().filter(|call| call.genotype != "--") {
+            let rsid = if call.rsid.starts_with("rs") {
+                call.rsid.as_str()
+            } else {
+                ""
+            };
+            stmt.execute(params![
+                rsid,
+                call.chromosome,
+                call.position as i64,
+                call.genotype
+            ])
+            .unwrap();
+        }
+    }
+    tx.commit().unwrap();
+    conn.execute("ANALYZE", []).unwrap();
+}
+
+fn time<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let start = Instant::now();
+    let rows = f();
+    (start.elapsed(), rows)
+}
+
+struct Queries {
+    by_rsid: &'static str,
+    by_range: &'static str,
+    all_rsids: &'static str,
+}
+
+const LEGACY_QUERIES: Queries = Queries {
+    by_rsid: "SELECT genotype FROM variants WHERE rsid = ?1",
+    by_range: "SELECT rsid, genotype FROM variants
+               WHERE chromosome = '7' AND position BETWEEN ?1 AND ?2",
+    all_rsids: "SELECT DISTINCT rsid, genotype FROM variants WHERE rsid LIKE 'rs%'",
+};
+
+const COMPACT_QUERIES: Queries = Queries {
+    by_rsid: "SELECT g.genotype FROM variants v
+              JOIN genotype_codes g ON g.code = v.genotype
+              WHERE v.rsid = ?1",
+    by_range: "SELECT v.rsid, g.genotype FROM variants v
+               JOIN genotype_codes g ON g.code = v.genotype
+               WHERE v.chromosome = 7 AND v.position BETWEEN ?1 AND ?2",
+    all_rsids: "SELECT DISTINCT v.rsid, g.genotype FROM variants v
+                JOIN genotype_codes g ON g.code = v.genotype
+                WHERE v.rsid IS NOT NULL",
+};
+
+fn run_queries(label: &str, path: &Path, queries: &Queries, rsids: &[String], numeric: bool) {
+    let conn = Connection::open(path).unwrap();
+    let size = fs::metadata(path).unwrap().len();
+
+    let (lookup_time, found) = time(|| {
+        let mut stmt = conn.prepare(queries.by_rsid).unwrap();
+        let mut found = 0;
+        for rsid in rsids {
+            let mut rows = if numeric {
+                let number: i64 = rsid[2..].parse().unwrap();
+                stmt.query(params![number]).unwrap()
+            } else {
+                stmt.query(params![rsid]).unwrap()
+            };
+            while rows.next().unwrap().is_some() {
+                found += 1;
+            }
+        }
+        found
+    });
+
+    let (range_time, range_rows) = time(|| {
+        let mut stmt = conn.prepare(queries.by_range).unwrap();
+        let mut total = 0;
+        for start in (0..50_000_000i64).step_by(5_000_000) {
+            let mut rows = stmt.query(params![start, start + 1_000_000]).unwrap();
+            while rows.next().unwrap().is_some() {
+                total += 1;
+            }
+        }
+        total
+    });
+
+    let (scan_time, scan_rows) = time(|| {
+        let mut stmt = conn.prepare(queries.all_rsids).unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        let mut total = 0;
+        while rows.next().unwrap().is_some() {
+            total += 1;
+        }
+        total
+    });
+
+    println!("{}", label);
+    println!("  file size        {:>10.1} MiB", size as f64 / 1_048_576.0);
+    println!(
+        "  rsID lookups     {:>10.1} ms  ({} lookups, {} rows)",
+        lookup_time.as_secs_f64() * 1000.0,
+        rsids.len(),
+        found
+    );
+    println!(
+        "  range queries    {:>10.1} ms  ({} rows)",
+        range_time.as_secs_f64() * 1000.0,
+        range_rows
+    );
+    println!(
+        "  rsID scan        {:>10.1} ms  ({} rows)",
+        scan_time.as_secs_f64() * 1000.0,
+        scan_rows
+    );
+}
+
+fn main() {
+    let count = std::env::var("BIOVAULT_BENCH_VARIANTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_VARIANTS);
+
+    let dir: PathBuf = std::env::temp_dir().join(format!("biovault_bench_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let calls = synthetic_calls(count);
+    let mut rng = Lcg(7);
+    let rsids: Vec<String> = (0..LOOKUPS)
+        .map(|_| &calls[(rng.next() % calls.len() as u64) as usize].rsid)
+        .filter(|rsid| rsid.starts_with("rs"))
+        .cloned()
+        .collect();
+
+    let input = dir.join("genome.txt");
+    write_23andme_file(&input, &calls);
+
+    let legacy = dir.join("legacy.sqlite");
+    write_legacy_database(&legacy, &calls);
+
+    let compact = PathBuf::from(
+        biovault_rust_lib::process_23andme(input.to_str().unwrap(), "bench", dir.to_str().unwrap())
+            .unwrap(),
+    );
+
+    let migrated = dir.join("migrated.sqlite");
+    fs::copy(&legacy, &migrated).unwrap();
+    drop(biovault_rust_lib::open_genome_database(migrated.to_str().unwrap(), None).unwrap());
+
+    println!("{} synthetic calls\n", calls.len());
+    run_queries(
+        "text layout (schema v2)",
+        &legacy,
+        &LEGACY_QUERIES,
+        &rsids,
+        false,
+    );
+    run_queries(
+        "compact layout (new import)",
+        &compact,
+        &COMPACT_QUERIES,
+        &rsids,
+        true,
+    );
+    run_queries(
+        "compact layout (migrated v2)",
+        &migrated,
+        &COMPACT_QUERIES,
+        &rsids,
+        true,
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -3,26 +3,71 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/**
+ * Schema version written by this library into `PRAGMA user_version`
+ */
+#define SCHEMA_VERSION 4
+
+/**
+ * Progress callback for long-running C calls. `phase` is a static,
+ * NUL-terminated phase name such as `parsing` or `clinvar_lookup`, and
+ * `total` is an estimate that is 0 when unknown. Returning non-zero cancels
+ * the operation, which then fails with `CANCELLED`. May be null.
+ */
+typedef int32_t (*BiovaultProgressCallback)(void *context,
+                                            const char *phase,
+                                            uint64_t processed,
+                                            uint64_t total);
+
+/**
+ * Log callback for C hosts, e.g. forwarding to `os_log`. `level` is 1
+ * (error) to 5 (trace); `target` and `message` are NUL-terminated and only
+ * valid during the call. May be called from any thread.
+ */
+typedef void (*BiovaultLogCallback)(void *context,
+                                    int32_t level,
+                                    const char *target,
+                                    const char *message);
+
 /**
  * Process a 23andMe file and create an SQLite database.
  *
- * Returns a newly-allocated C string containing the full path to the
- * created database file on success, or a null pointer on error.
+ * Returns a result envelope (see `ffi_result`) whose `data` is the full path
+ * to the created database file.
  *
  * # Safety
- * - `input_path`, `custom_name`, and `output_dir` must be valid pointers to
- *   NUL-terminated UTF-8 strings and remain valid for the duration of the call.
+ * - `input_path`, `custom_name`, and `output_dir` must be null or valid
+ *   pointers to NUL-terminated strings that remain valid for the duration of
+ *   the call. Null or non-UTF-8 arguments produce an `INVALID_ARGUMENT` error.
  * - The returned pointer must be freed by calling `free_string` exactly once.
- * - Passing null or invalid pointers, or freeing the returned pointer by any
- *   other means is undefined behavior.
+ * - Freeing the returned pointer by any other means is undefined behavior.
  */
 char *process_23andme_file(const char *input_path, const char *custom_name, const char *output_dir);
 
 /**
- * Free memory allocated by `process_23andme_file`.
+ * Process a 23andMe file into an SQLite database encrypted with `key`.
+ *
+ * The key should come from the platform keystore (iOS Keychain / Android
+ * Keystore) and must be supplied again to open the database.
+ *
+ * Returns a result envelope whose `data` is the full path to the created
+ * database file.
+ *
+ * # Safety
+ * - `input_path`, `custom_name`, `output_dir` and `key` must be null or valid
+ *   pointers to NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *process_23andme_file_encrypted(const char *input_path,
+                                     const char *custom_name,
+                                     const char *output_dir,
+                                     const char *key);
+
+/**
+ * Free a string returned by any of the functions above.
  *
  * # Safety
- * - `ptr` must be a pointer previously returned by `process_23andme_file`.
+ * - `ptr` must be null or a pointer previously returned by this library.
  * - It must not have been freed already.
  * - Passing any other pointer, or double-freeing, is undefined behavior.
  */
@@ -32,5 +77,282 @@ int32_t rust_add(int32_t a, int32_t b);
 
 /**
  * Analyze user genome against ClinVar database
+ *
+ * Returns a result envelope whose `data` is the analysis result.
+ *
+ * # Safety
+ * - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+ *   NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
  */
 char *analyze_clinvar(const char *user_db_path, const char *clinvar_db_path);
+
+/**
+ * Re-run ClinVar analysis and diff it against the last run stored in the
+ * user database. The new run is stored for the next comparison.
+ *
+ * Returns a result envelope whose `data` is the diff, or `null` if there
+ * was no previous run.
+ *
+ * # Safety
+ * - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+ *   NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *reanalyze_clinvar(const char *user_db_path, const char *clinvar_db_path);
+
+/**
+ * Diff the ClinVar analysis of a genome between two ClinVar database releases.
+ *
+ * Returns a result envelope whose `data` is the diff.
+ *
+ * # Safety
+ * - `user_db_path`, `old_clinvar_db_path` and `new_clinvar_db_path` must be
+ *   null or valid pointers to NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *diff_clinvar_releases(const char *user_db_path,
+                            const char *old_clinvar_db_path,
+                            const char *new_clinvar_db_path);
+
+/**
+ * Run ClinVar analysis and store the result and its provenance in the user
+ * database.
+ *
+ * Returns a result envelope whose `data` is the stored run.
+ *
+ * # Safety
+ * - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+ *   NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *analyze_and_store_clinvar(const char *user_db_path, const char *clinvar_db_path);
+
+/**
+ * List the analysis runs stored in a user database, newest first.
+ *
+ * Returns a result envelope whose `data` is an array of runs.
+ *
+ * # Safety
+ * - `user_db_path` must be null or a valid pointer to a NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *list_analysis_runs(const char *user_db_path);
+
+/**
+ * Load a stored analysis run with its findings.
+ *
+ * Returns a result envelope whose `data` is the run, or `null` if the run
+ * does not exist.
+ *
+ * # Safety
+ * - `user_db_path` must be null or a valid pointer to a NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *load_analysis_run(const char *user_db_path, int64_t run_id);
+
+/**
+ * Delete a stored analysis run and its findings.
+ *
+ * Returns a result envelope whose `data` is `true` if the run was deleted
+ * and `false` if it did not exist.
+ *
+ * # Safety
+ * - `user_db_path` must be null or a valid pointer to a NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *delete_analysis_run(const char *user_db_path, int64_t run_id);
+
+/**
+ * Open a user genome database, validate it and migrate its schema to the
+ * current version.
+ *
+ * Returns a result envelope whose `data` is the schema version. Files that
+ * are not genome databases fail with `NOT_A_GENOME_DATABASE`.
+ *
+ * # Safety
+ * - `db_path` must be null or a valid pointer to a NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *migrate_genome_database(const char *db_path);
+
+/**
+ * Analyze an encrypted user genome against the (unencrypted) ClinVar database
+ *
+ * Returns a result envelope whose `data` is the analysis result. A wrong key
+ * fails with `WRONG_KEY`.
+ *
+ * # Safety
+ * - `user_db_path`, `user_db_key` and `clinvar_db_path` must be null or valid
+ *   pointers to NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *analyze_clinvar_encrypted(const char *user_db_path,
+                                const char *user_db_key,
+                                const char *clinvar_db_path);
+
+/**
+ * Change the encryption key of a genome database in place.
+ *
+ * A null `old_key` means the database is currently plaintext; a null
+ * `new_key` removes encryption. Returns a result envelope whose `data` is
+ * `null` on success.
+ *
+ * # Safety
+ * - `db_path` must be null or a valid pointer to a NUL-terminated string.
+ * - `old_key` and `new_key` must each be null or a valid pointer to a
+ *   NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *rekey_genome_database(const char *db_path, const char *old_key, const char *new_key);
+
+/**
+ * Write a decrypted copy of an encrypted genome database to `output_path`,
+ * e.g. for export to other tools. Returns a result envelope whose `data` is
+ * `null` on success.
+ *
+ * # Safety
+ * - `db_path`, `key` and `output_path` must be null or valid pointers to
+ *   NUL-terminated strings.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *export_decrypted_genome_database(const char *db_path,
+                                       const char *key,
+                                       const char *output_path);
+
+/**
+ * Import a genome file into a genome database, creating the database if it
+ * does not exist. Several genomes (e.g. a household) can share one database.
+ *
+ * The input file is content-hashed; if it was imported before, a non-zero
+ * `replace` re-imports it in place, otherwise it is skipped. `person` and
+ * `key` may be null (no label / plaintext database).
+ *
+ * `progress` may be null; otherwise it is called with `context` while the
+ * file is parsed and inserted, and can cancel the import (see
+ * `BiovaultProgressCallback`). A cancelled import leaves the database as it was.
+ *
+ * Returns a result envelope whose `data` describes the outcome.
+ *
+ * # Safety
+ * - `input_path`, `db_path` and `custom_name` must be null or valid pointers
+ *   to NUL-terminated strings.
+ * - `person` and `key` must each be null or a valid pointer to a
+ *   NUL-terminated string.
+ * - `progress` is called on the calling thread and must be safe to call with
+ *   `context` until this function returns.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *import_genome_file(const char *input_path,
+                         const char *db_path,
+                         const char *custom_name,
+                         const char *person,
+                         const char *key,
+                         int32_t replace,
+                         BiovaultProgressCallback progress,
+                         void *context);
+
+/**
+ * List the genomes stored in a genome database. `key` may be null for
+ * plaintext databases.
+ *
+ * Returns a result envelope whose `data` is an array of genomes.
+ *
+ * # Safety
+ * - `db_path` must be null or a valid pointer to a NUL-terminated string.
+ * - `key` must be null or a valid pointer to a NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *list_genomes(const char *db_path, const char *key);
+
+/**
+ * Analyze one genome of a shared genome database against ClinVar.
+ * `user_db_key` may be null for plaintext databases. `progress` may be null;
+ * otherwise it is called with `context` per ClinVar lookup chunk and can
+ * cancel the analysis (see `BiovaultProgressCallback`).
+ *
+ * Returns a result envelope whose `data` is the analysis result.
+ *
+ * # Safety
+ * - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+ *   NUL-terminated strings.
+ * - `user_db_key` must be null or a valid pointer to a NUL-terminated string.
+ * - `progress` is called on the calling thread and must be safe to call with
+ *   `context` until this function returns.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *analyze_clinvar_genome(const char *user_db_path,
+                             const char *user_db_key,
+                             int64_t genome_id,
+                             const char *clinvar_db_path,
+                             BiovaultProgressCallback progress,
+                             void *context);
+
+/**
+ * Export a genome to a standard file format.
+ *
+ * `format` is one of `vcf`, `23andme` or `plink`. For PLINK, `output_path`
+ * is the prefix of the `.bed`/`.bim`/`.fam` files. `genome_id` of 0 selects
+ * the only genome in the database. `reference_db_path` points to a local
+ * reference allele database; it is required for VCF and optional for PLINK.
+ * `key` and `reference_db_path` may be null.
+ *
+ * Returns a result envelope whose `data` is the export summary.
+ *
+ * # Safety
+ * - `db_path`, `format` and `output_path` must be null or valid pointers to
+ *   NUL-terminated strings.
+ * - `key` and `reference_db_path` must each be null or a valid pointer to a
+ *   NUL-terminated string.
+ * - The returned pointer must be freed by calling `free_string` exactly once.
+ */
+char *export_genome(const char *db_path,
+                    const char *key,
+                    int64_t genome_id,
+                    const char *format,
+                    const char *output_path,
+                    const char *reference_db_path);
+
+/**
+ * Export a stored analysis run as a GA4GH Phenopacket v2 document
+ * (`format` "phenopacket") or FHIR Genomics Reporting bundle ("fhir").
+ * Returns a result envelope whose `data` is the JSON document.
+ *
+ * # Safety
+ * `user_db_path` and `format` must be null or valid, NUL-terminated C
+ * strings; `key` must be null or a valid C string. The returned string must
+ * be released with `free_string`.
+ */
+char *export_clinical_report(const char *user_db_path,
+                             const char *key,
+                             int64_t run_id,
+                             const char *format);
+
+/**
+ * Write an offline findings report (`format` "html" or "pdf") for a genome
+ * from its stored ClinVar analysis. `genome_id` 0 selects the only genome and
+ * `run_id` 0 the genome's most recent run. Returns a result envelope whose
+ * `data` is `null` on success.
+ *
+ * # Safety
+ * `user_db_path`, `format` and `output_path` must be null or valid,
+ * NUL-terminated C strings; `key` must be null or a valid C string. The
+ * returned string must be released with `free_string`.
+ */
+char *generate_report(const char *user_db_path,
+                      const char *key,
+                      int64_t genome_id,
+                      int64_t run_id,
+                      const char *format,
+                      const char *output_path);
+
+/**
+ * Register a callback receiving log events up to `max_level` (1 = error,
+ * 5 = trace; 3 = info is a good default), replacing any previous one. A null
+ * callback turns logging off. Log events never contain rsIDs or genotypes.
+ *
+ * # Safety
+ * `context` is passed back to `callback` as is, from any thread, until the
+ * callback is replaced; it must stay valid for that long.
+ */
+void set_log_callback(BiovaultLogCallback callback, void *context, int32_t max_level);
@@ -0,0 +1,471 @@
+//! Tamper-evident log of genome data access.
+//!
+//! Every open of a genome database, variant query, ClinVar analysis and export
+//! appends a row to `access_log`. That includes the opens behind read-only
+//! calls such as `list_genomes` and `query_variants`, so those need a writable
+//! database file and fail on read-only media. Reading the log or the consent
+//! audit adds no row, so checking the trail leaves it as it was.
+//!
+//! Each row stores the SHA-256 of its own fields and of the previous row's
+//! hash, so editing or deleting any row breaks the chain from that point on.
+//! `access_log_head` holds the number of rows and the latest hash, which
+//! catches rows cut from the end. Triggers reject UPDATE and DELETE on the log
+//! and any change to the head other than moving it to the newest row.
+//!
+//! Anyone holding the database key can still rewrite the whole chain
+//! consistently. Callers that need protection against that can keep the
+//! head hash somewhere else, such as the platform keychain, and pass it to
+//! `verify_access_log` as an anchor.
+
+use crate::consent::Purpose;
+use crate::database::open_genome_database_unlogged;
+use crate::error::BiovaultError;
+use rusqlite::{Connection, OptionalExtension, Transaction, TransactionBehavior, params};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::RwLock;
+use tracing::warn;
+
+/// `prev_hash` of the first row
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Caller recorded when the app has not named itself
+const DEFAULT_CALLER: &str = "library";
+
+static CALLER: RwLock<Option<String>> = RwLock::new(None);
+
+/// Name the component using the library, e.g. `ios-app` or `cli`, for the
+/// entries it causes. `None` restores the default.
+pub fn set_access_caller(caller: Option<String>) {
+    *CALLER.write().unwrap_or_else(|e| e.into_inner()) = caller;
+}
+
+fn current_caller() -> String {
+    CALLER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_CALLER.to_string())
+}
+
+/// What kind of access an entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessEvent {
+    Open,
+    Query,
+    Analysis,
+    Export,
+}
+
+impl AccessEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            AccessEvent::Open => "open",
+            AccessEvent::Query => "query",
+            AccessEvent::Analysis => "analysis",
+            AccessEvent::Export => "export",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "open" => Some(AccessEvent::Open),
+            "query" => Some(AccessEvent::Query),
+            "analysis" => Some(AccessEvent::Analysis),
+            "export" => Some(AccessEvent::Export),
+            _ => None,
+        }
+    }
+}
+
+/// One row of the access log
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct AccessLogEntry {
+    pub id: i64,
+    /// `None` for events written by a newer library version
+    pub event: Option<AccessEvent>,
+    pub caller: String,
+    pub purpose: Option<Purpose>,
+    /// Genotype rows read or written
+    pub rows: i64,
+    pub created_at: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// Result of checking the hash chain
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct AccessLogVerification {
+    pub intact: bool,
+    pub entries: i64,
+    /// Hash of the last entry; keep it elsewhere to anchor later checks
+    pub head_hash: Option<String>,
+    /// First entry whose hash or link does not match
+    pub first_invalid_id: Option<i64>,
+    pub problem: Option<String>,
+}
+
+fn entry_hash(
+    prev_hash: &str,
+    id: i64,
+    created_at: &str,
+    event: &str,
+    caller: &str,
+    purpose: Option<&str>,
+    rows: i64,
+) -> String {
+    let fields = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}",
+        prev_hash,
+        id,
+        created_at,
+        event,
+        caller,
+        purpose.unwrap_or(""),
+        rows
+    );
+    format!("{:x}", Sha256::digest(fields.as_bytes()))
+}
+
+/// Append an entry for the current caller. Runs in its own immediate
+/// transaction unless the connection is already inside one, so concurrent
+/// writers cannot fork the chain.
+pub(crate) fn record_access(
+    conn: &Connection,
+    event: AccessEvent,
+    purpose: Option<Purpose>,
+    rows: i64,
+) -> Result<(), BiovaultError> {
+    let transaction = if conn.is_autocommit() {
+        Some(Transaction::new_unchecked(
+            conn,
+            TransactionBehavior::Immediate,
+        )?)
+    } else {
+        None
+    };
+
+    let head: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT entries, hash FROM access_log_head WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (entries, prev_hash) = head.unwrap_or((0, GENESIS_HASH.to_string()));
+
+    let id = conn.query_row(
+        "SELECT COALESCE(MAX(id), 0) + 1 FROM access_log",
+        [],
+        |row| row.get::<_, i64>(0),
+    )?;
+    let created_at = chrono::Utc::now().to_rfc3339();
+    let caller = current_caller();
+    let purpose = purpose.map(Purpose::name);
+    let hash = entry_hash(
+        &prev_hash,
+        id,
+        &created_at,
+        event.name(),
+        &caller,
+        purpose,
+        rows,
+    );
+
+    conn.execute(
+        "INSERT INTO access_log (id, event, caller, purpose, rows, created_at, prev_hash, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            id,
+            event.name(),
+            caller,
+            purpose,
+            rows,
+            created_at,
+            prev_hash,
+            hash
+        ],
+    )?;
+    conn.execute(
+        "INSERT INTO access_log_head (id, entries, hash) VALUES (1, ?1, ?2)
+         ON CONFLICT (id) DO UPDATE SET entries = excluded.entries, hash = excluded.hash",
+        params![entries + 1, hash],
+    )?;
+
+    if let Some(transaction) = transaction {
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+const ENTRY_COLUMNS: &str = "id, event, caller, purpose, rows, created_at, prev_hash, hash";
+
+fn entry_from_row(
+    row: &rusqlite::Row,
+) -> rusqlite::Result<(AccessLogEntry, String, Option<String>)> {
+    let event: String = row.get(1)?;
+    let purpose: Option<String> = row.get(3)?;
+    Ok((
+        AccessLogEntry {
+            id: row.get(0)?,
+            event: AccessEvent::from_name(&event),
+            caller: row.get(2)?,
+            purpose: purpose.as_deref().and_then(Purpose::from_name),
+            rows: row.get(4)?,
+            created_at: row.get(5)?,
+            prev_hash: row.get(6)?,
+            hash: row.get(7)?,
+        },
+        event,
+        purpose,
+    ))
+}
+
+/// Access log entries, newest first. Listing is not itself logged.
+pub fn list_access_log(
+    db_path: &Path,
+    key: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<AccessLogEntry>, BiovaultError> {
+    let conn = open_genome_database_unlogged(db_path, key)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM access_log ORDER BY id DESC LIMIT ?1",
+        ENTRY_COLUMNS
+    ))?;
+    let entries = stmt.query_map([limit.unwrap_or(-1)], |row| {
+        entry_from_row(row).map(|(entry, _, _)| entry)
+    })?;
+    Ok(entries.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Check the hash chain from the first entry to the recorded head. `anchor`
+/// is a head hash saved earlier; the check fails if it is no longer in the
+/// chain. Verifying does not add an entry, so the head stays where it was.
+pub fn verify_access_log(
+    db_path: &Path,
+    key: Option<&str>,
+    anchor: Option<&str>,
+) -> Result<AccessLogVerification, BiovaultError> {
+    let conn = open_genome_database_unlogged(db_path, key)?;
+    let verification = verify_chain(&conn, anchor)?;
+    if !verification.intact {
+        warn!(
+            "access log verification failed: {}",
+            verification.problem.as_deref().unwrap_or("unknown")
+        );
+    }
+    Ok(verification)
+}
+
+/// Record the first problem found
+fn fail(verification: &mut AccessLogVerification, id: Option<i64>, problem: String) {
+    if verification.intact {
+        verification.intact = false;
+        verification.first_invalid_id = id;
+        verification.problem = Some(problem);
+    }
+}
+
+fn verify_chain(
+    conn: &Connection,
+    anchor: Option<&str>,
+) -> Result<AccessLogVerification, BiovaultError> {
+    let mut verification = AccessLogVerification {
+        intact: true,
+        entries: 0,
+        head_hash: None,
+        first_invalid_id: None,
+        problem: None,
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM access_log ORDER BY id",
+        ENTRY_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], entry_from_row)?;
+
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut anchor_found = false;
+    for row in rows {
+        let (entry, event, purpose) = row?;
+        verification.entries += 1;
+        let expected_id = verification.entries;
+        if entry.id != expected_id {
+            fail(
+                &mut verification,
+                Some(entry.id),
+                format!("entry {} is missing", expected_id),
+            );
+        }
+        if entry.prev_hash != prev_hash {
+            fail(
+                &mut verification,
+                Some(entry.id),
+                format!("entry {} does not link to the entry before it", entry.id),
+            );
+        }
+        let expected = entry_hash(
+            &entry.prev_hash,
+            entry.id,
+            &entry.created_at,
+            &event,
+            &entry.caller,
+            purpose.as_deref(),
+            entry.rows,
+        );
+        if entry.hash != expected {
+            fail(
+                &mut verification,
+                Some(entry.id),
+                format!("entry {} was modified", entry.id),
+            );
+        }
+        anchor_found |= anchor == Some(entry.hash.as_str());
+        prev_hash = entry.hash;
+    }
+    if verification.entries > 0 {
+        verification.head_hash = Some(prev_hash.clone());
+    }
+
+    let head: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT entries, hash FROM access_log_head WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (head_entries, head_hash) = head.unwrap_or((0, GENESIS_HASH.to_string()));
+    let entries = verification.entries;
+    if head_entries != entries {
+        fail(
+            &mut verification,
+            None,
+            format!(
+                "the log has {} entries but {} were recorded",
+                entries, head_entries
+            ),
+        );
+    } else if head_hash != prev_hash {
+        fail(
+            &mut verification,
+            None,
+            "the last entry does not match the recorded head".to_string(),
+        );
+    }
+    if anchor.is_some() && !anchor_found {
+        fail(
+            &mut verification,
+            None,
+            "the anchor hash is no longer in the log".to_string(),
+        );
+    }
+
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{create_genome_storage, open_genome_database};
+    use crate::storage::Storage;
+
+    #[test]
+    fn reading_the_log_does_not_extend_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        create_genome_storage(&Storage::from(db_path.as_path()), None).unwrap();
+        open_genome_database(&db_path, None).unwrap();
+        open_genome_database(&db_path, None).unwrap();
+
+        // Creating the database counts as an open too
+        let entries = list_access_log(&db_path, None, None).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(
+            entries
+                .iter()
+                .all(|entry| entry.event == Some(AccessEvent::Open))
+        );
+
+        let first = verify_access_log(&db_path, None, None).unwrap();
+        let head = first.head_hash.clone().unwrap();
+        for _ in 0..3 {
+            list_access_log(&db_path, None, Some(1)).unwrap();
+            crate::consent::list_consent_audit(&db_path, None, None).unwrap();
+        }
+        let again = verify_access_log(&db_path, None, Some(&head)).unwrap();
+        assert!(again.intact);
+        assert_eq!(again.entries, 3);
+        assert_eq!(again.head_hash, Some(head));
+    }
+
+    #[test]
+    fn edited_entries_break_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        create_genome_storage(&Storage::from(db_path.as_path()), None).unwrap();
+        for _ in 0..3 {
+            open_genome_database(&db_path, None).unwrap();
+        }
+
+        let conn = open_genome_database_unlogged(&db_path, None).unwrap();
+        conn.execute_batch(
+            "DROP TRIGGER access_log_no_update;
+             UPDATE access_log SET caller = 'someone else' WHERE id = 2;",
+        )
+        .unwrap();
+        drop(conn);
+
+        let verification = verify_access_log(&db_path, None, None).unwrap();
+        assert!(!verification.intact);
+        assert_eq!(verification.first_invalid_id, Some(2));
+    }
+
+    #[test]
+    fn truncated_logs_are_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        create_genome_storage(&Storage::from(db_path.as_path()), None).unwrap();
+        for _ in 0..4 {
+            open_genome_database(&db_path, None).unwrap();
+        }
+        let anchor = verify_access_log(&db_path, None, None)
+            .unwrap()
+            .head_hash
+            .unwrap();
+
+        // The head cannot be rewound or removed while its triggers are there
+        let conn = open_genome_database_unlogged(&db_path, None).unwrap();
+        let rewind = "UPDATE access_log_head
+                      SET entries = 3, hash = (SELECT hash FROM access_log WHERE id = 3)";
+        assert!(conn.execute(rewind, []).is_err());
+        assert!(conn.execute("DELETE FROM access_log_head", []).is_err());
+
+        // Cutting the tail alone leaves the head pointing past the end
+        conn.execute_batch(
+            "DROP TRIGGER access_log_no_delete;
+             DELETE FROM access_log WHERE id > 3;",
+        )
+        .unwrap();
+        let verification = verify_access_log(&db_path, None, None).unwrap();
+        assert!(!verification.intact);
+        assert_eq!(verification.entries, 3);
+        assert_eq!(
+            verification.problem.as_deref(),
+            Some("the log has 3 entries but 5 were recorded")
+        );
+
+        // A rewound head hides the cut from everyone but an anchored check
+        conn.execute_batch("DROP TRIGGER access_log_head_follows_log")
+            .unwrap();
+        conn.execute(rewind, []).unwrap();
+        drop(conn);
+        assert!(verify_access_log(&db_path, None, None).unwrap().intact);
+        let verification = verify_access_log(&db_path, None, Some(&anchor)).unwrap();
+        assert!(!verification.intact);
+        assert_eq!(
+            verification.problem.as_deref(),
+            Some("the anchor hash is no longer in the log")
+        );
+    }
+}
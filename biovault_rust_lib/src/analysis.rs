@@ -1,9 +1,18 @@
-use rusqlite::Connection;
+use crate::access_log::{self, AccessEvent};
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::open_genome_storage;
+use crate::encoding;
+use crate::error::BiovaultError;
+use crate::genomes;
+use crate::progress::{Phase, Progress};
+use crate::storage::Storage;
+use rusqlite::{Connection, OpenFlags};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::error::Error;
+use std::path::Path;
+use tracing::{info, info_span};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
 pub struct ClinVarVariant {
     pub rsid: String,
     pub chrom: String,
@@ -14,10 +23,10 @@ pub struct ClinVarVariant {
     pub clnsig: String,
     pub clnrevstat: String,
     pub condition: String,
-    pub user_genotype: Option<String>,  // Added to store user's actual genotype
+    pub user_genotype: Option<String>, // Added to store user's actual genotype
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
 pub struct GeneGroup {
     pub gene: String,
     pub variants: Vec<ClinVarVariant>,
@@ -32,7 +41,7 @@ pub struct GeneGroup {
     pub conditions: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
 pub struct AnalysisResult {
     pub matches: Vec<ClinVarVariant>,
     pub gene_groups: Vec<GeneGroup>,
@@ -40,9 +49,166 @@ pub struct AnalysisResult {
     pub matches_found: i32,
 }
 
-/// Get significance score for sorting (lower = more significant)
-fn get_significance_score(clnsig: &str) -> i32 {
+/// How many copies of the ClinVar ALT allele the user's genotype carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum Zygosity {
+    Homozygous,
+    Heterozygous,
+    /// One copy on a haploid call (X/Y/MT in males)
+    Hemizygous,
+    /// The genotype only has non-ALT alleles
+    NonCarrier,
+    /// No genotype, or alleles that cannot be compared (indels, no-calls)
+    Unknown,
+}
+
+impl Zygosity {
+    /// Stable name, as used in JSON and by the CLI
+    pub fn name(self) -> &'static str {
+        match self {
+            Zygosity::Homozygous => "homozygous",
+            Zygosity::Heterozygous => "heterozygous",
+            Zygosity::Hemizygous => "hemizygous",
+            Zygosity::NonCarrier => "non_carrier",
+            Zygosity::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "_").as_str() {
+            "homozygous" | "hom" => Some(Zygosity::Homozygous),
+            "heterozygous" | "het" => Some(Zygosity::Heterozygous),
+            "hemizygous" | "hemi" => Some(Zygosity::Hemizygous),
+            "non_carrier" | "noncarrier" => Some(Zygosity::NonCarrier),
+            "unknown" => Some(Zygosity::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl ClinVarVariant {
+    /// The ALT allele the user carries, or the first ALT if they carry none
+    pub fn carried_alt(&self) -> Option<&str> {
+        let mut alts = self.alt_allele.split(',').filter(|a| !a.is_empty());
+        let genotype = self.user_genotype.as_deref().unwrap_or("");
+        alts.clone()
+            .find(|alt| alt.len() == 1 && genotype.contains(*alt))
+            .or_else(|| alts.next())
+    }
+
+    /// Zygosity of the user's genotype for this variant's ALT allele(s)
+    pub fn zygosity(&self) -> Zygosity {
+        let Some(genotype) = self.user_genotype.as_deref() else {
+            return Zygosity::Unknown;
+        };
+        let alts: Vec<&str> = self.alt_allele.split(',').collect();
+        let comparable =
+            |allele: &str| allele.len() == 1 && matches!(allele, "A" | "C" | "G" | "T");
+        if !comparable(&self.ref_allele)
+            || !alts.iter().all(|alt| comparable(alt))
+            || genotype.is_empty()
+            || genotype.len() > 2
+            || !genotype.chars().all(|c| matches!(c, 'A' | 'C' | 'G' | 'T'))
+        {
+            return Zygosity::Unknown;
+        }
+
+        let carried = genotype
+            .chars()
+            .filter(|c| alts.iter().any(|alt| alt.starts_with(*c)))
+            .count();
+        match (genotype.len(), carried) {
+            (1, 1) => Zygosity::Hemizygous,
+            (2, 2) => Zygosity::Homozygous,
+            (2, 1) => Zygosity::Heterozygous,
+            _ => Zygosity::NonCarrier,
+        }
+    }
+
+    /// ClinVar review status as the 0-4 "gold stars" shown on the ClinVar site
+    pub fn review_stars(&self) -> u8 {
+        let status = self.clnrevstat.to_lowercase();
+        if status.contains("practice_guideline") {
+            4
+        } else if status.contains("expert_panel") {
+            3
+        } else if status.contains("multiple_submitters") && status.contains("no_conflicts") {
+            2
+        } else if status.contains("criteria_provided") {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Significance labels as returned by `get_significance_label`
+pub const SIGNIFICANCE_LABELS: [&str; 5] = [
+    "Pathogenic",
+    "Likely_pathogenic",
+    "Uncertain_significance",
+    "Conflicting",
+    "Benign",
+];
+
+/// Narrows ClinVar findings. Empty lists match everything.
+#[derive(Debug, Clone, Default)]
+pub struct ClinVarFilter {
+    /// Any of these `SIGNIFICANCE_LABELS`, compared case-insensitively
+    pub significance: Vec<String>,
+    /// Minimum `review_stars`
+    pub min_stars: u8,
+    /// Any of these genes, compared case-insensitively
+    pub genes: Vec<String>,
+    pub zygosity: Vec<Zygosity>,
+}
+
+impl ClinVarFilter {
+    pub fn matches(&self, variant: &ClinVarVariant) -> bool {
+        let label = get_significance_label(&variant.clnsig);
+        (self.significance.is_empty()
+            || self
+                .significance
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(&label)))
+            && variant.review_stars() >= self.min_stars
+            && (self.genes.is_empty()
+                || variant.gene.split(['|', ';', ',']).any(|gene| {
+                    self.genes
+                        .iter()
+                        .any(|g| g.eq_ignore_ascii_case(gene.trim()))
+                }))
+            && (self.zygosity.is_empty() || self.zygosity.contains(&variant.zygosity()))
+    }
+}
+
+impl AnalysisResult {
+    /// Keep only the matches `filter` accepts, regrouping them by gene
+    pub fn filtered(self, filter: &ClinVarFilter) -> AnalysisResult {
+        let matches: Vec<ClinVarVariant> = self
+            .matches
+            .into_iter()
+            .filter(|variant| filter.matches(variant))
+            .collect();
+        AnalysisResult {
+            gene_groups: group_variants_by_gene(matches.clone()),
+            matches_found: matches.len() as i32,
+            matches,
+            rsids_searched: self.rsids_searched,
+        }
+    }
+}
+
+/// Get significance score for sorting (lower = more significant).
+/// ClinVar's conflicting values (`Conflicting_classifications_of_pathogenicity`,
+/// `Conflicting_interpretations_of_pathogenicity`) also mention pathogenicity,
+/// so they are recognised first.
+pub(crate) fn get_significance_score(clnsig: &str) -> i32 {
     let sig = clnsig.to_lowercase();
+    if sig.contains("conflicting") {
+        return 4;
+    }
     if sig.contains("pathogenic") && !sig.contains("likely") {
         return 1;
     }
@@ -52,60 +218,66 @@ fn get_significance_score(clnsig: &str) -> i32 {
     if sig.contains("uncertain") {
         return 3;
     }
-    if sig.contains("conflicting") {
-        return 4;
-    }
     5 // Benign or other
 }
 
 /// Get significance label
-fn get_significance_label(clnsig: &str) -> String {
+pub(crate) fn get_significance_label(clnsig: &str) -> String {
     let sig = clnsig.to_lowercase();
+    if sig.contains("conflicting") {
+        return "Conflicting".to_string();
+    }
     if sig.contains("pathogenic") && !sig.contains("likely") {
         return "Pathogenic".to_string();
     }
     if sig.contains("likely_pathogenic") {
         return "Likely_pathogenic".to_string();
     }
-    if sig.contains("conflicting") {
-        return "Conflicting".to_string();
-    }
     if sig.contains("benign") {
         return "Benign".to_string();
     }
     "Uncertain_significance".to_string()
 }
 
-/// Extract rsIDs and genotypes from user genome database
-pub fn get_rsids_and_genotypes_from_user_database(db_path: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
-    let conn = Connection::open(db_path)?;
+/// Extract rsIDs and genotypes of one genome from the user genome database.
+/// `genome_id` may be omitted if the database holds a single genome.
+pub fn get_rsids_and_genotypes_from_user_database(
+    conn: &Connection,
+    genome_id: Option<i64>,
+) -> Result<HashMap<String, String>, BiovaultError> {
+    let genome_id = genomes::resolve_genome_id(conn, genome_id)?;
 
     let mut stmt = conn.prepare(
-        "SELECT DISTINCT rsid, genotype FROM variants WHERE rsid LIKE 'rs%' ORDER BY rsid"
+        "SELECT DISTINCT v.rsid, g.genotype
+         FROM variants v
+         JOIN genotype_codes g ON g.code = v.genotype
+         WHERE v.file_id = ?1 AND v.rsid > 0
+         ORDER BY v.rsid",
     )?;
 
-    let rsid_iter = stmt.query_map([], |row| {
+    let rsid_iter = stmt.query_map([genome_id], |row| {
         Ok((
-            row.get::<_, String>(0)?,  // rsid
-            row.get::<_, String>(1)?   // genotype
+            row.get::<_, i64>(0)?,    // rsid number
+            row.get::<_, String>(1)?, // genotype
         ))
     })?;
 
     let mut rsid_genotype_map = HashMap::new();
     for result in rsid_iter {
         let (rsid, genotype) = result?;
-        rsid_genotype_map.insert(rsid, genotype);
+        rsid_genotype_map.insert(encoding::rsid_string(rsid), genotype);
     }
 
     Ok(rsid_genotype_map)
 }
 
-/// Lookup variants by rsID list with batching
+/// Lookup variants by rsID list with batching.
+/// The ClinVar reference database is public data and never encrypted.
 pub fn lookup_variants_by_rsid(
-    clinvar_db_path: &str,
+    conn: &Connection,
     rsid_genotype_map: &HashMap<String, String>,
-) -> Result<Vec<ClinVarVariant>, Box<dyn Error>> {
-    let conn = Connection::open(clinvar_db_path)?;
+    progress: &mut Progress,
+) -> Result<Vec<ClinVarVariant>, BiovaultError> {
     let mut results = Vec::new();
 
     // Convert HashMap keys to Vec for chunking
@@ -113,7 +285,9 @@ pub fn lookup_variants_by_rsid(
 
     // Process in chunks of 999 to stay under SQLite parameter limit
     const CHUNK_SIZE: usize = 999;
-    for chunk in rsids.chunks(CHUNK_SIZE) {
+    let total = rsids.len() as u64;
+    for (index, chunk) in rsids.chunks(CHUNK_SIZE).enumerate() {
+        progress.update(Phase::ClinvarLookup, (index * CHUNK_SIZE) as u64, total)?;
         let placeholders = vec!["?"; chunk.len()].join(",");
         let query = format!(
             "SELECT rsid, chrom, pos, ref, alt, gene, clnsig, clnrevstat, condition
@@ -121,48 +295,47 @@ pub fn lookup_variants_by_rsid(
              WHERE rsid IN ({})
              ORDER BY
                CASE
-                 WHEN clnsig LIKE '%Pathogenic%' AND clnsig NOT LIKE '%Likely_pathogenic%' THEN 1
+                 WHEN clnsig LIKE '%Conflicting%' THEN 4
+                 WHEN clnsig LIKE '%Pathogenic%' AND clnsig NOT LIKE '%Likely%' THEN 1
                  WHEN clnsig LIKE '%Likely_pathogenic%' THEN 2
                  WHEN clnsig LIKE '%Uncertain%' THEN 3
-                 ELSE 4
+                 ELSE 5
                END,
                gene",
             placeholders
         );
-        
+
         let mut stmt = conn.prepare(&query)?;
-        let variant_iter = stmt.query_map(
-            rusqlite::params_from_iter(chunk.iter()),
-            |row| {
-                let rsid: String = row.get(0)?;
-                let user_genotype = rsid_genotype_map.get(&rsid).cloned();
-                Ok(ClinVarVariant {
-                    rsid: rsid.clone(),
-                    chrom: row.get(1)?,
-                    pos: row.get(2)?,
-                    ref_allele: row.get(3)?,
-                    alt_allele: row.get(4)?,
-                    gene: row.get(5)?,
-                    clnsig: row.get(6)?,
-                    clnrevstat: row.get(7)?,
-                    condition: row.get(8)?,
-                    user_genotype,
-                })
-            },
-        )?;
-        
+        let variant_iter = stmt.query_map(rusqlite::params_from_iter(chunk.iter()), |row| {
+            let rsid: String = row.get(0)?;
+            let user_genotype = rsid_genotype_map.get(&rsid).cloned();
+            Ok(ClinVarVariant {
+                rsid: rsid.clone(),
+                chrom: row.get(1)?,
+                pos: row.get(2)?,
+                ref_allele: row.get(3)?,
+                alt_allele: row.get(4)?,
+                gene: row.get(5)?,
+                clnsig: row.get(6)?,
+                clnrevstat: row.get(7)?,
+                condition: row.get(8)?,
+                user_genotype,
+            })
+        })?;
+
         for variant in variant_iter {
             results.push(variant?);
         }
     }
-    
+    progress.update(Phase::ClinvarLookup, total, total)?;
+
     Ok(results)
 }
 
 /// Group variants by gene and calculate statistics
 pub fn group_variants_by_gene(variants: Vec<ClinVarVariant>) -> Vec<GeneGroup> {
     let mut gene_map: HashMap<String, Vec<ClinVarVariant>> = HashMap::new();
-    
+
     // Group variants by gene
     for variant in variants {
         let gene = if variant.gene.is_empty() {
@@ -170,49 +343,40 @@ pub fn group_variants_by_gene(variants: Vec<ClinVarVariant>) -> Vec<GeneGroup> {
         } else {
             variant.gene.clone()
         };
-        
-        gene_map.entry(gene).or_insert_with(Vec::new).push(variant);
+
+        gene_map.entry(gene).or_default().push(variant);
     }
-    
+
     // Convert to GeneGroup objects with statistics
     let mut gene_groups: Vec<GeneGroup> = gene_map
         .into_iter()
         .map(|(gene, gene_variants)| {
             // Calculate significance counts
-            let pathogenic_count = gene_variants
-                .iter()
-                .filter(|v| {
-                    let sig = v.clnsig.to_lowercase();
-                    sig.contains("pathogenic") && !sig.contains("likely")
-                })
-                .count() as i32;
-                
-            let likely_pathogenic_count = gene_variants
-                .iter()
-                .filter(|v| v.clnsig.to_lowercase().contains("likely_pathogenic"))
-                .count() as i32;
-                
-            let uncertain_count = gene_variants
-                .iter()
-                .filter(|v| v.clnsig.to_lowercase().contains("uncertain"))
-                .count() as i32;
-                
-            let conflicting_count = gene_variants
-                .iter()
-                .filter(|v| v.clnsig.to_lowercase().contains("conflicting"))
-                .count() as i32;
-            
+            let count_scored = |score: i32| {
+                gene_variants
+                    .iter()
+                    .filter(|v| get_significance_score(&v.clnsig) == score)
+                    .count() as i32
+            };
+            let pathogenic_count = count_scored(1);
+            let likely_pathogenic_count = count_scored(2);
+            let uncertain_count = count_scored(3);
+            let conflicting_count = count_scored(4);
+
             // Find most significant variant
             let most_significant_variant = gene_variants
                 .iter()
                 .min_by_key(|v| get_significance_score(&v.clnsig))
                 .unwrap();
-            
+
             // Get unique conditions
             let mut conditions: Vec<String> = gene_variants
                 .iter()
                 .filter_map(|v| {
-                    if v.condition.is_empty() || v.condition == "not_provided" || v.condition == "not_specified" {
+                    if v.condition.is_empty()
+                        || v.condition == "not_provided"
+                        || v.condition == "not_specified"
+                    {
                         None
                     } else {
                         Some(v.condition.replace('_', " "))
@@ -223,14 +387,14 @@ pub fn group_variants_by_gene(variants: Vec<ClinVarVariant>) -> Vec<GeneGroup> {
                 .collect();
             conditions.sort();
             conditions.truncate(3); // Limit to top 3
-            
+
             // Get unique rsIDs
             let unique_rsids = gene_variants
                 .iter()
                 .map(|v| &v.rsid)
                 .collect::<std::collections::HashSet<_>>()
                 .len() as i32;
-            
+
             GeneGroup {
                 gene: gene.clone(),
                 variants: gene_variants.clone(),
@@ -246,38 +410,112 @@ pub fn group_variants_by_gene(variants: Vec<ClinVarVariant>) -> Vec<GeneGroup> {
             }
         })
         .collect();
-    
+
     // Sort by significance, then by gene name
     gene_groups.sort_by(|a, b| {
         a.significance_score
             .cmp(&b.significance_score)
             .then_with(|| a.gene.cmp(&b.gene))
     });
-    
+
     gene_groups
 }
 
 /// Main analysis function - combines all steps
 pub fn analyze_clinvar_matches(
     user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    clinvar_db_path: &str,
+) -> Result<AnalysisResult, BiovaultError> {
+    analyze_clinvar_matches_with_progress(
+        user_db_path,
+        user_db_key,
+        genome_id,
+        clinvar_db_path,
+        &mut Progress::none(),
+    )
+}
+
+/// `analyze_clinvar_matches` with progress reporting and cancellation
+pub fn analyze_clinvar_matches_with_progress(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
     clinvar_db_path: &str,
-) -> Result<AnalysisResult, Box<dyn Error>> {
-    eprintln!("Rust Analysis: Starting ClinVar analysis...");
+    progress: &mut Progress,
+) -> Result<AnalysisResult, BiovaultError> {
+    analyze_clinvar_storage(
+        &Storage::from(Path::new(user_db_path)),
+        user_db_key,
+        genome_id,
+        &Storage::from(Path::new(clinvar_db_path)),
+        progress,
+    )
+}
+
+/// `analyze_clinvar_matches_with_progress` for databases in any `Storage`.
+/// ClinVar is opened read-only, so a wrong path fails instead of creating an
+/// empty database.
+pub fn analyze_clinvar_storage(
+    user_db: &Storage,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    clinvar_db: &Storage,
+    progress: &mut Progress,
+) -> Result<AnalysisResult, BiovaultError> {
+    let user_conn = open_genome_storage(user_db, user_db_key)?;
+    consent::require_consent(
+        &user_conn,
+        Purpose::ClinvarAnalysis,
+        consent::OWNER,
+        Granularity::Individual,
+    )?;
+    let clinvar_conn = clinvar_db.open(
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let result = analyze_genome(&user_conn, genome_id, &clinvar_conn, progress)?;
+    record_analysis(&user_conn, &result)?;
+    Ok(result)
+}
+
+fn record_analysis(user_conn: &Connection, result: &AnalysisResult) -> Result<(), BiovaultError> {
+    access_log::record_access(
+        user_conn,
+        AccessEvent::Analysis,
+        Some(Purpose::ClinvarAnalysis),
+        result.rsids_searched as i64,
+    )
+}
+
+pub(crate) fn analyze_genome(
+    user_conn: &Connection,
+    genome_id: Option<i64>,
+    clinvar_conn: &Connection,
+    progress: &mut Progress,
+) -> Result<AnalysisResult, BiovaultError> {
+    let _span = info_span!("analysis", genome_id = genome_id).entered();
+    info!("starting ClinVar analysis");
 
     // Step 1: Extract rsIDs AND genotypes from user database
-    let rsid_genotype_map = get_rsids_and_genotypes_from_user_database(user_db_path)?;
+    progress.update(Phase::LoadingGenotypes, 0, 0)?;
+    let rsid_genotype_map = get_rsids_and_genotypes_from_user_database(user_conn, genome_id)?;
     let rsids_searched = rsid_genotype_map.len() as i32;
-    eprintln!("Rust Analysis: Found {} rsIDs to search", rsids_searched);
+    info!("found {} rsIDs to search", rsids_searched);
 
     // Step 2: Query ClinVar for matches with genotype info
-    let matches = lookup_variants_by_rsid(clinvar_db_path, &rsid_genotype_map)?;
+    let matches = lookup_variants_by_rsid(clinvar_conn, &rsid_genotype_map, progress)?;
     let matches_found = matches.len() as i32;
-    eprintln!("Rust Analysis: Found {} ClinVar matches", matches_found);
-    
+    info!("found {} ClinVar matches", matches_found);
+
     // Step 3: Group by gene
+    progress.update(Phase::Grouping, 0, matches.len() as u64)?;
     let gene_groups = group_variants_by_gene(matches.clone());
-    eprintln!("Rust Analysis: Grouped into {} genes", gene_groups.len());
-    
+    progress.update(Phase::Grouping, matches.len() as u64, matches.len() as u64)?;
+    info!("grouped into {} genes", gene_groups.len());
+
     Ok(AnalysisResult {
         matches,
         gene_groups,
@@ -285,3 +523,124 @@ pub fn analyze_clinvar_matches(
         matches_found,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::codes;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+
+    fn clinvar_variant(rsid: &str, clnsig: &str) -> ClinVarVariant {
+        ClinVarVariant {
+            rsid: rsid.to_string(),
+            chrom: "1".to_string(),
+            pos: 100,
+            ref_allele: "A".to_string(),
+            alt_allele: "G".to_string(),
+            gene: "GENE1".to_string(),
+            clnsig: clnsig.to_string(),
+            clnrevstat: "criteria_provided,_single_submitter".to_string(),
+            condition: "Some_condition".to_string(),
+            user_genotype: Some("AG".to_string()),
+        }
+    }
+
+    #[test]
+    fn significance_follows_clinvar_clnsig_values() {
+        for (clnsig, label, score) in [
+            ("Pathogenic", "Pathogenic", 1),
+            ("Pathogenic/Likely_pathogenic", "Likely_pathogenic", 2),
+            ("Likely_pathogenic", "Likely_pathogenic", 2),
+            ("Uncertain_significance", "Uncertain_significance", 3),
+            (
+                "Conflicting_classifications_of_pathogenicity",
+                "Conflicting",
+                4,
+            ),
+            (
+                "Conflicting_interpretations_of_pathogenicity",
+                "Conflicting",
+                4,
+            ),
+            ("Benign/Likely_benign", "Benign", 5),
+        ] {
+            assert_eq!(get_significance_label(clnsig), label, "{}", clnsig);
+            assert_eq!(get_significance_score(clnsig), score, "{}", clnsig);
+        }
+    }
+
+    #[test]
+    fn filter_separates_conflicting_from_pathogenic() {
+        let result = AnalysisResult {
+            matches: vec![
+                clinvar_variant("rs1", "Pathogenic"),
+                clinvar_variant("rs2", "Conflicting_classifications_of_pathogenicity"),
+                clinvar_variant("rs3", "Conflicting_interpretations_of_pathogenicity"),
+                clinvar_variant("rs4", "Likely_pathogenic"),
+            ],
+            gene_groups: Vec::new(),
+            rsids_searched: 4,
+            matches_found: 4,
+        };
+        let rsids = |significance: &str| {
+            let filter = ClinVarFilter {
+                significance: vec![significance.to_string()],
+                ..Default::default()
+            };
+            result
+                .clone()
+                .filtered(&filter)
+                .matches
+                .into_iter()
+                .map(|v| v.rsid)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rsids("pathogenic"), ["rs1"]);
+        assert_eq!(rsids("conflicting"), ["rs2", "rs3"]);
+        assert_eq!(rsids("likely_pathogenic"), ["rs4"]);
+
+        let group = &result.filtered(&ClinVarFilter::default()).gene_groups[0];
+        assert_eq!(
+            (
+                group.pathogenic_count,
+                group.likely_pathogenic_count,
+                group.conflicting_count
+            ),
+            (1, 1, 2)
+        );
+        assert_eq!(group.most_significant, "Pathogenic");
+    }
+
+    #[test]
+    fn a_missing_clinvar_database_is_not_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("genome.txt");
+        std::fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\nrs1\t1\t100\tAG\n",
+        )
+        .unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        let clinvar = dir.path().join("clinvar.sqlite");
+        let error = analyze_clinvar_matches(
+            db_path.to_str().unwrap(),
+            None,
+            None,
+            clinvar.to_str().unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(error.code(), codes::FILE_NOT_FOUND);
+        assert!(!clinvar.exists());
+    }
+}
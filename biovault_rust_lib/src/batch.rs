@@ -0,0 +1,672 @@
+//! Batch import of many genome files, e.g. a research cohort's kits.
+//!
+//! Files are listed from a directory or a CSV manifest and parsed in
+//! parallel by a bounded pool of worker threads. Each file gets its own
+//! database, or all of them go into one cohort database, in which case the
+//! writes are serialized. Failures are collected per file instead of
+//! stopping the batch.
+
+use crate::database;
+use crate::error::{BiovaultError, ErrorReport};
+use crate::genomes::{self, DuplicatePolicy, ImportOutcome, ImportStatus};
+use crate::history::bytes_checksum;
+use crate::logging;
+use crate::parsers::{ParseResult, twenty_three_and_me};
+use crate::progress::Progress;
+use crate::storage::Storage;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{info, info_span};
+
+/// Input formats a manifest may name. Empty means 23andMe.
+const SUPPORTED_FORMATS: [&str; 1] = ["23andme"];
+
+/// Genomes with fewer calls than this are flagged as possibly truncated
+const MIN_EXPECTED_VARIANTS: usize = 100_000;
+
+/// One file to import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchEntry {
+    pub path: PathBuf,
+    /// Genome name; also the database name when importing one per file
+    pub name: String,
+    /// Expected input format from the manifest, if given
+    pub format: Option<String>,
+}
+
+/// Where a batch is imported to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchTarget {
+    /// One `<name>.sqlite` database per file in this directory
+    PerFile(PathBuf),
+    /// All files merged into this cohort database
+    Cohort(PathBuf),
+}
+
+/// What happened to one file of a batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItem {
+    pub path: String,
+    pub name: String,
+    pub outcome: Option<ImportOutcome>,
+    pub error: Option<ErrorReport>,
+    /// Warnings about files that imported but look suspicious
+    pub diagnostics: Vec<String>,
+}
+
+/// Result of a batch import, with the items in input order
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchSummary {
+    pub imported: usize,
+    pub skipped: usize,
+    pub replaced: usize,
+    pub failed: usize,
+    pub items: Vec<BatchItem>,
+}
+
+/// The genome files (`.txt` and `.zip`) directly inside `dir`, named after
+/// their file stem
+pub fn entries_from_directory(dir: &Path) -> Result<Vec<BatchEntry>, BiovaultError> {
+    let mut entries = Vec::new();
+    for dir_entry in std::fs::read_dir(dir).map_err(|e| BiovaultError::io(dir, e))? {
+        let path = dir_entry.map_err(|e| BiovaultError::io(dir, e))?.path();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        if path.is_file() && matches!(extension.as_deref(), Some("txt" | "zip")) {
+            entries.push(BatchEntry {
+                name: file_stem(&path),
+                path,
+                format: None,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Read a CSV manifest with a `path,name,format` header. `name` and `format`
+/// may be missing or empty; relative paths are resolved against the
+/// manifest's directory. Blank lines and lines starting with `#` are ignored.
+pub fn entries_from_manifest(manifest: &Path) -> Result<Vec<BatchEntry>, BiovaultError> {
+    let content = std::fs::read_to_string(manifest).map_err(|e| BiovaultError::io(manifest, e))?;
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    let parse_error = |line: usize, message: &str| BiovaultError::Parse {
+        path: manifest.to_path_buf(),
+        message: format!("line {}: {}", line, message),
+    };
+
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let Some((header_line, header)) = lines.next() else {
+        return Err(parse_error(1, "empty manifest"));
+    };
+    let header: Vec<String> = split_csv_line(header)
+        .map_err(|message| parse_error(header_line, &message))?
+        .iter()
+        .map(|column| column.to_ascii_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|c| c == name);
+    let Some(path_column) = column("path") else {
+        return Err(parse_error(header_line, "missing 'path' column"));
+    };
+    let name_column = column("name");
+    let format_column = column("format");
+
+    let mut entries = Vec::new();
+    for (line_number, line) in lines {
+        let fields = split_csv_line(line).map_err(|message| parse_error(line_number, &message))?;
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| fields.get(i))
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+        let Some(path) = field(Some(path_column)) else {
+            return Err(parse_error(line_number, "missing path"));
+        };
+        let path = base.join(path);
+        entries.push(BatchEntry {
+            name: field(name_column)
+                .map(str::to_string)
+                .unwrap_or_else(|| file_stem(&path)),
+            format: field(format_column).map(str::to_string),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+/// Split one CSV line, honouring double-quoted fields with `""` escapes
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "genome".to_string())
+}
+
+/// File name for a genome's own database
+fn database_file_name(name: &str) -> String {
+    format!("{}.sqlite", name.replace([' ', '/', '\\'], "_"))
+}
+
+/// Import `entries` with up to `workers` threads. Fails only if the target
+/// cannot be prepared; per-file failures are reported in the summary.
+pub fn import_batch(
+    entries: &[BatchEntry],
+    target: &BatchTarget,
+    workers: usize,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+) -> Result<BatchSummary, BiovaultError> {
+    let _span = info_span!("batch", files = entries.len()).entered();
+
+    let cohort = match target {
+        BatchTarget::PerFile(dir) => {
+            std::fs::create_dir_all(dir).map_err(|e| BiovaultError::io(dir, e))?;
+            None
+        }
+        BatchTarget::Cohort(db_path) => {
+            let storage = Storage::from(db_path.as_path());
+            let conn = if storage.exists()? {
+                database::open_genome_storage(&storage, key)?
+            } else {
+                database::create_genome_storage(&storage, key)?
+            };
+            Some(Mutex::new(conn))
+        }
+    };
+
+    let results: Vec<Mutex<Option<BatchItem>>> = entries.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    let workers = workers.clamp(1, entries.len().max(1));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(entry) = entries.get(index) else {
+                        break;
+                    };
+                    let item = import_entry(entry, entries, target, cohort.as_ref(), key, policy);
+                    if let Ok(mut slot) = results[index].lock() {
+                        *slot = Some(item);
+                    }
+                }
+            });
+        }
+    });
+
+    let mut summary = BatchSummary::default();
+    for (entry, result) in entries.iter().zip(results) {
+        let item =
+            result.into_inner().ok().flatten().unwrap_or_else(|| {
+                failed_item(entry, &BiovaultError::Other("worker failed".into()))
+            });
+        match item.outcome.as_ref().map(|outcome| outcome.status) {
+            Some(ImportStatus::Imported) => summary.imported += 1,
+            Some(ImportStatus::Skipped) => summary.skipped += 1,
+            Some(ImportStatus::Replaced) => summary.replaced += 1,
+            None => summary.failed += 1,
+        }
+        summary.items.push(item);
+    }
+
+    info!(
+        "{} imported, {} skipped, {} replaced, {} failed",
+        summary.imported, summary.skipped, summary.replaced, summary.failed
+    );
+    Ok(summary)
+}
+
+fn failed_item(entry: &BatchEntry, error: &BiovaultError) -> BatchItem {
+    BatchItem {
+        path: entry.path.to_string_lossy().into_owned(),
+        name: entry.name.clone(),
+        outcome: None,
+        error: Some(error.report()),
+        diagnostics: Vec::new(),
+    }
+}
+
+/// Import one entry; never fails, never panics
+fn import_entry(
+    entry: &BatchEntry,
+    entries: &[BatchEntry],
+    target: &BatchTarget,
+    cohort: Option<&Mutex<Connection>>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+) -> BatchItem {
+    let _span = info_span!("import", name = entry.name.as_str()).entered();
+    let mut diagnostics = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        check_entry(entry, entries, target)?;
+        let bytes = std::fs::read(&entry.path).map_err(|e| BiovaultError::io(&entry.path, e))?;
+        let content_hash = bytes_checksum(&bytes);
+        let parse = |diagnostics: &mut Vec<String>| {
+            let parse_result = twenty_three_and_me::parse_23andme_bytes(
+                &entry.path,
+                &bytes,
+                &mut Progress::none(),
+            )?;
+            diagnostics.extend(parse_diagnostics(&parse_result));
+            Ok(parse_result)
+        };
+
+        match (target, cohort) {
+            (BatchTarget::Cohort(db_path), Some(conn)) => {
+                import_into_cohort(conn, db_path, entry, &content_hash, policy, || {
+                    parse(&mut diagnostics)
+                })
+            }
+            (BatchTarget::PerFile(dir), _) => {
+                let storage = Storage::from(dir.join(database_file_name(&entry.name)).as_path());
+                import_into_own_database(&storage, entry, &content_hash, key, policy, || {
+                    parse(&mut diagnostics)
+                })
+            }
+            (BatchTarget::Cohort(_), None) => {
+                Err(BiovaultError::Other("cohort database is not open".into()))
+            }
+        }
+    }))
+    .unwrap_or_else(|payload| Err(BiovaultError::from_panic(payload)));
+
+    match result {
+        Ok(outcome) => {
+            if outcome.status == ImportStatus::Skipped {
+                diagnostics.push(format!(
+                    "same file as genome {} already in the database",
+                    outcome.genome_id
+                ));
+            }
+            BatchItem {
+                path: entry.path.to_string_lossy().into_owned(),
+                name: entry.name.clone(),
+                outcome: Some(outcome),
+                error: None,
+                diagnostics,
+            }
+        }
+        Err(error) => {
+            logging::log_failure("import", &error);
+            let mut item = failed_item(entry, &error);
+            item.diagnostics = diagnostics;
+            item
+        }
+    }
+}
+
+/// Reject entries that cannot be imported before reading them
+fn check_entry(
+    entry: &BatchEntry,
+    entries: &[BatchEntry],
+    target: &BatchTarget,
+) -> Result<(), BiovaultError> {
+    if let Some(format) = &entry.format
+        && !SUPPORTED_FORMATS
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(format))
+    {
+        return Err(BiovaultError::Parse {
+            path: entry.path.clone(),
+            message: format!(
+                "unsupported format '{}'; supported: {}",
+                format,
+                SUPPORTED_FORMATS.join(", ")
+            ),
+        });
+    }
+
+    // Two files must not end up in one per-file database
+    if let BatchTarget::PerFile(_) = target {
+        let file_name = database_file_name(&entry.name);
+        let first = entries
+            .iter()
+            .find(|other| database_file_name(&other.name) == file_name);
+        if first.is_some_and(|first| !std::ptr::eq(first, entry)) {
+            return Err(BiovaultError::InvalidArgument(format!(
+                "name '{}' is used by an earlier file in the batch",
+                entry.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Warnings about a parsed file that imports fine but looks suspicious
+fn parse_diagnostics(parse_result: &ParseResult) -> Vec<String> {
+    let metadata = &parse_result.metadata;
+    let mut diagnostics = Vec::new();
+    if metadata.total_variants < MIN_EXPECTED_VARIANTS {
+        diagnostics.push(format!(
+            "only {} genotype calls; the file may be truncated",
+            metadata.total_variants
+        ));
+    }
+    if metadata.rsid_count * 2 < metadata.total_variants {
+        diagnostics.push(format!(
+            "only {} of {} calls have rsIDs",
+            metadata.rsid_count, metadata.total_variants
+        ));
+    }
+    diagnostics
+}
+
+/// Parse outside the lock and insert under it, so workers only wait for
+/// each other while writing
+fn import_into_cohort(
+    conn: &Mutex<Connection>,
+    db_path: &Path,
+    entry: &BatchEntry,
+    content_hash: &str,
+    policy: DuplicatePolicy,
+    parse: impl FnOnce() -> Result<ParseResult, BiovaultError>,
+) -> Result<ImportOutcome, BiovaultError> {
+    let lock = || {
+        conn.lock()
+            .map_err(|_| BiovaultError::Other("cohort database lock poisoned".into()))
+    };
+    let outcome = |genome_id, status| ImportOutcome {
+        db_path: db_path.to_string_lossy().into_owned(),
+        genome_id,
+        status,
+        content_hash: content_hash.to_string(),
+    };
+
+    let existing = genomes::find_genome_by_hash(&*lock()?, content_hash)?;
+    if let (Some(genome_id), DuplicatePolicy::Skip) = (existing, policy) {
+        return Ok(outcome(genome_id, ImportStatus::Skipped));
+    }
+
+    let parse_result = parse()?;
+
+    let conn = lock()?;
+    // Another worker may have imported the same file meanwhile
+    let existing = genomes::find_genome_by_hash(&conn, content_hash)?;
+    if let (Some(genome_id), DuplicatePolicy::Skip) = (existing, policy) {
+        return Ok(outcome(genome_id, ImportStatus::Skipped));
+    }
+    let (genome_id, status) = genomes::store_genome(
+        &conn,
+        existing,
+        parse_result,
+        &entry.name,
+        None,
+        content_hash,
+        &mut Progress::none(),
+    )?;
+    Ok(outcome(genome_id, status))
+}
+
+/// Import into the entry's own database, removing it again if it was
+/// created for a failed import
+fn import_into_own_database(
+    storage: &Storage,
+    entry: &BatchEntry,
+    content_hash: &str,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+    parse: impl FnOnce() -> Result<ParseResult, BiovaultError>,
+) -> Result<ImportOutcome, BiovaultError> {
+    let created = !storage.exists()?;
+    let conn = if created {
+        database::create_genome_storage(storage, key)?
+    } else {
+        database::open_genome_storage(storage, key)?
+    };
+
+    let result = import_into_cohort(
+        &Mutex::new(conn),
+        storage.path(),
+        entry,
+        content_hash,
+        policy,
+        parse,
+    );
+    if created && result.is_err() {
+        let _ = storage.remove();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::codes;
+    use std::fs;
+
+    fn write_genome(dir: &Path, file_name: &str, calls: &[(&str, i64, &str)]) -> PathBuf {
+        let mut content = String::from("# rsid\tchromosome\tposition\tgenotype\n");
+        for (rsid, position, genotype) in calls {
+            content.push_str(&format!("{}\t1\t{}\t{}\n", rsid, position, genotype));
+        }
+        let path = dir.join(file_name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn entry(path: PathBuf, name: &str, format: Option<&str>) -> BatchEntry {
+        BatchEntry {
+            path,
+            name: name.to_string(),
+            format: format.map(str::to_string),
+        }
+    }
+
+    fn error_code(item: &BatchItem) -> Option<&'static str> {
+        item.error.as_ref().map(|error| error.code)
+    }
+
+    #[test]
+    fn csv_lines_split_on_unquoted_commas() {
+        assert_eq!(split_csv_line("a,b,,c").unwrap(), ["a", "b", "", "c"]);
+        assert_eq!(
+            split_csv_line(r#""kits/a, b.txt", "Ann ""A"" Lee",23andme"#).unwrap(),
+            ["kits/a, b.txt", r#"Ann "A" Lee"#, "23andme"]
+        );
+        assert!(split_csv_line(r#""open,end"#).is_err());
+    }
+
+    #[test]
+    fn manifest_entries_resolve_against_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("manifest.csv");
+        fs::write(
+            &manifest,
+            "# cohort kits\n\
+             Name,PATH,Format\n\
+             \n\
+             alice,kits/alice.txt,23andMe\n\
+             ,kits/bob.zip,\n\
+             \"carol, c\",/data/carol.txt\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries_from_manifest(&manifest).unwrap(),
+            [
+                entry(dir.path().join("kits/alice.txt"), "alice", Some("23andMe")),
+                entry(dir.path().join("kits/bob.zip"), "bob", None),
+                entry(PathBuf::from("/data/carol.txt"), "carol, c", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest_errors_name_the_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("manifest.csv");
+        let error = |content: &str| {
+            fs::write(&manifest, content).unwrap();
+            let error = entries_from_manifest(&manifest).unwrap_err();
+            assert_eq!(error.code(), codes::PARSE_ERROR);
+            error.to_string()
+        };
+
+        assert!(error("# only a comment\n").contains("line 1: empty manifest"));
+        assert!(error("name,format\nalice,23andme\n").contains("line 1: missing 'path' column"));
+        assert!(error("path,name\na.txt,a\n,b\n").contains("line 3: missing path"));
+        assert!(error("path,name\n\"a.txt,a\n").contains("line 2: unterminated"));
+        assert_eq!(
+            entries_from_manifest(&dir.path().join("missing.csv"))
+                .unwrap_err()
+                .code(),
+            codes::FILE_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn directory_entries_are_genome_files_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.TXT", "a.zip", "notes.md", "c.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("d.txt")).unwrap();
+
+        let names: Vec<String> = entries_from_directory(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn workers_report_every_file_in_input_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out");
+        let entries = vec![
+            entry(
+                write_genome(dir.path(), "a.txt", &[("rs1", 100, "AG")]),
+                "a",
+                None,
+            ),
+            entry(dir.path().join("missing.txt"), "missing", None),
+            entry(
+                write_genome(dir.path(), "b.txt", &[("rs2", 200, "CC")]),
+                "b",
+                Some("23andme"),
+            ),
+            entry(
+                write_genome(dir.path(), "c.txt", &[("rs3", 300, "TT")]),
+                "c",
+                Some("vcf"),
+            ),
+            entry(
+                write_genome(dir.path(), "d.txt", &[("rs4", 400, "GG")]),
+                "a",
+                None,
+            ),
+        ];
+
+        let summary = import_batch(
+            &entries,
+            &BatchTarget::PerFile(output.clone()),
+            3,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        assert_eq!(
+            (
+                summary.imported,
+                summary.skipped,
+                summary.replaced,
+                summary.failed
+            ),
+            (2, 0, 0, 3)
+        );
+        let items: Vec<(&str, Option<&str>)> = summary
+            .items
+            .iter()
+            .map(|item| (item.name.as_str(), error_code(item)))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("a", None),
+                ("missing", Some(codes::FILE_NOT_FOUND)),
+                ("b", None),
+                ("c", Some(codes::PARSE_ERROR)),
+                ("a", Some(codes::INVALID_ARGUMENT)),
+            ]
+        );
+        // Tiny files import but are flagged
+        assert!(summary.items[0].diagnostics[0].contains("may be truncated"));
+
+        // Failed files leave no database behind
+        let mut databases: Vec<String> = fs::read_dir(&output)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        databases.sort();
+        assert_eq!(databases, ["a.sqlite", "b.sqlite"]);
+    }
+
+    #[test]
+    fn cohort_imports_skip_files_already_in_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let cohort = dir.path().join("cohort.sqlite");
+        let a = write_genome(dir.path(), "a.txt", &[("rs1", 100, "AG")]);
+        let b = write_genome(dir.path(), "b.txt", &[("rs2", 200, "CC")]);
+        let entries = vec![
+            entry(a.clone(), "a", None),
+            entry(b, "b", None),
+            entry(a, "a again", None),
+        ];
+        let target = BatchTarget::Cohort(cohort.clone());
+
+        let summary = import_batch(&entries, &target, 1, None, DuplicatePolicy::Skip).unwrap();
+        assert_eq!((summary.imported, summary.skipped), (2, 1));
+        let first = summary.items[0].outcome.as_ref().unwrap();
+        let again = summary.items[2].outcome.as_ref().unwrap();
+        assert_eq!(again.status, ImportStatus::Skipped);
+        assert_eq!(again.genome_id, first.genome_id);
+
+        // A second run over the same files changes nothing
+        let summary = import_batch(&entries, &target, 4, None, DuplicatePolicy::Skip).unwrap();
+        assert_eq!((summary.imported, summary.skipped), (0, 3));
+
+        let genomes = genomes::list_genomes(cohort.to_str().unwrap(), None).unwrap();
+        assert_eq!(genomes.len(), 2);
+    }
+}
@@ -1,14 +1,80 @@
+use biovault_rust_lib::{
+    AccessEvent, AnalysisResult, BatchTarget, CONSENT_OWNER, ClinVarFilter, CohortOptions,
+    ConsentGrant, DatasiteConfig, DuplicatePolicy, ExportFormat, Granularity, ImportStatus,
+    JobStatus, Locus, LogLevel, LogSink, Progress, ProgressUpdate, Purpose, ReportFormat,
+    SIGNIFICANCE_LABELS, Storage, VariantQuery, Zygosity,
+};
 use std::env;
 use std::fs;
+use std::io::IsTerminal;
 use std::path::Path;
 
 fn print_usage_and_exit() -> ! {
     eprintln!(
-        "Usage:\n  biovault parse --file <input> --output <dir> [--name <name>] [--json]\n\n  Legacy (still supported):\n  biovault <input> <custom_name> <output_dir>\n\nNotes:\n  - <input> is a 23andMe .txt or .zip file\n  - <dir> is the output directory for generated files (created if missing)"
+        "Usage:\n  biovault parse --file <input> --output <dir> [--name <name>] [--json]\n  biovault import --file <input> --db <path> [--name <name>] [--person <person>] [--replace] [--json]\n  biovault batch (--dir <dir> | --manifest <csv>) (--output <dir> | --cohort <db>) [--workers <n>]\n      [--replace] [--json]\n  biovault cohort --db <path> [--db <path> ...] [--max-missing <0-1>] [--matrix <prefix>] [--stats <file>]\n      [--json]\n  biovault datasite --syftbox <dir> --email <email> --db <path> --allow <json> [--clinvar <db>] [--json]\n  biovault access-log (list|verify) --db <path> [--limit <n>] [--anchor <hash>] [--json]\n  biovault consent (list|grant|revoke|audit) --db <path> [--purpose <name|*>] [--recipient <who>]\n      [--granularity <individual|aggregate>] [--expires <rfc3339>] [--id <n>] [--limit <n>] [--json]\n  biovault genomes --db <path> [--json]\n  biovault query --db <path> [--genome <id>] [--rsid <list>] [--region <chr:start-end>] [--gene <symbol>]\n      [--genotype <pattern>] [--offset <n>] [--limit <n>] [--json]\n  biovault bundle export --db <path> --output <file> [--passphrase-file <file>] [--json]\n  biovault bundle import --file <bundle> --db <path> [--passphrase-file <file>] [--json]\n  biovault delete --db <path> [--genome <id>] [--json]\n  biovault redact --db <path> --locus <locus> [--locus <locus> ...] [--clinvar <db>] [--output <path>] [--json]\n  biovault export --db <path> --format <vcf|23andme|plink> --output <path> [--genome <id>] [--reference <db>] [--json]\n  biovault report --db <path> --output <file> [--format <html|pdf>] [--genome <id>] [--run <id>]\n  biovault analyze clinvar --genome <db> --clinvar <db> [--genome-id <id>] [--significance <list>]\n      [--min-stars <0-4>] [--gene <list>] [--zygosity <list>] [--format <table|json|tsv>]\n\n  Legacy (still supported):\n  biovault <input> <custom_name> <output_dir>\n\nNotes:\n  - <input> is a 23andMe .txt or .zip file\n  - <dir> is the output directory for generated files (created if missing)\n  - import adds the file to <path> (created if missing); re-imports of the same\n    file are skipped unless --replace is given\n  - batch imports every .txt/.zip in --dir, or the files of a path,name,format CSV\n    manifest, into one <name>.sqlite each under --output or all into --cohort;\n    it exits 1 if any file failed\n  - cohort aligns every genome in the given databases on position; markers missing\n    in more than --max-missing of samples (default 0.1) are dropped. --matrix writes\n    a PLINK .bed/.bim/.fam genotype matrix and --stats a TSV of allele frequencies,\n    missingness and Hardy-Weinberg p-values\n  - datasite answers the research jobs in <dir>/datasites/<email>/app_data/biovault/\n    inbox/<requester>/ that the --allow list permits, with differentially private\n    results in outbox/<requester>/; only requesters named in the list get a folder\n    they can write to, and each also needs an aggregate dp_query consent grant\n  - access-log lists every open, query, analysis and export of the database, newest\n    first; verify checks the hash chain (and that --anchor, a head hash it printed\n    earlier, is still in it) and exits 1 if entries were edited or removed\n  - consent grant adds a grant for --purpose (clinvar_analysis, export, report, cohort,\n    dp_query or *, the default) to --recipient (default self, the owner) at\n    --granularity (default aggregate); revoke takes the grant --id and audit lists\n    recorded checks, newest first. The owner (self) gets a grant for every purpose\n    on first use unless one was ever made; other recipients have none until added\n  - query lists stored genotype calls ordered by position; filters combine, --gene
+    takes a symbol from the bundled panel of screened genes (not genome-wide) and
+    --genotype a pattern with ? for one allele and * for any (e.g. A? or *T*). It
+    prints the --offset of the next page when there are more rows (--limit defaults
+    to 100, at most 1000)
+  - bundle moves a genome database and its analysis history between devices as one
+    encrypted file; the passphrase is read from --passphrase-file or the
+    BIOVAULT_PASSPHRASE environment variable. import refuses modified bundles and
+    upgrades databases from older versions
+  - delete securely removes a genome, its variants and its stored analyses; the
+    database file and its journal are zeroed and removed with the last genome
+  - redact strips each --locus (an rsID, a region such as 19:45409011-45412650, or a
+    gene symbol resolved through --clinvar) from every genome, into a copy at
+    --output or in place
+  - export --format plink treats --output as the .bed/.bim/.fam prefix; vcf needs\n    --reference, a database with a reference_alleles (chrom, pos, ref) table\n  - report uses the genome's latest stored ClinVar analysis unless --run is given;\n    the format defaults to the --output extension\n  - analyze filters take comma-separated lists; significance is one of\n    pathogenic, likely_pathogenic, uncertain_significance, conflicting, benign and\n    zygosity one of homozygous, heterozygous, hemizygous, non_carrier, unknown.\n    Exits 0 with findings, 3 when nothing matches, 1 on errors and 2 on bad usage\n  - set BIOVAULT_KEY to open genome databases encrypted with that key; import,\n    batch and bundle import then create encrypted databases\n  - set BIOVAULT_LOG=error|warn|info|debug|trace for library logs on stderr\n    (default warn)"
     );
     std::process::exit(2);
 }
 
+/// Key of encrypted genome databases, from the BIOVAULT_KEY environment
+/// variable. Never on the command line, where other processes can read it.
+fn database_key() -> Option<String> {
+    env::var("BIOVAULT_KEY").ok().filter(|key| !key.is_empty())
+}
+
+/// Library log events on stderr
+struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn log(&self, level: LogLevel, _target: &str, message: &str) {
+        eprintln!("[{:?}] {}", level, message);
+    }
+}
+
+/// Progress bar on stderr, drawn only when stderr is a terminal
+fn progress_bar<'a>() -> Progress<'a> {
+    if !std::io::stderr().is_terminal() {
+        return Progress::none();
+    }
+
+    Progress::new(|update: ProgressUpdate| {
+        const WIDTH: u64 = 30;
+        let line = if update.total > 0 {
+            let processed = update.processed.min(update.total);
+            let filled = (processed * WIDTH / update.total) as usize;
+            format!(
+                "{:<18} [{}{}] {:>3}% ({}/{})",
+                update.phase.name(),
+                "#".repeat(filled),
+                "-".repeat(WIDTH as usize - filled),
+                processed * 100 / update.total,
+                processed,
+                update.total
+            )
+        } else {
+            format!("{:<18} ...", update.phase.name())
+        };
+        eprint!("\r\x1b[2K{}", line);
+        if update.total > 0 && update.processed >= update.total {
+            eprintln!();
+        }
+    })
+}
+
 fn cmd_parse(mut args: impl Iterator<Item = String>) -> i32 {
     let mut file: Option<String> = None;
     let mut output: Option<String> = None;
@@ -88,56 +154,1792 @@ fn cmd_parse(mut args: impl Iterator<Item = String>) -> i32 {
             0
         }
         Err(err) => {
-            eprintln!("Error: {}", err);
+            eprintln!("Error [{}]: {}", err.code(), err);
             1
         }
     }
 }
 
-fn main() {
-    let mut args = env::args().skip(1);
-    match args.next() {
-        Some(cmd) if cmd == "parse" => {
-            let code = cmd_parse(args);
-            std::process::exit(code);
+fn cmd_import(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut file: Option<String> = None;
+    let mut db: Option<String> = None;
+    let mut name: Option<String> = None;
+    let mut person: Option<String> = None;
+    let mut replace = false;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => {
+                file = args.next();
+            }
+            "--db" => {
+                db = args.next();
+            }
+            "--name" => {
+                name = args.next();
+            }
+            "--person" => {
+                person = args.next();
+            }
+            "--replace" => {
+                replace = true;
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
         }
-        Some(first) => {
-            // Legacy positional invocation: <input> <custom_name> <output_dir>
-            let input_path = first;
-            let custom_name = args.next().unwrap_or_else(|| {
-                eprintln!("Missing <custom_name> argument");
-                print_usage_and_exit();
-            });
-            let output_dir = args.next().unwrap_or_else(|| {
-                eprintln!("Missing <output_dir> argument");
-                print_usage_and_exit();
+    }
+
+    let input_path = match file {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --file <path>");
+            return 2;
+        }
+    };
+    let db_path = match db {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --db <path>");
+            return 2;
+        }
+    };
+
+    let input = Path::new(&input_path);
+    if !input.exists() {
+        eprintln!("Input file not found: {}", input.display());
+        return 1;
+    }
+
+    let derived_name = name.unwrap_or_else(|| {
+        input
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("genome")
+            .to_string()
+    });
+    let policy = if replace {
+        DuplicatePolicy::Replace
+    } else {
+        DuplicatePolicy::Skip
+    };
+
+    match biovault_rust_lib::import_genome_with_progress_safe(
+        &input_path,
+        &db_path,
+        &derived_name,
+        person.as_deref(),
+        database_key().as_deref(),
+        policy,
+        &mut progress_bar(),
+    ) {
+        Ok(outcome) => {
+            if json {
+                match serde_json::to_string(&outcome) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                let status = match outcome.status {
+                    ImportStatus::Imported => "imported",
+                    ImportStatus::Skipped => "already imported, skipped",
+                    ImportStatus::Replaced => "replaced",
+                };
+                println!("genome {}: {}", outcome.genome_id, status);
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_batch(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut dir: Option<String> = None;
+    let mut manifest: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut cohort: Option<String> = None;
+    let mut workers: Option<String> = None;
+    let mut replace = false;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => {
+                dir = args.next();
+            }
+            "--manifest" => {
+                manifest = args.next();
+            }
+            "--output" => {
+                output = args.next();
+            }
+            "--cohort" => {
+                cohort = args.next();
+            }
+            "--workers" => {
+                workers = args.next();
+            }
+            "--replace" => {
+                replace = true;
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let entries = match (dir, manifest) {
+        (Some(dir), None) => biovault_rust_lib::batch_entries_from_directory_safe(&dir),
+        (None, Some(manifest)) => biovault_rust_lib::batch_entries_from_manifest_safe(&manifest),
+        _ => {
+            eprintln!("Give exactly one of --dir <dir> or --manifest <csv>");
+            return 2;
+        }
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            return 1;
+        }
+    };
+    let target = match (output, cohort) {
+        (Some(output), None) => BatchTarget::PerFile(output.into()),
+        (None, Some(cohort)) => BatchTarget::Cohort(cohort.into()),
+        _ => {
+            eprintln!("Give exactly one of --output <dir> or --cohort <db>");
+            return 2;
+        }
+    };
+    let workers = match workers.map(|w| w.parse::<usize>()) {
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => {
+            eprintln!("--workers must be a positive number");
+            return 2;
+        }
+        None => None,
+    };
+    let policy = if replace {
+        DuplicatePolicy::Replace
+    } else {
+        DuplicatePolicy::Skip
+    };
+
+    let summary = match biovault_rust_lib::import_batch_safe(
+        &entries,
+        &target,
+        workers,
+        database_key().as_deref(),
+        policy,
+    ) {
+        Ok(summary) => summary,
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            return 1;
+        }
+    };
+
+    if json {
+        match serde_json::to_string(&summary) {
+            Ok(s) => println!("{}", s),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return 1;
+            }
+        }
+    } else {
+        for item in &summary.items {
+            match (&item.outcome, &item.error) {
+                (Some(outcome), _) => {
+                    let status = match outcome.status {
+                        ImportStatus::Imported => "imported",
+                        ImportStatus::Skipped => "already imported, skipped",
+                        ImportStatus::Replaced => "replaced",
+                    };
+                    println!(
+                        "{}: {} as genome {} in {}",
+                        item.path, status, outcome.genome_id, outcome.db_path
+                    );
+                }
+                (None, Some(error)) => {
+                    println!("{}: failed [{}] {}", item.path, error.code, error.message)
+                }
+                (None, None) => println!("{}: failed", item.path),
+            }
+            for diagnostic in &item.diagnostics {
+                println!("  warning: {}", diagnostic);
+            }
+        }
+        println!(
+            "{} files: {} imported, {} skipped, {} replaced, {} failed",
+            summary.items.len(),
+            summary.imported,
+            summary.skipped,
+            summary.replaced,
+            summary.failed
+        );
+    }
+
+    if summary.failed > 0 { 1 } else { 0 }
+}
+
+fn cmd_cohort(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut dbs: Vec<String> = Vec::new();
+    let mut max_missing: Option<String> = None;
+    let mut matrix: Option<String> = None;
+    let mut stats: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                dbs.extend(args.next());
+            }
+            "--max-missing" => {
+                max_missing = args.next();
+            }
+            "--matrix" => {
+                matrix = args.next();
+            }
+            "--stats" => {
+                stats = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    if dbs.is_empty() {
+        eprintln!("Missing required --db <path>");
+        return 2;
+    }
+    let mut options = CohortOptions::default();
+    match max_missing.map(|m| m.parse::<f64>()) {
+        Some(Ok(m)) if (0.0..=1.0).contains(&m) => options.max_missingness = m,
+        Some(_) => {
+            eprintln!("--max-missing must be a number between 0 and 1");
+            return 2;
+        }
+        None => {}
+    }
+
+    let db_paths: Vec<&str> = dbs.iter().map(String::as_str).collect();
+    let result =
+        biovault_rust_lib::load_cohort_safe(&db_paths, database_key().as_deref(), &options)
+            .and_then(|cohort| {
+                let mut output_files = Vec::new();
+                if let Some(prefix) = &matrix {
+                    output_files.extend(cohort.write_plink(prefix)?);
+                }
+                if let Some(path) = &stats {
+                    cohort.write_stats(Path::new(path))?;
+                    output_files.push(path.clone());
+                }
+                Ok((cohort, output_files))
             });
+    let (cohort, output_files) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            return 1;
+        }
+    };
 
-            let input = Path::new(&input_path);
-            if !input.exists() {
-                eprintln!("Input file not found: {}", input.display());
-                std::process::exit(1);
+    let summary = cohort.summary();
+    if json {
+        let value = serde_json::json!({
+            "summary": summary,
+            "output_files": output_files,
+        });
+        println!("{}", value);
+    } else {
+        println!("{:<24} {:>10}  DATABASE", "SAMPLE", "CALL RATE");
+        for sample in &summary.samples {
+            println!(
+                "{:<24} {:>9.2}%  {} (genome {})",
+                sample.name,
+                sample.call_rate * 100.0,
+                sample.db_path,
+                sample.genome_id
+            );
+        }
+        println!(
+            "{} markers kept of {} ({} too sparse, {} multiallelic)",
+            summary.markers_kept,
+            summary.markers_seen,
+            summary.markers_too_sparse,
+            summary.markers_multiallelic
+        );
+        for path in &output_files {
+            println!("wrote {}", path);
+        }
+    }
+    0
+}
+
+fn cmd_datasite(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut syftbox: Option<String> = None;
+    let mut email: Option<String> = None;
+    let mut db: Option<String> = None;
+    let mut allow: Option<String> = None;
+    let mut clinvar: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--syftbox" => {
+                syftbox = args.next();
             }
-            let out = Path::new(&output_dir);
-            if !out.exists()
-                && let Err(e) = fs::create_dir_all(out)
-            {
-                eprintln!("Failed to create output directory {}: {}", out.display(), e);
-                std::process::exit(1);
+            "--email" => {
+                email = args.next();
             }
-            if !out.is_dir() {
-                eprintln!("Output path is not a directory: {}", out.display());
-                std::process::exit(1);
+            "--db" => {
+                db = args.next();
+            }
+            "--allow" => {
+                allow = args.next();
+            }
+            "--clinvar" => {
+                clinvar = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
             }
+        }
+    }
 
-            match biovault_rust_lib::process_23andme(&input_path, &custom_name, &output_dir) {
-                Ok(db_path) => println!("{}", db_path),
-                Err(err) => {
-                    eprintln!("Error: {}", err);
-                    std::process::exit(1);
+    let (Some(syftbox), Some(email), Some(db), Some(allow)) = (syftbox, email, db, allow) else {
+        eprintln!(
+            "Missing required --syftbox <dir>, --email <email>, --db <path> or --allow <json>"
+        );
+        return 2;
+    };
+    let config = DatasiteConfig {
+        syftbox_dir: syftbox.into(),
+        email,
+        db_path: db.into(),
+        key: database_key(),
+        clinvar_db_path: clinvar.map(Into::into),
+    };
+
+    match biovault_rust_lib::process_datasite_jobs_safe(&config, &allow) {
+        Ok(outcomes) => {
+            if json {
+                match serde_json::to_string(&outcomes) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
                 }
+            } else {
+                for outcome in &outcomes {
+                    let status = match outcome.status {
+                        JobStatus::Answered => "answered",
+                        JobStatus::Rejected => "rejected",
+                        JobStatus::Failed => "failed",
+                    };
+                    println!(
+                        "{} from {}: {}{}",
+                        outcome.id,
+                        outcome.requester.as_deref().unwrap_or("unknown"),
+                        status,
+                        outcome
+                            .reason
+                            .as_deref()
+                            .map(|reason| format!(" ({})", reason))
+                            .unwrap_or_default()
+                    );
+                }
+                println!("{} jobs processed", outcomes.len());
             }
+            0
         }
-        None => print_usage_and_exit(),
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_access_log(mut args: impl Iterator<Item = String>) -> i32 {
+    let verify = match args.next().as_deref() {
+        Some("list") => false,
+        Some("verify") => true,
+        Some(other) => {
+            eprintln!(
+                "Unknown access-log action: {}; available: list, verify",
+                other
+            );
+            return 2;
+        }
+        None => {
+            eprintln!("Missing access-log action; available: list, verify");
+            return 2;
+        }
+    };
+
+    let mut db: Option<String> = None;
+    let mut limit: Option<i64> = None;
+    let mut anchor: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--limit" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(v) => limit = Some(v),
+                None => {
+                    eprintln!("--limit must be a number");
+                    return 2;
+                }
+            },
+            "--anchor" => {
+                anchor = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let Some(db_path) = db else {
+        eprintln!("Missing required --db <path>");
+        return 2;
+    };
+
+    if verify {
+        return match biovault_rust_lib::verify_access_log_safe(
+            &db_path,
+            database_key().as_deref(),
+            anchor.as_deref(),
+        ) {
+            Ok(verification) => {
+                if json {
+                    match serde_json::to_string(&verification) {
+                        Ok(s) => println!("{}", s),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            return 1;
+                        }
+                    }
+                } else if verification.intact {
+                    println!(
+                        "Access log intact: {} entries, head {}",
+                        verification.entries,
+                        verification.head_hash.as_deref().unwrap_or("-")
+                    );
+                } else {
+                    println!(
+                        "Access log TAMPERED: {}",
+                        verification.problem.as_deref().unwrap_or("unknown problem")
+                    );
+                }
+                if verification.intact { 0 } else { 1 }
+            }
+            Err(err) => {
+                eprintln!("Error [{}]: {}", err.code(), err);
+                1
+            }
+        };
+    }
+
+    match biovault_rust_lib::list_access_log_safe(&db_path, database_key().as_deref(), limit) {
+        Ok(entries) => {
+            if json {
+                match serde_json::to_string(&entries) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                for entry in &entries {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{} rows",
+                        entry.created_at,
+                        entry.event.map(AccessEvent::name).unwrap_or("unknown"),
+                        entry.caller,
+                        entry.purpose.map(Purpose::name).unwrap_or("-"),
+                        entry.rows
+                    );
+                }
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn print_grant(grant: &ConsentGrant) {
+    let status = if grant.revoked_at.is_some() {
+        "revoked".to_string()
+    } else {
+        match &grant.expires_at {
+            Some(expires_at) => format!("until {}", expires_at),
+            None => "active".to_string(),
+        }
+    };
+    println!(
+        "{}\t{}\t{}\t{}\t{}",
+        grant.id,
+        grant.purpose.label(),
+        grant.recipient,
+        grant.granularity.name(),
+        status
+    );
+}
+
+fn cmd_consent(mut args: impl Iterator<Item = String>) -> i32 {
+    let action = match args.next() {
+        Some(action) if ["list", "grant", "revoke", "audit"].contains(&action.as_str()) => action,
+        Some(other) => {
+            eprintln!(
+                "Unknown consent action: {}; available: list, grant, revoke, audit",
+                other
+            );
+            return 2;
+        }
+        None => {
+            eprintln!("Missing consent action; available: list, grant, revoke, audit");
+            return 2;
+        }
+    };
+
+    let mut db: Option<String> = None;
+    let mut purpose: Option<Purpose> = None;
+    let mut recipient = CONSENT_OWNER.to_string();
+    let mut granularity = Granularity::Aggregate;
+    let mut expires: Option<String> = None;
+    let mut id: Option<i64> = None;
+    let mut limit: Option<i64> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--purpose" => match args.next().as_deref() {
+                Some("*") => purpose = None,
+                Some(name) => match Purpose::from_name(name) {
+                    Some(p) => purpose = Some(p),
+                    None => {
+                        eprintln!("Unknown purpose: {}", name);
+                        return 2;
+                    }
+                },
+                None => {
+                    eprintln!("Missing value for --purpose");
+                    return 2;
+                }
+            },
+            "--recipient" => match args.next() {
+                Some(value) => recipient = value,
+                None => {
+                    eprintln!("Missing value for --recipient");
+                    return 2;
+                }
+            },
+            "--granularity" => match args.next().as_deref().and_then(Granularity::from_name) {
+                Some(g) => granularity = g,
+                None => {
+                    eprintln!("--granularity must be individual or aggregate");
+                    return 2;
+                }
+            },
+            "--expires" => {
+                expires = args.next();
+            }
+            "--id" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(v) => id = Some(v),
+                None => {
+                    eprintln!("--id must be a grant id");
+                    return 2;
+                }
+            },
+            "--limit" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(v) => limit = Some(v),
+                None => {
+                    eprintln!("--limit must be a number");
+                    return 2;
+                }
+            },
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let Some(db_path) = db else {
+        eprintln!("Missing required --db <path>");
+        return 2;
+    };
+
+    let output = match action.as_str() {
+        "list" => biovault_rust_lib::list_consent_grants_safe(&db_path, database_key().as_deref())
+            .map(|grants| {
+                if json {
+                    serde_json::to_string(&grants)
+                } else {
+                    grants.iter().for_each(print_grant);
+                    Ok(String::new())
+                }
+            }),
+        "grant" => biovault_rust_lib::grant_consent_safe(
+            &db_path,
+            database_key().as_deref(),
+            purpose,
+            &recipient,
+            granularity,
+            expires.as_deref(),
+        )
+        .map(|grant| {
+            if json {
+                serde_json::to_string(&grant)
+            } else {
+                print_grant(&grant);
+                Ok(String::new())
+            }
+        }),
+        "revoke" => {
+            let Some(id) = id else {
+                eprintln!("Missing required --id <n>");
+                return 2;
+            };
+            match biovault_rust_lib::revoke_consent_safe(&db_path, database_key().as_deref(), id) {
+                Ok(true) => Ok(Ok(format!("Revoked grant {}", id))),
+                Ok(false) => {
+                    eprintln!("Grant {} is not active", id);
+                    return 1;
+                }
+                Err(err) => Err(err),
+            }
+        }
+        _ => biovault_rust_lib::list_consent_audit_safe(&db_path, database_key().as_deref(), limit)
+            .map(|decisions| {
+                if json {
+                    serde_json::to_string(&decisions)
+                } else {
+                    for decision in &decisions {
+                        println!(
+                            "{}\t{}\t{}\t{}\t{}",
+                            decision.created_at,
+                            decision.purpose.name(),
+                            decision.recipient,
+                            decision.granularity.name(),
+                            if decision.allowed {
+                                "allowed"
+                            } else {
+                                "denied"
+                            }
+                        );
+                    }
+                    Ok(String::new())
+                }
+            }),
+    };
+
+    match output {
+        Ok(Ok(text)) => {
+            if !text.is_empty() {
+                println!("{}", text);
+            }
+            0
+        }
+        Ok(Err(e)) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_genomes(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut db: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let db_path = match db {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --db <path>");
+            return 2;
+        }
+    };
+
+    match biovault_rust_lib::list_genomes_safe(&db_path, database_key().as_deref()) {
+        Ok(genomes) => {
+            if json {
+                match serde_json::to_string(&genomes) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                for genome in genomes {
+                    println!(
+                        "{}\t{}\t{}\t{} variants",
+                        genome.id,
+                        genome.name,
+                        genome.person.as_deref().unwrap_or("-"),
+                        genome.total_variants
+                    );
+                }
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_query(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut db: Option<String> = None;
+    let mut query = VariantQuery::default();
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().ok_or_else(|| {
+                eprintln!("Missing value for {}", name);
+                2
+            })
+        };
+        let number = |name: &str, value: String| {
+            value.parse::<u32>().map_err(|_| {
+                eprintln!("{} must be a non-negative number", name);
+                2
+            })
+        };
+        let parsed: Result<(), i32> = match arg.as_str() {
+            "--db" => value("--db").map(|v| db = Some(v)),
+            "--genome" => value("--genome").and_then(|v| match v.parse::<i64>() {
+                Ok(id) => {
+                    query.genome_id = Some(id);
+                    Ok(())
+                }
+                Err(_) => {
+                    eprintln!("--genome must be a genome id");
+                    Err(2)
+                }
+            }),
+            "--rsid" => value("--rsid").map(|v| query.rsids.extend(split_list(&v))),
+            "--region" => value("--region").and_then(|v| {
+                let region = v.split_once(':').and_then(|(chromosome, range)| {
+                    let (start, end) = range.split_once('-').unwrap_or((range, range));
+                    Some((
+                        chromosome.to_string(),
+                        start.replace(',', "").parse::<i64>().ok()?,
+                        end.replace(',', "").parse::<i64>().ok()?,
+                    ))
+                });
+                match region {
+                    Some((chromosome, start, end)) => {
+                        query.chromosome = Some(chromosome);
+                        query.start = Some(start);
+                        query.end = Some(end);
+                        Ok(())
+                    }
+                    // A bare chromosome selects all of it
+                    None if !v.contains(':') => {
+                        query.chromosome = Some(v);
+                        Ok(())
+                    }
+                    None => {
+                        eprintln!("--region must look like 19:45409011-45412650");
+                        Err(2)
+                    }
+                }
+            }),
+            "--gene" => value("--gene").map(|v| query.gene = Some(v)),
+            "--genotype" => value("--genotype").map(|v| query.genotype = Some(v)),
+            "--offset" => value("--offset")
+                .and_then(|v| number("--offset", v))
+                .map(|n| query.offset = n),
+            "--limit" => value("--limit")
+                .and_then(|v| number("--limit", v))
+                .map(|n| query.limit = n),
+            "--json" => {
+                json = true;
+                Ok(())
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                Err(2)
+            }
+        };
+        if let Err(code) = parsed {
+            return code;
+        }
+    }
+
+    let Some(db_path) = db else {
+        eprintln!("Missing required --db <path>");
+        return 2;
+    };
+
+    match biovault_rust_lib::query_variants_safe(&db_path, database_key().as_deref(), &query) {
+        Ok(page) => {
+            if json {
+                match serde_json::to_string(&page) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                for variant in &page.variants {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        variant.genome_id,
+                        variant.rsid.as_deref().unwrap_or("-"),
+                        variant.chromosome,
+                        variant.position,
+                        variant.genotype
+                    );
+                }
+                if let Some(next_offset) = page.next_offset {
+                    println!("More variants: --offset {}", next_offset);
+                }
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_bundle(mut args: impl Iterator<Item = String>) -> i32 {
+    let export = match args.next().as_deref() {
+        Some("export") => true,
+        Some("import") => false,
+        Some(other) => {
+            eprintln!(
+                "Unknown bundle action: {}; available: export, import",
+                other
+            );
+            return 2;
+        }
+        None => {
+            eprintln!("Missing bundle action; available: export, import");
+            return 2;
+        }
+    };
+
+    let mut db: Option<String> = None;
+    let mut file: Option<String> = None;
+    let mut passphrase_file: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--output" if export => {
+                file = args.next();
+            }
+            "--file" if !export => {
+                file = args.next();
+            }
+            "--passphrase-file" => {
+                passphrase_file = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let (Some(db_path), Some(bundle_path)) = (db, file) else {
+        if export {
+            eprintln!("Missing required --db <path> or --output <file>");
+        } else {
+            eprintln!("Missing required --file <bundle> or --db <path>");
+        }
+        return 2;
+    };
+    // Never on the command line, where other processes can read it
+    let passphrase = match passphrase_file {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(text) => text.trim_end_matches(['\r', '\n']).to_string(),
+            Err(e) => {
+                eprintln!("Error: cannot read {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => match env::var("BIOVAULT_PASSPHRASE") {
+            Ok(passphrase) => passphrase,
+            Err(_) => {
+                eprintln!("Missing passphrase: use --passphrase-file or BIOVAULT_PASSPHRASE");
+                return 2;
+            }
+        },
+    };
+
+    let result = if export {
+        biovault_rust_lib::export_bundle_safe(
+            &db_path,
+            database_key().as_deref(),
+            &bundle_path,
+            &passphrase,
+        )
+    } else {
+        biovault_rust_lib::import_bundle_safe(
+            &bundle_path,
+            &passphrase,
+            &db_path,
+            database_key().as_deref(),
+        )
+    };
+    match result {
+        Ok(manifest) => {
+            if json {
+                match serde_json::to_string(&manifest) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                println!(
+                    "{} {} genomes and {} analysis runs into {}",
+                    if export { "Bundled" } else { "Restored" },
+                    manifest.genomes.len(),
+                    manifest.analysis_runs,
+                    if export { &bundle_path } else { &db_path }
+                );
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_delete(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut db: Option<String> = None;
+    let mut genome_id: Option<i64> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--genome" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(v) => genome_id = Some(v),
+                None => {
+                    eprintln!("--genome must be a genome id");
+                    return 2;
+                }
+            },
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let Some(db_path) = db else {
+        eprintln!("Missing required --db <path>");
+        return 2;
+    };
+
+    match biovault_rust_lib::delete_genome_safe(&db_path, database_key().as_deref(), genome_id) {
+        Ok(report) => {
+            if json {
+                match serde_json::to_string(&report) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                println!(
+                    "Deleted genome {}: {} variants, {} analysis runs",
+                    report.genome_id, report.variants_deleted, report.analysis_runs_deleted
+                );
+                if report.database_removed {
+                    println!("Removed {}", report.files_removed.join(", "));
+                }
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_redact(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut db: Option<String> = None;
+    let mut loci = Vec::new();
+    let mut clinvar: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--locus" => match args.next().map(|v| Locus::parse(&v)) {
+                Some(Ok(locus)) => loci.push(locus),
+                Some(Err(err)) => {
+                    eprintln!("{}", err);
+                    return 2;
+                }
+                None => {
+                    eprintln!("Missing value for --locus");
+                    return 2;
+                }
+            },
+            "--clinvar" => {
+                clinvar = args.next();
+            }
+            "--output" => {
+                output = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let Some(db_path) = db else {
+        eprintln!("Missing required --db <path>");
+        return 2;
+    };
+    if loci.is_empty() {
+        eprintln!("Missing required --locus <locus>");
+        return 2;
+    }
+
+    match biovault_rust_lib::redact_loci_safe(
+        &db_path,
+        database_key().as_deref(),
+        &loci,
+        clinvar.as_deref(),
+        output.as_deref(),
+    ) {
+        Ok(summary) => {
+            if json {
+                match serde_json::to_string(&summary) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                println!(
+                    "Removed {} variants and {} findings; wrote {}",
+                    summary.variants_removed, summary.findings_removed, summary.output_path
+                );
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_export(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut db: Option<String> = None;
+    let mut format: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut genome: Option<String> = None;
+    let mut reference: Option<String> = None;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--format" => {
+                format = args.next();
+            }
+            "--output" => {
+                output = args.next();
+            }
+            "--genome" => {
+                genome = args.next();
+            }
+            "--reference" => {
+                reference = args.next();
+            }
+            "--json" => {
+                json = true;
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let db_path = match db {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --db <path>");
+            return 2;
+        }
+    };
+    let format = match format.as_deref().map(ExportFormat::from_name) {
+        Some(Some(f)) => f,
+        Some(None) => {
+            eprintln!("Unknown --format; expected vcf, 23andme or plink");
+            return 2;
+        }
+        None => {
+            eprintln!("Missing required --format <vcf|23andme|plink>");
+            return 2;
+        }
+    };
+    let output_path = match output {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --output <path>");
+            return 2;
+        }
+    };
+    let genome_id = match genome.map(|g| g.parse::<i64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            eprintln!("--genome must be a genome id");
+            return 2;
+        }
+        None => None,
+    };
+
+    match biovault_rust_lib::export_genome_safe(
+        &db_path,
+        database_key().as_deref(),
+        genome_id,
+        format,
+        &output_path,
+        reference.as_deref(),
+    ) {
+        Ok(summary) => {
+            if json {
+                match serde_json::to_string(&summary) {
+                    Ok(s) => println!("{}", s),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                for file in &summary.output_files {
+                    println!("{}", file);
+                }
+                eprintln!(
+                    "Wrote {} variants ({} skipped)",
+                    summary.variants_written, summary.variants_skipped
+                );
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+fn cmd_report(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut db: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut format: Option<String> = None;
+    let mut genome: Option<String> = None;
+    let mut run: Option<String> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => {
+                db = args.next();
+            }
+            "--output" => {
+                output = args.next();
+            }
+            "--format" => {
+                format = args.next();
+            }
+            "--genome" => {
+                genome = args.next();
+            }
+            "--run" => {
+                run = args.next();
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+
+    let db_path = match db {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --db <path>");
+            return 2;
+        }
+    };
+    let output_path = match output {
+        Some(p) => p,
+        None => {
+            eprintln!("Missing required --output <file>");
+            return 2;
+        }
+    };
+    let format = match format {
+        Some(name) => ReportFormat::from_name(&name),
+        None => ReportFormat::from_path(&output_path),
+    };
+    let Some(format) = format else {
+        eprintln!("Unknown report format; use --format html or --format pdf");
+        return 2;
+    };
+    let genome_id = match genome.map(|g| g.parse::<i64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            eprintln!("--genome must be a genome id");
+            return 2;
+        }
+        None => None,
+    };
+    let run_id = match run.map(|r| r.parse::<i64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            eprintln!("--run must be an analysis run id");
+            return 2;
+        }
+        None => None,
+    };
+
+    match biovault_rust_lib::generate_report_safe(
+        &db_path,
+        database_key().as_deref(),
+        genome_id,
+        run_id,
+        format,
+        &output_path,
+    ) {
+        Ok(()) => {
+            println!("{}", output_path);
+            0
+        }
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            1
+        }
+    }
+}
+
+/// `analyze` exits with this when the analysis ran but nothing matched
+const EXIT_NO_FINDINGS: i32 = 3;
+
+/// Output layouts for `analyze`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AnalyzeOutput {
+    Table,
+    Json,
+    Tsv,
+}
+
+fn cmd_analyze(mut args: impl Iterator<Item = String>) -> i32 {
+    match args.next().as_deref() {
+        Some("clinvar") => cmd_analyze_clinvar(args),
+        Some(other) => {
+            eprintln!("Unknown analysis: {}; available: clinvar", other);
+            2
+        }
+        None => {
+            eprintln!("Missing analysis; available: clinvar");
+            2
+        }
+    }
+}
+
+/// Split a comma-separated option value, dropping empty items
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn cmd_analyze_clinvar(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut genome: Option<String> = None;
+    let mut clinvar: Option<String> = None;
+    let mut genome_id: Option<String> = None;
+    let mut output = AnalyzeOutput::Table;
+    let mut filter = ClinVarFilter::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().ok_or_else(|| {
+                eprintln!("Missing value for {}", name);
+                2
+            })
+        };
+        let parsed: Result<(), i32> = match arg.as_str() {
+            "--genome" => value("--genome").map(|v| genome = Some(v)),
+            "--clinvar" => value("--clinvar").map(|v| clinvar = Some(v)),
+            "--genome-id" => value("--genome-id").map(|v| genome_id = Some(v)),
+            "--significance" => value("--significance").and_then(|v| {
+                for label in split_list(&v) {
+                    let known = SIGNIFICANCE_LABELS
+                        .iter()
+                        .find(|known| known.eq_ignore_ascii_case(&label));
+                    let Some(known) = known else {
+                        eprintln!("Unknown significance: {}", label);
+                        return Err(2);
+                    };
+                    filter.significance.push(known.to_string());
+                }
+                Ok(())
+            }),
+            "--min-stars" => value("--min-stars").and_then(|v| match v.parse::<u8>() {
+                Ok(stars) if stars <= 4 => {
+                    filter.min_stars = stars;
+                    Ok(())
+                }
+                _ => {
+                    eprintln!("--min-stars must be 0 to 4");
+                    Err(2)
+                }
+            }),
+            "--gene" => value("--gene").map(|v| filter.genes.extend(split_list(&v))),
+            "--zygosity" => value("--zygosity").and_then(|v| {
+                for name in split_list(&v) {
+                    let Some(zygosity) = Zygosity::from_name(&name) else {
+                        eprintln!("Unknown zygosity: {}", name);
+                        return Err(2);
+                    };
+                    filter.zygosity.push(zygosity);
+                }
+                Ok(())
+            }),
+            "--format" => value("--format").and_then(|v| {
+                output = match v.as_str() {
+                    "table" => AnalyzeOutput::Table,
+                    "json" => AnalyzeOutput::Json,
+                    "tsv" => AnalyzeOutput::Tsv,
+                    _ => {
+                        eprintln!("Unknown --format; expected table, json or tsv");
+                        return Err(2);
+                    }
+                };
+                Ok(())
+            }),
+            "--json" => {
+                output = AnalyzeOutput::Json;
+                Ok(())
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                Err(2)
+            }
+        };
+        if let Err(code) = parsed {
+            return code;
+        }
+    }
+
+    let Some(genome_path) = genome else {
+        eprintln!("Missing required --genome <db>");
+        return 2;
+    };
+    let Some(clinvar_path) = clinvar else {
+        eprintln!("Missing required --clinvar <db>");
+        return 2;
+    };
+    let genome_id = match genome_id.map(|g| g.parse::<i64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            eprintln!("--genome-id must be a genome id");
+            return 2;
+        }
+        None => None,
+    };
+
+    let result = biovault_rust_lib::analyze_clinvar_storage_safe(
+        &Storage::from(Path::new(&genome_path)),
+        database_key().as_deref(),
+        genome_id,
+        &Storage::from(Path::new(&clinvar_path)),
+        &mut progress_bar(),
+    );
+    let result = match result {
+        Ok(result) => result.filtered(&filter),
+        Err(err) => {
+            eprintln!("Error [{}]: {}", err.code(), err);
+            return 1;
+        }
+    };
+
+    match output {
+        AnalyzeOutput::Json => match serde_json::to_string(&result) {
+            Ok(s) => println!("{}", s),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return 1;
+            }
+        },
+        AnalyzeOutput::Tsv => print_findings_tsv(&result),
+        AnalyzeOutput::Table => print_findings_table(&result),
+    }
+
+    if result.matches.is_empty() {
+        EXIT_NO_FINDINGS
+    } else {
+        0
+    }
+}
+
+const FINDING_COLUMNS: [&str; 8] = [
+    "gene",
+    "rsid",
+    "significance",
+    "stars",
+    "zygosity",
+    "genotype",
+    "review_status",
+    "condition",
+];
+
+/// The columns of one finding, in `FINDING_COLUMNS` order
+fn finding_row(variant: &biovault_rust_lib::ClinVarVariant) -> [String; 8] {
+    [
+        variant.gene.clone(),
+        variant.rsid.clone(),
+        variant.clnsig.clone(),
+        variant.review_stars().to_string(),
+        variant.zygosity().name().to_string(),
+        variant.user_genotype.clone().unwrap_or_default(),
+        variant.clnrevstat.clone(),
+        variant.condition.clone(),
+    ]
+}
+
+fn print_findings_tsv(result: &AnalysisResult) {
+    println!("{}", FINDING_COLUMNS.join("\t"));
+    for variant in &result.matches {
+        let row = finding_row(variant).map(|field| field.replace(['\t', '\n'], " "));
+        println!("{}", row.join("\t"));
+    }
+}
+
+fn print_findings_table(result: &AnalysisResult) {
+    if result.matches.is_empty() {
+        println!(
+            "No ClinVar findings ({} rsIDs searched)",
+            result.rsids_searched
+        );
+        return;
+    }
+
+    // Review status is long and mostly implied by the stars
+    let columns = [0, 1, 2, 3, 4, 5, 7];
+    let rows: Vec<[String; 8]> = result.matches.iter().map(finding_row).collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|&c| {
+            rows.iter()
+                .map(|row| row[c].chars().count())
+                .chain([FINDING_COLUMNS[c].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(columns.iter().map(|&c| FINDING_COLUMNS[c]).collect());
+    for row in &rows {
+        line(columns.iter().map(|&c| row[c].as_str()).collect());
+    }
+    println!(
+        "\n{} findings in {} genes ({} rsIDs searched)",
+        result.matches_found,
+        result.gene_groups.len(),
+        result.rsids_searched
+    );
+}
+
+fn main() {
+    let level = env::var("BIOVAULT_LOG")
+        .ok()
+        .and_then(|name| LogLevel::from_name(&name))
+        .unwrap_or(LogLevel::Warn);
+    biovault_rust_lib::set_log_sink(Some(Box::new(StderrSink)), level);
+    biovault_rust_lib::set_access_caller(Some("cli".to_string()));
+
+    let mut args = env::args().skip(1);
+    match args.next() {
+        Some(cmd) if cmd == "parse" => {
+            let code = cmd_parse(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "import" => {
+            let code = cmd_import(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "batch" => {
+            let code = cmd_batch(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "cohort" => {
+            let code = cmd_cohort(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "datasite" => {
+            let code = cmd_datasite(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "access-log" => {
+            let code = cmd_access_log(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "consent" => {
+            let code = cmd_consent(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "genomes" => {
+            let code = cmd_genomes(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "query" => {
+            let code = cmd_query(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "bundle" => {
+            let code = cmd_bundle(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "delete" => {
+            let code = cmd_delete(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "redact" => {
+            let code = cmd_redact(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "export" => {
+            let code = cmd_export(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "report" => {
+            let code = cmd_report(args);
+            std::process::exit(code);
+        }
+        Some(cmd) if cmd == "analyze" => {
+            let code = cmd_analyze(args);
+            std::process::exit(code);
+        }
+        Some(first) => {
+            // Legacy positional invocation: <input> <custom_name> <output_dir>
+            let input_path = first;
+            let custom_name = args.next().unwrap_or_else(|| {
+                eprintln!("Missing <custom_name> argument");
+                print_usage_and_exit();
+            });
+            let output_dir = args.next().unwrap_or_else(|| {
+                eprintln!("Missing <output_dir> argument");
+                print_usage_and_exit();
+            });
+
+            let input = Path::new(&input_path);
+            if !input.exists() {
+                eprintln!("Input file not found: {}", input.display());
+                std::process::exit(1);
+            }
+            let out = Path::new(&output_dir);
+            if !out.exists()
+                && let Err(e) = fs::create_dir_all(out)
+            {
+                eprintln!("Failed to create output directory {}: {}", out.display(), e);
+                std::process::exit(1);
+            }
+            if !out.is_dir() {
+                eprintln!("Output path is not a directory: {}", out.display());
+                std::process::exit(1);
+            }
+
+            match biovault_rust_lib::process_23andme(&input_path, &custom_name, &output_dir) {
+                Ok(db_path) => println!("{}", db_path),
+                Err(err) => {
+                    eprintln!("Error [{}]: {}", err.code(), err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => print_usage_and_exit(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A genome with two calls and a ClinVar release matching the first
+    fn fixtures(dir: &Path) -> (String, String) {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\nrs1\t1\t100\tAG\nrs2\t1\t200\tCC\n",
+        )
+        .unwrap();
+        let genome = dir.join("genome.sqlite").to_string_lossy().into_owned();
+        biovault_rust_lib::import_genome_safe(
+            input.to_str().unwrap(),
+            &genome,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        let clinvar = dir.join("clinvar.sqlite");
+        rusqlite::Connection::open(&clinvar)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                     gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);
+                 INSERT INTO variants VALUES ('rs1', '1', 100, 'A', 'G', 'GENE1',
+                     'Conflicting_classifications_of_pathogenicity',
+                     'criteria_provided,_conflicting_classifications', 'Some_condition');",
+            )
+            .unwrap();
+        (genome, clinvar.to_string_lossy().into_owned())
+    }
+
+    fn analyze(args: &[&str]) -> i32 {
+        cmd_analyze(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn analyze_exit_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (genome, clinvar) = fixtures(dir.path());
+        let base = ["clinvar", "--genome", &genome, "--clinvar", &clinvar];
+        let with = |extra: &[&'static str]| {
+            let mut args = base.to_vec();
+            args.extend_from_slice(extra);
+            analyze(&args)
+        };
+
+        assert_eq!(with(&["--format", "json"]), 0);
+        assert_eq!(with(&["--significance", "conflicting", "--json"]), 0);
+        assert_eq!(
+            with(&["--significance", "pathogenic", "--json"]),
+            EXIT_NO_FINDINGS
+        );
+
+        let missing = dir.path().join("missing.sqlite");
+        let missing = missing.to_str().unwrap();
+        assert_eq!(
+            analyze(&["clinvar", "--genome", &genome, "--clinvar", missing]),
+            1
+        );
+
+        assert_eq!(with(&["--significance", "harmful"]), 2);
+        assert_eq!(with(&["--min-stars", "5"]), 2);
+        assert_eq!(analyze(&["clinvar", "--genome", &genome]), 2);
+        assert_eq!(analyze(&["prs"]), 2);
+        assert_eq!(analyze(&[]), 2);
     }
 }
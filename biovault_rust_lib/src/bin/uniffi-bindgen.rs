@@ -0,0 +1,6 @@
+//! Generates the Swift and Kotlin bindings from the built library:
+//! `cargo run --features uniffi-cli --bin uniffi-bindgen generate --library <lib> --language swift --out-dir <dir>`
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,569 @@
+//! Swift and Kotlin API generated with UniFFI.
+//!
+//! These functions mirror the public safe API but return typed records
+//! (`AnalysisResult`, `GenomeRecord`, ...) instead of JSON strings. The
+//! bindings are generated from the built library with the `uniffi-bindgen`
+//! binary. The hand-written C functions and JNI entrypoints stay available
+//! for existing callers.
+
+use crate::access_log::{self, AccessLogEntry, AccessLogVerification};
+use crate::analysis::{self, AnalysisResult};
+#[cfg(not(target_family = "wasm"))]
+use crate::bundle::{self, BundleManifest};
+use crate::clinical::{self, ClinicalFormat};
+use crate::consent::{self, ConsentDecision, ConsentGrant, Granularity, Purpose};
+use crate::database;
+#[cfg(not(target_family = "wasm"))]
+use crate::deletion::{self, DeletionReport, Locus, RedactionSummary};
+use crate::diff::{self, ClinVarDiff};
+use crate::error::BiovaultError;
+use crate::export::{self, ExportFormat, ExportSummary};
+use crate::genomes::{self, DuplicatePolicy, GenomeRecord, ImportOutcome};
+use crate::history::{self, AnalysisRun, StoredAnalysis};
+use crate::logging::{self, LogLevel, LogSink};
+use crate::migrations;
+use crate::progress::{CancellationToken, Phase, Progress, ProgressUpdate};
+use crate::query::{self, GeneLocation, VariantPage, VariantQuery};
+use crate::report::{self, ReportFormat};
+use std::fmt;
+use std::path::Path;
+
+/// Free-form JSON, passed to Swift and Kotlin as a string
+pub type JsonValue = serde_json::Value;
+
+uniffi::custom_type!(JsonValue, String);
+
+impl crate::UniffiCustomTypeConverter for JsonValue {
+    type Builtin = String;
+
+    fn into_custom(val: String) -> uniffi::Result<Self> {
+        Ok(serde_json::from_str(&val)?)
+    }
+
+    fn from_custom(obj: Self) -> String {
+        obj.to_string()
+    }
+}
+
+/// Error thrown to Swift and Kotlin. `code` is one of the stable
+/// `error_codes`; `context` is a JSON object with details, if any.
+#[derive(Debug, uniffi::Error)]
+pub enum ApiError {
+    Failed {
+        code: String,
+        message: String,
+        context: Option<String>,
+    },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Failed { code, message, .. } => write!(f, "{}: {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<BiovaultError> for ApiError {
+    fn from(error: BiovaultError) -> Self {
+        let report = error.report();
+        ApiError::Failed {
+            code: report.code.to_string(),
+            message: report.message,
+            context: report.context.map(|context| context.to_string()),
+        }
+    }
+}
+
+/// Receives progress from long-running calls; `total` is 0 when unknown.
+/// Returning true from `is_cancelled` stops the call with `CANCELLED`.
+#[uniffi::export(callback_interface)]
+pub trait ProgressListener: Send + Sync {
+    fn on_progress(&self, phase: Phase, processed: u64, total: u64);
+    fn is_cancelled(&self) -> bool;
+}
+
+/// Adapt an optional foreign listener to `Progress`
+fn listener_progress(listener: Option<&dyn ProgressListener>) -> Progress<'_> {
+    let Some(listener) = listener else {
+        return Progress::none();
+    };
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    Progress::new(move |update: ProgressUpdate| {
+        listener.on_progress(update.phase, update.processed, update.total);
+        if listener.is_cancelled() {
+            cancel.cancel();
+        }
+    })
+    .with_cancellation(token)
+}
+
+/// Receives the library's log events, e.g. to forward them to os_log or
+/// Logcat. Events up to `Debug` never contain rsIDs or genotypes.
+#[uniffi::export(callback_interface)]
+pub trait LogListener: Send + Sync {
+    fn on_log(&self, level: LogLevel, target: String, message: String);
+}
+
+struct ListenerSink(Box<dyn LogListener>);
+
+impl LogSink for ListenerSink {
+    fn log(&self, level: LogLevel, target: &str, message: &str) {
+        self.0
+            .on_log(level, target.to_string(), message.to_string());
+    }
+}
+
+/// Register the listener receiving log events up to `max_level`, replacing
+/// any previous one. `None` turns logging off.
+#[uniffi::export]
+pub fn set_log_listener(listener: Option<Box<dyn LogListener>>, max_level: LogLevel) {
+    let sink = listener.map(|listener| Box::new(ListenerSink(listener)) as Box<dyn LogSink>);
+    logging::set_log_sink(sink, max_level);
+}
+
+/// Process a 23andMe file into a new genome database in `output_dir`,
+/// encrypted if `key` is given. Returns the database path.
+#[uniffi::export]
+pub fn process_genome_file(
+    input_path: String,
+    custom_name: String,
+    output_dir: String,
+    key: Option<String>,
+) -> Result<String, ApiError> {
+    Ok(crate::process_file_internal(
+        &input_path,
+        &custom_name,
+        &output_dir,
+        key.as_deref(),
+    )?)
+}
+
+/// Import a genome file into a (possibly shared) genome database, creating
+/// it if needed
+#[uniffi::export]
+pub fn import_genome(
+    input_path: String,
+    db_path: String,
+    custom_name: String,
+    person: Option<String>,
+    key: Option<String>,
+    policy: DuplicatePolicy,
+    listener: Option<Box<dyn ProgressListener>>,
+) -> Result<ImportOutcome, ApiError> {
+    Ok(genomes::import_genome_with_progress(
+        &input_path,
+        Path::new(&db_path),
+        &custom_name,
+        person.as_deref(),
+        key.as_deref(),
+        policy,
+        &mut listener_progress(listener.as_deref()),
+    )?)
+}
+
+/// List the genomes stored in a genome database
+#[uniffi::export]
+pub fn list_genomes(db_path: String, key: Option<String>) -> Result<Vec<GenomeRecord>, ApiError> {
+    Ok(genomes::list_genomes(&db_path, key.as_deref())?)
+}
+
+/// Analyze a genome against ClinVar. `genome_id` may be omitted if the
+/// database holds a single genome.
+#[uniffi::export]
+pub fn analyze_clinvar(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    genome_id: Option<i64>,
+    clinvar_db_path: String,
+    listener: Option<Box<dyn ProgressListener>>,
+) -> Result<AnalysisResult, ApiError> {
+    Ok(analysis::analyze_clinvar_matches_with_progress(
+        &user_db_path,
+        user_db_key.as_deref(),
+        genome_id,
+        &clinvar_db_path,
+        &mut listener_progress(listener.as_deref()),
+    )?)
+}
+
+/// Diff a genome's ClinVar matches between two ClinVar releases
+#[uniffi::export]
+pub fn diff_clinvar_releases(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    genome_id: Option<i64>,
+    old_clinvar_db_path: String,
+    new_clinvar_db_path: String,
+) -> Result<ClinVarDiff, ApiError> {
+    Ok(diff::diff_clinvar_releases(
+        &user_db_path,
+        user_db_key.as_deref(),
+        genome_id,
+        &old_clinvar_db_path,
+        &new_clinvar_db_path,
+    )?)
+}
+
+/// Re-run ClinVar analysis and diff it against the last stored run.
+/// Returns `None` on the first run.
+#[uniffi::export]
+pub fn reanalyze_clinvar(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    genome_id: Option<i64>,
+    clinvar_db_path: String,
+) -> Result<Option<ClinVarDiff>, ApiError> {
+    Ok(diff::reanalyze_clinvar(
+        &user_db_path,
+        user_db_key.as_deref(),
+        genome_id,
+        &clinvar_db_path,
+    )?)
+}
+
+/// Run ClinVar analysis and store the result with its provenance
+#[uniffi::export]
+pub fn analyze_and_store_clinvar(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    genome_id: Option<i64>,
+    clinvar_db_path: String,
+) -> Result<StoredAnalysis, ApiError> {
+    Ok(history::analyze_and_store_clinvar(
+        &user_db_path,
+        user_db_key.as_deref(),
+        genome_id,
+        &clinvar_db_path,
+    )?)
+}
+
+/// List stored analysis runs, newest first
+#[uniffi::export]
+pub fn list_analysis_runs(
+    user_db_path: String,
+    user_db_key: Option<String>,
+) -> Result<Vec<AnalysisRun>, ApiError> {
+    Ok(history::list_analysis_runs(
+        &user_db_path,
+        user_db_key.as_deref(),
+    )?)
+}
+
+/// Load a stored analysis run, or `None` if it does not exist
+#[uniffi::export]
+pub fn load_analysis_run(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    run_id: i64,
+) -> Result<Option<StoredAnalysis>, ApiError> {
+    Ok(history::load_analysis_run(
+        &user_db_path,
+        user_db_key.as_deref(),
+        run_id,
+    )?)
+}
+
+/// Delete a stored analysis run. Returns false if it did not exist.
+#[uniffi::export]
+pub fn delete_analysis_run(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    run_id: i64,
+) -> Result<bool, ApiError> {
+    Ok(history::delete_analysis_run(
+        &user_db_path,
+        user_db_key.as_deref(),
+        run_id,
+    )?)
+}
+
+/// Upgrade a genome database to the current schema version and return it
+#[uniffi::export]
+pub fn migrate_genome_database(db_path: String, key: Option<String>) -> Result<i32, ApiError> {
+    let conn = database::open_genome_database(Path::new(&db_path), key.as_deref())?;
+    Ok(migrations::schema_version(&conn).map_err(BiovaultError::from)?)
+}
+
+/// Change the encryption of a genome database in place. `None` means
+/// plaintext for either key.
+#[uniffi::export]
+pub fn rekey_genome_database(
+    db_path: String,
+    old_key: Option<String>,
+    new_key: Option<String>,
+) -> Result<(), ApiError> {
+    Ok(database::rekey_genome_database(
+        Path::new(&db_path),
+        old_key.as_deref(),
+        new_key.as_deref(),
+    )?)
+}
+
+/// Write a plaintext copy of an encrypted genome database
+#[uniffi::export]
+pub fn export_decrypted_genome_database(
+    db_path: String,
+    key: String,
+    output_path: String,
+) -> Result<(), ApiError> {
+    Ok(database::export_decrypted_genome_database(
+        Path::new(&db_path),
+        &key,
+        Path::new(&output_path),
+    )?)
+}
+
+/// Export a genome as VCF, 23andMe TSV or PLINK
+#[uniffi::export]
+pub fn export_genome(
+    db_path: String,
+    key: Option<String>,
+    genome_id: Option<i64>,
+    format: ExportFormat,
+    output_path: String,
+    reference_db_path: Option<String>,
+) -> Result<ExportSummary, ApiError> {
+    Ok(export::export_genome(
+        &db_path,
+        key.as_deref(),
+        genome_id,
+        format,
+        &output_path,
+        reference_db_path.as_deref(),
+    )?)
+}
+
+/// Export a stored analysis run as a Phenopacket or FHIR bundle
+#[uniffi::export]
+pub fn export_clinical_report(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    run_id: i64,
+    format: ClinicalFormat,
+) -> Result<JsonValue, ApiError> {
+    Ok(clinical::export_analysis_run(
+        &user_db_path,
+        user_db_key.as_deref(),
+        run_id,
+        format,
+    )?)
+}
+
+/// Write an HTML or PDF findings report from a stored ClinVar analysis
+#[uniffi::export]
+pub fn generate_report(
+    user_db_path: String,
+    user_db_key: Option<String>,
+    genome_id: Option<i64>,
+    run_id: Option<i64>,
+    format: ReportFormat,
+    output_path: String,
+) -> Result<(), ApiError> {
+    Ok(report::write_report(
+        &user_db_path,
+        user_db_key.as_deref(),
+        genome_id,
+        run_id,
+        format,
+        &output_path,
+    )?)
+}
+
+/// Add a consent grant. `purpose` of `None` covers every purpose;
+/// `expires_at` is RFC 3339.
+#[uniffi::export]
+pub fn grant_consent(
+    db_path: String,
+    key: Option<String>,
+    purpose: Option<Purpose>,
+    recipient: String,
+    granularity: Granularity,
+    expires_at: Option<String>,
+) -> Result<ConsentGrant, ApiError> {
+    Ok(consent::grant_consent(
+        Path::new(&db_path),
+        key.as_deref(),
+        purpose,
+        &recipient,
+        granularity,
+        expires_at.as_deref(),
+    )?)
+}
+
+/// Revoke a consent grant. Returns false if it was not active.
+#[uniffi::export]
+pub fn revoke_consent(
+    db_path: String,
+    key: Option<String>,
+    grant_id: i64,
+) -> Result<bool, ApiError> {
+    Ok(consent::revoke_consent(
+        Path::new(&db_path),
+        key.as_deref(),
+        grant_id,
+    )?)
+}
+
+/// List the consent manifest, including revoked and expired grants
+#[uniffi::export]
+pub fn list_consent_grants(
+    db_path: String,
+    key: Option<String>,
+) -> Result<Vec<ConsentGrant>, ApiError> {
+    Ok(consent::list_consent_grants(
+        Path::new(&db_path),
+        key.as_deref(),
+    )?)
+}
+
+/// List recorded consent decisions, newest first
+#[uniffi::export]
+pub fn list_consent_audit(
+    db_path: String,
+    key: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<ConsentDecision>, ApiError> {
+    Ok(consent::list_consent_audit(
+        Path::new(&db_path),
+        key.as_deref(),
+        limit,
+    )?)
+}
+
+/// Name the app in access log entries, e.g. `ios-app`. `None` restores the
+/// default.
+#[uniffi::export]
+pub fn set_access_caller(caller: Option<String>) {
+    access_log::set_access_caller(caller);
+}
+
+/// List the access log, newest first
+#[uniffi::export]
+pub fn list_access_log(
+    db_path: String,
+    key: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<AccessLogEntry>, ApiError> {
+    Ok(access_log::list_access_log(
+        Path::new(&db_path),
+        key.as_deref(),
+        limit,
+    )?)
+}
+
+/// Check the access log's hash chain. `anchor` is a head hash saved from an
+/// earlier verification.
+#[uniffi::export]
+pub fn verify_access_log(
+    db_path: String,
+    key: Option<String>,
+    anchor: Option<String>,
+) -> Result<AccessLogVerification, ApiError> {
+    Ok(access_log::verify_access_log(
+        Path::new(&db_path),
+        key.as_deref(),
+        anchor.as_deref(),
+    )?)
+}
+
+/// Securely delete a genome, removing the database file if it was the last
+/// one
+#[cfg(not(target_family = "wasm"))]
+#[uniffi::export]
+pub fn delete_genome(
+    db_path: String,
+    key: Option<String>,
+    genome_id: Option<i64>,
+) -> Result<DeletionReport, ApiError> {
+    Ok(deletion::delete_genome(
+        Path::new(&db_path),
+        key.as_deref(),
+        genome_id,
+    )?)
+}
+
+/// Strip loci from a genome database, in place or into a copy at
+/// `output_path`. Gene loci need `clinvar_db_path`.
+#[cfg(not(target_family = "wasm"))]
+#[uniffi::export]
+pub fn redact_loci(
+    db_path: String,
+    key: Option<String>,
+    loci: Vec<Locus>,
+    clinvar_db_path: Option<String>,
+    output_path: Option<String>,
+) -> Result<RedactionSummary, ApiError> {
+    Ok(deletion::redact_loci(
+        Path::new(&db_path),
+        key.as_deref(),
+        &loci,
+        clinvar_db_path.as_deref().map(Path::new),
+        output_path.as_deref().map(Path::new),
+    )?)
+}
+
+/// Write a genome database and its analysis history into a bundle encrypted
+/// with `passphrase`, for moving to another device or backing up
+#[cfg(not(target_family = "wasm"))]
+#[uniffi::export]
+pub fn export_bundle(
+    db_path: String,
+    key: Option<String>,
+    output_path: String,
+    passphrase: String,
+) -> Result<BundleManifest, ApiError> {
+    Ok(bundle::export_bundle(
+        Path::new(&db_path),
+        key.as_deref(),
+        Path::new(&output_path),
+        &passphrase,
+    )?)
+}
+
+/// Restore a bundle into a new genome database, encrypted with `key` if given
+#[cfg(not(target_family = "wasm"))]
+#[uniffi::export]
+pub fn import_bundle(
+    bundle_path: String,
+    passphrase: String,
+    db_path: String,
+    key: Option<String>,
+) -> Result<BundleManifest, ApiError> {
+    Ok(bundle::import_bundle(
+        Path::new(&bundle_path),
+        &passphrase,
+        Path::new(&db_path),
+        key.as_deref(),
+    )?)
+}
+
+/// Page through the stored variants of a genome database by rsID, region,
+/// gene or genotype
+#[uniffi::export]
+pub fn query_variants(
+    db_path: String,
+    key: Option<String>,
+    query: VariantQuery,
+) -> Result<VariantPage, ApiError> {
+    Ok(query::query_variants(
+        Path::new(&db_path),
+        key.as_deref(),
+        &query,
+    )?)
+}
+
+/// Where a gene lies on GRCh37, if it is in the bundled gene panel
+#[uniffi::export]
+pub fn gene_location(symbol: String) -> Option<GeneLocation> {
+    query::gene_location(&symbol)
+}
+
+/// The genes `VariantQuery::gene` accepts
+#[uniffi::export]
+pub fn gene_panel() -> Vec<GeneLocation> {
+    query::gene_panel()
+}
@@ -0,0 +1,484 @@
+//! Encrypted, portable bundles of a genome database.
+//!
+//! A bundle is one file holding a whole genome database (genomes, analysis
+//! history, consent and access log) plus a JSON manifest, zipped and sealed
+//! with XChaCha20-Poly1305 under a key derived from a passphrase with
+//! Argon2id. It moves a user's data to a new phone or into a backup without
+//! depending on the database key of the old device.
+//!
+//! Layout: the magic `BVBUNDLE`, a format version byte, the Argon2 memory,
+//! time and parallelism costs (u32 little-endian), a 16-byte salt and a
+//! 24-byte nonce, then the ciphertext. The header is authenticated as
+//! associated data, so changing any byte of the file makes decryption fail.
+
+use crate::access_log::{self, AccessEvent};
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::{self, open_genome_database};
+use crate::error::BiovaultError;
+use crate::genomes::{self, GenomeRecord};
+use crate::history::LIBRARY_VERSION;
+use crate::migrations;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use tracing::info;
+
+const MAGIC: &[u8; 8] = b"BVBUNDLE";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+/// Refuse bundles asking for more than 1 GiB of Argon2 memory
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "genome.sqlite";
+
+/// What a bundle holds, stored inside it next to the database
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub library_version: String,
+    /// Schema of the bundled database; older schemas are migrated on import
+    pub schema_version: i32,
+    pub created_at: String,
+    pub genomes: Vec<GenomeRecord>,
+    pub analysis_runs: i64,
+    /// SHA-256 of the bundled database file, checked on import
+    pub database_sha256: String,
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], BiovaultError> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| BiovaultError::Other(format!("no secure randomness available: {}", e)))?;
+    Ok(bytes)
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: Params,
+) -> Result<chacha20poly1305::Key, BiovaultError> {
+    let mut key = chacha20poly1305::Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| BiovaultError::Other(format!("key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), BiovaultError> {
+    if passphrase.chars().count() < 8 {
+        return Err(BiovaultError::InvalidArgument(
+            "the bundle passphrase must have at least 8 characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// A compacted, plaintext image of the database, built in memory so no
+/// decrypted copy touches the disk
+fn database_image(conn: &Connection) -> Result<Vec<u8>, BiovaultError> {
+    let version = migrations::schema_version(conn)?;
+    conn.execute("ATTACH DATABASE ':memory:' AS bundle KEY ''", [])?;
+    let image = conn
+        .query_row("SELECT sqlcipher_export('bundle')", [], |_| Ok(()))
+        .and_then(|_| conn.pragma_update(Some("bundle"), "user_version", version))
+        .and_then(|_| conn.serialize("bundle"))
+        .map(|data| data.to_vec());
+    conn.execute("DETACH DATABASE bundle", [])?;
+    Ok(image?)
+}
+
+/// Zip the manifest and database image, encrypt them under `passphrase` and
+/// write the bundle to `output_path`
+fn seal_bundle(
+    output_path: &Path,
+    passphrase: &str,
+    manifest: &BundleManifest,
+    database: &[u8],
+) -> Result<(), BiovaultError> {
+    let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    archive.start_file(MANIFEST_ENTRY, options)?;
+    archive.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    archive.start_file(DATABASE_ENTRY, options)?;
+    archive.write_all(database)?;
+    let archive = archive.finish()?.into_inner();
+
+    let params = Params::default();
+    let salt = random_bytes::<SALT_LEN>()?;
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(FORMAT_VERSION);
+    header.extend_from_slice(&params.m_cost().to_le_bytes());
+    header.extend_from_slice(&params.t_cost().to_le_bytes());
+    header.extend_from_slice(&params.p_cost().to_le_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt, params)?);
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &archive,
+                aad: &header,
+            },
+        )
+        .map_err(|_| BiovaultError::Other("bundle encryption failed".to_string()))?;
+
+    let mut file =
+        fs::File::create_new(output_path).map_err(|e| BiovaultError::io(output_path, e))?;
+    file.write_all(&header)
+        .and_then(|_| file.write_all(&ciphertext))
+        .and_then(|_| file.sync_all())
+        .map_err(|e| BiovaultError::io(output_path, e))
+}
+
+/// Write every genome and the analysis history of a database into an
+/// encrypted bundle at `output_path`
+pub fn export_bundle(
+    db_path: &Path,
+    key: Option<&str>,
+    output_path: &Path,
+    passphrase: &str,
+) -> Result<BundleManifest, BiovaultError> {
+    check_passphrase(passphrase)?;
+    if output_path.exists() {
+        return Err(BiovaultError::AlreadyExists(output_path.to_path_buf()));
+    }
+
+    let conn = open_genome_database(db_path, key)?;
+    consent::require_consent(
+        &conn,
+        Purpose::Export,
+        consent::OWNER,
+        Granularity::Individual,
+    )?;
+    let genomes = genomes::genomes_in(&conn)?;
+    let analysis_runs: i64 =
+        conn.query_row("SELECT COUNT(*) FROM analysis_runs", [], |row| row.get(0))?;
+
+    // `sqlcipher_export` copies live rows only, so free pages that may still
+    // hold deleted data stay behind
+    let database = database_image(&conn)?;
+
+    let manifest = BundleManifest {
+        format_version: FORMAT_VERSION as u32,
+        library_version: LIBRARY_VERSION.to_string(),
+        schema_version: migrations::SCHEMA_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        genomes,
+        analysis_runs,
+        database_sha256: format!("{:x}", Sha256::digest(&database)),
+    };
+
+    seal_bundle(output_path, passphrase, &manifest, &database)?;
+
+    let variants: i64 = manifest
+        .genomes
+        .iter()
+        .map(|genome| genome.total_variants)
+        .sum();
+    access_log::record_access(&conn, AccessEvent::Export, Some(Purpose::Export), variants)?;
+    info!(
+        "bundled {} genomes and {} analysis runs into {:?}",
+        manifest.genomes.len(),
+        analysis_runs,
+        output_path
+    );
+    Ok(manifest)
+}
+
+/// Decrypt and unpack a bundle. Fails with `WRONG_KEY` if the passphrase is
+/// wrong or the file was modified.
+fn open_bundle(
+    bundle_path: &Path,
+    passphrase: &str,
+) -> Result<(BundleManifest, Vec<u8>), BiovaultError> {
+    let bytes = fs::read(bundle_path).map_err(|e| BiovaultError::io(bundle_path, e))?;
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(BiovaultError::InvalidBundle(
+            "not a BioVault bundle".to_string(),
+        ));
+    }
+    let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+    if header[MAGIC.len()] != FORMAT_VERSION {
+        return Err(BiovaultError::InvalidBundle(format!(
+            "bundle format {} is not supported",
+            header[MAGIC.len()]
+        )));
+    }
+
+    let cost = |i: usize| {
+        let start = MAGIC.len() + 1 + 4 * i;
+        u32::from_le_bytes([
+            header[start],
+            header[start + 1],
+            header[start + 2],
+            header[start + 3],
+        ])
+    };
+    let (m_cost, t_cost, p_cost) = (cost(0), cost(1), cost(2));
+    if m_cost > MAX_MEMORY_KIB {
+        return Err(BiovaultError::InvalidBundle(format!(
+            "bundle asks for {} KiB of key derivation memory",
+            m_cost
+        )));
+    }
+    let params = Params::new(m_cost, t_cost, p_cost, None)
+        .map_err(|e| BiovaultError::InvalidBundle(format!("key derivation parameters: {}", e)))?;
+    let salt_start = MAGIC.len() + 1 + 3 * 4;
+    let salt = &header[salt_start..salt_start + SALT_LEN];
+    let nonce = &header[salt_start + SALT_LEN..];
+
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, salt, params)?);
+    let archive = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| BiovaultError::WrongKey)?;
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive))?;
+    let manifest: BundleManifest = serde_json::from_reader(archive.by_name(MANIFEST_ENTRY)?)?;
+    let mut database = Vec::new();
+    archive
+        .by_name(DATABASE_ENTRY)?
+        .read_to_end(&mut database)?;
+
+    if format!("{:x}", Sha256::digest(&database)) != manifest.database_sha256 {
+        return Err(BiovaultError::InvalidBundle(
+            "the database does not match the manifest checksum".to_string(),
+        ));
+    }
+    if manifest.schema_version > migrations::SCHEMA_VERSION {
+        return Err(BiovaultError::UnsupportedSchema {
+            found: manifest.schema_version as i64,
+            supported: migrations::SCHEMA_VERSION as i64,
+        });
+    }
+    Ok((manifest, database))
+}
+
+/// Restore a bundle into a new genome database at `db_path`, encrypted with
+/// `key` if given. The database is checked and migrated to the current
+/// schema before it is written.
+pub fn import_bundle(
+    bundle_path: &Path,
+    passphrase: &str,
+    db_path: &Path,
+    key: Option<&str>,
+) -> Result<BundleManifest, BiovaultError> {
+    if db_path.exists() {
+        return Err(BiovaultError::AlreadyExists(db_path.to_path_buf()));
+    }
+    let (manifest, database) = open_bundle(bundle_path, passphrase)?;
+
+    let mut conn = Connection::open_in_memory()?;
+    conn.deserialize_read_exact("main", Cursor::new(&database), database.len(), false)?;
+    if migrations::schema_version(&conn)? != manifest.schema_version {
+        return Err(BiovaultError::InvalidBundle(
+            "the database does not match the manifest schema version".to_string(),
+        ));
+    }
+    let status: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if status != "ok" {
+        return Err(BiovaultError::InvalidBundle(format!(
+            "the bundled database is damaged: {}",
+            status
+        )));
+    }
+    let from_version = migrations::migrate(&conn)?;
+    if from_version < migrations::SCHEMA_VERSION {
+        info!(
+            "upgraded bundled database from schema version {} to {}",
+            from_version,
+            migrations::SCHEMA_VERSION
+        );
+    }
+    database::export_database(&conn, db_path, key.unwrap_or(""))?;
+
+    info!(
+        "restored {} genomes from {:?} into {:?}",
+        manifest.genomes.len(),
+        bundle_path,
+        db_path
+    );
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::codes;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use std::path::PathBuf;
+
+    const PASSPHRASE: &str = "correct horse battery";
+
+    fn genome_database(dir: &Path) -> PathBuf {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\n\
+             rs1\t1\t100\tAG\n\
+             rs2\t2\t200\tCC\n",
+        )
+        .unwrap();
+        let db = dir.join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        db
+    }
+
+    fn exported(dir: &Path) -> PathBuf {
+        let bundle = dir.join("genome.bvbundle");
+        export_bundle(&genome_database(dir), None, &bundle, PASSPHRASE).unwrap();
+        bundle
+    }
+
+    fn variants(db: &Path, key: Option<&str>) -> Vec<(i64, i64, Option<i64>)> {
+        let conn = open_genome_database(db, key).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT chromosome, position, rsid FROM variants ORDER BY position")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn bundles_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = exported(dir.path());
+        let restored = dir.path().join("restored.sqlite");
+
+        let manifest = import_bundle(&bundle, PASSPHRASE, &restored, Some("new-key")).unwrap();
+        assert_eq!(manifest.schema_version, migrations::SCHEMA_VERSION);
+        assert_eq!(manifest.genomes.len(), 1);
+        assert_eq!(manifest.genomes[0].total_variants, 2);
+        assert_eq!(
+            variants(&restored, Some("new-key")),
+            variants(&dir.path().join("genome.sqlite"), None)
+        );
+        let err = open_genome_database(&restored, None).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+
+        let err = import_bundle(&bundle, PASSPHRASE, &restored, None).unwrap_err();
+        assert_eq!(err.code(), codes::ALREADY_EXISTS);
+        let err = export_bundle(&restored, Some("new-key"), &bundle, PASSPHRASE).unwrap_err();
+        assert_eq!(err.code(), codes::ALREADY_EXISTS);
+    }
+
+    #[test]
+    fn wrong_passphrase_is_a_wrong_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = exported(dir.path());
+        let restored = dir.path().join("restored.sqlite");
+
+        let err = import_bundle(&bundle, "not the passphrase", &restored, None).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+        assert!(!restored.exists());
+    }
+
+    #[test]
+    fn any_flipped_byte_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = exported(dir.path());
+        let original = fs::read(&bundle).unwrap();
+        let restored = dir.path().join("restored.sqlite");
+
+        // The magic and format version are checked before decryption; a
+        // changed salt or nonce byte, or any ciphertext byte, fails the tag
+        let salt_start = MAGIC.len() + 1 + 3 * 4;
+        for (offset, code) in [
+            (0, codes::INVALID_BUNDLE),
+            (MAGIC.len(), codes::INVALID_BUNDLE),
+            (salt_start, codes::WRONG_KEY),
+            (HEADER_LEN - 1, codes::WRONG_KEY),
+            (HEADER_LEN, codes::WRONG_KEY),
+            (original.len() - 1, codes::WRONG_KEY),
+        ] {
+            let mut bytes = original.clone();
+            bytes[offset] ^= 0x01;
+            fs::write(&bundle, &bytes).unwrap();
+            let err = import_bundle(&bundle, PASSPHRASE, &restored, None).unwrap_err();
+            assert_eq!(err.code(), code, "byte {}", offset);
+            assert!(!restored.exists());
+        }
+    }
+
+    #[test]
+    fn older_schema_bundles_are_migrated_on_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = genome_database(dir.path());
+        // Schema 7 is the current one without the index of migration 8
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch("DROP INDEX idx_variants_file; PRAGMA user_version = 7;")
+            .unwrap();
+        let database = database_image(&conn).unwrap();
+        let manifest = BundleManifest {
+            format_version: FORMAT_VERSION as u32,
+            library_version: "0.0.9".to_string(),
+            schema_version: 7,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            genomes: genomes::genomes_in(&conn).unwrap(),
+            analysis_runs: 0,
+            database_sha256: format!("{:x}", Sha256::digest(&database)),
+        };
+        let bundle = dir.path().join("old.bvbundle");
+        seal_bundle(&bundle, PASSPHRASE, &manifest, &database).unwrap();
+
+        let restored = dir.path().join("restored.sqlite");
+        let imported = import_bundle(&bundle, PASSPHRASE, &restored, None).unwrap();
+        assert_eq!(imported.schema_version, 7);
+
+        let conn = Connection::open(&restored).unwrap();
+        assert_eq!(
+            migrations::schema_version(&conn).unwrap(),
+            migrations::SCHEMA_VERSION
+        );
+        let indexed: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_variants_file'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(indexed, 1);
+        assert_eq!(variants(&restored, None), variants(&db, None));
+
+        // A manifest claiming another schema than the database is refused
+        let mismatched = BundleManifest {
+            schema_version: 6,
+            ..manifest
+        };
+        let bundle = dir.path().join("mismatched.bvbundle");
+        seal_bundle(&bundle, PASSPHRASE, &mismatched, &database).unwrap();
+        let err =
+            import_bundle(&bundle, PASSPHRASE, &dir.path().join("other.sqlite"), None).unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_BUNDLE);
+    }
+}
@@ -0,0 +1,748 @@
+use crate::analysis::{AnalysisResult, ClinVarVariant, Zygosity, get_significance_score};
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::open_genome_database;
+use crate::encoding;
+use crate::error::BiovaultError;
+use crate::genomes;
+use crate::history::{self, LIBRARY_VERSION};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const LOINC: &str = "http://loinc.org";
+const GENOMICS_REPORTING: &str = "http://hl7.org/fhir/uv/genomics-reporting/StructureDefinition";
+
+const DISCLAIMER: &str = "Consumer genotyping array data matched to ClinVar by rsID. These \
+     findings are not clinically validated; confirm them with a clinical laboratory before \
+     making medical decisions.";
+
+/// Interchange formats for sharing findings with clinicians
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum ClinicalFormat {
+    /// GA4GH Phenopacket v2 JSON
+    Phenopacket,
+    /// HL7 FHIR R4 Genomics Reporting bundle
+    Fhir,
+}
+
+impl ClinicalFormat {
+    /// Parse the format names used by the CLI and FFI
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "phenopacket" | "ga4gh" => Some(ClinicalFormat::Phenopacket),
+            "fhir" | "fhir-bundle" => Some(ClinicalFormat::Fhir),
+            _ => None,
+        }
+    }
+}
+
+/// Who and what a clinical export describes
+#[derive(Debug, Clone)]
+pub struct ReportContext {
+    /// Subject identifier: the genome's person label, else its name
+    pub subject_id: String,
+    pub assembly: String,
+    pub clinvar_release: String,
+    /// RFC 3339 timestamp of the analysis
+    pub created: String,
+    pub run_id: Option<i64>,
+}
+
+impl ReportContext {
+    fn document_id(&self) -> String {
+        match self.run_id {
+            Some(run_id) => format!("biovault-run-{}", run_id),
+            None => format!("biovault-{}", self.created),
+        }
+    }
+}
+
+/// Load a stored analysis run together with the context needed to export it
+pub fn load_report_source(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    run_id: i64,
+) -> Result<(AnalysisResult, ReportContext), BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+    consent::require_consent(
+        &conn,
+        Purpose::Report,
+        consent::OWNER,
+        Granularity::Individual,
+    )?;
+    let stored = history::load_analysis_run(user_db_path, user_db_key, run_id)?
+        .ok_or_else(|| BiovaultError::NotFound(format!("Analysis run {}", run_id)))?;
+    let genome = genomes::load_genome(&conn, stored.run.genome_id)?;
+
+    let context = ReportContext {
+        subject_id: genome.person.unwrap_or(genome.name),
+        assembly: genome.assembly.unwrap_or_else(|| "GRCh37".to_string()),
+        clinvar_release: stored.run.clinvar_release,
+        created: stored.run.created_at,
+        run_id: Some(stored.run.id),
+    };
+
+    Ok((stored.result, context))
+}
+
+/// Export a stored analysis run as a Phenopacket or FHIR bundle
+pub fn export_analysis_run(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    run_id: i64,
+    format: ClinicalFormat,
+) -> Result<Value, BiovaultError> {
+    let (result, context) = load_report_source(user_db_path, user_db_key, run_id)?;
+    Ok(match format {
+        ClinicalFormat::Phenopacket => phenopacket(&result, &context),
+        ClinicalFormat::Fhir => fhir_bundle(&result, &context),
+    })
+}
+
+/// Serialise findings as a GA4GH Phenopacket v2 document.
+///
+/// Findings are grouped into one interpretation per ClinVar condition. Only
+/// the condition text is known, so each diagnosis uses the NCIT "Unknown"
+/// disease term labelled with that text.
+pub fn phenopacket(result: &AnalysisResult, context: &ReportContext) -> Value {
+    let mut by_condition: BTreeMap<String, Vec<&ClinVarVariant>> = BTreeMap::new();
+    for variant in &result.matches {
+        by_condition
+            .entry(condition_label(&variant.condition))
+            .or_default()
+            .push(variant);
+    }
+
+    let interpretations: Vec<Value> = by_condition
+        .iter()
+        .enumerate()
+        .map(|(index, (condition, variants))| {
+            let genomic_interpretations: Vec<Value> = variants
+                .iter()
+                .map(|variant| phenopacket_genomic_interpretation(variant, context))
+                .collect();
+            json!({
+                "id": format!("{}-{}", context.document_id(), index + 1),
+                "progressStatus": "COMPLETED",
+                "diagnosis": {
+                    "disease": { "id": "NCIT:C17998", "label": condition },
+                    "genomicInterpretations": genomic_interpretations,
+                },
+                "summary": format!("ClinVar {} matches for {}", context.clinvar_release, condition),
+            })
+        })
+        .collect();
+
+    json!({
+        "id": context.document_id(),
+        "subject": { "id": context.subject_id },
+        "interpretations": interpretations,
+        "metaData": {
+            "created": context.created,
+            "createdBy": format!("biovault {}", LIBRARY_VERSION),
+            "resources": [
+                {
+                    "id": "clinvar",
+                    "name": "ClinVar",
+                    "url": "https://www.ncbi.nlm.nih.gov/clinvar/",
+                    "version": context.clinvar_release,
+                    "namespacePrefix": "clinvar",
+                    "iriPrefix": "https://www.ncbi.nlm.nih.gov/clinvar/",
+                },
+                {
+                    "id": "dbsnp",
+                    "name": "dbSNP",
+                    "url": "https://www.ncbi.nlm.nih.gov/snp/",
+                    "version": "unknown",
+                    "namespacePrefix": "dbSNP",
+                    "iriPrefix": "https://www.ncbi.nlm.nih.gov/snp/",
+                },
+                {
+                    "id": "hgnc",
+                    "name": "HUGO Gene Nomenclature Committee",
+                    "url": "https://www.genenames.org",
+                    "version": "unknown",
+                    "namespacePrefix": "hgnc.symbol",
+                    "iriPrefix": "https://www.genenames.org/tools/search/#!/?query=",
+                },
+                {
+                    "id": "ncit",
+                    "name": "NCI Thesaurus",
+                    "url": "http://purl.obolibrary.org/obo/ncit.owl",
+                    "version": "unknown",
+                    "namespacePrefix": "NCIT",
+                    "iriPrefix": "http://purl.obolibrary.org/obo/NCIT_",
+                },
+                {
+                    "id": "geno",
+                    "name": "Genotype Ontology",
+                    "url": "http://purl.obolibrary.org/obo/geno.owl",
+                    "version": "unknown",
+                    "namespacePrefix": "GENO",
+                    "iriPrefix": "http://purl.obolibrary.org/obo/GENO_",
+                },
+            ],
+            "phenopacketSchemaVersion": "2.0",
+        },
+    })
+}
+
+fn phenopacket_genomic_interpretation(variant: &ClinVarVariant, context: &ReportContext) -> Value {
+    let zygosity = variant.zygosity();
+    let status = match zygosity {
+        Zygosity::NonCarrier => "REJECTED",
+        _ if zygosity_present(zygosity) && get_significance_score(&variant.clnsig) <= 2 => {
+            "CANDIDATE"
+        }
+        _ => "UNKNOWN_STATUS",
+    };
+    let alt = variant.carried_alt().unwrap_or("");
+
+    let mut descriptor = json!({
+        "id": variant.rsid,
+        "label": format!("{} {}", variant.gene, variant.rsid).trim().to_string(),
+        "moleculeContext": "genomic",
+        "vcfRecord": {
+            "genomeAssembly": context.assembly,
+            "chrom": variant.chrom,
+            // 64-bit integers are strings in the protobuf JSON mapping
+            "pos": variant.pos.to_string(),
+            "ref": variant.ref_allele,
+            "alt": alt,
+        },
+        "xrefs": [format!("dbSNP:{}", variant.rsid)],
+    });
+    if !variant.gene.is_empty() {
+        descriptor["geneContext"] = json!({
+            "valueId": format!("hgnc.symbol:{}", variant.gene),
+            "symbol": variant.gene,
+        });
+    }
+    if let Some(hgvs) = hgvs(variant, context) {
+        let syntax = if hgvs.contains(":m.") {
+            "hgvs.m"
+        } else {
+            "hgvs.g"
+        };
+        descriptor["expressions"] = json!([{ "syntax": syntax, "value": hgvs }]);
+    }
+    if let Some((id, label)) = geno_allelic_state(zygosity) {
+        descriptor["allelicState"] = json!({ "id": id, "label": label });
+    }
+
+    json!({
+        "subjectOrBiosampleId": context.subject_id,
+        "interpretationStatus": status,
+        "variantInterpretation": {
+            "acmgPathogenicityClassification": acmg_classification(&variant.clnsig),
+            "therapeuticActionability": "UNKNOWN_ACTIONABILITY",
+            "variationDescriptor": descriptor,
+        },
+    })
+}
+
+/// Serialise findings as an HL7 FHIR R4 Genomics Reporting bundle.
+///
+/// The bundle holds the patient, a genomics DiagnosticReport, one variant
+/// Observation per finding and a diagnostic-implication Observation for each
+/// finding the subject carries.
+pub fn fhir_bundle(result: &AnalysisResult, context: &ReportContext) -> Value {
+    let seed = format!(
+        "{}/{}/{}",
+        context.subject_id,
+        context.created,
+        context.document_id()
+    );
+    let patient_url = urn_uuid(&format!("{}/patient", seed));
+    let subject = json!({ "reference": patient_url });
+
+    let mut entries = vec![json!({
+        "fullUrl": patient_url,
+        "resource": {
+            "resourceType": "Patient",
+            "identifier": [{ "system": "urn:biovault:genome", "value": context.subject_id }],
+        },
+    })];
+    let mut report_results = Vec::new();
+
+    for (index, variant) in result.matches.iter().enumerate() {
+        let zygosity = variant.zygosity();
+        let variant_url = urn_uuid(&format!("{}/variant/{}", seed, index));
+        entries.push(json!({
+            "fullUrl": variant_url,
+            "resource": fhir_variant_observation(variant, zygosity, context, &subject),
+        }));
+        report_results.push(json!({ "reference": variant_url }));
+
+        if zygosity_present(zygosity) || zygosity == Zygosity::Unknown {
+            let implication_url = urn_uuid(&format!("{}/implication/{}", seed, index));
+            entries.push(json!({
+                "fullUrl": implication_url,
+                "resource": fhir_implication_observation(variant, context, &subject, &variant_url),
+            }));
+            report_results.push(json!({ "reference": implication_url }));
+        }
+    }
+
+    let report_url = urn_uuid(&format!("{}/report", seed));
+    entries.insert(
+        1,
+        json!({
+            "fullUrl": report_url,
+            "resource": {
+                "resourceType": "DiagnosticReport",
+                "meta": { "profile": [format!("{}/genomic-report", GENOMICS_REPORTING)] },
+                "status": "final",
+                "category": [{
+                    "coding": [{
+                        "system": "http://terminology.hl7.org/CodeSystem/v2-0074",
+                        "code": "GE",
+                        "display": "Genetics",
+                    }],
+                }],
+                "code": loinc("51969-4", "Genetic analysis report"),
+                "subject": subject,
+                "issued": context.created,
+                "result": report_results,
+                "conclusion": format!(
+                    "{} of {} rsIDs matched ClinVar {}. {}",
+                    result.matches_found, result.rsids_searched, context.clinvar_release, DISCLAIMER
+                ),
+            },
+        }),
+    );
+
+    json!({
+        "resourceType": "Bundle",
+        "identifier": { "system": "urn:biovault:report", "value": context.document_id() },
+        "type": "collection",
+        "timestamp": context.created,
+        "entry": entries,
+    })
+}
+
+fn fhir_variant_observation(
+    variant: &ClinVarVariant,
+    zygosity: Zygosity,
+    context: &ReportContext,
+    subject: &Value,
+) -> Value {
+    let value = match zygosity {
+        Zygosity::NonCarrier => loinc("LA9634-2", "Absent"),
+        Zygosity::Unknown => loinc("LA11884-6", "Indeterminate"),
+        _ => loinc("LA9633-4", "Present"),
+    };
+
+    let mut components = Vec::new();
+    if !variant.gene.is_empty() {
+        components.push(component(
+            "48018-6",
+            "Gene studied [ID]",
+            json!({ "valueCodeableConcept": { "text": variant.gene } }),
+        ));
+    }
+    components.push(component(
+        "81252-9",
+        "Discrete genetic variant",
+        json!({ "valueCodeableConcept": {
+            "coding": [{
+                "system": "http://www.ncbi.nlm.nih.gov/projects/SNP",
+                "code": variant.rsid,
+                "display": variant.rsid,
+            }],
+        }}),
+    ));
+    if context.assembly.eq_ignore_ascii_case("GRCh37") {
+        components.push(component(
+            "62374-4",
+            "Human reference sequence assembly version",
+            json!({ "valueCodeableConcept": loinc("LA14029-5", "GRCh37") }),
+        ));
+    }
+    if let Some(accession) = encoding::refseq_accession(&context.assembly, &variant.chrom) {
+        components.push(component(
+            "48013-7",
+            "Genomic reference sequence [ID]",
+            json!({ "valueCodeableConcept": {
+                "coding": [{ "system": "http://www.ncbi.nlm.nih.gov/refseq", "code": accession }],
+            }}),
+        ));
+    }
+    if let Some(hgvs) = hgvs(variant, context) {
+        components.push(component(
+            "81290-9",
+            "Genomic DNA change (gHGVS)",
+            json!({ "valueCodeableConcept": {
+                "coding": [{ "system": "http://varnomen.hgvs.org", "code": hgvs }],
+            }}),
+        ));
+    }
+    components.push(component(
+        "81254-5",
+        "Genomic allele start-end",
+        json!({ "valueRange": {
+            "low": { "value": variant.pos },
+            "high": { "value": variant.pos },
+        }}),
+    ));
+    components.push(component(
+        "69547-8",
+        "Genomic ref allele [ID]",
+        json!({ "valueString": variant.ref_allele }),
+    ));
+    if let Some(alt) = variant.carried_alt() {
+        components.push(component(
+            "69551-0",
+            "Genomic alt allele [ID]",
+            json!({ "valueString": alt }),
+        ));
+    }
+    if let Some((code, display)) = loinc_allelic_state(zygosity) {
+        components.push(component(
+            "53034-5",
+            "Allelic state",
+            json!({ "valueCodeableConcept": loinc(code, display) }),
+        ));
+    }
+
+    let mut observation = json!({
+        "resourceType": "Observation",
+        "meta": { "profile": [format!("{}/variant", GENOMICS_REPORTING)] },
+        "status": "final",
+        "category": [laboratory_category()],
+        "code": loinc("69548-6", "Genetic variant assessment"),
+        "subject": subject,
+        "valueCodeableConcept": value,
+        "component": components,
+    });
+    if let Some(genotype) = &variant.user_genotype {
+        observation["note"] = json!([{ "text": format!("Reported genotype: {}", genotype) }]);
+    }
+    observation
+}
+
+fn fhir_implication_observation(
+    variant: &ClinVarVariant,
+    context: &ReportContext,
+    subject: &Value,
+    variant_url: &str,
+) -> Value {
+    let significance = match significance_loinc(&variant.clnsig) {
+        Some((code, display)) => {
+            let mut concept = loinc(code, display);
+            concept["text"] = json!(variant.clnsig);
+            concept
+        }
+        None => json!({ "text": variant.clnsig }),
+    };
+
+    json!({
+        "resourceType": "Observation",
+        "meta": { "profile": [format!("{}/diagnostic-implication", GENOMICS_REPORTING)] },
+        "status": "final",
+        "category": [laboratory_category()],
+        "code": {
+            "coding": [{
+                "system": "http://hl7.org/fhir/uv/genomics-reporting/CodeSystem/tbd-codes-cs",
+                "code": "diagnostic-implication",
+                "display": "Diagnostic Implication",
+            }],
+        },
+        "subject": subject,
+        "derivedFrom": [{ "reference": variant_url }],
+        "method": { "text": format!("ClinVar {} lookup by rsID", context.clinvar_release) },
+        "component": [
+            component(
+                "53037-8",
+                "Genetic variation clinical significance [Imp]",
+                json!({ "valueCodeableConcept": significance }),
+            ),
+            component(
+                "81259-4",
+                "Associated phenotype",
+                json!({ "valueCodeableConcept": { "text": condition_label(&variant.condition) } }),
+            ),
+        ],
+        "note": [{ "text": format!("ClinVar review status: {}", variant.clnrevstat.replace('_', " ")) }],
+    })
+}
+
+fn hgvs(variant: &ClinVarVariant, context: &ReportContext) -> Option<String> {
+    encoding::hgvs_genomic(
+        &context.assembly,
+        &variant.chrom,
+        variant.pos,
+        &variant.ref_allele,
+        variant.carried_alt()?,
+    )
+}
+
+fn zygosity_present(zygosity: Zygosity) -> bool {
+    matches!(
+        zygosity,
+        Zygosity::Homozygous | Zygosity::Heterozygous | Zygosity::Hemizygous
+    )
+}
+
+fn condition_label(condition: &str) -> String {
+    match condition {
+        "" | "not_provided" | "not_specified" => "Condition not specified".to_string(),
+        _ => condition.replace('_', " ").replace('|', "; "),
+    }
+}
+
+fn acmg_classification(clnsig: &str) -> &'static str {
+    let sig = clnsig.to_lowercase();
+    if sig.contains("conflicting") {
+        "NOT_PROVIDED"
+    } else if sig.contains("likely_pathogenic") {
+        "LIKELY_PATHOGENIC"
+    } else if sig.contains("pathogenic") {
+        "PATHOGENIC"
+    } else if sig.contains("uncertain") {
+        "UNCERTAIN_SIGNIFICANCE"
+    } else if sig.contains("likely_benign") {
+        "LIKELY_BENIGN"
+    } else if sig.contains("benign") {
+        "BENIGN"
+    } else {
+        "NOT_PROVIDED"
+    }
+}
+
+fn significance_loinc(clnsig: &str) -> Option<(&'static str, &'static str)> {
+    match acmg_classification(clnsig) {
+        "PATHOGENIC" => Some(("LA6668-3", "Pathogenic")),
+        "LIKELY_PATHOGENIC" => Some(("LA26332-9", "Likely pathogenic")),
+        "UNCERTAIN_SIGNIFICANCE" => Some(("LA26333-7", "Uncertain significance")),
+        "LIKELY_BENIGN" => Some(("LA26334-5", "Likely benign")),
+        "BENIGN" => Some(("LA6675-8", "Benign")),
+        _ => None,
+    }
+}
+
+fn geno_allelic_state(zygosity: Zygosity) -> Option<(&'static str, &'static str)> {
+    match zygosity {
+        Zygosity::Heterozygous => Some(("GENO:0000135", "heterozygous")),
+        Zygosity::Homozygous => Some(("GENO:0000136", "homozygous")),
+        Zygosity::Hemizygous => Some(("GENO:0000134", "hemizygous")),
+        Zygosity::NonCarrier | Zygosity::Unknown => None,
+    }
+}
+
+fn loinc_allelic_state(zygosity: Zygosity) -> Option<(&'static str, &'static str)> {
+    match zygosity {
+        Zygosity::Heterozygous => Some(("LA6706-1", "Heterozygous")),
+        Zygosity::Homozygous => Some(("LA6705-3", "Homozygous")),
+        Zygosity::Hemizygous => Some(("LA6707-9", "Hemizygous")),
+        Zygosity::NonCarrier | Zygosity::Unknown => None,
+    }
+}
+
+fn loinc(code: &str, display: &str) -> Value {
+    json!({ "coding": [{ "system": LOINC, "code": code, "display": display }] })
+}
+
+fn component(code: &str, display: &str, value: Value) -> Value {
+    let mut component = json!({ "code": loinc(code, display) });
+    if let (Some(target), Value::Object(value)) = (component.as_object_mut(), value) {
+        target.extend(value);
+    }
+    component
+}
+
+fn laboratory_category() -> Value {
+    json!({
+        "coding": [{
+            "system": "http://terminology.hl7.org/CodeSystem/observation-category",
+            "code": "laboratory",
+        }],
+    })
+}
+
+/// Stable `urn:uuid:` for a bundle entry, so re-exporting a run yields the
+/// same references
+fn urn_uuid(seed: &str) -> String {
+    let digest = Sha256::digest(seed.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x50;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(schema: &str, document: &Value) -> Vec<String> {
+        let schema: Value = serde_json::from_str(schema).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        validator
+            .iter_errors(document)
+            .map(|error| format!("{} at {}", error, error.instance_path()))
+            .collect()
+    }
+
+    fn phenopacket_errors(document: &Value) -> Vec<String> {
+        validate(
+            include_str!("../schemas/phenopacket-v2.schema.json"),
+            document,
+        )
+    }
+
+    fn fhir_errors(document: &Value) -> Vec<String> {
+        validate(
+            include_str!("../schemas/fhir-r4-genomics-bundle.schema.json"),
+            document,
+        )
+    }
+
+    fn variant(
+        rsid: &str,
+        chrom: &str,
+        gene: &str,
+        clnsig: &str,
+        genotype: Option<&str>,
+    ) -> ClinVarVariant {
+        ClinVarVariant {
+            rsid: rsid.to_string(),
+            chrom: chrom.to_string(),
+            pos: 100,
+            ref_allele: "G".to_string(),
+            alt_allele: "A".to_string(),
+            gene: gene.to_string(),
+            clnsig: clnsig.to_string(),
+            clnrevstat: "criteria_provided,_single_submitter".to_string(),
+            condition: "Hereditary_cancer-predisposing_syndrome|not_provided".to_string(),
+            user_genotype: genotype.map(str::to_string),
+        }
+    }
+
+    /// One finding per zygosity, with and without a gene
+    fn findings() -> (AnalysisResult, ReportContext) {
+        let matches = vec![
+            variant("rs1", "1", "BRCA1", "Pathogenic", Some("AG")),
+            variant("rs2", "MT", "MT-ND1", "Likely_pathogenic", Some("A")),
+            variant("rs3", "X", "", "Benign", Some("GG")),
+            variant(
+                "rs4",
+                "2",
+                "TP53",
+                "Conflicting_classifications_of_pathogenicity",
+                None,
+            ),
+            variant("rs5", "3", "MLH1", "Uncertain_significance", Some("AA")),
+            variant(
+                "rs6",
+                "17",
+                "BRCA1",
+                "Conflicting_interpretations_of_pathogenicity",
+                Some("GA"),
+            ),
+        ];
+        let result = AnalysisResult {
+            matches_found: matches.len() as i32,
+            rsids_searched: 600_000,
+            gene_groups: Vec::new(),
+            matches,
+        };
+        let context = ReportContext {
+            subject_id: "alice".to_string(),
+            assembly: "GRCh37".to_string(),
+            clinvar_release: "2025-01".to_string(),
+            created: "2025-01-31T12:00:00.123+00:00".to_string(),
+            run_id: Some(7),
+        };
+        (result, context)
+    }
+
+    #[test]
+    fn phenopackets_match_the_bundled_schema() {
+        let (result, context) = findings();
+        let document = phenopacket(&result, &context);
+        assert_eq!(phenopacket_errors(&document), Vec::<String>::new());
+        // Every finding shares one condition
+        assert_eq!(document["interpretations"].as_array().unwrap().len(), 1);
+
+        let empty = AnalysisResult {
+            matches: Vec::new(),
+            gene_groups: Vec::new(),
+            rsids_searched: 0,
+            matches_found: 0,
+        };
+        assert_eq!(
+            phenopacket_errors(&phenopacket(&empty, &context)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn fhir_bundles_match_the_bundled_schema() {
+        let (result, context) = findings();
+        let bundle = fhir_bundle(&result, &context);
+        assert_eq!(fhir_errors(&bundle), Vec::<String>::new());
+        // Patient, report, six variants and five implications (not the
+        // non-carrier)
+        assert_eq!(bundle["entry"].as_array().unwrap().len(), 13);
+    }
+
+    #[test]
+    fn only_carried_pathogenic_findings_are_candidates() {
+        let (result, context) = findings();
+        let document = phenopacket(&result, &context);
+        let statuses: Vec<(&str, &str)> =
+            document["interpretations"][0]["diagnosis"]["genomicInterpretations"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|interpretation| {
+                    (
+                        interpretation["variantInterpretation"]["variationDescriptor"]["id"]
+                            .as_str()
+                            .unwrap(),
+                        interpretation["interpretationStatus"].as_str().unwrap(),
+                    )
+                })
+                .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("rs1", "CANDIDATE"),
+                ("rs2", "CANDIDATE"),
+                ("rs3", "REJECTED"),
+                ("rs4", "UNKNOWN_STATUS"),
+                ("rs5", "UNKNOWN_STATUS"),
+                // Carried, but ClinVar submitters disagree
+                ("rs6", "UNKNOWN_STATUS"),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_schemas_catch_malformed_documents() {
+        let (result, context) = findings();
+
+        let mut document = phenopacket(&result, &context);
+        document["metaData"]["phenopacketSchemaVersion"] = json!("1.0");
+        document["interpretations"][0]["progressStatus"] = json!("DONE");
+        assert_eq!(phenopacket_errors(&document).len(), 2);
+
+        let mut bundle = fhir_bundle(&result, &context);
+        bundle["entry"][1]["resource"]["status"] = json!("done");
+        bundle["timestamp"] = json!("31/01/2025");
+        assert_eq!(fhir_errors(&bundle).len(), 2);
+    }
+}
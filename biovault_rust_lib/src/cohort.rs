@@ -0,0 +1,626 @@
+//! Cohort-level views over many imported genomes.
+//!
+//! Genomes from any number of genome databases are aligned on chromosome and
+//! position into one samples × markers genotype matrix. Per-marker allele
+//! frequencies, missingness and Hardy-Weinberg p-values are computed from it,
+//! and it can be written as a PLINK 1 binary fileset for downstream tools.
+
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::open_genome_database;
+use crate::encoding::{self, CHROM_X};
+use crate::error::BiovaultError;
+use crate::export::{create_output, finish_outputs, for_each_call, snp_alleles};
+use crate::genomes;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, info_span};
+
+/// Bases a call code can hold, in code order
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+/// Options for building a cohort
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CohortOptions {
+    /// Drop markers missing in more than this fraction of samples, like
+    /// PLINK's `--geno`. 0 keeps only markers called in every sample.
+    pub max_missingness: f64,
+}
+
+impl Default for CohortOptions {
+    fn default() -> Self {
+        CohortOptions {
+            max_missingness: 0.1,
+        }
+    }
+}
+
+/// One genome in a cohort
+#[derive(Debug, Clone, Serialize)]
+pub struct CohortSample {
+    /// The person if known, else the genome name; unique within the cohort
+    pub name: String,
+    pub db_path: String,
+    pub genome_id: i64,
+    /// Fraction of the kept markers called in this sample
+    pub call_rate: f64,
+}
+
+/// Per-marker summary over the cohort. A1 is the minor allele and A2 the
+/// major one; A1 is "0" for monomorphic markers.
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkerStats {
+    pub chromosome: String,
+    pub position: i64,
+    pub rsid: Option<String>,
+    pub a1: String,
+    pub a2: String,
+    pub a1_frequency: f64,
+    /// Samples with a call
+    pub called: usize,
+    pub missingness: f64,
+    pub hom_a1: usize,
+    pub het: usize,
+    pub hom_a2: usize,
+    /// Exact Hardy-Weinberg test; autosomes only
+    pub hwe_p: Option<f64>,
+}
+
+/// Counts describing how a cohort was built
+#[derive(Debug, Clone, Serialize)]
+pub struct CohortSummary {
+    pub samples: Vec<CohortSample>,
+    /// SNP markers called in at least one sample
+    pub markers_seen: usize,
+    pub markers_kept: usize,
+    /// Dropped for exceeding `max_missingness`
+    pub markers_too_sparse: usize,
+    /// Dropped for having more than two alleles in the cohort
+    pub markers_multiallelic: usize,
+}
+
+/// A samples × markers genotype matrix with per-marker statistics
+pub struct Cohort {
+    summary: CohortSummary,
+    markers: Vec<MarkerStats>,
+    /// Per kept marker, one call code per sample; see `call_code`
+    calls: Vec<Vec<u8>>,
+    chromosome_codes: Vec<i64>,
+}
+
+/// A marker while the genomes are being read
+struct MarkerCalls {
+    chromosome: i64,
+    position: i64,
+    rsid: Option<i64>,
+    calls: Vec<u8>,
+}
+
+/// Code a SNP call into one byte: 0 is missing, otherwise one more than the
+/// two base indices packed into four bits. Haploid calls are coded as
+/// homozygous, as in the PLINK export.
+fn call_code(genotype: &str) -> u8 {
+    let Some(alleles) = snp_alleles(genotype) else {
+        return 0;
+    };
+    let index = |allele: char| BASES.iter().position(|b| *b == allele).unwrap_or(0) as u8;
+    let first = index(alleles[0]);
+    let second = alleles.last().map(|a| index(*a)).unwrap_or(first);
+    1 + (first << 2 | second)
+}
+
+fn call_alleles(code: u8) -> Option<(usize, usize)> {
+    let code = code.checked_sub(1)?;
+    Some(((code >> 2) as usize, (code & 0b11) as usize))
+}
+
+/// Load every genome from `db_paths` into one cohort. Databases may hold one
+/// genome each or a whole cohort; all of them must open with `key`.
+pub fn load_cohort(
+    db_paths: &[PathBuf],
+    key: Option<&str>,
+    options: &CohortOptions,
+) -> Result<Cohort, BiovaultError> {
+    if !(0.0..=1.0).contains(&options.max_missingness) {
+        return Err(BiovaultError::InvalidArgument(
+            "max_missingness must be between 0 and 1".to_string(),
+        ));
+    }
+
+    let connections = db_paths
+        .iter()
+        .map(|path| {
+            let conn = open_genome_database(path, key)?;
+            consent::require_consent(
+                &conn,
+                Purpose::Cohort,
+                consent::OWNER,
+                Granularity::Individual,
+            )?;
+            Ok((path, conn))
+        })
+        .collect::<Result<Vec<_>, BiovaultError>>()?;
+    let mut samples = Vec::new();
+    // Index into `connections` of each sample's database
+    let mut sources = Vec::new();
+    let mut names = HashSet::new();
+    for (source, (path, conn)) in connections.iter().enumerate() {
+        for genome in genomes::genomes_in(conn)? {
+            let base = genome.person.clone().unwrap_or_else(|| genome.name.clone());
+            let mut name = base.clone();
+            let mut suffix = 1;
+            while !names.insert(name.clone()) {
+                suffix += 1;
+                name = format!("{}_{}", base, suffix);
+            }
+            samples.push(CohortSample {
+                name,
+                db_path: path.to_string_lossy().into_owned(),
+                genome_id: genome.id,
+                call_rate: 0.0,
+            });
+            sources.push(source);
+        }
+    }
+    if samples.is_empty() {
+        return Err(BiovaultError::NoGenomes);
+    }
+
+    let _span = info_span!("cohort", samples = samples.len()).entered();
+
+    let mut index: HashMap<(i64, i64), usize> = HashMap::new();
+    let mut markers: Vec<MarkerCalls> = Vec::new();
+    for (sample, source) in sources.into_iter().enumerate() {
+        let conn = &connections[source].1;
+        for_each_call(conn, samples[sample].genome_id, |call| {
+            let code = call_code(&call.genotype);
+            if code == 0 {
+                return Ok(());
+            }
+            let marker = *index
+                .entry((call.chromosome, call.position))
+                .or_insert_with(|| {
+                    markers.push(MarkerCalls {
+                        chromosome: call.chromosome,
+                        position: call.position,
+                        rsid: call.rsid,
+                        calls: vec![0; samples.len()],
+                    });
+                    markers.len() - 1
+                });
+            let marker = &mut markers[marker];
+            // Extra probes at a position keep the first call
+            if marker.calls[sample] == 0 {
+                marker.calls[sample] = code;
+            }
+            if marker.rsid.is_none() {
+                marker.rsid = call.rsid;
+            }
+            Ok(())
+        })?;
+        debug!("read genome {} of {}", sample + 1, samples.len());
+    }
+    drop(index);
+    markers.sort_by_key(|marker| (marker.chromosome, marker.position));
+
+    let markers_seen = markers.len();
+    let mut markers_too_sparse = 0;
+    let mut markers_multiallelic = 0;
+    let mut kept_markers = Vec::new();
+    let mut calls = Vec::new();
+    let mut chromosome_codes = Vec::new();
+    let mut sample_calls = vec![0usize; samples.len()];
+    for marker in markers {
+        let called = marker.calls.iter().filter(|code| **code != 0).count();
+        let missingness = 1.0 - called as f64 / samples.len() as f64;
+        if missingness > options.max_missingness + f64::EPSILON {
+            markers_too_sparse += 1;
+            continue;
+        }
+        let Some(stats) = marker_stats(&marker, called, missingness) else {
+            markers_multiallelic += 1;
+            continue;
+        };
+        for (count, code) in sample_calls.iter_mut().zip(&marker.calls) {
+            if *code != 0 {
+                *count += 1;
+            }
+        }
+        kept_markers.push(stats);
+        chromosome_codes.push(marker.chromosome);
+        calls.push(marker.calls);
+    }
+
+    let kept = kept_markers.len();
+    for (sample, called) in samples.iter_mut().zip(sample_calls) {
+        sample.call_rate = if kept > 0 {
+            called as f64 / kept as f64
+        } else {
+            0.0
+        };
+    }
+    let summary = CohortSummary {
+        samples,
+        markers_seen,
+        markers_kept: kept,
+        markers_too_sparse,
+        markers_multiallelic,
+    };
+
+    info!(
+        "kept {} of {} markers ({} too sparse, {} multiallelic)",
+        kept, markers_seen, markers_too_sparse, markers_multiallelic
+    );
+    Ok(Cohort {
+        summary,
+        markers: kept_markers,
+        calls,
+        chromosome_codes,
+    })
+}
+
+/// Allele and genotype counts for a marker, or `None` if it has more than
+/// two alleles in the cohort
+fn marker_stats(marker: &MarkerCalls, called: usize, missingness: f64) -> Option<MarkerStats> {
+    let mut allele_counts = [0usize; 4];
+    for (first, second) in marker.calls.iter().filter_map(|code| call_alleles(*code)) {
+        allele_counts[first] += 1;
+        allele_counts[second] += 1;
+    }
+    let mut alleles: Vec<usize> = (0..4).filter(|a| allele_counts[*a] > 0).collect();
+    if alleles.len() > 2 {
+        return None;
+    }
+    // Major allele first; ties go to base order
+    alleles.sort_by_key(|a| std::cmp::Reverse(allele_counts[*a]));
+    let major = alleles[0];
+    let minor = alleles.get(1).copied();
+
+    let (mut hom_a1, mut het, mut hom_a2) = (0, 0, 0);
+    for (first, second) in marker.calls.iter().filter_map(|code| call_alleles(*code)) {
+        match (first == major, second == major) {
+            (true, true) => hom_a2 += 1,
+            (false, false) => hom_a1 += 1,
+            _ => het += 1,
+        }
+    }
+
+    let autosomal = (1..CHROM_X).contains(&marker.chromosome);
+    Some(MarkerStats {
+        chromosome: encoding::chromosome_name(marker.chromosome),
+        position: marker.position,
+        rsid: marker.rsid.map(encoding::rsid_string),
+        a1: minor
+            .map(|a| BASES[a].to_string())
+            .unwrap_or_else(|| "0".to_string()),
+        a2: BASES[major].to_string(),
+        a1_frequency: minor
+            .map(|a| allele_counts[a] as f64 / (2 * called) as f64)
+            .unwrap_or(0.0),
+        called,
+        missingness,
+        hom_a1,
+        het,
+        hom_a2,
+        hwe_p: autosomal.then(|| hwe_exact(het, hom_a1, hom_a2)),
+    })
+}
+
+/// Exact Hardy-Weinberg equilibrium test (Wigginton, Cutler & Abecasis 2005)
+fn hwe_exact(het: usize, hom1: usize, hom2: usize) -> f64 {
+    let genotypes = het + hom1 + hom2;
+    if genotypes == 0 {
+        return 1.0;
+    }
+    let hom_rare = hom1.min(hom2);
+    let rare = 2 * hom_rare + het;
+
+    let mut probs = vec![0.0f64; rare + 1];
+    // Start from the most likely heterozygote count, with the parity of `rare`
+    let mut mid = rare * (2 * genotypes - rare) / (2 * genotypes);
+    if mid % 2 != rare % 2 {
+        mid += 1;
+    }
+    probs[mid] = 1.0;
+    let mut sum = 1.0;
+
+    let mut curr_hom_rare = (rare - mid) / 2;
+    let mut curr_hom_common = genotypes - mid - curr_hom_rare;
+    let mut hets = mid;
+    while hets >= 2 {
+        probs[hets - 2] = probs[hets] * (hets * (hets - 1)) as f64
+            / (4 * (curr_hom_rare + 1) * (curr_hom_common + 1)) as f64;
+        sum += probs[hets - 2];
+        curr_hom_rare += 1;
+        curr_hom_common += 1;
+        hets -= 2;
+    }
+
+    let mut curr_hom_rare = (rare - mid) / 2;
+    let mut curr_hom_common = genotypes - mid - curr_hom_rare;
+    let mut hets = mid;
+    while hets + 2 <= rare {
+        probs[hets + 2] = probs[hets] * (4 * curr_hom_rare * curr_hom_common) as f64
+            / ((hets + 2) * (hets + 1)) as f64;
+        sum += probs[hets + 2];
+        curr_hom_rare -= 1;
+        curr_hom_common -= 1;
+        hets += 2;
+    }
+
+    let observed = probs[het];
+    let p: f64 = probs.iter().filter(|p| **p <= observed).sum::<f64>() / sum;
+    p.min(1.0)
+}
+
+impl Cohort {
+    pub fn summary(&self) -> &CohortSummary {
+        &self.summary
+    }
+
+    pub fn samples(&self) -> &[CohortSample] {
+        &self.summary.samples
+    }
+
+    /// Kept markers, sorted by chromosome and position
+    pub fn markers(&self) -> &[MarkerStats] {
+        &self.markers
+    }
+
+    /// Write the genotype matrix as a PLINK 1 binary fileset at `prefix`
+    /// (`.bed`, `.bim`, `.fam`), with A1 the minor allele. Returns the paths
+    /// written.
+    pub fn write_plink(&self, prefix: &str) -> Result<Vec<String>, BiovaultError> {
+        let paths = ["bed", "bim", "fam"].map(|ext| PathBuf::from(format!("{}.{}", prefix, ext)));
+        let mut bed = create_output(&paths[0])?;
+        let mut bim = create_output(&paths[1])?;
+        let mut fam = create_output(&paths[2])?;
+
+        for sample in self.samples() {
+            // PLINK ids cannot contain whitespace
+            let id: String = sample
+                .name
+                .chars()
+                .map(|c| if c.is_whitespace() { '_' } else { c })
+                .collect();
+            // Sex and phenotype unknown
+            writeln!(fam, "{} {} 0 0 0 -9", id, id)?;
+        }
+
+        // Magic number and SNP-major mode
+        bed.write_all(&[0x6c, 0x1b, 0x01])?;
+        let mut row = vec![0u8; self.samples().len().div_ceil(4)];
+        for ((marker, calls), chromosome) in self
+            .markers
+            .iter()
+            .zip(&self.calls)
+            .zip(&self.chromosome_codes)
+        {
+            let id = marker
+                .rsid
+                .clone()
+                .unwrap_or_else(|| format!("{}:{}", chromosome, marker.position));
+            writeln!(
+                bim,
+                "{}\t{}\t0\t{}\t{}\t{}",
+                chromosome, id, marker.position, marker.a1, marker.a2
+            )?;
+
+            row.fill(0);
+            let major = BASES.iter().position(|b| b.to_string() == marker.a2);
+            for (sample, code) in calls.iter().enumerate() {
+                let bits = match call_alleles(*code) {
+                    None => 0b01,
+                    Some((first, second)) => match (Some(first) == major, Some(second) == major) {
+                        (true, true) => 0b11,
+                        (false, false) => 0b00,
+                        _ => 0b10,
+                    },
+                };
+                row[sample / 4] |= bits << (2 * (sample % 4));
+            }
+            bed.write_all(&row)?;
+        }
+
+        finish_outputs(vec![bed, bim, fam])?;
+        Ok(paths
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /// Write the per-marker statistics as a tab-separated file
+    pub fn write_stats(&self, path: &Path) -> Result<(), BiovaultError> {
+        let mut out = create_output(path)?;
+        writeln!(
+            out,
+            "CHR\tSNP\tPOS\tA1\tA2\tA1_FREQ\tN_CALLED\tMISSING\tHOM_A1\tHET\tHOM_A2\tHWE_P"
+        )?;
+        for marker in &self.markers {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{:.6}\t{}\t{:.6}\t{}\t{}\t{}\t{}",
+                marker.chromosome,
+                marker.rsid.as_deref().unwrap_or("."),
+                marker.position,
+                marker.a1,
+                marker.a2,
+                marker.a1_frequency,
+                marker.called,
+                marker.missingness,
+                marker.hom_a1,
+                marker.het,
+                marker.hom_a2,
+                marker
+                    .hwe_p
+                    .map(|p| format!("{:.4e}", p))
+                    .unwrap_or_else(|| "NA".to_string())
+            )?;
+        }
+        out.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use std::fs;
+
+    /// One database per sample, each holding a genome named after it with
+    /// calls at 1:100 and 1:200
+    fn sample_dbs(dir: &Path, calls: &[(&str, &str)]) -> Vec<PathBuf> {
+        calls
+            .iter()
+            .enumerate()
+            .map(|(sample, (first, second))| {
+                let input = dir.join(format!("s{}.txt", sample));
+                fs::write(
+                    &input,
+                    format!(
+                        "# rsid\tchromosome\tposition\tgenotype\n\
+                         rs1\t1\t100\t{}\n\
+                         rs2\t1\t200\t{}\n",
+                        first, second
+                    ),
+                )
+                .unwrap();
+                let db_path = dir.join(format!("s{}.sqlite", sample));
+                import_genome(
+                    input.to_str().unwrap(),
+                    &db_path,
+                    &format!("s{}", sample),
+                    None,
+                    None,
+                    DuplicatePolicy::Skip,
+                )
+                .unwrap();
+                db_path
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected * 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn hwe_exact_matches_known_p_values() {
+        // Example from Wigginton, Cutler & Abecasis (2005)
+        assert_close(hwe_exact(57, 14, 50), 0.8422797565707926);
+        assert_close(hwe_exact(100, 3, 200), 0.01507949352639056);
+        // Too few heterozygotes, then too many
+        assert_close(hwe_exact(0, 10, 10), 1.3403021576354265e-6);
+        assert_close(hwe_exact(20, 0, 0), 8.947138608114572e-6);
+        assert_close(hwe_exact(0, 1, 1), 1.0 / 3.0);
+        assert_close(hwe_exact(10, 5, 5), 1.0);
+        // Monomorphic and empty markers are in equilibrium
+        assert_close(hwe_exact(0, 0, 30), 1.0);
+        assert_close(hwe_exact(0, 0, 0), 1.0);
+    }
+
+    #[test]
+    fn call_codes_round_trip() {
+        assert_eq!(call_code("--"), 0);
+        assert_eq!(call_alleles(0), None);
+        assert_eq!(call_alleles(call_code("AG")), Some((0, 2)));
+        assert_eq!(call_alleles(call_code("TC")), Some((3, 1)));
+        // Haploid calls count as homozygous
+        assert_eq!(call_alleles(call_code("G")), Some((2, 2)));
+    }
+
+    #[test]
+    fn plink_bed_uses_snp_major_two_bit_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dbs = sample_dbs(
+            dir.path(),
+            &[
+                ("AA", "CC"),
+                ("AG", "CC"),
+                ("GG", "CC"),
+                ("--", "CC"),
+                ("GG", "CC"),
+            ],
+        );
+        let cohort = load_cohort(
+            &dbs,
+            None,
+            &CohortOptions {
+                max_missingness: 0.2,
+            },
+        )
+        .unwrap();
+
+        let marker = &cohort.markers()[0];
+        assert_eq!((marker.a1.as_str(), marker.a2.as_str()), ("A", "G"));
+        assert_eq!(marker.a1_frequency, 3.0 / 8.0);
+        assert_eq!(
+            (marker.called, marker.hom_a1, marker.het, marker.hom_a2),
+            (4, 1, 1, 2)
+        );
+        assert_close(marker.missingness, 0.2);
+        let monomorphic = &cohort.markers()[1];
+        assert_eq!(
+            (monomorphic.a1.as_str(), monomorphic.a2.as_str()),
+            ("0", "C")
+        );
+        assert_eq!(cohort.samples()[3].call_rate, 0.5);
+
+        let prefix = dir.path().join("cohort");
+        cohort.write_plink(prefix.to_str().unwrap()).unwrap();
+
+        // Per sample, low bits first: 00 hom A1, 10 het, 11 hom A2, 01 missing
+        assert_eq!(
+            fs::read(prefix.with_extension("bed")).unwrap(),
+            [0x6c, 0x1b, 0x01, 0b01_11_10_00, 0b11, 0b11_11_11_11, 0b11]
+        );
+        assert_eq!(
+            fs::read_to_string(prefix.with_extension("bim")).unwrap(),
+            "1\trs1\t0\t100\tA\tG\n1\trs2\t0\t200\t0\tC\n"
+        );
+        let fam = fs::read_to_string(prefix.with_extension("fam")).unwrap();
+        assert_eq!(fam.lines().next(), Some("s0 s0 0 0 0 -9"));
+        assert_eq!(fam.lines().count(), 5);
+    }
+
+    #[test]
+    fn sparse_and_multiallelic_markers_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let dbs = sample_dbs(dir.path(), &[("AA", "CC"), ("CG", "--"), ("TT", "CT")]);
+        let cohort = load_cohort(
+            &dbs,
+            None,
+            &CohortOptions {
+                max_missingness: 0.0,
+            },
+        )
+        .unwrap();
+
+        let summary = cohort.summary();
+        assert_eq!(
+            (
+                summary.markers_seen,
+                summary.markers_kept,
+                summary.markers_too_sparse,
+                summary.markers_multiallelic
+            ),
+            (2, 0, 1, 1)
+        );
+        assert!(
+            load_cohort(
+                &dbs,
+                None,
+                &CohortOptions {
+                    max_missingness: 1.5
+                }
+            )
+            .is_err()
+        );
+    }
+}
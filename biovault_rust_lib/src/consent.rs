@@ -0,0 +1,671 @@
+//! Consent manifest and data-use checks.
+//!
+//! A genome database holds the uses its owner has consented to: which
+//! purposes, for which recipients, at which granularity and until when. Every
+//! analysis, export, report and cohort entry point, and every differentially
+//! private query, asks `require_consent` before reading genotypes, and every
+//! decision is written to `consent_audit`.
+//!
+//! Local entry points ask on behalf of the owner (`self`). The first time the
+//! owner uses a database that has never had a grant to `self`, one covering
+//! every purpose at individual granularity is added to the manifest, so a new
+//! import can be analysed straight away. Revoking that grant, or replacing it
+//! with narrower ones, restricts local use; it is never added again. Other
+//! recipients, such as datasite requesters, only get what the owner grants.
+
+use crate::database::{open_genome_database, open_genome_database_unlogged};
+use crate::error::BiovaultError;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Recipient name for the database owner on their own device
+pub const OWNER: &str = "self";
+
+/// Recipient or purpose wildcard in a grant
+const ANY: &str = "*";
+
+/// What a use of the data is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum Purpose {
+    ClinvarAnalysis,
+    Export,
+    Report,
+    Cohort,
+    DpQuery,
+}
+
+impl Purpose {
+    pub fn name(self) -> &'static str {
+        match self {
+            Purpose::ClinvarAnalysis => "clinvar_analysis",
+            Purpose::Export => "export",
+            Purpose::Report => "report",
+            Purpose::Cohort => "cohort",
+            Purpose::DpQuery => "dp_query",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "_").as_str() {
+            "clinvar_analysis" | "clinvar" => Some(Purpose::ClinvarAnalysis),
+            "export" => Some(Purpose::Export),
+            "report" => Some(Purpose::Report),
+            "cohort" => Some(Purpose::Cohort),
+            "dp_query" | "dp" => Some(Purpose::DpQuery),
+            _ => None,
+        }
+    }
+
+    /// Parse a purpose as stored in the database, which is always `name()`
+    /// exactly. Unlike `from_name` no spelling variants are accepted.
+    fn from_stored(name: &str) -> Option<Self> {
+        [
+            Purpose::ClinvarAnalysis,
+            Purpose::Export,
+            Purpose::Report,
+            Purpose::Cohort,
+            Purpose::DpQuery,
+        ]
+        .into_iter()
+        .find(|purpose| purpose.name() == name)
+    }
+}
+
+/// The purposes a grant covers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum GrantPurpose {
+    /// Every purpose, stored as `*`
+    All,
+    Only {
+        purpose: Purpose,
+    },
+    /// A purpose this library version does not know, e.g. written by a newer
+    /// version. Such grants permit nothing.
+    Unrecognised {
+        name: String,
+    },
+}
+
+impl GrantPurpose {
+    fn from_stored(name: &str) -> Self {
+        if name == ANY {
+            return GrantPurpose::All;
+        }
+        match Purpose::from_stored(name) {
+            Some(purpose) => GrantPurpose::Only { purpose },
+            None => GrantPurpose::Unrecognised {
+                name: name.to_string(),
+            },
+        }
+    }
+
+    /// How the grant is written in listings: `*` or the purpose name
+    pub fn label(&self) -> &str {
+        match self {
+            GrantPurpose::All => ANY,
+            GrantPurpose::Only { purpose } => purpose.name(),
+            GrantPurpose::Unrecognised { name } => name,
+        }
+    }
+
+    fn covers(&self, requested: Purpose) -> bool {
+        match self {
+            GrantPurpose::All => true,
+            GrantPurpose::Only { purpose } => *purpose == requested,
+            GrantPurpose::Unrecognised { .. } => false,
+        }
+    }
+}
+
+/// How much detail a use releases. Consent to individual-level data covers
+/// aggregates too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    /// Genotypes or findings of one person
+    Individual,
+    /// Counts or statistics over many genomes
+    Aggregate,
+}
+
+impl Granularity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Granularity::Individual => "individual",
+            Granularity::Aggregate => "aggregate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "individual" => Some(Granularity::Individual),
+            "aggregate" => Some(Granularity::Aggregate),
+            _ => None,
+        }
+    }
+
+    fn covers(self, requested: Granularity) -> bool {
+        self == Granularity::Individual || requested == Granularity::Aggregate
+    }
+}
+
+/// One entry of the consent manifest
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct ConsentGrant {
+    pub id: i64,
+    pub purpose: GrantPurpose,
+    /// A recipient such as a datasite email, `self` for the owner, or `*`
+    pub recipient: String,
+    pub granularity: Granularity,
+    /// RFC 3339; `None` never expires
+    pub expires_at: Option<String>,
+    pub created_at: String,
+    pub revoked_at: Option<String>,
+}
+
+/// A recorded consent check
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct ConsentDecision {
+    pub id: i64,
+    pub purpose: Purpose,
+    pub recipient: String,
+    pub granularity: Granularity,
+    pub allowed: bool,
+    /// The grant that allowed the use
+    pub grant_id: Option<i64>,
+    pub created_at: String,
+}
+
+const GRANT_COLUMNS: &str =
+    "id, purpose, recipient, granularity, expires_at, created_at, revoked_at";
+
+/// A stored grant. An unknown granularity is an error rather than a guess,
+/// since reading it as either value could widen the grant.
+fn grant_from_row(row: &rusqlite::Row) -> rusqlite::Result<ConsentGrant> {
+    let purpose: String = row.get(1)?;
+    let granularity: String = row.get(3)?;
+    let granularity = Granularity::from_name(&granularity).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            3,
+            rusqlite::types::Type::Text,
+            format!("unknown consent granularity '{}'", granularity).into(),
+        )
+    })?;
+    Ok(ConsentGrant {
+        id: row.get(0)?,
+        purpose: GrantPurpose::from_stored(&purpose),
+        recipient: row.get(2)?,
+        granularity,
+        expires_at: row.get(4)?,
+        created_at: row.get(5)?,
+        revoked_at: row.get(6)?,
+    })
+}
+
+impl ConsentGrant {
+    fn permits(
+        &self,
+        purpose: Purpose,
+        recipient: &str,
+        granularity: Granularity,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        let expired = self.expires_at.as_deref().is_some_and(|expires_at| {
+            // An unreadable expiry is treated as already passed
+            chrono::DateTime::parse_from_rfc3339(expires_at)
+                .map(|expires_at| expires_at <= now)
+                .unwrap_or(true)
+        });
+        self.revoked_at.is_none()
+            && !expired
+            && self.purpose.covers(purpose)
+            && (self.recipient == ANY || self.recipient.eq_ignore_ascii_case(recipient))
+            && self.granularity.covers(granularity)
+    }
+}
+
+/// Check that the owner consented to this use and record the decision.
+/// Fails with `CONSENT_REQUIRED` if no active grant covers it.
+pub(crate) fn require_consent(
+    conn: &Connection,
+    purpose: Purpose,
+    recipient: &str,
+    granularity: Granularity,
+) -> Result<(), BiovaultError> {
+    if recipient == OWNER {
+        add_first_owner_grant(conn)?;
+    }
+    let now = chrono::Utc::now();
+    let grant_id = active_grants(conn)?
+        .iter()
+        .find(|grant| grant.permits(purpose, recipient, granularity, now))
+        .map(|grant| grant.id);
+
+    conn.execute(
+        "INSERT INTO consent_audit (purpose, recipient, granularity, allowed, grant_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            purpose.name(),
+            recipient,
+            granularity.name(),
+            grant_id.is_some(),
+            grant_id,
+            now.to_rfc3339()
+        ],
+    )?;
+
+    if grant_id.is_none() {
+        warn!("no consent for {} by {}", purpose.name(), recipient);
+        return Err(BiovaultError::ConsentRequired {
+            purpose: purpose.name().to_string(),
+            recipient: recipient.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Grant the owner every purpose unless a grant to `self` was ever made,
+/// including one since revoked
+fn add_first_owner_grant(conn: &Connection) -> rusqlite::Result<()> {
+    let added = conn.execute(
+        "INSERT INTO consent_grants (purpose, recipient, granularity, created_at)
+         SELECT ?1, ?2, ?3, ?4
+         WHERE NOT EXISTS (SELECT 1 FROM consent_grants WHERE recipient = ?2)",
+        params![
+            ANY,
+            OWNER,
+            Granularity::Individual.name(),
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    if added > 0 {
+        info!(
+            "consent grant {} added for the owner",
+            conn.last_insert_rowid()
+        );
+    }
+    Ok(())
+}
+
+fn active_grants(conn: &Connection) -> rusqlite::Result<Vec<ConsentGrant>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM consent_grants WHERE revoked_at IS NULL ORDER BY id",
+        GRANT_COLUMNS
+    ))?;
+    let grants = stmt.query_map([], grant_from_row)?;
+    grants.collect()
+}
+
+/// Add a grant to the consent manifest. `purpose` of `None` grants every
+/// purpose; `expires_at` is an RFC 3339 timestamp.
+pub fn grant_consent(
+    db_path: &Path,
+    key: Option<&str>,
+    purpose: Option<Purpose>,
+    recipient: &str,
+    granularity: Granularity,
+    expires_at: Option<&str>,
+) -> Result<ConsentGrant, BiovaultError> {
+    if recipient.trim().is_empty() {
+        return Err(BiovaultError::InvalidArgument(
+            "recipient must not be empty".to_string(),
+        ));
+    }
+    let expires_at = match expires_at {
+        Some(expires_at) => Some(
+            chrono::DateTime::parse_from_rfc3339(expires_at)
+                .map_err(|e| {
+                    BiovaultError::InvalidArgument(format!("expiry '{}': {}", expires_at, e))
+                })?
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339(),
+        ),
+        None => None,
+    };
+
+    let conn = open_genome_database(db_path, key)?;
+    conn.execute(
+        "INSERT INTO consent_grants (purpose, recipient, granularity, expires_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            purpose.map(Purpose::name).unwrap_or(ANY),
+            recipient.trim(),
+            granularity.name(),
+            expires_at,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    info!("consent grant {} added", id);
+
+    Ok(conn.query_row(
+        &format!("SELECT {} FROM consent_grants WHERE id = ?1", GRANT_COLUMNS),
+        [id],
+        grant_from_row,
+    )?)
+}
+
+/// Revoke a grant. Returns false if it does not exist or was already revoked.
+pub fn revoke_consent(
+    db_path: &Path,
+    key: Option<&str>,
+    grant_id: i64,
+) -> Result<bool, BiovaultError> {
+    let conn = open_genome_database(db_path, key)?;
+    let revoked = conn.execute(
+        "UPDATE consent_grants SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+        params![chrono::Utc::now().to_rfc3339(), grant_id],
+    )?;
+    if revoked > 0 {
+        info!("consent grant {} revoked", grant_id);
+    }
+    Ok(revoked > 0)
+}
+
+/// The consent manifest, including revoked and expired grants
+pub fn list_consent_grants(
+    db_path: &Path,
+    key: Option<&str>,
+) -> Result<Vec<ConsentGrant>, BiovaultError> {
+    let conn = open_genome_database(db_path, key)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM consent_grants ORDER BY id",
+        GRANT_COLUMNS
+    ))?;
+    let grants = stmt.query_map([], grant_from_row)?;
+    Ok(grants.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Recorded consent decisions, newest first. Listing them is not itself
+/// recorded in the access log.
+pub fn list_consent_audit(
+    db_path: &Path,
+    key: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<ConsentDecision>, BiovaultError> {
+    let conn = open_genome_database_unlogged(db_path, key)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, purpose, recipient, granularity, allowed, grant_id, created_at
+         FROM consent_audit ORDER BY id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit.unwrap_or(-1)], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, bool>(4)?,
+            row.get::<_, Option<i64>>(5)?,
+            row.get::<_, String>(6)?,
+        ))
+    })?;
+
+    let mut decisions = Vec::new();
+    for row in rows {
+        let (id, purpose, recipient, granularity, allowed, grant_id, created_at) = row?;
+        // Skip purposes written by a newer library version
+        let (Some(purpose), Some(granularity)) = (
+            Purpose::from_name(&purpose),
+            Granularity::from_name(&granularity),
+        ) else {
+            continue;
+        };
+        decisions.push(ConsentDecision {
+            id,
+            purpose,
+            recipient,
+            granularity,
+            allowed,
+            grant_id,
+            created_at,
+        });
+    }
+    Ok(decisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+    use crate::error::codes;
+    use crate::export::{self, ExportFormat};
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use crate::migrations;
+
+    fn manifest_with(purpose: &str, recipient: &str) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO consent_grants (purpose, recipient, granularity, created_at)
+             VALUES (?1, ?2, 'individual', '2025-01-01T00:00:00Z')",
+            params![purpose, recipient],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn allowed(conn: &Connection, purpose: Purpose) -> bool {
+        match require_consent(conn, purpose, "alice@uni.edu", Granularity::Aggregate) {
+            Ok(()) => true,
+            Err(BiovaultError::ConsentRequired { .. }) => false,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn unrecognised_purposes_grant_nothing() {
+        for stored in [
+            "DP_QUERY",
+            "dp-query",
+            "dp",
+            "dp_qeury",
+            "genome_sequencing",
+            "",
+        ] {
+            let conn = manifest_with(stored, "alice@uni.edu");
+            assert!(
+                !allowed(&conn, Purpose::DpQuery),
+                "'{}' granted dp_query",
+                stored
+            );
+            assert!(
+                !allowed(&conn, Purpose::Export),
+                "'{}' granted export",
+                stored
+            );
+        }
+    }
+
+    #[test]
+    fn wildcard_and_named_purposes() {
+        let conn = manifest_with("*", "alice@uni.edu");
+        assert!(allowed(&conn, Purpose::DpQuery));
+        assert!(allowed(&conn, Purpose::Cohort));
+
+        let conn = manifest_with("dp_query", "alice@uni.edu");
+        assert!(allowed(&conn, Purpose::DpQuery));
+        assert!(!allowed(&conn, Purpose::Cohort));
+    }
+
+    #[test]
+    fn grants_list_their_stored_purpose() {
+        let conn = manifest_with("future_purpose", "alice@uni.edu");
+        let grants = active_grants(&conn).unwrap();
+        let grant = grants.last().unwrap();
+        assert_eq!(
+            grant.purpose,
+            GrantPurpose::Unrecognised {
+                name: "future_purpose".to_string()
+            }
+        );
+        assert_eq!(grant.purpose.label(), "future_purpose");
+    }
+
+    fn audit(conn: &Connection) -> Vec<(String, String, bool)> {
+        conn.prepare("SELECT purpose, recipient, allowed FROM consent_audit ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn the_owner_is_granted_once_on_first_use() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&conn).unwrap();
+
+        require_consent(&conn, Purpose::Export, OWNER, Granularity::Individual).unwrap();
+        require_consent(&conn, Purpose::Report, OWNER, Granularity::Individual).unwrap();
+        let grants = active_grants(&conn).unwrap();
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].purpose, GrantPurpose::All);
+        assert_eq!(grants[0].recipient, OWNER);
+
+        // Nobody else is covered by it
+        assert!(!allowed(&conn, Purpose::DpQuery));
+
+        // Once revoked it is not added again
+        conn.execute(
+            "UPDATE consent_grants SET revoked_at = '2025-01-02T00:00:00Z'",
+            [],
+        )
+        .unwrap();
+        assert!(matches!(
+            require_consent(&conn, Purpose::Export, OWNER, Granularity::Individual),
+            Err(BiovaultError::ConsentRequired { .. })
+        ));
+        let grants: i64 = conn
+            .query_row("SELECT COUNT(*) FROM consent_grants", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(grants, 1);
+        assert_eq!(
+            audit(&conn)
+                .into_iter()
+                .map(|(purpose, _, allowed)| (purpose, allowed))
+                .collect::<Vec<_>>(),
+            vec![
+                ("export".to_string(), true),
+                ("report".to_string(), true),
+                ("dp_query".to_string(), false),
+                ("export".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_stored_granularity_is_an_error() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO consent_grants (purpose, recipient, granularity, created_at)
+             VALUES ('*', 'alice@uni.edu', 'everything', '2025-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        let error = require_consent(
+            &conn,
+            Purpose::DpQuery,
+            "alice@uni.edu",
+            Granularity::Aggregate,
+        )
+        .unwrap_err();
+        assert_eq!(error.code(), codes::DATABASE_ERROR);
+        assert!(error.to_string().contains("everything"), "{}", error);
+    }
+
+    #[test]
+    fn denied_export_and_analysis_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("genome.txt");
+        std::fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\nrs1\t1\t100\tAG\n",
+        )
+        .unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        let db = db_path.to_str().unwrap();
+        let clinvar = dir.path().join("clinvar.sqlite");
+        Connection::open(&clinvar)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                     gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);",
+            )
+            .unwrap();
+        let clinvar = clinvar.to_str().unwrap();
+        let export = |name: &str| {
+            let output = dir.path().join(name);
+            let result = export::export_genome(
+                db,
+                None,
+                None,
+                ExportFormat::TwentyThreeAndMe,
+                output.to_str().unwrap(),
+                None,
+            );
+            (result, output)
+        };
+
+        let (result, output) = export("first.txt");
+        result.unwrap();
+        assert!(output.exists());
+
+        let owner_grant = list_consent_grants(&db_path, None).unwrap()[0].id;
+        assert!(revoke_consent(&db_path, None, owner_grant).unwrap());
+
+        let (result, output) = export("second.txt");
+        assert_eq!(result.unwrap_err().code(), codes::CONSENT_REQUIRED);
+        assert!(!output.exists());
+        let error = analysis::analyze_clinvar_matches(db, None, None, clinvar).unwrap_err();
+        assert_eq!(error.code(), codes::CONSENT_REQUIRED);
+
+        let decisions: Vec<(Purpose, String, bool)> = list_consent_audit(&db_path, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.purpose, d.recipient, d.allowed))
+            .collect();
+        assert_eq!(
+            decisions,
+            vec![
+                (Purpose::ClinvarAnalysis, OWNER.to_string(), false),
+                (Purpose::Export, OWNER.to_string(), false),
+                (Purpose::Export, OWNER.to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_check_is_audited() {
+        let conn = manifest_with("dp_query", "alice@uni.edu");
+        allowed(&conn, Purpose::DpQuery);
+        allowed(&conn, Purpose::Export);
+        let decisions: Vec<(String, bool)> = conn
+            .prepare("SELECT purpose, allowed FROM consent_audit ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            decisions,
+            vec![
+                ("dp_query".to_string(), true),
+                ("export".to_string(), false)
+            ]
+        );
+    }
+}
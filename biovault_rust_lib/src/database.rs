@@ -1,57 +1,140 @@
+use crate::access_log::{self, AccessEvent};
+use crate::encoding::{self, CHROM_UNKNOWN, GenotypeDictionary};
+use crate::error::BiovaultError;
+use crate::migrations::{self, table_exists};
 use crate::parsers::ParseResult;
-use rusqlite::Connection;
-use std::error::Error;
+use crate::progress::{Phase, Progress};
+use crate::storage::Storage;
+use rusqlite::{Connection, OpenFlags, params};
+use std::collections::HashMap;
 use std::path::Path;
+use tracing::{debug, info};
 
-/// Create SQLite database from parsed genome data
-pub fn create_genome_database(
-    parse_result: ParseResult,
-    output_path: &Path,
-    custom_name: &str,
-) -> Result<String, Box<dyn Error>> {
-    eprintln!("Rust DB: Opening database at {:?}", output_path);
-    let conn = Connection::open(output_path)?;
-
-    eprintln!("Rust DB: Creating tables...");
-    // Create tables matching your JS schema
-    conn.execute(
-        "CREATE TABLE genome_metadata (
-            id INTEGER PRIMARY KEY,
-            file_name TEXT NOT NULL,
-            source_format TEXT NOT NULL,
-            total_variants INTEGER NOT NULL,
-            rsid_count INTEGER NOT NULL,
-            assembly TEXT,
-            upload_date TEXT NOT NULL,
-            db_name TEXT NOT NULL
-        )",
-        [],
-    )?;
+/// Unlock an encrypted database. Must run before any other statement.
+/// Fails if the key is wrong or the file is not a database.
+fn apply_key(conn: &Connection, key: &str) -> Result<(), BiovaultError> {
+    check_encryption_available()?;
+    conn.pragma_update(None, "key", key)?;
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .map_err(|_| BiovaultError::WrongKey)?;
+    Ok(())
+}
 
-    conn.execute(
-        "CREATE TABLE variants (
-            id INTEGER PRIMARY KEY,
-            file_id INTEGER NOT NULL,
-            rsid TEXT,
-            chromosome TEXT NOT NULL,
-            position INTEGER NOT NULL,
-            genotype TEXT NOT NULL,
-            source_format TEXT NOT NULL,
-            FOREIGN KEY(file_id) REFERENCES genome_metadata(id) ON DELETE CASCADE
-        )",
-        [],
-    )?;
+/// The WebAssembly build has no SQLCipher; refuse keys rather than silently
+/// storing genomes in plaintext
+fn check_encryption_available() -> Result<(), BiovaultError> {
+    if cfg!(target_family = "wasm") {
+        return Err(BiovaultError::InvalidArgument(
+            "encryption is not available in the WebAssembly build".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Open an existing user genome database, checking that it is one of ours and
+/// upgrading its schema to the current version.
+///
+/// `key` unlocks databases created with encryption; pass `None` for plaintext
+/// databases. The open is recorded in the access log, so the file must be
+/// writable even when the caller only reads.
+pub fn open_genome_database(path: &Path, key: Option<&str>) -> Result<Connection, BiovaultError> {
+    open_genome_storage(&Storage::from(path), key)
+}
 
-    conn.execute("CREATE INDEX idx_variants_rsid ON variants (rsid)", [])?;
+/// `open_genome_database` for a database in any `Storage`
+pub fn open_genome_storage(
+    storage: &Storage,
+    key: Option<&str>,
+) -> Result<Connection, BiovaultError> {
+    let conn = open_genome_storage_unlogged(storage, key)?;
+    access_log::record_access(&conn, AccessEvent::Open, None, 0)?;
+    Ok(conn)
+}
 
-    conn.execute(
-        "CREATE INDEX idx_variants_chr_pos ON variants (chromosome, position)",
-        [],
+/// `open_genome_database` without recording the open in the access log, for
+/// reading the audit trails themselves; reading them must not change them
+pub(crate) fn open_genome_database_unlogged(
+    path: &Path,
+    key: Option<&str>,
+) -> Result<Connection, BiovaultError> {
+    open_genome_storage_unlogged(&Storage::from(path), key)
+}
+
+fn open_genome_storage_unlogged(
+    storage: &Storage,
+    key: Option<&str>,
+) -> Result<Connection, BiovaultError> {
+    let conn = storage.open(
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
     )?;
 
-    eprintln!("Rust DB: Tables created successfully");
+    if let Some(key) = key {
+        apply_key(&conn, key)?;
+    }
+
+    // Unversioned files are only accepted if they have the original layout
+    let version = migrations::schema_version(&conn)?;
+    if version == 0
+        && !(table_exists(&conn, "genome_metadata")? && table_exists(&conn, "variants")?)
+    {
+        return Err(BiovaultError::NotAGenomeDatabase(
+            storage.path().to_path_buf(),
+        ));
+    }
+
+    let from_version = migrations::migrate(&conn)?;
+    if from_version < migrations::SCHEMA_VERSION {
+        info!(
+            "upgraded {:?} from schema version {} to {}",
+            storage.path(),
+            from_version,
+            migrations::SCHEMA_VERSION
+        );
+    }
+
+    Ok(conn)
+}
+
+/// Create a new, empty genome database with the current schema.
+///
+/// If `key` is given the database is encrypted with SQLCipher.
+pub fn create_genome_storage(
+    storage: &Storage,
+    key: Option<&str>,
+) -> Result<Connection, BiovaultError> {
+    debug!("creating database at {:?}", storage.path());
+    if key.is_some() {
+        check_encryption_available()?;
+    }
+    let conn = storage.open(OpenFlags::default())?;
+
+    if let Some(key) = key {
+        debug!("encrypting database");
+        conn.pragma_update(None, "key", key)?;
+    }
+
+    migrations::migrate(&conn)?;
+    access_log::record_access(&conn, AccessEvent::Open, None, 0)?;
+
+    debug!("tables created");
+    Ok(conn)
+}
 
-    // Insert metadata
+/// Add a parsed genome file to a database. Returns the new genome id.
+///
+/// `content_hash` identifies the input file so re-imports can be detected.
+pub fn insert_genome(
+    conn: &Connection,
+    parse_result: ParseResult,
+    custom_name: &str,
+    person: Option<&str>,
+    content_hash: &str,
+    progress: &mut Progress,
+) -> Result<i64, BiovaultError> {
     let upload_date = chrono::Utc::now().to_rfc3339();
     let db_name = format!(
         "{}_{}",
@@ -59,61 +142,347 @@ pub fn create_genome_database(
         chrono::Utc::now().timestamp()
     );
 
-    eprintln!("Rust DB: Inserting metadata...");
-    conn.execute(
-        "INSERT INTO genome_metadata 
-         (file_name, source_format, total_variants, rsid_count, assembly, upload_date, db_name)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        [
+    let tx = conn.unchecked_transaction()?;
+
+    tx.execute(
+        "INSERT INTO genome_metadata
+         (file_name, source_format, total_variants, rsid_count, assembly, upload_date, db_name,
+          person, content_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
             custom_name,
-            &parse_result.metadata.source_format,
-            &parse_result.metadata.total_variants.to_string(),
-            &parse_result.metadata.rsid_count.to_string(),
+            parse_result.metadata.source_format,
+            parse_result.metadata.total_variants as i64,
+            parse_result.metadata.rsid_count as i64,
             "GRCh37", // Default for 23andMe
-            &upload_date,
-            &db_name,
+            upload_date,
+            db_name,
+            person,
+            content_hash,
         ],
     )?;
 
     // Get the last inserted id for genome_metadata
-    let file_id = conn.last_insert_rowid();
+    let file_id = tx.last_insert_rowid();
 
-    // Get variant count before moving the vector
-    let variant_count = parse_result.variants.len();
+    insert_variants(&tx, file_id, parse_result, progress)?;
+    tx.commit()?;
+
+    // Analyze for better query performance. The import is committed, so
+    // this step is no longer cancellable.
+    progress.report(Phase::Indexing, 0, 0);
+    conn.execute("ANALYZE", [])?;
 
-    eprintln!("Rust DB: Inserting {} variants...", variant_count);
+    Ok(file_id)
+}
+
+/// Replace the variants and metadata of an existing genome with a fresh parse.
+/// The genome keeps its id.
+pub fn replace_genome(
+    conn: &Connection,
+    genome_id: i64,
+    parse_result: ParseResult,
+    custom_name: &str,
+    person: Option<&str>,
+    content_hash: &str,
+    progress: &mut Progress,
+) -> Result<(), BiovaultError> {
+    let upload_date = chrono::Utc::now().to_rfc3339();
 
-    // Insert variants in batches for performance
     let tx = conn.unchecked_transaction()?;
-    {
-        let mut stmt = tx.prepare(
-            "INSERT INTO variants
-             (file_id, rsid, chromosome, position, genotype, source_format)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        )?;
-
-        for variant in parse_result.variants {
-            stmt.execute([
-                &file_id.to_string(),
-                variant.rsid.as_deref().unwrap_or(""),
-                &variant.chromosome,
-                &variant.position.to_string(),
-                &variant.genotype,
-                &variant.source_format,
-            ])?;
-        }
-    }
+    tx.execute("DELETE FROM variants WHERE file_id = ?1", [genome_id])?;
+    tx.execute(
+        "UPDATE genome_metadata
+         SET file_name = ?2, source_format = ?3, total_variants = ?4, rsid_count = ?5,
+             upload_date = ?6, person = COALESCE(?7, person), content_hash = ?8
+         WHERE id = ?1",
+        params![
+            genome_id,
+            custom_name,
+            parse_result.metadata.source_format,
+            parse_result.metadata.total_variants as i64,
+            parse_result.metadata.rsid_count as i64,
+            upload_date,
+            person,
+            content_hash,
+        ],
+    )?;
+
+    insert_variants(&tx, genome_id, parse_result, progress)?;
     tx.commit()?;
 
-    // Analyze for better query performance
+    progress.report(Phase::Indexing, 0, 0);
     conn.execute("ANALYZE", [])?;
 
-    eprintln!(
-        "Rust DB: Database creation completed successfully. Inserted {} variants",
-        variant_count
-    );
+    Ok(())
+}
+
+/// Insert the variants of one genome in the compact layout. Cancelling
+/// returns an error, so the caller's transaction is rolled back.
+fn insert_variants(
+    conn: &Connection,
+    file_id: i64,
+    parse_result: ParseResult,
+    progress: &mut Progress,
+) -> Result<(), BiovaultError> {
+    let variant_count = parse_result.variants.len();
+    let total = variant_count as u64;
+    debug!("inserting {} variants", variant_count);
+
+    let mut genotypes = GenotypeDictionary::load(conn)?;
+    // Some chips have several probes at one position
+    let mut probes: HashMap<(i64, u64), i64> = HashMap::new();
+
+    let mut stmt = conn.prepare(
+        "INSERT INTO variants
+         (chromosome, position, file_id, probe, rsid, genotype)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+
+    for (inserted, variant) in parse_result.variants.into_iter().enumerate() {
+        progress.update(Phase::Inserting, inserted as u64, total)?;
+        // Chromosome 0 marks unplaced calls; any other unknown name would be
+        // lost, as `migrate` refuses to do on upgrade
+        let chromosome = match encoding::chromosome_code(&variant.chromosome) {
+            Some(code) => code,
+            None if variant.chromosome.trim() == "0" => CHROM_UNKNOWN,
+            None => {
+                return Err(BiovaultError::InvalidArgument(format!(
+                    "unrecognised chromosome '{}' at position {}",
+                    variant.chromosome, variant.position
+                )));
+            }
+        };
+        let probe = probes.entry((chromosome, variant.position)).or_default();
+        let genotype = genotypes.code(conn, &variant.genotype)?;
+
+        stmt.execute(params![
+            chromosome,
+            variant.position as i64,
+            file_id,
+            *probe,
+            variant.rsid.as_deref().and_then(encoding::rsid_number),
+            genotype,
+        ])?;
+        *probe += 1;
+    }
+    progress.update(Phase::Inserting, total, total)?;
+
+    info!("inserted {} variants for genome {}", variant_count, file_id);
+
+    Ok(())
+}
+
+/// Copy an open database into a new file with `sqlcipher_export`.
+/// An empty key writes a plaintext copy.
+pub(crate) fn export_database(
+    conn: &Connection,
+    output_path: &Path,
+    key: &str,
+) -> Result<(), BiovaultError> {
+    if output_path.exists() {
+        return Err(BiovaultError::AlreadyExists(output_path.to_path_buf()));
+    }
+
+    let version = migrations::schema_version(conn)?;
+    let output = output_path
+        .to_str()
+        .ok_or_else(|| BiovaultError::InvalidArgument("Output path is not valid UTF-8".into()))?;
+
+    // Genome connections are opened without SQLITE_OPEN_CREATE, so ATTACH
+    // cannot create the file itself
+    std::fs::File::create(output_path)?;
+
+    let result = conn
+        .execute("ATTACH DATABASE ?1 AS export KEY ?2", [output, key])
+        .and_then(|_| {
+            let exported = conn
+                .query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))
+                .and_then(|_| conn.pragma_update(Some("export"), "user_version", version));
+            conn.execute("DETACH DATABASE export", [])?;
+            exported
+        });
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(output_path);
+        return Err(e.into());
+    }
 
-    Ok(db_name)
+    Ok(())
+}
+
+/// Write a plaintext copy of an encrypted genome database
+pub fn export_decrypted_genome_database(
+    path: &Path,
+    key: &str,
+    output_path: &Path,
+) -> Result<(), BiovaultError> {
+    let conn = open_genome_database(path, Some(key))?;
+    export_database(&conn, output_path, "")?;
+
+    info!("exported decrypted copy to {:?}", output_path);
+    Ok(())
+}
+
+/// Change the encryption of a genome database in place.
+///
+/// `old_key` / `new_key` of `None` mean plaintext, so this also encrypts an
+/// existing plaintext database or removes encryption. Both `None` only
+/// checks that the database opens.
+pub fn rekey_genome_database(
+    path: &Path,
+    old_key: Option<&str>,
+    new_key: Option<&str>,
+) -> Result<(), BiovaultError> {
+    let conn = open_genome_database(path, old_key)?;
+
+    match (old_key, new_key) {
+        (None, None) => return Ok(()),
+        (Some(_), Some(new_key)) => {
+            conn.pragma_update(None, "rekey", new_key)?;
+            info!("rekeyed database");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // SQLCipher cannot rekey to or from plaintext; export into a new file and swap
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".rekey");
+    let tmp_path = std::path::PathBuf::from(tmp_name);
+    if tmp_path.exists() {
+        std::fs::remove_file(&tmp_path)?;
+    }
+
+    export_database(&conn, &tmp_path, new_key.unwrap_or(""))?;
+    drop(conn);
+    // A journal or WAL of the old file must not be applied to the new one,
+    // and may hold its pages in the old encryption
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match std::fs::remove_file(&sidecar) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    std::fs::rename(&tmp_path, path)?;
+
+    info!("changed database encryption");
+    Ok(())
 }
 
 // Removed unused helper `get_documents_dir` to keep the public surface minimal.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::codes;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn genome_database(dir: &Path, key: Option<&str>) -> PathBuf {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\n\
+             rs1\t1\t100\tAG\n\
+             rs2\tX\t200\tC\n",
+        )
+        .unwrap();
+        let db = dir.join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db,
+            "genome",
+            None,
+            key,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        db
+    }
+
+    fn variant_count(path: &Path, key: Option<&str>) -> Result<i64, BiovaultError> {
+        let conn = open_genome_database(path, key)?;
+        Ok(conn.query_row("SELECT COUNT(*) FROM variants", [], |row| row.get(0))?)
+    }
+
+    fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    #[test]
+    fn wrong_keys_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let encrypted = genome_database(dir.path(), Some("secret"));
+        assert_eq!(variant_count(&encrypted, Some("secret")).unwrap(), 2);
+
+        let err = variant_count(&encrypted, Some("guess")).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+        assert!(variant_count(&encrypted, None).is_err());
+
+        let plain = tempfile::tempdir().unwrap();
+        let plain = genome_database(plain.path(), None);
+        let err = variant_count(&plain, Some("secret")).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+    }
+
+    #[test]
+    fn rekeying_moves_between_plaintext_and_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = genome_database(dir.path(), None);
+        let inode = |db: &Path| std::os::unix::fs::MetadataExt::ino(&fs::metadata(db).unwrap());
+        let before = inode(&db);
+
+        // Nothing to change, so no copy replaces the file
+        rekey_genome_database(&db, None, None).unwrap();
+        assert_eq!(inode(&db), before);
+
+        // Leftovers of the plaintext file must not outlive it
+        for suffix in ["-wal", "-shm"] {
+            fs::write(sidecar(&db, suffix), b"stale").unwrap();
+        }
+        rekey_genome_database(&db, None, Some("first")).unwrap();
+        for suffix in ["-journal", "-wal", "-shm", ".rekey"] {
+            assert!(!sidecar(&db, suffix).exists(), "{} left behind", suffix);
+        }
+        assert!(variant_count(&db, None).is_err());
+        assert_eq!(variant_count(&db, Some("first")).unwrap(), 2);
+
+        rekey_genome_database(&db, Some("first"), Some("second")).unwrap();
+        let err = variant_count(&db, Some("first")).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+        assert_eq!(variant_count(&db, Some("second")).unwrap(), 2);
+
+        let err = rekey_genome_database(&db, Some("first"), None).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+        rekey_genome_database(&db, Some("second"), None).unwrap();
+        assert_eq!(variant_count(&db, None).unwrap(), 2);
+    }
+
+    #[test]
+    fn decrypted_exports_leave_the_original_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = genome_database(dir.path(), Some("secret"));
+        let output = dir.path().join("plain.sqlite");
+
+        let err = export_decrypted_genome_database(&db, "guess", &output).unwrap_err();
+        assert_eq!(err.code(), codes::WRONG_KEY);
+        assert!(!output.exists());
+
+        export_decrypted_genome_database(&db, "secret", &output).unwrap();
+        assert_eq!(variant_count(&output, None).unwrap(), 2);
+        assert_eq!(variant_count(&db, Some("secret")).unwrap(), 2);
+        assert_eq!(
+            migrations::schema_version(&Connection::open(&output).unwrap()).unwrap(),
+            migrations::SCHEMA_VERSION
+        );
+
+        let err = export_decrypted_genome_database(&db, "secret", &output).unwrap_err();
+        assert_eq!(err.code(), codes::ALREADY_EXISTS);
+    }
+}
@@ -0,0 +1,709 @@
+//! Answering research requests through a SyftBox datasite.
+//!
+//! The sync client mirrors each datasite under
+//! `<syftbox>/datasites/<email>/`. Each requester drops job files into their
+//! own folder of the owner's inbox, `app_data/biovault/inbox/<requester>/`;
+//! the owner's device checks each job against an allow-list kept outside the
+//! synced folder, runs the permitted differentially private query on the
+//! local genome database, and writes the noisy aggregate, or a refusal, to
+//! `app_data/biovault/outbox/<requester>/`. Nothing else leaves the device.
+//!
+//! The requester named inside a job file is only a claim. Who a job is from
+//! is decided by the inbox folder it is in: the owner's device gives each
+//! requester in the allow-list a folder with a SyftBox permission file that
+//! lets only that requester write to it, and the SyftBox server enforces it.
+//! Jobs outside such a folder, or naming someone else, are rejected.
+
+use crate::error::BiovaultError;
+use crate::privacy::{self, DpQuery, Mechanism, NoisyValue, PrsWeight};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, info_span, warn};
+
+/// Folder under `app_data` used by this app
+const APP_NAME: &str = "biovault";
+
+/// SyftBox access control file, applying to its folder and everything below
+const PERMISSION_FILE: &str = "syft.pub.yaml";
+
+/// Where a datasite lives and the local data it answers from
+#[derive(Debug, Clone)]
+pub struct DatasiteConfig {
+    /// The SyftBox folder, containing `datasites/`
+    pub syftbox_dir: PathBuf,
+    /// The datasite owner's email
+    pub email: String,
+    /// Genome database queries run on
+    pub db_path: PathBuf,
+    pub key: Option<String>,
+    /// ClinVar database for carrier count queries; they are refused without one
+    pub clinvar_db_path: Option<PathBuf>,
+}
+
+impl DatasiteConfig {
+    pub fn app_dir(&self) -> PathBuf {
+        app_dir(&self.syftbox_dir, &self.email)
+    }
+
+    pub fn inbox_dir(&self) -> PathBuf {
+        self.app_dir().join("inbox")
+    }
+
+    pub fn outbox_dir(&self) -> PathBuf {
+        self.app_dir().join("outbox")
+    }
+}
+
+fn app_dir(syftbox_dir: &Path, email: &str) -> PathBuf {
+    syftbox_dir
+        .join("datasites")
+        .join(email)
+        .join("app_data")
+        .join(APP_NAME)
+}
+
+/// The analysis a research job asks for. Unlike `DpQuery` it names no local
+/// files; the datasite supplies those.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub enum JobQuery {
+    AlleleCounts {
+        rsids: Vec<String>,
+    },
+    CarrierCounts {
+        genes: Vec<String>,
+    },
+    PrsHistogram {
+        weights: Vec<PrsWeight>,
+        min: f64,
+        max: f64,
+        bins: usize,
+    },
+}
+
+impl JobQuery {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobQuery::AlleleCounts { .. } => "allele_counts",
+            JobQuery::CarrierCounts { .. } => "carrier_counts",
+            JobQuery::PrsHistogram { .. } => "prs_histogram",
+        }
+    }
+}
+
+/// A research request, as written to a datasite's inbox
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResearchJob {
+    /// Letters, digits, `-` and `_`; also the file name in the inbox and outbox
+    pub id: String,
+    /// Email of the researcher's datasite; must match the inbox folder the
+    /// job arrives in
+    pub requester: String,
+    pub query: JobQuery,
+    pub mechanism: Mechanism,
+    pub epsilon: f64,
+}
+
+/// Analyses the owner permits, per requester. Only requesters named in a
+/// rule get an inbox folder, so `*` rules apply to them and nobody else.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AllowList {
+    pub rules: Vec<AllowRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllowRule {
+    /// Requester email, or `*` for anyone
+    pub requester: String,
+    /// A `JobQuery` name, e.g. `allele_counts`
+    pub query: String,
+    /// Most epsilon one job may spend
+    pub max_epsilon: f64,
+}
+
+impl AllowList {
+    /// Whether some rule permits `job` from `requester`, the sender SyftBox
+    /// authenticated rather than the one the job names
+    pub fn permits(&self, requester: &str, job: &ResearchJob) -> bool {
+        self.rules.iter().any(|rule| {
+            (rule.requester == "*" || rule.requester.eq_ignore_ascii_case(requester))
+                && rule.query == job.query.name()
+                && job.epsilon <= rule.max_epsilon
+        })
+    }
+
+    /// The requesters named in rules, lowercased and without `*`
+    fn requesters(&self) -> Vec<String> {
+        let mut requesters: Vec<String> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.requester != "*")
+            .map(|rule| rule.requester.to_ascii_lowercase())
+            .collect();
+        requesters.sort();
+        requesters.dedup();
+        requesters
+    }
+}
+
+/// Read an allow-list from a JSON file
+pub fn load_allow_list(path: &Path) -> Result<AllowList, BiovaultError> {
+    let content = fs::read_to_string(path).map_err(|e| BiovaultError::io(path, e))?;
+    serde_json::from_str(&content).map_err(|e| BiovaultError::Parse {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Answered; the response holds the noisy result
+    Answered,
+    /// Not permitted by the allow-list, or not a valid job
+    Rejected,
+    /// Permitted but could not be answered, e.g. the budget is spent
+    Failed,
+}
+
+/// What a job's response in the outbox holds. Only the noisy values and the
+/// parameters needed to interpret them are included.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResponse {
+    pub id: String,
+    pub status: JobStatus,
+    /// An error code for failures, or why a job was rejected
+    pub reason: Option<String>,
+    pub query: Option<String>,
+    pub mechanism: Option<Mechanism>,
+    pub epsilon: Option<f64>,
+    pub noise_scale: Option<f64>,
+    pub values: Vec<NoisyValue>,
+    pub answered_at: String,
+}
+
+/// Summary of one processed inbox file
+#[derive(Debug, Clone, Serialize)]
+pub struct JobOutcome {
+    pub id: String,
+    /// The authenticated requester; `None` for jobs outside a requester folder
+    pub requester: Option<String>,
+    pub status: JobStatus,
+    pub reason: Option<String>,
+}
+
+/// Write `job` into the requester's folder of the inbox of the datasite owned
+/// by `owner_email`, for the sync client to deliver. Returns the file written.
+pub fn submit_job(
+    syftbox_dir: &Path,
+    owner_email: &str,
+    job: &ResearchJob,
+) -> Result<PathBuf, BiovaultError> {
+    check_job_id(&job.id)?;
+    check_email(owner_email)?;
+    check_email(&job.requester)?;
+    let inbox = app_dir(syftbox_dir, owner_email)
+        .join("inbox")
+        .join(job.requester.to_ascii_lowercase());
+    let path = inbox.join(format!("{}.json", job.id));
+    if path.exists() {
+        return Err(BiovaultError::AlreadyExists(path));
+    }
+    write_atomically(&path, &serde_json::to_vec_pretty(job)?)?;
+    Ok(path)
+}
+
+/// SyftBox rules giving only `email` access to a folder, writing too if
+/// `write` is set
+fn permission_rules(email: &str, write: bool) -> String {
+    let writers = if write {
+        format!("\n    - '{}'", email)
+    } else {
+        " []".to_string()
+    };
+    format!(
+        "# Written by BioVault; rewritten whenever the datasite runs\n\
+         rules:\n\
+         - pattern: '**'\n  \
+           access:\n    \
+             admin: []\n    \
+             read:\n    - '{}'\n    \
+             write:{}\n",
+        email, writers
+    )
+}
+
+/// Write a permission file unless it already has this content
+fn ensure_permissions(dir: &Path, rules: &str) -> Result<(), BiovaultError> {
+    let path = dir.join(PERMISSION_FILE);
+    if fs::read_to_string(&path).is_ok_and(|current| current == rules) {
+        return Ok(());
+    }
+    write_atomically(&path, rules.as_bytes())
+}
+
+/// Give each requester named in `allow_list` an inbox folder only they can
+/// write to and an outbox folder only they can read. The owner's own folders
+/// stay private to the owner.
+pub fn open_inboxes(config: &DatasiteConfig, allow_list: &AllowList) -> Result<(), BiovaultError> {
+    check_email(&config.email)?;
+    let app_dir = config.app_dir();
+    fs::create_dir_all(&app_dir).map_err(|e| BiovaultError::io(&app_dir, e))?;
+    ensure_permissions(&app_dir, &permission_rules(&config.email, true))?;
+
+    for requester in allow_list.requesters() {
+        check_email(&requester)?;
+        ensure_permissions(
+            &config.inbox_dir().join(&requester),
+            &permission_rules(&requester, true),
+        )?;
+        ensure_permissions(
+            &config.outbox_dir().join(&requester),
+            &permission_rules(&requester, false),
+        )?;
+    }
+    Ok(())
+}
+
+/// The `.json` files directly in `dir`, sorted
+fn job_files(dir: &Path) -> Result<Vec<PathBuf>, BiovaultError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| BiovaultError::io(dir, e))? {
+        let path = entry.map_err(|e| BiovaultError::io(dir, e))?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Answer every job in the datasite's inbox. Each job gets a response in the
+/// requester's outbox folder and is removed from the inbox; jobs that already
+/// have a response are removed without being run again. Inbox folders are
+/// opened first for the requesters in `allow_list`.
+pub fn process_jobs(
+    config: &DatasiteConfig,
+    allow_list: &AllowList,
+) -> Result<Vec<JobOutcome>, BiovaultError> {
+    let _span = info_span!("datasite").entered();
+    open_inboxes(config, allow_list)?;
+    let inbox = config.inbox_dir();
+    let outbox = config.outbox_dir();
+    let requesters = allow_list.requesters();
+
+    let mut outcomes = Vec::new();
+    // Loose files have no authenticated sender; they are answered with a
+    // rejection in the outbox itself
+    for job_file in job_files(&inbox)? {
+        outcomes.extend(process_job_file(
+            config, allow_list, &job_file, None, &outbox,
+        )?);
+    }
+
+    let mut folders = Vec::new();
+    for entry in fs::read_dir(&inbox).map_err(|e| BiovaultError::io(&inbox, e))? {
+        let path = entry.map_err(|e| BiovaultError::io(&inbox, e))?.path();
+        if path.is_dir() {
+            folders.push(path);
+        }
+    }
+    folders.sort();
+
+    for folder in folders {
+        // Only folders we opened are writable by one requester alone
+        let Some(requester) = folder
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| requesters.iter().any(|r| r == name))
+            .map(str::to_string)
+        else {
+            warn!("ignoring inbox folder {:?} of no allowed requester", folder);
+            continue;
+        };
+        for job_file in job_files(&folder)? {
+            outcomes.extend(process_job_file(
+                config,
+                allow_list,
+                &job_file,
+                Some(&requester),
+                &outbox.join(&requester),
+            )?);
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Answer one job file from `requester`'s inbox folder, or from the inbox
+/// itself when `requester` is `None`
+fn process_job_file(
+    config: &DatasiteConfig,
+    allow_list: &AllowList,
+    job_file: &Path,
+    requester: Option<&str>,
+    outbox: &Path,
+) -> Result<Option<JobOutcome>, BiovaultError> {
+    // The file name, not the claimed id, decides where the response goes
+    let Some(id) = job_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| check_job_id(stem).is_ok())
+        .map(str::to_string)
+    else {
+        warn!("ignoring inbox file with an invalid name");
+        return Ok(None);
+    };
+    let response_path = outbox.join(format!("{}.json", id));
+    if response_path.exists() {
+        remove_job_file(job_file);
+        return Ok(None);
+    }
+
+    let response = match (requester, read_job(job_file, &id)) {
+        (None, _) => rejection(
+            &id,
+            "jobs must be submitted to inbox/<requester email>/".to_string(),
+        ),
+        (Some(_), Err(reason)) => rejection(&id, reason),
+        (Some(requester), Ok(job)) if !job.requester.eq_ignore_ascii_case(requester) => rejection(
+            &id,
+            "the job names a requester other than its sender".to_string(),
+        ),
+        (Some(requester), Ok(job)) => answer_job(config, allow_list, requester, &job),
+    };
+    write_atomically(&response_path, &serde_json::to_vec_pretty(&response)?)?;
+    remove_job_file(job_file);
+
+    info!("job {}: {:?}", id, response.status);
+    Ok(Some(JobOutcome {
+        id,
+        requester: requester.map(str::to_string),
+        status: response.status,
+        reason: response.reason,
+    }))
+}
+
+fn read_job(path: &Path, id: &str) -> Result<ResearchJob, String> {
+    let content = fs::read(path).map_err(|_| "cannot read job file".to_string())?;
+    let job: ResearchJob =
+        serde_json::from_slice(&content).map_err(|e| format!("malformed job: {}", e))?;
+    if job.id != id {
+        return Err("job id does not match its file name".to_string());
+    }
+    Ok(job)
+}
+
+fn answer_job(
+    config: &DatasiteConfig,
+    allow_list: &AllowList,
+    requester: &str,
+    job: &ResearchJob,
+) -> JobResponse {
+    if !allow_list.permits(requester, job) {
+        return rejection(&job.id, "not permitted by the allow-list".to_string());
+    }
+
+    let query = match job.query.clone() {
+        JobQuery::AlleleCounts { rsids } => DpQuery::AlleleCounts { rsids },
+        JobQuery::CarrierCounts { genes } => match &config.clinvar_db_path {
+            Some(clinvar_db_path) => DpQuery::CarrierCounts {
+                genes,
+                clinvar_db_path: clinvar_db_path.to_string_lossy().into_owned(),
+            },
+            None => {
+                return rejection(&job.id, "no ClinVar database on this datasite".to_string());
+            }
+        },
+        JobQuery::PrsHistogram {
+            weights,
+            min,
+            max,
+            bins,
+        } => DpQuery::PrsHistogram {
+            weights,
+            min,
+            max,
+            bins,
+        },
+    };
+
+    match privacy::run_dp_query(
+        &config.db_path,
+        config.key.as_deref(),
+        requester,
+        &query,
+        job.mechanism,
+        job.epsilon,
+    ) {
+        Ok(release) => JobResponse {
+            id: job.id.clone(),
+            status: JobStatus::Answered,
+            reason: None,
+            query: Some(release.query.to_string()),
+            mechanism: Some(release.mechanism),
+            epsilon: Some(release.epsilon),
+            noise_scale: Some(release.noise_scale),
+            values: release.values,
+            answered_at: chrono::Utc::now().to_rfc3339(),
+        },
+        Err(BiovaultError::ConsentRequired { .. }) => {
+            rejection(&job.id, "the owner has not consented".to_string())
+        }
+        // Only the code: messages can name local files
+        Err(error) => JobResponse {
+            status: JobStatus::Failed,
+            reason: Some(error.code().to_string()),
+            ..rejection(&job.id, String::new())
+        },
+    }
+}
+
+fn rejection(id: &str, reason: String) -> JobResponse {
+    JobResponse {
+        id: id.to_string(),
+        status: JobStatus::Rejected,
+        reason: Some(reason),
+        query: None,
+        mechanism: None,
+        epsilon: None,
+        noise_scale: None,
+        values: Vec::new(),
+        answered_at: chrono::Utc::now().to_rfc3339(),
+    }
+}
+
+fn check_job_id(id: &str) -> Result<(), BiovaultError> {
+    if id.is_empty()
+        || id.len() > 128
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(BiovaultError::InvalidArgument(format!(
+            "job id '{}' must be letters, digits, '-' or '_'",
+            id
+        )));
+    }
+    Ok(())
+}
+
+/// Emails name folders and appear in permission files, so they must not be
+/// able to leave `datasites/` or break out of a quoted YAML string
+fn check_email(email: &str) -> Result<(), BiovaultError> {
+    if !email.contains('@')
+        || email.starts_with('.')
+        || email
+            .chars()
+            .any(|c| matches!(c, '/' | '\\' | '\'' | '"') || c.is_whitespace() || c.is_control())
+    {
+        return Err(BiovaultError::InvalidArgument(format!(
+            "'{}' is not a datasite email",
+            email
+        )));
+    }
+    Ok(())
+}
+
+fn remove_job_file(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        warn!("cannot remove answered job file: {}", e);
+    }
+}
+
+/// Write through a temporary file so the sync client never sees half a file
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), BiovaultError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| BiovaultError::io(parent, e))?;
+    }
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.tmp", file_name));
+    fs::write(&temp, contents).map_err(|e| BiovaultError::io(&temp, e))?;
+    fs::rename(&temp, path).map_err(|e| BiovaultError::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consent::{self, Granularity, Purpose};
+    use crate::database::{create_genome_storage, open_genome_database};
+    use crate::storage::Storage;
+
+    const OWNER: &str = "owner@home.org";
+    const ALICE: &str = "alice@uni.edu";
+    const MALLORY: &str = "mallory@evil.com";
+
+    fn datasite(dir: &Path) -> (DatasiteConfig, AllowList) {
+        let db_path = dir.join("genome.sqlite");
+        create_genome_storage(&Storage::from(db_path.as_path()), None).unwrap();
+        consent::grant_consent(
+            &db_path,
+            None,
+            Some(Purpose::DpQuery),
+            ALICE,
+            Granularity::Aggregate,
+            None,
+        )
+        .unwrap();
+        privacy::set_privacy_budget(&db_path, None, 1.0, 0.0).unwrap();
+        let config = DatasiteConfig {
+            syftbox_dir: dir.join("SyftBox"),
+            email: OWNER.to_string(),
+            db_path,
+            key: None,
+            clinvar_db_path: None,
+        };
+        let allow_list = AllowList {
+            rules: vec![AllowRule {
+                requester: ALICE.to_string(),
+                query: "allele_counts".to_string(),
+                max_epsilon: 0.5,
+            }],
+        };
+        (config, allow_list)
+    }
+
+    fn job(id: &str, requester: &str) -> ResearchJob {
+        ResearchJob {
+            id: id.to_string(),
+            requester: requester.to_string(),
+            query: JobQuery::AlleleCounts {
+                rsids: vec!["rs1".to_string()],
+            },
+            mechanism: Mechanism::Laplace,
+            epsilon: 0.25,
+        }
+    }
+
+    /// Drop `job` into `folder` of the inbox, as a sync client would
+    fn deliver(config: &DatasiteConfig, folder: Option<&str>, job: &ResearchJob) {
+        let dir = match folder {
+            Some(folder) => config.inbox_dir().join(folder),
+            None => config.inbox_dir(),
+        };
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(format!("{}.json", job.id)),
+            serde_json::to_vec(job).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn response(config: &DatasiteConfig, requester: &str, id: &str) -> JobResponse {
+        let path = config
+            .outbox_dir()
+            .join(requester)
+            .join(format!("{}.json", id));
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn answers_jobs_from_the_requesters_own_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, allow_list) = datasite(dir.path());
+        let path = submit_job(&config.syftbox_dir, OWNER, &job("job-1", ALICE)).unwrap();
+        assert_eq!(path, config.inbox_dir().join(ALICE).join("job-1.json"));
+
+        let outcomes = process_jobs(&config, &allow_list).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].requester.as_deref(), Some(ALICE));
+        assert_eq!(outcomes[0].status, JobStatus::Answered);
+        assert_eq!(
+            response(&config, ALICE, "job-1").status,
+            JobStatus::Answered
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn a_claimed_requester_is_not_trusted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, allow_list) = datasite(dir.path());
+        open_inboxes(&config, &allow_list).unwrap();
+
+        // Loose in the inbox, claiming to be Alice
+        deliver(&config, None, &job("loose", ALICE));
+        // In Alice's folder, claiming to be someone else
+        deliver(&config, Some(ALICE), &job("other", MALLORY));
+        // In a folder nobody was given, claiming to be Alice
+        deliver(&config, Some(MALLORY), &job("forged", ALICE));
+
+        let outcomes = process_jobs(&config, &allow_list).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(
+            outcomes
+                .iter()
+                .all(|outcome| outcome.status == JobStatus::Rejected)
+        );
+        assert_eq!(outcomes[0].requester, None);
+        assert!(config.outbox_dir().join("loose.json").exists());
+        assert_eq!(
+            response(&config, ALICE, "other").reason.as_deref(),
+            Some("the job names a requester other than its sender")
+        );
+        // Left alone, and nothing spent from the budget
+        assert!(
+            config
+                .inbox_dir()
+                .join(MALLORY)
+                .join("forged.json")
+                .exists()
+        );
+        let conn = open_genome_database(&config.db_path, None).unwrap();
+        let spent: i64 = conn
+            .query_row("SELECT COUNT(*) FROM privacy_ledger", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(spent, 0);
+    }
+
+    #[test]
+    fn wildcard_rules_match_only_requesters_with_a_folder() {
+        let allow_list = AllowList {
+            rules: vec![
+                AllowRule {
+                    requester: "*".to_string(),
+                    query: "allele_counts".to_string(),
+                    max_epsilon: 0.5,
+                },
+                AllowRule {
+                    requester: "Alice@Uni.edu".to_string(),
+                    query: "prs_histogram".to_string(),
+                    max_epsilon: 0.5,
+                },
+            ],
+        };
+        assert_eq!(allow_list.requesters(), vec![ALICE.to_string()]);
+        assert!(allow_list.permits(ALICE, &job("a", MALLORY)));
+
+        let mut greedy = job("b", ALICE);
+        greedy.epsilon = 0.75;
+        assert!(!allow_list.permits(ALICE, &greedy));
+    }
+
+    #[test]
+    fn folders_are_shared_only_with_their_requester() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, allow_list) = datasite(dir.path());
+        open_inboxes(&config, &allow_list).unwrap();
+
+        let inbox =
+            fs::read_to_string(config.inbox_dir().join(ALICE).join(PERMISSION_FILE)).unwrap();
+        assert!(inbox.contains("read:\n    - 'alice@uni.edu'\n    write:\n    - 'alice@uni.edu'"));
+        let outbox =
+            fs::read_to_string(config.outbox_dir().join(ALICE).join(PERMISSION_FILE)).unwrap();
+        assert!(outbox.contains("read:\n    - 'alice@uni.edu'\n    write: []"));
+        let app = fs::read_to_string(config.app_dir().join(PERMISSION_FILE)).unwrap();
+        assert!(app.contains(OWNER) && !app.contains(ALICE));
+    }
+
+    #[test]
+    fn emails_cannot_escape_their_folder_or_quotes() {
+        for email in ["../x@y", "a/b@c", "a'b@c", "a b@c", "a\n@c", "nobody"] {
+            assert!(check_email(email).is_err(), "{:?}", email);
+        }
+        assert!(check_email(ALICE).is_ok());
+    }
+}
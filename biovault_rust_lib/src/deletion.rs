@@ -0,0 +1,821 @@
+//! Secure deletion and redaction of genome data.
+//!
+//! Deleting rows or files is not enough to get rid of genotypes: SQLite keeps
+//! freed pages in the file, the rollback journal holds copies of the pages a
+//! transaction changed, and the file system keeps the blocks of removed
+//! files. Here deletions run with `secure_delete` and in-memory temp storage,
+//! the journal is kept and zeroed by us instead of just unlinked, and the
+//! database is vacuumed so no free pages remain. Whole files are zeroed
+//! before they are removed.
+//!
+//! Flash storage may still keep old copies of blocks. On phones the
+//! dependable guarantee is encryption: a deleted encrypted database is
+//! unreadable once its key is discarded.
+
+use crate::database::open_genome_database;
+use crate::encoding::{self, CHROM_X, CHROM_XY};
+use crate::error::BiovaultError;
+use crate::genomes;
+use rusqlite::{Connection, OpenFlags, params};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Files SQLite may keep next to a database
+const SIDECAR_SUFFIXES: &[&str] = &["-journal", "-wal", "-shm"];
+
+/// What `delete_genome` removed
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct DeletionReport {
+    pub genome_id: i64,
+    pub variants_deleted: i64,
+    pub analysis_runs_deleted: i64,
+    /// True if this was the last genome and the database file was removed
+    pub database_removed: bool,
+    /// Files zeroed and removed, including SQLite journal files
+    pub files_removed: Vec<String>,
+}
+
+/// A part of the genome to redact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(tag = "locus", rename_all = "snake_case")]
+pub enum Locus {
+    /// A gene symbol such as `APOE`, resolved through a ClinVar database
+    Gene {
+        name: String,
+    },
+    /// An inclusive range of positions on one chromosome
+    Region {
+        chromosome: String,
+        start: i64,
+        end: i64,
+    },
+    Rsid {
+        rsid: String,
+    },
+}
+
+impl Locus {
+    /// Parse `rs429358`, `19:45409011-45412650` or a gene symbol
+    pub fn parse(text: &str) -> Result<Self, BiovaultError> {
+        let text = text.trim();
+        if encoding::rsid_number(text).is_some() {
+            return Ok(Locus::Rsid {
+                rsid: text.to_string(),
+            });
+        }
+        if let Some((chromosome, range)) = text.split_once(':') {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let (Ok(start), Ok(end)) = (
+                start.replace(',', "").parse::<i64>(),
+                end.replace(',', "").parse::<i64>(),
+            ) else {
+                return Err(BiovaultError::InvalidArgument(format!(
+                    "region '{}' must look like 19:45409011-45412650",
+                    text
+                )));
+            };
+            return Ok(Locus::Region {
+                chromosome: chromosome.to_string(),
+                start,
+                end,
+            });
+        }
+        if text.is_empty() {
+            return Err(BiovaultError::InvalidArgument(
+                "locus must not be empty".to_string(),
+            ));
+        }
+        Ok(Locus::Gene {
+            name: text.to_string(),
+        })
+    }
+}
+
+/// Selects the variants in `temp.redact_rsids` or `temp.redact_regions`
+const AT_TARGETS: &str = "WHERE rsid IN (SELECT rsid FROM temp.redact_rsids)
+     OR EXISTS (
+         SELECT 1 FROM temp.redact_regions r
+         WHERE r.chromosome = variants.chromosome
+           AND variants.position BETWEEN r.start AND r.end
+     )";
+
+/// What `redact_loci` removed
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct RedactionSummary {
+    /// The redacted database: the copy if one was requested, else the input
+    pub output_path: String,
+    pub variants_removed: i64,
+    /// Stored ClinVar findings at the redacted loci
+    pub findings_removed: i64,
+}
+
+/// Loci resolved to stored rsID numbers and chromosome code ranges
+#[derive(Default)]
+struct Targets {
+    genes: HashSet<String>,
+    rsids: HashSet<i64>,
+    regions: Vec<(i64, i64, i64)>,
+}
+
+impl Targets {
+    fn add_region(&mut self, chromosome: i64, start: i64, end: i64) {
+        self.regions.push((chromosome, start, end));
+        // Pseudo-autosomal calls are stored as XY at X coordinates
+        if chromosome == CHROM_X {
+            self.regions.push((CHROM_XY, start, end));
+        }
+    }
+
+    fn matches(&self, rsid: Option<i64>, chromosome: i64, position: i64) -> bool {
+        rsid.is_some_and(|rsid| self.rsids.contains(&rsid))
+            || self
+                .regions
+                .iter()
+                .any(|&(c, start, end)| c == chromosome && (start..=end).contains(&position))
+    }
+}
+
+fn resolve_loci(loci: &[Locus], clinvar_db_path: Option<&Path>) -> Result<Targets, BiovaultError> {
+    let mut targets = Targets::default();
+    let mut genes = Vec::new();
+    for locus in loci {
+        match locus {
+            Locus::Rsid { rsid } => {
+                let number = encoding::rsid_number(rsid).ok_or_else(|| {
+                    BiovaultError::InvalidArgument(format!("'{}' is not an rsID", rsid))
+                })?;
+                targets.rsids.insert(number);
+            }
+            Locus::Region {
+                chromosome,
+                start,
+                end,
+            } => {
+                let code = encoding::chromosome_code(chromosome).ok_or_else(|| {
+                    BiovaultError::InvalidArgument(format!("unknown chromosome '{}'", chromosome))
+                })?;
+                if start > end || *start < 0 {
+                    return Err(BiovaultError::InvalidArgument(format!(
+                        "region {}:{}-{} is empty",
+                        chromosome, start, end
+                    )));
+                }
+                targets.add_region(code, *start, *end);
+            }
+            Locus::Gene { name } => genes.push(name.to_ascii_uppercase()),
+        }
+    }
+    if genes.is_empty() {
+        return Ok(targets);
+    }
+
+    let clinvar_db_path = clinvar_db_path.ok_or_else(|| {
+        BiovaultError::InvalidArgument("redacting genes needs a ClinVar database".to_string())
+    })?;
+    let clinvar = Connection::open_with_flags(
+        clinvar_db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut stmt =
+        clinvar.prepare("SELECT rsid, chrom, pos, gene FROM variants WHERE gene LIKE ?1")?;
+    for gene in genes {
+        // Cover the gene by both its rsIDs and the span of its ClinVar
+        // variants, so positions still match if rsIDs were merged
+        let mut span: Option<(i64, i64, i64)> = None;
+        let mut rows = stmt.query([format!("%{}%", gene)])?;
+        while let Some(row) = rows.next()? {
+            let symbols: String = row.get(3)?;
+            if !symbols
+                .split(['|', ';', ','])
+                .any(|symbol| symbol.trim().eq_ignore_ascii_case(&gene))
+            {
+                continue;
+            }
+            let rsid: Option<String> = row.get(0)?;
+            if let Some(number) = rsid.as_deref().and_then(encoding::rsid_number) {
+                targets.rsids.insert(number);
+            }
+            let chromosome: String = row.get(1)?;
+            let position: i64 = row.get(2)?;
+            let Some(code) = encoding::chromosome_code(&chromosome) else {
+                continue;
+            };
+            span = match span {
+                Some((c, start, end)) if c == code => {
+                    Some((c, start.min(position), end.max(position)))
+                }
+                Some(span) => Some(span),
+                None => Some((code, position, position)),
+            };
+        }
+        let Some((chromosome, start, end)) = span else {
+            return Err(BiovaultError::NotFound(format!("Gene {} in ClinVar", gene)));
+        };
+        targets.add_region(chromosome, start, end);
+        targets.genes.insert(gene);
+    }
+    Ok(targets)
+}
+
+/// Zero a file and remove it. Returns false if it did not exist.
+fn scrub_file(path: &Path) -> Result<bool, BiovaultError> {
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(BiovaultError::io(path, e)),
+    };
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| BiovaultError::io(path, e))?;
+    let zeros = vec![0u8; 64 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])
+            .map_err(|e| BiovaultError::io(path, e))?;
+        remaining -= n as u64;
+    }
+    file.sync_all().map_err(|e| BiovaultError::io(path, e))?;
+    drop(file);
+    fs::remove_file(path).map_err(|e| BiovaultError::io(path, e))?;
+    Ok(true)
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Make the connection's deletions leave no copies of the deleted pages:
+/// freed content is zeroed, temp data stays in memory and the journal is
+/// kept so `finish_scrubbing` can zero it
+fn start_scrubbing(conn: &Connection) -> Result<(), BiovaultError> {
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    conn.pragma_update(None, "secure_delete", true)?;
+    conn.pragma_update(None, "temp_store", "MEMORY")?;
+    conn.query_row("PRAGMA journal_mode = PERSIST", [], |_| Ok(()))?;
+    Ok(())
+}
+
+/// Vacuum, then zero and remove the journal and any other sidecar files
+fn finish_scrubbing(conn: &Connection, path: &Path) -> Result<Vec<String>, BiovaultError> {
+    conn.execute("VACUUM", [])?;
+    let mut removed = Vec::new();
+    for suffix in SIDECAR_SUFFIXES {
+        let sidecar = sidecar(path, suffix);
+        if scrub_file(&sidecar)? {
+            removed.push(sidecar.display().to_string());
+        }
+    }
+    conn.query_row("PRAGMA journal_mode = DELETE", [], |_| Ok(()))?;
+    Ok(removed)
+}
+
+/// The database file and the sidecars SQLite may keep next to it
+fn database_files(path: &Path) -> Vec<PathBuf> {
+    std::iter::once(path.to_path_buf())
+        .chain(SIDECAR_SUFFIXES.iter().map(|suffix| sidecar(path, suffix)))
+        .collect()
+}
+
+/// Free pages or sidecar files that can still hold deleted data
+fn scrub_residue(conn: &Connection, path: &Path) -> Result<Vec<String>, BiovaultError> {
+    let mut residue = Vec::new();
+    let free_pages: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    if free_pages > 0 {
+        residue.push(format!("{} free pages", free_pages));
+    }
+    for sidecar in &database_files(path)[1..] {
+        if fs::metadata(sidecar).is_ok_and(|metadata| metadata.len() > 0) {
+            residue.push(format!("{} still exists", sidecar.display()));
+        }
+    }
+    Ok(residue)
+}
+
+/// Selects the analysis runs of genome `?1`
+const RUNS_OF_GENOME: &str = "WHERE genome_id = ?1";
+
+/// Also selects runs without a genome, which can only be dropped with the
+/// last genome
+const RUNS_OF_LAST_GENOME: &str = "WHERE genome_id = ?1 OR genome_id IS NULL";
+
+/// What `privacy_ledger.parameters` holds once a genome has been deleted
+const SCRUBBED_PARAMETERS: &str = "null";
+
+fn count_residue(
+    residue: &mut Vec<String>,
+    conn: &Connection,
+    what: &str,
+    sql: &str,
+    genome_id: i64,
+) -> Result<(), BiovaultError> {
+    let count: i64 = conn.query_row(sql, [genome_id], |row| row.get(0))?;
+    if count > 0 {
+        residue.push(format!("{} {}", count, what));
+    }
+    Ok(())
+}
+
+/// Remove a genome and everything derived from it, leaving no trace in the
+/// file. If it is the database's last genome the whole file and its sidecars
+/// are zeroed and removed. Fails with `DELETION_INCOMPLETE` if anything is
+/// left afterwards.
+///
+/// Analysis runs recorded without a genome id are removed with the last
+/// genome; while other genomes remain they may belong to those and are kept.
+/// The parameters of differentially private queries, which can name rsIDs
+/// and genes, are scrubbed from `privacy_ledger`; the spend itself stays so
+/// the budget still holds.
+pub fn delete_genome(
+    db_path: &Path,
+    key: Option<&str>,
+    genome_id: Option<i64>,
+) -> Result<DeletionReport, BiovaultError> {
+    let conn = open_genome_database(db_path, key)?;
+    let genome_id = genomes::resolve_genome_id(&conn, genome_id)?;
+    start_scrubbing(&conn)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let genomes_left: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM genome_metadata WHERE id != ?1",
+        [genome_id],
+        |row| row.get(0),
+    )?;
+    let runs = if genomes_left == 0 {
+        RUNS_OF_LAST_GENOME
+    } else {
+        RUNS_OF_GENOME
+    };
+    tx.execute(
+        &format!(
+            "DELETE FROM analysis_findings WHERE run_id IN (SELECT id FROM analysis_runs {})",
+            runs
+        ),
+        [genome_id],
+    )?;
+    let analysis_runs_deleted =
+        tx.execute(&format!("DELETE FROM analysis_runs {}", runs), [genome_id])? as i64;
+    let variants_deleted =
+        tx.execute("DELETE FROM variants WHERE file_id = ?1", [genome_id])? as i64;
+    tx.execute("DELETE FROM genome_metadata WHERE id = ?1", [genome_id])?;
+    tx.execute(
+        "UPDATE privacy_ledger SET parameters = ?1 WHERE parameters != ?1",
+        [SCRUBBED_PARAMETERS],
+    )?;
+    tx.commit()?;
+
+    let mut report = DeletionReport {
+        genome_id,
+        variants_deleted,
+        analysis_runs_deleted,
+        database_removed: false,
+        files_removed: Vec::new(),
+    };
+
+    if genomes_left == 0 {
+        drop(conn);
+        for path in database_files(db_path) {
+            if scrub_file(&path)? {
+                report.files_removed.push(path.display().to_string());
+            }
+        }
+        let left: Vec<String> = database_files(db_path)
+            .into_iter()
+            .filter(|path| path.exists())
+            .map(|path| format!("{} still exists", path.display()))
+            .collect();
+        if !left.is_empty() {
+            return Err(BiovaultError::DeletionIncomplete {
+                path: db_path.to_path_buf(),
+                residue: left,
+            });
+        }
+        report.database_removed = true;
+        info!("deleted genome {} and removed {:?}", genome_id, db_path);
+        return Ok(report);
+    }
+
+    report.files_removed = finish_scrubbing(&conn, db_path)?;
+    let mut left = scrub_residue(&conn, db_path)?;
+    count_residue(
+        &mut left,
+        &conn,
+        "variants",
+        "SELECT COUNT(*) FROM variants WHERE file_id = ?1",
+        genome_id,
+    )?;
+    count_residue(
+        &mut left,
+        &conn,
+        "genome records",
+        "SELECT COUNT(*) FROM genome_metadata WHERE id = ?1",
+        genome_id,
+    )?;
+    count_residue(
+        &mut left,
+        &conn,
+        "analysis runs",
+        &format!("SELECT COUNT(*) FROM analysis_runs {}", RUNS_OF_GENOME),
+        genome_id,
+    )?;
+    if !left.is_empty() {
+        warn!("data left after deleting genome {}: {:?}", genome_id, left);
+        return Err(BiovaultError::DeletionIncomplete {
+            path: db_path.to_path_buf(),
+            residue: left,
+        });
+    }
+
+    info!(
+        "deleted genome {}: {} variants, {} analysis runs",
+        genome_id, variants_deleted, analysis_runs_deleted
+    );
+    Ok(report)
+}
+
+/// Strip `loci` from every genome in a database, along with stored ClinVar
+/// findings there. With `output_path` the input is left alone and a redacted
+/// copy is written, e.g. before sharing; without it the database is redacted
+/// in place. Gene loci need `clinvar_db_path`.
+pub fn redact_loci(
+    db_path: &Path,
+    key: Option<&str>,
+    loci: &[Locus],
+    clinvar_db_path: Option<&Path>,
+    output_path: Option<&Path>,
+) -> Result<RedactionSummary, BiovaultError> {
+    if loci.is_empty() {
+        return Err(BiovaultError::InvalidArgument(
+            "no loci to redact".to_string(),
+        ));
+    }
+    let targets = resolve_loci(loci, clinvar_db_path)?;
+
+    let path = match output_path {
+        Some(output_path) => {
+            if output_path.exists() {
+                return Err(BiovaultError::AlreadyExists(output_path.to_path_buf()));
+            }
+            // Settle any WAL content into the file before copying it
+            let source = open_genome_database(db_path, key)?;
+            source.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+            drop(source);
+            fs::copy(db_path, output_path).map_err(|e| BiovaultError::io(output_path, e))?;
+            output_path
+        }
+        None => db_path,
+    };
+
+    let conn = open_genome_database(path, key)?;
+    start_scrubbing(&conn)?;
+    let tx = conn.unchecked_transaction()?;
+
+    // Temp tables stay in memory under `start_scrubbing`
+    tx.execute_batch(
+        "CREATE TEMP TABLE redact_rsids (rsid INTEGER PRIMARY KEY);
+         CREATE TEMP TABLE redact_regions (chromosome INTEGER, start INTEGER, end INTEGER);",
+    )?;
+    {
+        let mut insert =
+            tx.prepare("INSERT OR IGNORE INTO temp.redact_rsids (rsid) VALUES (?1)")?;
+        for rsid in &targets.rsids {
+            insert.execute([rsid])?;
+        }
+        let mut insert = tx.prepare(
+            "INSERT INTO temp.redact_regions (chromosome, start, end) VALUES (?1, ?2, ?3)",
+        )?;
+        for &(chromosome, start, end) in &targets.regions {
+            insert.execute(params![chromosome, start, end])?;
+        }
+    }
+    let variants_removed = tx.execute(&format!("DELETE FROM variants {}", AT_TARGETS), [])? as i64;
+
+    let mut findings = Vec::new();
+    {
+        let mut stmt = tx.prepare("SELECT id, rsid, chrom, pos, gene FROM analysis_findings")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let rsid: String = row.get(1)?;
+            let chrom: String = row.get(2)?;
+            let position: i64 = row.get(3)?;
+            let gene: String = row.get(4)?;
+            let in_gene = gene
+                .split(['|', ';', ','])
+                .any(|symbol| targets.genes.contains(&symbol.trim().to_ascii_uppercase()));
+            let at_locus = encoding::chromosome_code(&chrom)
+                .is_some_and(|code| targets.matches(encoding::rsid_number(&rsid), code, position));
+            if in_gene || at_locus {
+                findings.push(row.get::<_, i64>(0)?);
+            }
+        }
+    }
+    for id in &findings {
+        tx.execute("DELETE FROM analysis_findings WHERE id = ?1", [id])?;
+    }
+
+    // Counts and the source file hash would describe what was removed
+    tx.execute_batch(
+        "UPDATE genome_metadata SET
+             total_variants = (SELECT COUNT(*) FROM variants WHERE file_id = genome_metadata.id),
+             rsid_count = (SELECT COUNT(*) FROM variants
+                           WHERE file_id = genome_metadata.id AND rsid > 0),
+             content_hash = NULL;
+         UPDATE analysis_runs SET
+             matches_found = (SELECT COUNT(*) FROM analysis_findings WHERE run_id = analysis_runs.id);",
+    )?;
+    tx.commit()?;
+
+    finish_scrubbing(&conn, path)?;
+    let mut left = scrub_residue(&conn, path)?;
+    let variants_left: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM variants {}", AT_TARGETS),
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch("DROP TABLE temp.redact_rsids; DROP TABLE temp.redact_regions;")?;
+    if variants_left > 0 {
+        left.push(format!("{} variants at redacted loci", variants_left));
+    }
+    if !left.is_empty() {
+        warn!("data left after redaction: {:?}", left);
+        return Err(BiovaultError::DeletionIncomplete {
+            path: path.to_path_buf(),
+            residue: left,
+        });
+    }
+
+    info!(
+        "redacted {} variants and {} findings in {:?}",
+        variants_removed,
+        findings.len(),
+        path
+    );
+    Ok(RedactionSummary {
+        output_path: path.display().to_string(),
+        variants_removed,
+        findings_removed: findings.len() as i64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use crate::history;
+
+    const ALPHA: &str = "rs111\t1\t100\tAG\nrs333\t2\t5000\tTT\n";
+    const BETA: &str = "rs222\t1\t900\tCT\nrs333\t2\t5000\tTC\n";
+
+    /// A database holding the genomes `alpha` (id 1) and `beta` (id 2), a
+    /// stored ClinVar run for each and a ClinVar database covering both
+    fn fixtures(dir: &Path) -> (PathBuf, PathBuf) {
+        let db = dir.join("genomes.sqlite");
+        for (name, rows) in [("alpha-genome", ALPHA), ("beta-genome", BETA)] {
+            let input = dir.join(format!("{}.txt", name));
+            fs::write(
+                &input,
+                format!("# rsid\tchromosome\tposition\tgenotype\n{}", rows),
+            )
+            .unwrap();
+            import_genome(
+                input.to_str().unwrap(),
+                &db,
+                name,
+                None,
+                None,
+                DuplicatePolicy::Skip,
+            )
+            .unwrap();
+        }
+
+        let clinvar = dir.join("clinvar.sqlite");
+        Connection::open(&clinvar)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                     gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);
+                 INSERT INTO variants VALUES ('rs111', '1', 100, 'A', 'G', 'ALPHAGENE',
+                     'Pathogenic', 'reviewed_by_expert_panel', 'Alpha_condition');
+                 INSERT INTO variants VALUES ('rs222', '1', 900, 'C', 'T', 'BETAGENE',
+                     'Pathogenic', 'reviewed_by_expert_panel', 'Beta_condition');",
+            )
+            .unwrap();
+
+        for genome_id in [1, 2] {
+            history::analyze_and_store_clinvar(
+                db.to_str().unwrap(),
+                None,
+                Some(genome_id),
+                clinvar.to_str().unwrap(),
+            )
+            .unwrap();
+        }
+        (db, clinvar)
+    }
+
+    fn count(db: &Path, sql: &str) -> i64 {
+        Connection::open(db)
+            .unwrap()
+            .query_row(sql, [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn contains(bytes: &[u8], text: &str) -> bool {
+        bytes
+            .windows(text.len())
+            .any(|window| window == text.as_bytes())
+    }
+
+    fn add_unowned_run(db: &Path) {
+        Connection::open(db)
+            .unwrap()
+            .execute(
+                "INSERT INTO analysis_runs (analysis_type, parameters, clinvar_release,
+                     clinvar_checksum, library_version, created_at, rsids_searched,
+                     matches_found, genome_id)
+                 VALUES ('clinvar', '{}', 'release', 'abc', '0', '2024-01-01', 0, 0, NULL)",
+                [],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn deleting_one_genome_leaves_the_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = fixtures(dir.path());
+        add_unowned_run(&db);
+        Connection::open(&db)
+            .unwrap()
+            .execute(
+                "INSERT INTO privacy_ledger (query, parameters, mechanism, epsilon, delta,
+                     created_at)
+                 VALUES ('carrier_counts', '{\"genes\":[\"ALPHAGENE\"],\"rsids\":[\"rs111\"]}',
+                     'laplace', 0.5, 0.0, '2024-01-01')",
+                [],
+            )
+            .unwrap();
+
+        let report = delete_genome(&db, None, Some(1)).unwrap();
+        assert_eq!(report.genome_id, 1);
+        assert_eq!(report.variants_deleted, 2);
+        assert_eq!(report.analysis_runs_deleted, 1);
+        assert!(!report.database_removed);
+
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM genome_metadata"), 1);
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM variants WHERE file_id = 2"),
+            2
+        );
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM variants WHERE file_id = 1"),
+            0
+        );
+        // The other genome's run and the run without a genome are kept
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM analysis_runs"), 2);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM analysis_findings"), 1);
+        // The spend stays, what it was spent on does not
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM privacy_ledger WHERE parameters = 'null' AND epsilon = 0.5"
+            ),
+            1
+        );
+
+        assert_eq!(count(&db, "PRAGMA freelist_count"), 0);
+        for suffix in SIDECAR_SUFFIXES {
+            assert!(!sidecar(&db, suffix).exists(), "{} left behind", suffix);
+        }
+        let bytes = fs::read(&db).unwrap();
+        for residue in ["alpha-genome", "ALPHAGENE", "Alpha_condition", "rs111"] {
+            assert!(!contains(&bytes, residue), "{} left in the file", residue);
+        }
+        assert!(contains(&bytes, "beta-genome"));
+        assert!(contains(&bytes, "Beta_condition"));
+    }
+
+    #[test]
+    fn deleting_the_last_genome_removes_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = fixtures(dir.path());
+        add_unowned_run(&db);
+
+        delete_genome(&db, None, Some(1)).unwrap();
+        let report = delete_genome(&db, None, Some(2)).unwrap();
+        assert!(report.database_removed);
+        // Its own run and the run without a genome
+        assert_eq!(report.analysis_runs_deleted, 2);
+        assert!(report.files_removed.contains(&db.display().to_string()));
+        for path in database_files(&db) {
+            assert!(!path.exists(), "{} left behind", path.display());
+        }
+
+        let err = delete_genome(&db, None, Some(2)).unwrap_err();
+        assert_eq!(err.code(), crate::error::codes::FILE_NOT_FOUND);
+    }
+
+    #[test]
+    fn loci_parse_from_text() {
+        assert_eq!(
+            Locus::parse("rs429358").unwrap(),
+            Locus::Rsid {
+                rsid: "rs429358".to_string()
+            }
+        );
+        assert_eq!(
+            Locus::parse("19:45,409,011-45,412,650").unwrap(),
+            Locus::Region {
+                chromosome: "19".to_string(),
+                start: 45409011,
+                end: 45412650
+            }
+        );
+        assert_eq!(
+            Locus::parse(" APOE ").unwrap(),
+            Locus::Gene {
+                name: "APOE".to_string()
+            }
+        );
+        assert!(Locus::parse("19:start-end").is_err());
+        assert!(Locus::parse("  ").is_err());
+    }
+
+    #[test]
+    fn redacts_by_region_rsid_and_gene() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, clinvar) = fixtures(dir.path());
+
+        let summary = redact_loci(
+            &db,
+            None,
+            &[Locus::parse("2:4000-6000").unwrap()],
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(summary.output_path, db.display().to_string());
+        assert_eq!(summary.variants_removed, 2);
+        assert_eq!(summary.findings_removed, 0);
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM variants WHERE chromosome = 2"),
+            0
+        );
+
+        let summary =
+            redact_loci(&db, None, &[Locus::parse("rs222").unwrap()], None, None).unwrap();
+        assert_eq!(summary.variants_removed, 1);
+        assert_eq!(summary.findings_removed, 1);
+
+        let gene = [Locus::parse("alphagene").unwrap()];
+        let err = redact_loci(&db, None, &gene, None, None).unwrap_err();
+        assert_eq!(err.code(), crate::error::codes::INVALID_ARGUMENT);
+        let summary = redact_loci(&db, None, &gene, Some(&clinvar), None).unwrap();
+        assert_eq!(summary.variants_removed, 1);
+        assert_eq!(summary.findings_removed, 1);
+
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM variants"), 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM analysis_findings"), 0);
+        // Counts that would describe the removed variants are updated
+        assert_eq!(
+            count(&db, "SELECT SUM(matches_found) FROM analysis_runs"),
+            0
+        );
+        assert_eq!(
+            count(&db, "SELECT SUM(total_variants) FROM genome_metadata"),
+            0
+        );
+        assert_eq!(count(&db, "PRAGMA freelist_count"), 0);
+        let bytes = fs::read(&db).unwrap();
+        for residue in ["ALPHAGENE", "BETAGENE", "rs111", "rs222"] {
+            assert!(!contains(&bytes, residue), "{} left in the file", residue);
+        }
+    }
+
+    #[test]
+    fn redacting_to_a_copy_leaves_the_input_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, clinvar) = fixtures(dir.path());
+        let output = dir.path().join("shared.sqlite");
+        let loci = [Locus::parse("ALPHAGENE").unwrap()];
+
+        let summary = redact_loci(&db, None, &loci, Some(&clinvar), Some(&output)).unwrap();
+        assert_eq!(summary.output_path, output.display().to_string());
+        assert_eq!(summary.variants_removed, 1);
+        assert_eq!(summary.findings_removed, 1);
+
+        assert_eq!(count(&output, "SELECT COUNT(*) FROM variants"), 3);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM variants"), 4);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM analysis_findings"), 2);
+        assert!(!contains(&fs::read(&output).unwrap(), "ALPHAGENE"));
+
+        let err = redact_loci(&db, None, &loci, Some(&clinvar), Some(&output)).unwrap_err();
+        assert_eq!(err.code(), crate::error::codes::ALREADY_EXISTS);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::analysis::{self, ClinVarVariant, get_significance_label, get_significance_score};
+use crate::error::BiovaultError;
+use crate::genomes;
+use crate::history::{self, CLINVAR_ANALYSIS, clinvar_release_label, file_checksum};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// Direction of a significance reclassification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum SignificanceDirection {
+    /// Now classified as more significant (e.g. Uncertain -> Likely_pathogenic)
+    Upgrade,
+    /// Now classified as less significant (e.g. Pathogenic -> Benign)
+    Downgrade,
+}
+
+/// A variant whose ClinVar significance changed between two releases
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct SignificanceChange {
+    pub rsid: String,
+    pub gene: String,
+    pub old_clnsig: String,
+    pub new_clnsig: String,
+    pub old_label: String,
+    pub new_label: String,
+    pub direction: SignificanceDirection,
+    pub user_genotype: Option<String>,
+}
+
+/// Differences between two ClinVar analyses of the same genome
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct ClinVarDiff {
+    pub old_release: String,
+    pub new_release: String,
+    pub new_matches: Vec<ClinVarVariant>,
+    pub dropped_matches: Vec<ClinVarVariant>,
+    pub upgrades: Vec<SignificanceChange>,
+    pub downgrades: Vec<SignificanceChange>,
+    pub unchanged_count: i32,
+}
+
+impl ClinVarDiff {
+    /// True if anything changed that the user should be told about
+    pub fn has_changes(&self) -> bool {
+        !self.new_matches.is_empty()
+            || !self.dropped_matches.is_empty()
+            || !self.upgrades.is_empty()
+            || !self.downgrades.is_empty()
+    }
+}
+
+/// Collapse matches to one entry per (rsID, gene), keeping the most significant
+fn index_by_rsid_and_gene(
+    matches: &[ClinVarVariant],
+) -> BTreeMap<(String, String), &ClinVarVariant> {
+    let mut index: BTreeMap<(String, String), &ClinVarVariant> = BTreeMap::new();
+
+    for variant in matches {
+        let key = (variant.rsid.clone(), variant.gene.clone());
+        match index.get(&key) {
+            Some(existing)
+                if get_significance_score(&existing.clnsig)
+                    <= get_significance_score(&variant.clnsig) => {}
+            _ => {
+                index.insert(key, variant);
+            }
+        }
+    }
+
+    index
+}
+
+/// Compare two sets of ClinVar matches for the same genome
+pub fn diff_matches(
+    old_release: &str,
+    old_matches: &[ClinVarVariant],
+    new_release: &str,
+    new_matches: &[ClinVarVariant],
+) -> ClinVarDiff {
+    let old_index = index_by_rsid_and_gene(old_matches);
+    let new_index = index_by_rsid_and_gene(new_matches);
+
+    let mut diff = ClinVarDiff {
+        old_release: old_release.to_string(),
+        new_release: new_release.to_string(),
+        new_matches: Vec::new(),
+        dropped_matches: Vec::new(),
+        upgrades: Vec::new(),
+        downgrades: Vec::new(),
+        unchanged_count: 0,
+    };
+
+    for (key, new_variant) in &new_index {
+        let Some(old_variant) = old_index.get(key) else {
+            diff.new_matches.push((*new_variant).clone());
+            continue;
+        };
+
+        let old_score = get_significance_score(&old_variant.clnsig);
+        let new_score = get_significance_score(&new_variant.clnsig);
+        if old_score == new_score {
+            diff.unchanged_count += 1;
+            continue;
+        }
+
+        // Lower score = more significant
+        let direction = if new_score < old_score {
+            SignificanceDirection::Upgrade
+        } else {
+            SignificanceDirection::Downgrade
+        };
+
+        let change = SignificanceChange {
+            rsid: new_variant.rsid.clone(),
+            gene: new_variant.gene.clone(),
+            old_clnsig: old_variant.clnsig.clone(),
+            new_clnsig: new_variant.clnsig.clone(),
+            old_label: get_significance_label(&old_variant.clnsig),
+            new_label: get_significance_label(&new_variant.clnsig),
+            direction,
+            user_genotype: new_variant.user_genotype.clone(),
+        };
+
+        match direction {
+            SignificanceDirection::Upgrade => diff.upgrades.push(change),
+            SignificanceDirection::Downgrade => diff.downgrades.push(change),
+        }
+    }
+
+    for (key, old_variant) in &old_index {
+        if !new_index.contains_key(key) {
+            diff.dropped_matches.push((*old_variant).clone());
+        }
+    }
+
+    diff
+}
+
+/// Run the ClinVar analysis against two ClinVar releases and diff the results
+pub fn diff_clinvar_releases(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    old_clinvar_db_path: &str,
+    new_clinvar_db_path: &str,
+) -> Result<ClinVarDiff, BiovaultError> {
+    let old_result = analysis::analyze_clinvar_matches(
+        user_db_path,
+        user_db_key,
+        genome_id,
+        old_clinvar_db_path,
+    )?;
+    let new_result = analysis::analyze_clinvar_matches(
+        user_db_path,
+        user_db_key,
+        genome_id,
+        new_clinvar_db_path,
+    )?;
+
+    let diff = diff_matches(
+        &clinvar_release_label(old_clinvar_db_path, &file_checksum(old_clinvar_db_path)?),
+        &old_result.matches,
+        &clinvar_release_label(new_clinvar_db_path, &file_checksum(new_clinvar_db_path)?),
+        &new_result.matches,
+    );
+
+    info!(
+        "{} new, {} dropped, {} upgraded, {} downgraded",
+        diff.new_matches.len(),
+        diff.dropped_matches.len(),
+        diff.upgrades.len(),
+        diff.downgrades.len()
+    );
+
+    Ok(diff)
+}
+
+/// Re-run the ClinVar analysis, diff it against the last stored run and
+/// store the new run in the user database.
+///
+/// Returns `None` if there was no previous run to compare against.
+pub fn reanalyze_clinvar(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    clinvar_db_path: &str,
+) -> Result<Option<ClinVarDiff>, BiovaultError> {
+    let genome_id = genomes::resolve_genome_id_at(user_db_path, user_db_key, genome_id)?;
+    let previous =
+        history::latest_analysis_run(user_db_path, user_db_key, CLINVAR_ANALYSIS, genome_id)?;
+    let current = history::analyze_and_store_clinvar(
+        user_db_path,
+        user_db_key,
+        Some(genome_id),
+        clinvar_db_path,
+    )?;
+
+    Ok(previous.map(|stored| {
+        diff_matches(
+            &stored.run.clinvar_release,
+            &stored.result.matches,
+            &current.run.clinvar_release,
+            &current.result.matches,
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use rusqlite::Connection;
+    use std::fs;
+    use std::path::Path;
+
+    fn variant(rsid: &str, gene: &str, clnsig: &str) -> ClinVarVariant {
+        ClinVarVariant {
+            rsid: rsid.to_string(),
+            chrom: "1".to_string(),
+            pos: 100,
+            ref_allele: "A".to_string(),
+            alt_allele: "G".to_string(),
+            gene: gene.to_string(),
+            clnsig: clnsig.to_string(),
+            clnrevstat: "reviewed_by_expert_panel".to_string(),
+            condition: "Some_condition".to_string(),
+            user_genotype: Some("AG".to_string()),
+        }
+    }
+
+    fn rsids(variants: &[ClinVarVariant]) -> Vec<&str> {
+        variants.iter().map(|v| v.rsid.as_str()).collect()
+    }
+
+    fn changes(changes: &[SignificanceChange]) -> Vec<(&str, &str, &str)> {
+        changes
+            .iter()
+            .map(|c| (c.rsid.as_str(), c.old_label.as_str(), c.new_label.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_are_sorted_into_new_dropped_and_reclassified() {
+        let old = [
+            variant("rs1", "GENE1", "Pathogenic"),
+            variant("rs2", "GENE2", "Uncertain_significance"),
+            variant("rs3", "GENE3", "Pathogenic"),
+            variant("rs4", "GENE4", "Likely_pathogenic"),
+        ];
+        let new = [
+            variant("rs1", "GENE1", "Pathogenic"),
+            variant("rs2", "GENE2", "Likely_pathogenic"),
+            variant("rs3", "GENE3", "Benign"),
+            variant("rs5", "GENE5", "Pathogenic"),
+        ];
+
+        let diff = diff_matches("old", &old, "new", &new);
+        assert_eq!(
+            (diff.old_release.as_str(), diff.new_release.as_str()),
+            ("old", "new")
+        );
+        assert_eq!(rsids(&diff.new_matches), ["rs5"]);
+        assert_eq!(rsids(&diff.dropped_matches), ["rs4"]);
+        assert_eq!(
+            changes(&diff.upgrades),
+            [("rs2", "Uncertain_significance", "Likely_pathogenic")]
+        );
+        assert_eq!(changes(&diff.downgrades), [("rs3", "Pathogenic", "Benign")]);
+        assert!(
+            diff.upgrades
+                .iter()
+                .all(|c| c.direction == SignificanceDirection::Upgrade)
+        );
+        assert!(
+            diff.downgrades
+                .iter()
+                .all(|c| c.direction == SignificanceDirection::Downgrade)
+        );
+        assert_eq!(diff.unchanged_count, 1);
+        assert!(diff.has_changes());
+
+        let same = diff_matches("old", &old, "new", &old);
+        assert_eq!(same.unchanged_count, 4);
+        assert!(!same.has_changes());
+    }
+
+    #[test]
+    fn pathogenic_to_conflicting_is_a_downgrade() {
+        let old = [variant("rs1", "GENE1", "Pathogenic")];
+        let new = [variant(
+            "rs1",
+            "GENE1",
+            "Conflicting_classifications_of_pathogenicity",
+        )];
+
+        let diff = diff_matches("old", &old, "new", &new);
+        assert!(diff.upgrades.is_empty());
+        assert_eq!(diff.downgrades.len(), 1);
+        assert_eq!(diff.downgrades[0].old_label, "Pathogenic");
+        assert_eq!(diff.downgrades[0].new_label, "Conflicting");
+    }
+
+    #[test]
+    fn duplicate_rsids_are_compared_by_their_most_significant_entry() {
+        // ClinVar can list an rsID more than once for the same gene
+        let old = [
+            variant("rs1", "GENE1", "Benign"),
+            variant("rs1", "GENE1", "Pathogenic"),
+            variant("rs2", "GENE2", "Uncertain_significance"),
+        ];
+        let new = [
+            variant("rs1", "GENE1", "Pathogenic"),
+            variant("rs1", "GENE1", "Likely_benign"),
+            variant("rs2", "GENE2", "Benign"),
+            variant("rs2", "GENE2", "Likely_pathogenic"),
+            variant("rs2", "GENE2X", "Pathogenic"),
+        ];
+
+        let diff = diff_matches("old", &old, "new", &new);
+        assert_eq!(diff.unchanged_count, 1);
+        assert_eq!(
+            changes(&diff.upgrades),
+            [("rs2", "Uncertain_significance", "Likely_pathogenic")]
+        );
+        assert!(diff.downgrades.is_empty());
+        assert_eq!(
+            diff.new_matches
+                .iter()
+                .map(|v| (v.rsid.as_str(), v.gene.as_str()))
+                .collect::<Vec<_>>(),
+            [("rs2", "GENE2X")]
+        );
+        assert!(diff.dropped_matches.is_empty());
+    }
+
+    fn clinvar(path: &Path, clnsig: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                 gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO variants VALUES ('rs1', '1', 100, 'A', 'G', 'GENE1', ?1,
+                 'reviewed_by_expert_panel', 'Some_condition')",
+            [clnsig],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn releases_with_the_same_file_name_get_different_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\nrs1\t1\t100\tAG\n",
+        )
+        .unwrap();
+        let db = dir.path().join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        let old = dir.path().join("a").join("clinvar.sqlite");
+        let new = dir.path().join("b").join("clinvar.sqlite");
+        clinvar(&old, "Pathogenic");
+        clinvar(&new, "Benign");
+
+        let diff = diff_clinvar_releases(
+            db.to_str().unwrap(),
+            None,
+            None,
+            old.to_str().unwrap(),
+            new.to_str().unwrap(),
+        )
+        .unwrap();
+        assert_ne!(diff.old_release, diff.new_release);
+        assert!(diff.old_release.starts_with("clinvar@"));
+        assert!(diff.new_release.starts_with("clinvar@"));
+        assert_eq!(changes(&diff.downgrades), [("rs1", "Pathogenic", "Benign")]);
+    }
+}
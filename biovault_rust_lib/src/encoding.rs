@@ -0,0 +1,258 @@
+use crate::error::BiovaultError;
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+/// Chromosome codes follow the PLINK convention so numeric chromosomes from
+/// other vendors (e.g. AncestryDNA's 23-26) map onto the same values.
+pub(crate) const CHROM_UNKNOWN: i64 = 0;
+pub(crate) const CHROM_X: i64 = 23;
+pub(crate) const CHROM_Y: i64 = 24;
+pub(crate) const CHROM_XY: i64 = 25;
+pub(crate) const CHROM_MT: i64 = 26;
+
+/// Genotype codes seeded into every database so common calls have the same
+/// code everywhere. Anything else is appended to the dictionary on import.
+pub(crate) const SEEDED_GENOTYPES: &[&str] = &[
+    "--", "AA", "AC", "AG", "AT", "CA", "CC", "CG", "CT", "GA", "GC", "GG", "GT", "TA", "TC", "TG",
+    "TT", "A", "C", "G", "T", "II", "DD", "DI", "ID", "I", "D",
+];
+
+/// Map a chromosome name to its code. Returns `None` for unrecognised names.
+pub fn chromosome_code(name: &str) -> Option<i64> {
+    let name = name.trim();
+    let name = name
+        .strip_prefix("chr")
+        .or_else(|| name.strip_prefix("Chr"))
+        .unwrap_or(name);
+
+    match name {
+        "X" | "x" => Some(CHROM_X),
+        "Y" | "y" => Some(CHROM_Y),
+        "XY" | "xy" | "PAR" => Some(CHROM_XY),
+        "MT" | "M" | "mt" | "m" => Some(CHROM_MT),
+        _ => match name.parse::<i64>() {
+            Ok(n) if (1..=CHROM_MT).contains(&n) => Some(n),
+            _ => None,
+        },
+    }
+}
+
+/// Map a chromosome code back to the name used in 23andMe files
+pub fn chromosome_name(code: i64) -> String {
+    match code {
+        CHROM_X => "X".to_string(),
+        CHROM_Y => "Y".to_string(),
+        CHROM_XY => "XY".to_string(),
+        CHROM_MT => "MT".to_string(),
+        1..=22 => code.to_string(),
+        _ => "0".to_string(),
+    }
+}
+
+/// Stored number of a variant ID: the numeric part of an rsID ("rs123" ->
+/// 123), negated for 23andMe's internal IDs ("i4000" -> -4000) so those keep
+/// their name. Other IDs have no number.
+pub fn rsid_number(rsid: &str) -> Option<i64> {
+    let (digits, sign) = match rsid.strip_prefix("rs") {
+        Some(digits) => (digits, 1),
+        None => (rsid.strip_prefix('i')?, -1),
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    match digits.parse::<i64>() {
+        Ok(number) if number > 0 => Some(sign * number),
+        _ => None,
+    }
+}
+
+/// Format a stored variant ID number back to its text form
+pub fn rsid_string(number: i64) -> String {
+    if number < 0 {
+        format!("i{}", -number)
+    } else {
+        format!("rs{}", number)
+    }
+}
+
+/// Small-int genotype dictionary backed by the `genotype_codes` table
+pub struct GenotypeDictionary {
+    codes: HashMap<String, i64>,
+    next_code: i64,
+}
+
+impl GenotypeDictionary {
+    /// Load the dictionary stored in a database
+    pub fn load(conn: &Connection) -> Result<Self, BiovaultError> {
+        let mut stmt = conn.prepare("SELECT code, genotype FROM genotype_codes")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut codes = HashMap::new();
+        let mut next_code = 0;
+        for row in rows {
+            let (code, genotype) = row?;
+            next_code = next_code.max(code + 1);
+            codes.insert(genotype, code);
+        }
+
+        Ok(GenotypeDictionary { codes, next_code })
+    }
+
+    /// Code for a genotype, adding it to the dictionary if it is new
+    pub fn code(&mut self, conn: &Connection, genotype: &str) -> Result<i64, BiovaultError> {
+        if let Some(code) = self.codes.get(genotype) {
+            return Ok(*code);
+        }
+
+        let code = self.next_code;
+        conn.execute(
+            "INSERT INTO genotype_codes (code, genotype) VALUES (?1, ?2)",
+            rusqlite::params![code, genotype],
+        )?;
+        self.codes.insert(genotype.to_string(), code);
+        self.next_code += 1;
+
+        Ok(code)
+    }
+}
+
+/// RefSeq accessions of the GRCh37 primary assembly, indexed by chromosome code
+const GRCH37_ACCESSIONS: [&str; 27] = [
+    "",
+    "NC_000001.10",
+    "NC_000002.11",
+    "NC_000003.11",
+    "NC_000004.11",
+    "NC_000005.9",
+    "NC_000006.11",
+    "NC_000007.13",
+    "NC_000008.10",
+    "NC_000009.11",
+    "NC_000010.10",
+    "NC_000011.9",
+    "NC_000012.11",
+    "NC_000013.10",
+    "NC_000014.8",
+    "NC_000015.9",
+    "NC_000016.9",
+    "NC_000017.10",
+    "NC_000018.9",
+    "NC_000019.9",
+    "NC_000020.10",
+    "NC_000021.8",
+    "NC_000022.10",
+    "NC_000023.10",
+    "NC_000024.9",
+    "NC_000023.10",
+    "NC_012920.1",
+];
+
+/// RefSeq accession of a chromosome, if known for the assembly
+pub fn refseq_accession(assembly: &str, chromosome: &str) -> Option<&'static str> {
+    if !assembly.eq_ignore_ascii_case("GRCh37") {
+        return None;
+    }
+    let code = chromosome_code(chromosome)?;
+    GRCH37_ACCESSIONS
+        .get(code as usize)
+        .copied()
+        .filter(|a| !a.is_empty())
+}
+
+/// Genomic HGVS for a single-nucleotide variant, e.g. `NC_000017.10:g.41245466G>A`.
+/// Returns `None` for other variant types or unknown assemblies.
+pub fn hgvs_genomic(
+    assembly: &str,
+    chromosome: &str,
+    position: i64,
+    ref_allele: &str,
+    alt_allele: &str,
+) -> Option<String> {
+    let is_base = |a: &str| a.len() == 1 && matches!(a, "A" | "C" | "G" | "T");
+    if !is_base(ref_allele) || !is_base(alt_allele) || ref_allele == alt_allele {
+        return None;
+    }
+
+    let accession = refseq_accession(assembly, chromosome)?;
+    // The mitochondrial genome uses the m. coordinate type
+    let kind = if chromosome_code(chromosome) == Some(CHROM_MT) {
+        "m"
+    } else {
+        "g"
+    };
+    Some(format!(
+        "{}:{}.{}{}>{}",
+        accession, kind, position, ref_allele, alt_allele
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chromosome_names_round_trip() {
+        for code in 1..=CHROM_MT {
+            assert_eq!(chromosome_code(&chromosome_name(code)), Some(code));
+        }
+        for (name, code) in [
+            ("chr7", 7),
+            ("Chr22", 22),
+            (" x ", CHROM_X),
+            ("chrY", CHROM_Y),
+            ("PAR", CHROM_XY),
+            ("M", CHROM_MT),
+            ("mt", CHROM_MT),
+        ] {
+            assert_eq!(chromosome_code(name), Some(code), "{}", name);
+        }
+        for name in ["0", "27", "-1", "GL000192.1", "chrUn", "1a", ""] {
+            assert_eq!(chromosome_code(name), None, "{}", name);
+        }
+        assert_eq!(chromosome_name(CHROM_UNKNOWN), "0");
+    }
+
+    #[test]
+    fn variant_ids_round_trip() {
+        for id in ["rs1", "rs429358", "i4000", "i713426"] {
+            let number = rsid_number(id).unwrap();
+            assert_eq!(rsid_string(number), id);
+        }
+        assert_eq!(rsid_number("rs429358"), Some(429358));
+        assert_eq!(rsid_number("i4000"), Some(-4000));
+        for id in [
+            "rs", "rs0", "i0", "rs-5", "i+5", "rs12a", "VG01S1", "4000", "",
+        ] {
+            assert_eq!(rsid_number(id), None, "{}", id);
+        }
+    }
+
+    #[test]
+    fn genotype_codes_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&conn).unwrap();
+
+        let mut dictionary = GenotypeDictionary::load(&conn).unwrap();
+        for (code, genotype) in SEEDED_GENOTYPES.iter().enumerate() {
+            assert_eq!(dictionary.code(&conn, genotype).unwrap(), code as i64);
+        }
+        let added = dictionary.code(&conn, "AGT").unwrap();
+        assert_eq!(added, SEEDED_GENOTYPES.len() as i64);
+        assert_eq!(dictionary.code(&conn, "AGT").unwrap(), added);
+
+        // A reloaded dictionary agrees and keeps numbering after the last code
+        let mut reloaded = GenotypeDictionary::load(&conn).unwrap();
+        assert_eq!(reloaded.code(&conn, "AGT").unwrap(), added);
+        assert_eq!(reloaded.code(&conn, "CTT").unwrap(), added + 1);
+        let genotype: String = conn
+            .query_row(
+                "SELECT genotype FROM genotype_codes WHERE code = ?1",
+                [added + 1],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(genotype, "CTT");
+    }
+}
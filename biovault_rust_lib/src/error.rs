@@ -0,0 +1,322 @@
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors returned by the library.
+///
+/// Every variant maps to a stable, machine-readable code (see
+/// [`BiovaultError::code`]) that is part of the FFI contract: apps switch on
+/// the code, the message is for humans and may change.
+#[derive(Debug)]
+pub enum BiovaultError {
+    /// A caller-supplied argument is missing, malformed or not valid UTF-8
+    InvalidArgument(String),
+    /// Reading or writing a file failed
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// A ZIP archive could not be read
+    Archive(zip::result::ZipError),
+    /// The input file is not a genome file we understand
+    Parse { path: PathBuf, message: String },
+    /// SQLite reported an error
+    Database(rusqlite::Error),
+    /// The file is a database, but not a genome database
+    NotAGenomeDatabase(PathBuf),
+    /// The encryption key is wrong, or the file is not a database at all
+    WrongKey,
+    /// A genome, analysis run or other record does not exist
+    NotFound(String),
+    /// An output file would be overwritten
+    AlreadyExists(PathBuf),
+    /// No genome id was given and the database holds several genomes
+    AmbiguousGenome,
+    /// The database holds no genomes
+    NoGenomes,
+    /// The database was written by a newer version of the library
+    UnsupportedSchema { found: i64, supported: i64 },
+    /// A result could not be serialised
+    Serialization(serde_json::Error),
+    /// The owner has not consented to this use of their data
+    ConsentRequired { purpose: String, recipient: String },
+    /// A genome bundle is malformed, damaged or from an unsupported version
+    InvalidBundle(String),
+    /// Genome data could still be found after deleting or redacting it
+    DeletionIncomplete { path: PathBuf, residue: Vec<String> },
+    /// A differentially private query needs more privacy budget than remains
+    PrivacyBudgetExhausted { requested: f64, remaining: f64 },
+    /// The caller cancelled the operation
+    Cancelled,
+    /// A bug in the library panicked; the panic was caught at the FFI boundary
+    Panic(String),
+    /// Anything else; the message describes what went wrong
+    Other(String),
+}
+
+/// Stable error codes. Never rename these; apps match on them.
+pub mod codes {
+    pub const INVALID_ARGUMENT: &str = "INVALID_ARGUMENT";
+    pub const FILE_NOT_FOUND: &str = "FILE_NOT_FOUND";
+    pub const PERMISSION_DENIED: &str = "PERMISSION_DENIED";
+    pub const DISK_FULL: &str = "DISK_FULL";
+    pub const IO_ERROR: &str = "IO_ERROR";
+    pub const CORRUPT_ARCHIVE: &str = "CORRUPT_ARCHIVE";
+    pub const PARSE_ERROR: &str = "PARSE_ERROR";
+    pub const DATABASE_ERROR: &str = "DATABASE_ERROR";
+    pub const DATABASE_CORRUPT: &str = "DATABASE_CORRUPT";
+    pub const DATABASE_BUSY: &str = "DATABASE_BUSY";
+    pub const NOT_A_GENOME_DATABASE: &str = "NOT_A_GENOME_DATABASE";
+    pub const WRONG_KEY: &str = "WRONG_KEY";
+    pub const NOT_FOUND: &str = "NOT_FOUND";
+    pub const ALREADY_EXISTS: &str = "ALREADY_EXISTS";
+    pub const GENOME_AMBIGUOUS: &str = "GENOME_AMBIGUOUS";
+    pub const NO_GENOMES: &str = "NO_GENOMES";
+    pub const UNSUPPORTED_SCHEMA: &str = "UNSUPPORTED_SCHEMA";
+    pub const SERIALIZATION_ERROR: &str = "SERIALIZATION_ERROR";
+    pub const CONSENT_REQUIRED: &str = "CONSENT_REQUIRED";
+    pub const DELETION_INCOMPLETE: &str = "DELETION_INCOMPLETE";
+    pub const INVALID_BUNDLE: &str = "INVALID_BUNDLE";
+    pub const PRIVACY_BUDGET_EXHAUSTED: &str = "PRIVACY_BUDGET_EXHAUSTED";
+    pub const CANCELLED: &str = "CANCELLED";
+    pub const INTERNAL_ERROR: &str = "INTERNAL_ERROR";
+}
+
+impl BiovaultError {
+    /// I/O error on a known file
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        BiovaultError::Io {
+            path: Some(path.to_path_buf()),
+            source,
+        }
+    }
+
+    /// Turn a caught panic payload into an error
+    pub(crate) fn from_panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        BiovaultError::Panic(message)
+    }
+
+    /// Run `f`, turning a panic inside it into an error. Used where a panic
+    /// must not unwind into foreign code.
+    pub(crate) fn catch_panic<T>(
+        f: impl FnOnce() -> Result<T, BiovaultError>,
+    ) -> Result<T, BiovaultError> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+            .unwrap_or_else(|payload| Err(BiovaultError::from_panic(payload)))
+    }
+
+    /// The stable code of this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            BiovaultError::InvalidArgument(_) => codes::INVALID_ARGUMENT,
+            BiovaultError::Io { source, .. } => io_code(source),
+            BiovaultError::Archive(zip::result::ZipError::Io(source)) => io_code(source),
+            BiovaultError::Archive(_) => codes::CORRUPT_ARCHIVE,
+            BiovaultError::Parse { .. } => codes::PARSE_ERROR,
+            BiovaultError::Database(error) => database_code(error),
+            BiovaultError::NotAGenomeDatabase(_) => codes::NOT_A_GENOME_DATABASE,
+            BiovaultError::WrongKey => codes::WRONG_KEY,
+            BiovaultError::NotFound(_) => codes::NOT_FOUND,
+            BiovaultError::AlreadyExists(_) => codes::ALREADY_EXISTS,
+            BiovaultError::AmbiguousGenome => codes::GENOME_AMBIGUOUS,
+            BiovaultError::NoGenomes => codes::NO_GENOMES,
+            BiovaultError::UnsupportedSchema { .. } => codes::UNSUPPORTED_SCHEMA,
+            BiovaultError::Serialization(_) => codes::SERIALIZATION_ERROR,
+            BiovaultError::ConsentRequired { .. } => codes::CONSENT_REQUIRED,
+            BiovaultError::InvalidBundle(_) => codes::INVALID_BUNDLE,
+            BiovaultError::DeletionIncomplete { .. } => codes::DELETION_INCOMPLETE,
+            BiovaultError::PrivacyBudgetExhausted { .. } => codes::PRIVACY_BUDGET_EXHAUSTED,
+            BiovaultError::Cancelled => codes::CANCELLED,
+            BiovaultError::Panic(_) | BiovaultError::Other(_) => codes::INTERNAL_ERROR,
+        }
+    }
+
+    /// Structured details for callers, e.g. the file involved
+    pub fn context(&self) -> Option<serde_json::Value> {
+        match self {
+            BiovaultError::Io {
+                path: Some(path), ..
+            }
+            | BiovaultError::Parse { path, .. }
+            | BiovaultError::NotAGenomeDatabase(path)
+            | BiovaultError::AlreadyExists(path) => {
+                Some(serde_json::json!({ "path": path.to_string_lossy() }))
+            }
+            BiovaultError::UnsupportedSchema { found, supported } => Some(serde_json::json!({
+                "found": found,
+                "supported": supported,
+            })),
+            BiovaultError::ConsentRequired { purpose, recipient } => Some(serde_json::json!({
+                "purpose": purpose,
+                "recipient": recipient,
+            })),
+            BiovaultError::DeletionIncomplete { path, residue } => Some(serde_json::json!({
+                "path": path,
+                "residue": residue,
+            })),
+            BiovaultError::PrivacyBudgetExhausted {
+                requested,
+                remaining,
+            } => Some(serde_json::json!({
+                "requested": requested,
+                "remaining": remaining,
+            })),
+            _ => None,
+        }
+    }
+
+    /// Serializable form used by the FFI and `--json` output
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            code: self.code(),
+            message: self.to_string(),
+            context: self.context(),
+        }
+    }
+}
+
+/// An error as it crosses the FFI boundary
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+    pub context: Option<serde_json::Value>,
+}
+
+fn io_code(error: &io::Error) -> &'static str {
+    match error.kind() {
+        io::ErrorKind::NotFound => codes::FILE_NOT_FOUND,
+        io::ErrorKind::PermissionDenied => codes::PERMISSION_DENIED,
+        io::ErrorKind::StorageFull => codes::DISK_FULL,
+        io::ErrorKind::AlreadyExists => codes::ALREADY_EXISTS,
+        _ => codes::IO_ERROR,
+    }
+}
+
+fn database_code(error: &rusqlite::Error) -> &'static str {
+    use rusqlite::ErrorCode;
+
+    match error.sqlite_error_code() {
+        Some(ErrorCode::DiskFull) => codes::DISK_FULL,
+        Some(ErrorCode::NotADatabase) => codes::WRONG_KEY,
+        Some(ErrorCode::DatabaseCorrupt) => codes::DATABASE_CORRUPT,
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => codes::DATABASE_BUSY,
+        Some(ErrorCode::CannotOpen) => codes::FILE_NOT_FOUND,
+        Some(ErrorCode::PermissionDenied) | Some(ErrorCode::ReadOnly) => codes::PERMISSION_DENIED,
+        _ => codes::DATABASE_ERROR,
+    }
+}
+
+impl fmt::Display for BiovaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BiovaultError::InvalidArgument(message) => write!(f, "Invalid argument: {}", message),
+            BiovaultError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            BiovaultError::Io { path: None, source } => write!(f, "{}", source),
+            BiovaultError::Archive(error) => write!(f, "Cannot read ZIP archive: {}", error),
+            BiovaultError::Parse { path, message } => {
+                write!(f, "Cannot parse {}: {}", path.display(), message)
+            }
+            BiovaultError::Database(error) => write!(f, "Database error: {}", error),
+            BiovaultError::NotAGenomeDatabase(path) => {
+                write!(f, "{} is not a genome database", path.display())
+            }
+            BiovaultError::WrongKey => write!(f, "Wrong encryption key or not a database"),
+            BiovaultError::NotFound(what) => write!(f, "{} not found", what),
+            BiovaultError::AlreadyExists(path) => {
+                write!(f, "Output file {} already exists", path.display())
+            }
+            BiovaultError::AmbiguousGenome => write!(
+                f,
+                "Database contains several genomes; choose one by genome id"
+            ),
+            BiovaultError::NoGenomes => write!(f, "Database contains no genomes"),
+            BiovaultError::UnsupportedSchema { found, supported } => write!(
+                f,
+                "Database schema version {} is newer than supported version {}",
+                found, supported
+            ),
+            BiovaultError::Serialization(error) => write!(f, "Serialization failed: {}", error),
+            BiovaultError::ConsentRequired { purpose, recipient } => {
+                write!(f, "No consent for {} by {}", purpose, recipient)
+            }
+            BiovaultError::InvalidBundle(message) => write!(f, "Invalid bundle: {}", message),
+            BiovaultError::DeletionIncomplete { path, residue } => write!(
+                f,
+                "Genome data remains in {}: {}",
+                path.display(),
+                residue.join("; ")
+            ),
+            BiovaultError::PrivacyBudgetExhausted {
+                requested,
+                remaining,
+            } => write!(
+                f,
+                "Privacy budget exhausted: query needs epsilon {} but {} remains",
+                requested, remaining
+            ),
+            BiovaultError::Cancelled => write!(f, "Operation cancelled"),
+            BiovaultError::Panic(message) => write!(f, "Internal error: {}", message),
+            BiovaultError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for BiovaultError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BiovaultError::Io { source, .. } => Some(source),
+            BiovaultError::Archive(error) => Some(error),
+            BiovaultError::Database(error) => Some(error),
+            BiovaultError::Serialization(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BiovaultError {
+    fn from(source: io::Error) -> Self {
+        BiovaultError::Io { path: None, source }
+    }
+}
+
+impl From<zip::result::ZipError> for BiovaultError {
+    fn from(error: zip::result::ZipError) -> Self {
+        BiovaultError::Archive(error)
+    }
+}
+
+impl From<rusqlite::Error> for BiovaultError {
+    fn from(error: rusqlite::Error) -> Self {
+        BiovaultError::Database(error)
+    }
+}
+
+impl From<serde_json::Error> for BiovaultError {
+    fn from(error: serde_json::Error) -> Self {
+        BiovaultError::Serialization(error)
+    }
+}
+
+impl From<String> for BiovaultError {
+    fn from(message: String) -> Self {
+        BiovaultError::Other(message)
+    }
+}
+
+impl From<&str> for BiovaultError {
+    fn from(message: &str) -> Self {
+        BiovaultError::Other(message.to_string())
+    }
+}
@@ -0,0 +1,846 @@
+use crate::access_log::{self, AccessEvent};
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::open_genome_database;
+use crate::encoding::{self, CHROM_UNKNOWN, CHROM_X, CHROM_XY};
+use crate::error::BiovaultError;
+use crate::genomes;
+use crate::history::LIBRARY_VERSION;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Statement};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, info_span};
+
+/// Standard formats a stored genome can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// Sorted VCF 4.3; needs a reference allele database for REF/ALT
+    Vcf,
+    /// Raw data TSV in the layout 23andMe uses
+    #[serde(rename = "23andme")]
+    TwentyThreeAndMe,
+    /// PLINK 1 binary fileset (.bed/.bim/.fam)
+    Plink,
+}
+
+impl ExportFormat {
+    /// Parse the format names used by the CLI and FFI
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vcf" => Some(ExportFormat::Vcf),
+            "23andme" | "tsv" => Some(ExportFormat::TwentyThreeAndMe),
+            "plink" | "bed" => Some(ExportFormat::Plink),
+            _ => None,
+        }
+    }
+}
+
+/// What an export wrote
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct ExportSummary {
+    pub format: ExportFormat,
+    pub genome_id: i64,
+    pub output_files: Vec<String>,
+    pub variants_written: i64,
+    /// Calls the format cannot represent, e.g. indels or positions missing
+    /// from the reference allele database
+    pub variants_skipped: i64,
+}
+
+/// One stored genotype call, in storage order
+pub(crate) struct Call {
+    pub(crate) chromosome: i64,
+    pub(crate) position: i64,
+    pub(crate) rsid: Option<i64>,
+    pub(crate) genotype: String,
+}
+
+/// Reference alleles by position, read from a local SQLite database with a
+/// `reference_alleles (chrom TEXT, pos INTEGER, ref TEXT)` table. Chromosome
+/// names follow 23andMe ("1".."22", "X", "Y", "MT").
+struct ReferenceAlleles<'conn> {
+    stmt: Statement<'conn>,
+}
+
+impl<'conn> ReferenceAlleles<'conn> {
+    fn new(conn: &'conn Connection) -> Result<Self, BiovaultError> {
+        let stmt = conn
+            .prepare("SELECT ref FROM reference_alleles WHERE chrom = ?1 AND pos = ?2 LIMIT 1")
+            .map_err(|_| {
+                BiovaultError::InvalidArgument(
+                    "Reference database has no reference_alleles table".to_string(),
+                )
+            })?;
+        Ok(ReferenceAlleles { stmt })
+    }
+
+    fn get(&mut self, chromosome: i64, position: i64) -> rusqlite::Result<Option<String>> {
+        // Pseudo-autosomal calls are on X in the reference
+        let chromosome = if chromosome == CHROM_XY {
+            CHROM_X
+        } else {
+            chromosome
+        };
+        self.stmt
+            .query_row(
+                rusqlite::params![encoding::chromosome_name(chromosome), position],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+}
+
+/// Export one genome from a genome database.
+///
+/// `output_path` is the file to write, or for PLINK the prefix of the
+/// `.bed`/`.bim`/`.fam` files. `genome_id` may be omitted if the database holds
+/// a single genome. VCF export requires `reference_db_path`; PLINK uses it when
+/// given to put the reference allele in A2.
+pub fn export_genome(
+    db_path: &str,
+    key: Option<&str>,
+    genome_id: Option<i64>,
+    format: ExportFormat,
+    output_path: &str,
+    reference_db_path: Option<&str>,
+) -> Result<ExportSummary, BiovaultError> {
+    let conn = open_genome_database(Path::new(db_path), key)?;
+    consent::require_consent(
+        &conn,
+        Purpose::Export,
+        consent::OWNER,
+        Granularity::Individual,
+    )?;
+    let genome_id = genomes::resolve_genome_id(&conn, genome_id)?;
+
+    let reference_conn = match reference_db_path {
+        Some(path) => Some(Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?),
+        None => None,
+    };
+    let mut reference = match &reference_conn {
+        Some(conn) => Some(ReferenceAlleles::new(conn)?),
+        None => None,
+    };
+
+    let _span = info_span!("export", genome_id, ?format).entered();
+    debug!(
+        "exporting genome {} as {:?} to {}",
+        genome_id, format, output_path
+    );
+
+    let summary = match format {
+        ExportFormat::Vcf => {
+            let reference = reference.as_mut().ok_or_else(|| {
+                BiovaultError::InvalidArgument(
+                    "VCF export needs a reference allele database".to_string(),
+                )
+            })?;
+            write_vcf(&conn, genome_id, Path::new(output_path), reference)?
+        }
+        ExportFormat::TwentyThreeAndMe => write_23andme(&conn, genome_id, Path::new(output_path))?,
+        ExportFormat::Plink => write_plink(&conn, genome_id, output_path, reference.as_mut())?,
+    };
+
+    info!(
+        "wrote {} variants, skipped {}",
+        summary.variants_written, summary.variants_skipped
+    );
+    access_log::record_access(
+        &conn,
+        AccessEvent::Export,
+        Some(Purpose::Export),
+        summary.variants_written + summary.variants_skipped,
+    )?;
+
+    Ok(summary)
+}
+
+/// Sample label for headers: the person if known, else the genome name
+fn sample_name(conn: &Connection, genome_id: i64) -> rusqlite::Result<String> {
+    conn.query_row(
+        "SELECT COALESCE(person, file_name) FROM genome_metadata WHERE id = ?1",
+        [genome_id],
+        |row| row.get(0),
+    )
+}
+
+/// Visit a genome's calls sorted by chromosome and position. Pseudo-autosomal
+/// calls are ordered with X since they are written there.
+pub(crate) fn for_each_call<F>(
+    conn: &Connection,
+    genome_id: i64,
+    mut f: F,
+) -> Result<(), BiovaultError>
+where
+    F: FnMut(Call) -> Result<(), BiovaultError>,
+{
+    let mut stmt = conn.prepare(
+        "SELECT v.chromosome, v.position, v.rsid, g.genotype
+         FROM variants v
+         JOIN genotype_codes g ON g.code = v.genotype
+         WHERE v.file_id = ?1
+         ORDER BY CASE v.chromosome WHEN ?2 THEN ?3 ELSE v.chromosome END, v.position, v.probe",
+    )?;
+    let mut rows = stmt.query(rusqlite::params![genome_id, CHROM_XY, CHROM_X])?;
+
+    while let Some(row) = rows.next()? {
+        f(Call {
+            chromosome: row.get(0)?,
+            position: row.get(1)?,
+            rsid: row.get(2)?,
+            genotype: row.get(3)?,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// An output file written under a temporary name next to its final path.
+/// `finish` moves it into place; dropped unfinished, e.g. when an export
+/// fails partway, it is removed so no truncated file is left behind.
+pub(crate) struct OutputFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl OutputFile {
+    /// Flush and sync the contents, keeping the temporary name
+    fn sync(&mut self) -> Result<(), BiovaultError> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Move the finished file to its final path
+    fn rename(&mut self) -> Result<(), BiovaultError> {
+        self.writer = None;
+        fs::rename(&self.temp_path, &self.path).map_err(|e| {
+            let _ = fs::remove_file(&self.temp_path);
+            BiovaultError::io(&self.path, e)
+        })
+    }
+
+    pub(crate) fn finish(self) -> Result<(), BiovaultError> {
+        finish_outputs(vec![self])
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.write(buf),
+            None => Err(std::io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Start writing `path`, which must not exist yet
+pub(crate) fn create_output(path: &Path) -> Result<OutputFile, BiovaultError> {
+    if path.exists() {
+        return Err(BiovaultError::AlreadyExists(path.to_path_buf()));
+    }
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.partial", file_name));
+    let file = File::create(&temp_path).map_err(|e| BiovaultError::io(&temp_path, e))?;
+    Ok(OutputFile {
+        path: path.to_path_buf(),
+        temp_path,
+        writer: Some(BufWriter::new(file)),
+    })
+}
+
+/// Move a set of outputs into place once all of them are written. If one
+/// cannot be moved, those already moved are removed again.
+pub(crate) fn finish_outputs(mut outputs: Vec<OutputFile>) -> Result<(), BiovaultError> {
+    for output in &mut outputs {
+        output.sync()?;
+    }
+    for index in 0..outputs.len() {
+        if let Err(e) = outputs[index].rename() {
+            for done in &outputs[..index] {
+                let _ = fs::remove_file(&done.path);
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// ID for a call: its rsID or 23andMe internal ID, or `chromosome:position`
+/// for probes without one
+fn call_id(call: &Call) -> String {
+    match call.rsid {
+        Some(rsid) => encoding::rsid_string(rsid),
+        None => format!(
+            "{}:{}",
+            encoding::chromosome_name(call.chromosome),
+            call.position
+        ),
+    }
+}
+
+/// Only plain SNP alleles can be expressed without sequence context
+pub(crate) fn snp_alleles(genotype: &str) -> Option<Vec<char>> {
+    let alleles: Vec<char> = genotype.chars().collect();
+    if alleles.is_empty()
+        || alleles.len() > 2
+        || !alleles.iter().all(|a| matches!(a, 'A' | 'C' | 'G' | 'T'))
+    {
+        return None;
+    }
+    Some(alleles)
+}
+
+/// ALT alleles and GT field for a call against a single-base reference
+fn vcf_genotype(genotype: &str, reference: &str) -> Option<(Vec<char>, String)> {
+    let alleles = snp_alleles(genotype)?;
+    let mut ref_chars = reference.chars();
+    let ref_allele = ref_chars.next()?.to_ascii_uppercase();
+    if ref_chars.next().is_some() {
+        return None;
+    }
+
+    let mut alts: Vec<char> = Vec::new();
+    let indices: Vec<String> = alleles
+        .iter()
+        .map(|allele| {
+            if *allele == ref_allele {
+                "0".to_string()
+            } else {
+                let index = match alts.iter().position(|alt| alt == allele) {
+                    Some(i) => i,
+                    None => {
+                        alts.push(*allele);
+                        alts.len() - 1
+                    }
+                };
+                (index + 1).to_string()
+            }
+        })
+        .collect();
+
+    Some((alts, indices.join("/")))
+}
+
+fn write_vcf(
+    conn: &Connection,
+    genome_id: i64,
+    output_path: &Path,
+    reference: &mut ReferenceAlleles,
+) -> Result<ExportSummary, BiovaultError> {
+    let assembly: Option<String> = conn.query_row(
+        "SELECT assembly FROM genome_metadata WHERE id = ?1",
+        [genome_id],
+        |row| row.get(0),
+    )?;
+    let sample = sample_name(conn, genome_id)?;
+
+    // Contig lines must precede the data, so collect them first
+    let mut contigs = BTreeSet::new();
+    {
+        let mut stmt =
+            conn.prepare("SELECT DISTINCT chromosome FROM variants WHERE file_id = ?1")?;
+        let chromosomes = stmt.query_map([genome_id], |row| row.get::<_, i64>(0))?;
+        for chromosome in chromosomes {
+            match chromosome? {
+                // Calls on unknown chromosomes are skipped below
+                CHROM_UNKNOWN => {}
+                CHROM_XY => {
+                    contigs.insert(CHROM_X);
+                }
+                chromosome => {
+                    contigs.insert(chromosome);
+                }
+            }
+        }
+    }
+
+    let mut out = create_output(output_path)?;
+    writeln!(out, "##fileformat=VCFv4.3")?;
+    writeln!(out, "##fileDate={}", chrono::Utc::now().format("%Y%m%d"))?;
+    writeln!(out, "##source=biovault_rust_lib-{}", LIBRARY_VERSION)?;
+    if let Some(assembly) = &assembly {
+        writeln!(out, "##reference={}", assembly)?;
+    }
+    for chromosome in &contigs {
+        writeln!(
+            out,
+            "##contig=<ID={}>",
+            encoding::chromosome_name(*chromosome)
+        )?;
+    }
+    writeln!(
+        out,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    writeln!(
+        out,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}",
+        sample
+    )?;
+
+    let mut written = 0;
+    let mut skipped = 0;
+    for_each_call(conn, genome_id, |call| {
+        if call.chromosome == CHROM_UNKNOWN {
+            skipped += 1;
+            return Ok(());
+        }
+        let Some(reference) = reference.get(call.chromosome, call.position)? else {
+            skipped += 1;
+            return Ok(());
+        };
+        let Some((alts, gt)) = vcf_genotype(&call.genotype, &reference) else {
+            skipped += 1;
+            return Ok(());
+        };
+
+        let chromosome = if call.chromosome == CHROM_XY {
+            CHROM_X
+        } else {
+            call.chromosome
+        };
+        let alt = if alts.is_empty() {
+            ".".to_string()
+        } else {
+            alts.iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t.\t.\t.\tGT\t{}",
+            encoding::chromosome_name(chromosome),
+            call.position,
+            call.rsid
+                .map(encoding::rsid_string)
+                .unwrap_or_else(|| ".".to_string()),
+            reference.to_ascii_uppercase(),
+            alt,
+            gt
+        )?;
+        written += 1;
+        Ok(())
+    })?;
+    out.finish()?;
+
+    Ok(ExportSummary {
+        format: ExportFormat::Vcf,
+        genome_id,
+        output_files: vec![output_path.to_string_lossy().to_string()],
+        variants_written: written,
+        variants_skipped: skipped,
+    })
+}
+
+fn write_23andme(
+    conn: &Connection,
+    genome_id: i64,
+    output_path: &Path,
+) -> Result<ExportSummary, BiovaultError> {
+    let assembly: Option<String> = conn.query_row(
+        "SELECT assembly FROM genome_metadata WHERE id = ?1",
+        [genome_id],
+        |row| row.get(0),
+    )?;
+
+    let mut out = create_output(output_path)?;
+    writeln!(
+        out,
+        "# This data file was exported by biovault_rust_lib {} on {}",
+        LIBRARY_VERSION,
+        chrono::Utc::now().to_rfc3339()
+    )?;
+    writeln!(
+        out,
+        "# It follows the 23andMe raw data layout. Positions are on {}.",
+        assembly.as_deref().unwrap_or("an unknown assembly")
+    )?;
+    writeln!(
+        out,
+        "# Probes without an rsID are listed as chromosome:position."
+    )?;
+    writeln!(out, "# rsid\tchromosome\tposition\tgenotype")?;
+
+    let mut written = 0;
+    for_each_call(conn, genome_id, |call| {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            call_id(&call),
+            encoding::chromosome_name(call.chromosome),
+            call.position,
+            call.genotype
+        )?;
+        written += 1;
+        Ok(())
+    })?;
+    out.finish()?;
+
+    Ok(ExportSummary {
+        format: ExportFormat::TwentyThreeAndMe,
+        genome_id,
+        output_files: vec![output_path.to_string_lossy().to_string()],
+        variants_written: written,
+        variants_skipped: 0,
+    })
+}
+
+/// A1/A2 alleles and the 2-bit .bed code for a single-sample biallelic call.
+/// A2 is the reference allele when known; "0" marks a missing allele.
+fn plink_genotype(genotype: &str, reference: Option<&str>) -> Option<(char, char, u8)> {
+    let alleles = snp_alleles(genotype)?;
+    // Haploid calls (X/Y/MT in males) are coded as homozygous
+    let (first, second) = (alleles[0], *alleles.last()?);
+
+    let (a1, a2) = match reference.and_then(|r| r.chars().next()) {
+        Some(ref_allele) => {
+            let ref_allele = ref_allele.to_ascii_uppercase();
+            let others: BTreeSet<char> = alleles
+                .iter()
+                .copied()
+                .filter(|a| *a != ref_allele)
+                .collect();
+            match others.len() {
+                0 => ('0', ref_allele),
+                1 => (*others.iter().next()?, ref_allele),
+                _ => return None,
+            }
+        }
+        None if first == second => ('0', first),
+        None => (second, first),
+    };
+
+    let a1_count = [first, second].iter().filter(|a| **a == a1).count();
+    let code = match a1_count {
+        2 => 0b00,
+        1 => 0b10,
+        _ => 0b11,
+    };
+
+    Some((a1, a2, code))
+}
+
+fn write_plink(
+    conn: &Connection,
+    genome_id: i64,
+    output_prefix: &str,
+    mut reference: Option<&mut ReferenceAlleles>,
+) -> Result<ExportSummary, BiovaultError> {
+    let bed_path = PathBuf::from(format!("{}.bed", output_prefix));
+    let bim_path = PathBuf::from(format!("{}.bim", output_prefix));
+    let fam_path = PathBuf::from(format!("{}.fam", output_prefix));
+
+    let mut bed = create_output(&bed_path)?;
+    let mut bim = create_output(&bim_path)?;
+    let mut fam = create_output(&fam_path)?;
+
+    // PLINK ids cannot contain whitespace
+    let sample: String = sample_name(conn, genome_id)?
+        .chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect();
+    // Sex and phenotype unknown
+    writeln!(fam, "{} {} 0 0 0 -9", sample, sample)?;
+
+    // Magic number and SNP-major mode
+    bed.write_all(&[0x6c, 0x1b, 0x01])?;
+
+    let mut written = 0;
+    let mut skipped = 0;
+    for_each_call(conn, genome_id, |call| {
+        let reference_allele = match reference.as_mut() {
+            Some(reference) => reference.get(call.chromosome, call.position)?,
+            None => None,
+        };
+        let Some((a1, a2, code)) = plink_genotype(&call.genotype, reference_allele.as_deref())
+        else {
+            skipped += 1;
+            return Ok(());
+        };
+
+        // PLINK chromosome codes match the stored codes
+        writeln!(
+            bim,
+            "{}\t{}\t0\t{}\t{}\t{}",
+            call.chromosome,
+            call_id(&call),
+            call.position,
+            a1,
+            a2
+        )?;
+        // One sample per variant: a single byte with the call in the low bits
+        bed.write_all(&[code])?;
+        written += 1;
+        Ok(())
+    })?;
+
+    finish_outputs(vec![bed, bim, fam])?;
+
+    Ok(ExportSummary {
+        format: ExportFormat::Plink,
+        genome_id,
+        output_files: [bed_path, bim_path, fam_path]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect(),
+        variants_written: written,
+        variants_skipped: skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+
+    /// A database holding one genome, with a 23andMe internal ID, a probe
+    /// without a usable ID and a call on an unknown chromosome
+    fn genome_db(dir: &Path) -> String {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\n\
+             rs1\t1\t100\tAG\n\
+             rs2\t1\t200\tCC\n\
+             i4000\t3\t400\tAA\n\
+             VG01S1\t4\t450\tCT\n\
+             rs5\tX\t500\tA\n\
+             rs7\t0\t700\tGG\n",
+        )
+        .unwrap();
+        let db_path = dir.join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        db_path.to_string_lossy().into_owned()
+    }
+
+    /// A reference allele database with `(chrom, pos, ref)` rows
+    fn reference_db(dir: &Path, rows: &[(&str, i64, rusqlite::types::Value)]) -> String {
+        let path = dir.join("reference.sqlite");
+        let conn = Connection::open(&path).unwrap();
+        // No declared types, so test rows can hold values of the wrong type
+        conn.execute("CREATE TABLE reference_alleles (chrom, pos, ref)", [])
+            .unwrap();
+        for (chrom, pos, reference) in rows {
+            conn.execute(
+                "INSERT INTO reference_alleles VALUES (?1, ?2, ?3)",
+                rusqlite::params![chrom, pos, reference],
+            )
+            .unwrap();
+        }
+        path.to_string_lossy().into_owned()
+    }
+
+    fn text(value: &str) -> rusqlite::types::Value {
+        rusqlite::types::Value::Text(value.to_string())
+    }
+
+    fn data_lines(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn probes_without_an_id_are_named_by_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = genome_db(dir.path());
+        let output = dir.path().join("export.txt");
+        export_genome(
+            &db_path,
+            None,
+            None,
+            ExportFormat::TwentyThreeAndMe,
+            output.to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            data_lines(&output),
+            vec![
+                "rs7\t0\t700\tGG",
+                "rs1\t1\t100\tAG",
+                "rs2\t1\t200\tCC",
+                "i4000\t3\t400\tAA",
+                "4:450\t4\t450\tCT",
+                "rs5\tX\t500\tA",
+            ]
+        );
+
+        // The export reads back as the same calls
+        let copy = dir.path().join("copy.sqlite");
+        let outcome = import_genome(
+            output.to_str().unwrap(),
+            &copy,
+            "copy",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        let again = dir.path().join("again.txt");
+        export_genome(
+            copy.to_str().unwrap(),
+            None,
+            Some(outcome.genome_id),
+            ExportFormat::TwentyThreeAndMe,
+            again.to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(data_lines(&again), data_lines(&output));
+    }
+
+    #[test]
+    fn vcf_export_skips_unknown_chromosomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = genome_db(dir.path());
+        let reference = reference_db(
+            dir.path(),
+            &[
+                ("0", 700, text("G")),
+                ("1", 100, text("A")),
+                ("1", 200, text("C")),
+                ("3", 400, text("G")),
+                ("4", 450, text("C")),
+                ("X", 500, text("G")),
+            ],
+        );
+        let output = dir.path().join("export.vcf");
+        let summary = export_genome(
+            &db_path,
+            None,
+            None,
+            ExportFormat::Vcf,
+            output.to_str().unwrap(),
+            Some(&reference),
+        )
+        .unwrap();
+        assert_eq!((summary.variants_written, summary.variants_skipped), (5, 1));
+
+        let vcf = fs::read_to_string(&output).unwrap();
+        let contigs: Vec<&str> = vcf
+            .lines()
+            .filter(|line| line.starts_with("##contig"))
+            .collect();
+        assert_eq!(
+            contigs,
+            vec![
+                "##contig=<ID=1>",
+                "##contig=<ID=3>",
+                "##contig=<ID=4>",
+                "##contig=<ID=X>"
+            ]
+        );
+        assert!(
+            data_lines(&output)
+                .iter()
+                .all(|line| !line.starts_with("0\t"))
+        );
+        assert!(vcf.contains("3\t400\ti4000\tG\tA\t.\t.\t.\tGT\t1/1"));
+        assert!(vcf.contains("4\t450\t.\tC\tT\t.\t.\t.\tGT\t0/1"));
+    }
+
+    #[test]
+    fn failed_exports_leave_no_partial_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = genome_db(dir.path());
+        // A reference allele the reader cannot use, after some calls are
+        // already written
+        let reference = reference_db(
+            dir.path(),
+            &[
+                ("1", 100, text("A")),
+                ("1", 200, rusqlite::types::Value::Integer(5)),
+            ],
+        );
+        let before: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+
+        let output = dir.path().join("export.vcf");
+        assert!(
+            export_genome(
+                &db_path,
+                None,
+                None,
+                ExportFormat::Vcf,
+                output.to_str().unwrap(),
+                Some(&reference),
+            )
+            .is_err()
+        );
+        let prefix = dir.path().join("plink");
+        assert!(
+            export_genome(
+                &db_path,
+                None,
+                None,
+                ExportFormat::Plink,
+                prefix.to_str().unwrap(),
+                Some(&reference),
+            )
+            .is_err()
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), before.len());
+    }
+
+    #[test]
+    fn exports_never_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = genome_db(dir.path());
+        let output = dir.path().join("export.txt");
+        fs::write(&output, "keep me").unwrap();
+        assert!(matches!(
+            export_genome(
+                &db_path,
+                None,
+                None,
+                ExportFormat::TwentyThreeAndMe,
+                output.to_str().unwrap(),
+                None,
+            ),
+            Err(BiovaultError::AlreadyExists(_))
+        ));
+        assert_eq!(fs::read_to_string(&output).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,483 @@
+use crate::database::{self, open_genome_database};
+use crate::error::BiovaultError;
+use crate::history::bytes_checksum;
+use crate::parsers::{ParseResult, twenty_three_and_me};
+use crate::progress::Progress;
+use crate::storage::Storage;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, info_span};
+
+/// What to do when the same input file was already imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Keep the existing genome and do nothing
+    Skip,
+    /// Re-parse the file and replace the existing genome's variants
+    Replace,
+}
+
+/// Result of importing a file into a genome database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Imported,
+    Skipped,
+    Replaced,
+}
+
+/// A genome file held in memory, e.g. one picked in a browser
+#[derive(Debug, Clone, Copy)]
+pub struct GenomeFile<'a> {
+    /// Original file name, used to recognise ZIP archives and in errors
+    pub name: &'a str,
+    pub bytes: &'a [u8],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct ImportOutcome {
+    pub db_path: String,
+    pub genome_id: i64,
+    pub status: ImportStatus,
+    pub content_hash: String,
+}
+
+/// A genome stored in a (possibly shared) genome database
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct GenomeRecord {
+    pub id: i64,
+    pub name: String,
+    pub person: Option<String>,
+    pub source_format: String,
+    pub total_variants: i64,
+    pub rsid_count: i64,
+    pub assembly: Option<String>,
+    pub upload_date: String,
+    pub content_hash: Option<String>,
+}
+
+/// Import a genome file into `db_path`, creating the database if needed.
+///
+/// The input is identified by its SHA-256, so importing the same file twice is
+/// detected and handled according to `policy`. `person` labels whose genome
+/// this is when several people share one database.
+pub fn import_genome(
+    input_path: &str,
+    db_path: &Path,
+    custom_name: &str,
+    person: Option<&str>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+) -> Result<ImportOutcome, BiovaultError> {
+    import_genome_with_progress(
+        input_path,
+        db_path,
+        custom_name,
+        person,
+        key,
+        policy,
+        &mut Progress::none(),
+    )
+}
+
+/// `import_genome` with progress reporting and cancellation. A cancelled
+/// import leaves the database as it was; a database created for it is removed.
+pub fn import_genome_with_progress(
+    input_path: &str,
+    db_path: &Path,
+    custom_name: &str,
+    person: Option<&str>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+    progress: &mut Progress,
+) -> Result<ImportOutcome, BiovaultError> {
+    let bytes =
+        std::fs::read(input_path).map_err(|e| BiovaultError::io(Path::new(input_path), e))?;
+    import_genome_bytes(
+        GenomeFile {
+            name: input_path,
+            bytes: &bytes,
+        },
+        &Storage::from(db_path),
+        custom_name,
+        person,
+        key,
+        policy,
+        progress,
+    )
+}
+
+/// `import_genome_with_progress` for a file held in memory and a database in
+/// any `Storage`
+pub fn import_genome_bytes(
+    file: GenomeFile,
+    storage: &Storage,
+    custom_name: &str,
+    person: Option<&str>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+    progress: &mut Progress,
+) -> Result<ImportOutcome, BiovaultError> {
+    let _span = info_span!("import", file = file.name).entered();
+    let content_hash = bytes_checksum(file.bytes);
+    progress.check()?;
+
+    let created = !storage.exists()?;
+    let conn = if created {
+        database::create_genome_storage(storage, key)?
+    } else {
+        database::open_genome_storage(storage, key)?
+    };
+
+    let existing = find_genome_by_hash(&conn, &content_hash)?;
+    let outcome = |genome_id, status| ImportOutcome {
+        db_path: storage.path().to_string_lossy().to_string(),
+        genome_id,
+        status,
+        content_hash: content_hash.clone(),
+    };
+
+    if let (Some(genome_id), DuplicatePolicy::Skip) = (existing, policy) {
+        info!("already imported as genome {}, skipping", genome_id);
+        return Ok(outcome(genome_id, ImportStatus::Skipped));
+    }
+
+    let result = (|| {
+        let parse_result =
+            twenty_three_and_me::parse_23andme_bytes(Path::new(file.name), file.bytes, progress)?;
+        info!(
+            "parsed {} variants, {} with rsIDs",
+            parse_result.metadata.total_variants, parse_result.metadata.rsid_count
+        );
+
+        let (genome_id, status) = store_genome(
+            &conn,
+            existing,
+            parse_result,
+            custom_name,
+            person,
+            &content_hash,
+            progress,
+        )?;
+        Ok(outcome(genome_id, status))
+    })();
+
+    // Don't leave an empty database behind after a failed first import
+    if created && result.is_err() {
+        drop(conn);
+        let _ = storage.remove();
+    }
+    result
+}
+
+/// Insert a parsed genome, or replace the `existing` genome with it
+pub(crate) fn store_genome(
+    conn: &Connection,
+    existing: Option<i64>,
+    parse_result: ParseResult,
+    custom_name: &str,
+    person: Option<&str>,
+    content_hash: &str,
+    progress: &mut Progress,
+) -> Result<(i64, ImportStatus), BiovaultError> {
+    match existing {
+        Some(genome_id) => {
+            database::replace_genome(
+                conn,
+                genome_id,
+                parse_result,
+                custom_name,
+                person,
+                content_hash,
+                progress,
+            )?;
+            Ok((genome_id, ImportStatus::Replaced))
+        }
+        None => {
+            let genome_id = database::insert_genome(
+                conn,
+                parse_result,
+                custom_name,
+                person,
+                content_hash,
+                progress,
+            )?;
+            Ok((genome_id, ImportStatus::Imported))
+        }
+    }
+}
+
+pub(crate) fn find_genome_by_hash(
+    conn: &Connection,
+    content_hash: &str,
+) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM genome_metadata WHERE content_hash = ?1 ORDER BY id LIMIT 1",
+        [content_hash],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+const GENOME_COLUMNS: &str = "id, file_name, person, source_format, total_variants, rsid_count,
+     assembly, upload_date, content_hash";
+
+fn genome_from_row(row: &rusqlite::Row) -> rusqlite::Result<GenomeRecord> {
+    Ok(GenomeRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        person: row.get(2)?,
+        source_format: row.get(3)?,
+        total_variants: row.get(4)?,
+        rsid_count: row.get(5)?,
+        assembly: row.get(6)?,
+        upload_date: row.get(7)?,
+        content_hash: row.get(8)?,
+    })
+}
+
+/// List the genomes stored in a database
+pub fn list_genomes(db_path: &str, key: Option<&str>) -> Result<Vec<GenomeRecord>, BiovaultError> {
+    let conn = open_genome_database(Path::new(db_path), key)?;
+    Ok(genomes_in(&conn)?)
+}
+
+/// The genomes in an open database, by id
+pub(crate) fn genomes_in(conn: &Connection) -> rusqlite::Result<Vec<GenomeRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM genome_metadata ORDER BY id",
+        GENOME_COLUMNS
+    ))?;
+    let genome_iter = stmt.query_map([], genome_from_row)?;
+
+    let mut genomes = Vec::new();
+    for genome in genome_iter {
+        genomes.push(genome?);
+    }
+
+    Ok(genomes)
+}
+
+/// Load one genome's metadata, resolving `genome_id` like `resolve_genome_id`
+pub(crate) fn load_genome(
+    conn: &Connection,
+    genome_id: Option<i64>,
+) -> Result<GenomeRecord, BiovaultError> {
+    let genome_id = resolve_genome_id(conn, genome_id)?;
+    Ok(conn.query_row(
+        &format!(
+            "SELECT {} FROM genome_metadata WHERE id = ?1",
+            GENOME_COLUMNS
+        ),
+        [genome_id],
+        genome_from_row,
+    )?)
+}
+
+/// Pick the genome an operation applies to. Without an explicit id the
+/// database must hold exactly one genome.
+pub(crate) fn resolve_genome_id(
+    conn: &Connection,
+    genome_id: Option<i64>,
+) -> Result<i64, BiovaultError> {
+    if let Some(genome_id) = genome_id {
+        let exists: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM genome_metadata WHERE id = ?1",
+            [genome_id],
+            |row| row.get(0),
+        )?;
+        if exists == 0 {
+            return Err(BiovaultError::NotFound(format!("Genome {}", genome_id)));
+        }
+        return Ok(genome_id);
+    }
+
+    let mut stmt = conn.prepare("SELECT id FROM genome_metadata ORDER BY id LIMIT 2")?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    match ids.as_slice() {
+        [only] => Ok(*only),
+        [] => Err(BiovaultError::NoGenomes),
+        _ => Err(BiovaultError::AmbiguousGenome),
+    }
+}
+
+/// `resolve_genome_id` for a database given by path
+pub(crate) fn resolve_genome_id_at(
+    db_path: &str,
+    key: Option<&str>,
+    genome_id: Option<i64>,
+) -> Result<i64, BiovaultError> {
+    let conn = open_genome_database(Path::new(db_path), key)?;
+    resolve_genome_id(&conn, genome_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str =
+        "# This data file generated by 23andMe\n# rsid\tchromosome\tposition\tgenotype\n";
+
+    fn genome_file(dir: &Path, name: &str, rows: &[&str]) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("{}{}\n", HEADER, rows.join("\n"))).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// (genome, rsID, position, genotype) of every stored call
+    fn calls(db_path: &Path) -> Vec<(i64, Option<i64>, i64, String)> {
+        let conn = open_genome_database(db_path, None).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT v.file_id, v.rsid, v.position, g.genotype
+                 FROM variants v JOIN genotype_codes g ON g.code = v.genotype
+                 ORDER BY v.file_id, v.chromosome, v.position",
+            )
+            .unwrap();
+        stmt.query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+    }
+
+    #[test]
+    fn internal_ids_are_kept_and_unknown_chromosomes_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        let genome = genome_file(
+            dir.path(),
+            "genome.txt",
+            &["rs1\t1\t100\tAG", "i4000\t1\t400\tCC", "rs7\t0\t700\tGG"],
+        );
+        import_genome(
+            &genome,
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        assert_eq!(
+            calls(&db_path),
+            vec![
+                (1, Some(7), 700, "GG".to_string()),
+                (1, Some(1), 100, "AG".to_string()),
+                (1, Some(-4000), 400, "CC".to_string()),
+            ]
+        );
+        let conn = open_genome_database(&db_path, None).unwrap();
+        let decoded: String = conn
+            .query_row(
+                "SELECT rsid FROM variants_decoded WHERE position = 400",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(decoded, "i4000");
+        drop(conn);
+
+        let contig = genome_file(
+            dir.path(),
+            "contig.txt",
+            &["rs1\t1\t100\tAG", "rs9\tGL000192.1\t900\tTT"],
+        );
+        let err = import_genome(
+            &contig,
+            &db_path,
+            "contig",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), crate::error::codes::INVALID_ARGUMENT);
+        assert!(err.to_string().contains("GL000192.1"));
+        assert_eq!(
+            list_genomes(db_path.to_str().unwrap(), None).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn replacing_a_genome_leaves_the_others_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("family.sqlite");
+        let alice = genome_file(
+            dir.path(),
+            "alice.txt",
+            &["rs1\t1\t100\tAG", "rs2\t1\t200\tCC"],
+        );
+        let bob = genome_file(
+            dir.path(),
+            "bob.txt",
+            &["rs1\t1\t100\tGG", "rs3\t2\t300\tTT"],
+        );
+        let import = |path: &str, name, policy| {
+            import_genome(path, &db_path, name, None, None, policy).unwrap()
+        };
+
+        let first = import(&alice, "alice", DuplicatePolicy::Skip);
+        let second = import(&bob, "bob", DuplicatePolicy::Skip);
+        let bob_calls: Vec<_> = calls(&db_path)
+            .into_iter()
+            .filter(|call| call.0 == second.genome_id)
+            .collect();
+        assert_eq!(bob_calls.len(), 2);
+
+        let again = import(&alice, "alice", DuplicatePolicy::Skip);
+        assert_eq!(again.status, ImportStatus::Skipped);
+        let replaced = import(&alice, "alice again", DuplicatePolicy::Replace);
+        assert_eq!(replaced.status, ImportStatus::Replaced);
+        assert_eq!(replaced.genome_id, first.genome_id);
+
+        // Replace with different content, as a corrected file would
+        let conn = open_genome_database(&db_path, None).unwrap();
+        let corrected = twenty_three_and_me::parse_23andme_bytes(
+            Path::new("alice.txt"),
+            format!("{}rs1\t1\t100\tAA\n", HEADER).as_bytes(),
+            &mut Progress::none(),
+        )
+        .unwrap();
+        store_genome(
+            &conn,
+            Some(first.genome_id),
+            corrected,
+            "alice corrected",
+            None,
+            "corrected",
+            &mut Progress::none(),
+        )
+        .unwrap();
+        drop(conn);
+
+        let after = calls(&db_path);
+        let alice_calls: Vec<_> = after
+            .iter()
+            .filter(|call| call.0 == first.genome_id)
+            .collect();
+        assert_eq!(
+            alice_calls,
+            vec![&(first.genome_id, Some(1), 100, "AA".to_string())]
+        );
+        let untouched: Vec<_> = after
+            .into_iter()
+            .filter(|call| call.0 == second.genome_id)
+            .collect();
+        assert_eq!(untouched, bob_calls);
+
+        let genomes = list_genomes(db_path.to_str().unwrap(), None).unwrap();
+        assert_eq!(genomes.len(), 2);
+        assert_eq!(genomes[0].name, "alice corrected");
+        assert_eq!(genomes[0].total_variants, 1);
+        assert_eq!(genomes[1].name, "bob");
+        assert_eq!(genomes[1].total_variants, 2);
+    }
+}
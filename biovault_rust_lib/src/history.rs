@@ -0,0 +1,560 @@
+use crate::analysis::{self, AnalysisResult, ClinVarVariant};
+use crate::database::open_genome_database;
+use crate::error::BiovaultError;
+use crate::genomes;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tracing::info;
+
+/// Analysis type recorded for ClinVar runs
+pub const CLINVAR_ANALYSIS: &str = "clinvar";
+
+/// Version of this library, recorded with every run
+pub const LIBRARY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Where an analysis result came from, so it can be reproduced later
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct AnalysisProvenance {
+    pub analysis_type: String,
+    pub genome_id: Option<i64>,
+    pub parameters: serde_json::Value,
+    pub clinvar_release: String,
+    pub clinvar_checksum: String,
+    pub library_version: String,
+}
+
+/// Summary of a persisted analysis run
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct AnalysisRun {
+    pub id: i64,
+    pub analysis_type: String,
+    pub parameters: serde_json::Value,
+    pub clinvar_release: String,
+    pub clinvar_checksum: String,
+    pub library_version: String,
+    pub created_at: String,
+    pub rsids_searched: i32,
+    pub matches_found: i32,
+    pub genome_id: Option<i64>,
+}
+
+/// A persisted run together with its reconstructed result
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct StoredAnalysis {
+    pub run: AnalysisRun,
+    pub result: AnalysisResult,
+}
+
+/// Hex digits of the checksum kept in a release label
+const RELEASE_CHECKSUM_DIGITS: usize = 12;
+
+/// Human-readable release label for a ClinVar database file: its file stem
+/// and the start of its checksum, e.g. `clinvar_23andme@ba7816bf8f01`.
+/// The app stores every release under the same file name, so the stem
+/// alone would not tell two releases apart.
+pub fn clinvar_release_label(clinvar_db_path: &str, checksum: &str) -> String {
+    let stem = Path::new(clinvar_db_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(clinvar_db_path);
+    let digits = checksum.get(..RELEASE_CHECKSUM_DIGITS).unwrap_or(checksum);
+    format!("{}@{}", stem, digits)
+}
+
+/// SHA-256 of a file as lowercase hex
+pub fn file_checksum(path: &str) -> Result<String, BiovaultError> {
+    let mut file = std::fs::File::open(path).map_err(|e| BiovaultError::io(Path::new(path), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// `file_checksum` for a file held in memory
+pub fn bytes_checksum(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Build the provenance record for a ClinVar analysis
+pub fn clinvar_provenance(
+    clinvar_db_path: &str,
+    genome_id: i64,
+    parameters: serde_json::Value,
+) -> Result<AnalysisProvenance, BiovaultError> {
+    let clinvar_checksum = file_checksum(clinvar_db_path)?;
+    Ok(AnalysisProvenance {
+        analysis_type: CLINVAR_ANALYSIS.to_string(),
+        genome_id: Some(genome_id),
+        parameters,
+        clinvar_release: clinvar_release_label(clinvar_db_path, &clinvar_checksum),
+        clinvar_checksum,
+        library_version: LIBRARY_VERSION.to_string(),
+    })
+}
+
+/// Store an analysis result and its findings in the user genome database.
+/// Returns the id of the new run.
+pub fn save_analysis_run(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    provenance: &AnalysisProvenance,
+    result: &AnalysisResult,
+) -> Result<i64, BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+
+    let created_at = chrono::Utc::now().to_rfc3339();
+    let parameters = serde_json::to_string(&provenance.parameters)?;
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO analysis_runs
+         (analysis_type, parameters, clinvar_release, clinvar_checksum, library_version,
+          created_at, rsids_searched, matches_found, genome_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            provenance.analysis_type,
+            parameters,
+            provenance.clinvar_release,
+            provenance.clinvar_checksum,
+            provenance.library_version,
+            created_at,
+            result.rsids_searched,
+            result.matches_found,
+            provenance.genome_id,
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO analysis_findings
+             (run_id, rsid, chrom, pos, ref_allele, alt_allele, gene, clnsig, clnrevstat, condition, user_genotype)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+
+        for finding in &result.matches {
+            stmt.execute(params![
+                run_id,
+                finding.rsid,
+                finding.chrom,
+                finding.pos,
+                finding.ref_allele,
+                finding.alt_allele,
+                finding.gene,
+                finding.clnsig,
+                finding.clnrevstat,
+                finding.condition,
+                finding.user_genotype,
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    info!(
+        "stored run {} with {} findings",
+        run_id,
+        result.matches.len()
+    );
+
+    Ok(run_id)
+}
+
+const RUN_COLUMNS: &str = "id, analysis_type, parameters, clinvar_release, clinvar_checksum,
+     library_version, created_at, rsids_searched, matches_found, genome_id";
+
+fn run_from_row(row: &rusqlite::Row) -> rusqlite::Result<AnalysisRun> {
+    let parameters: String = row.get(2)?;
+    Ok(AnalysisRun {
+        id: row.get(0)?,
+        analysis_type: row.get(1)?,
+        parameters: serde_json::from_str(&parameters).unwrap_or(serde_json::Value::Null),
+        clinvar_release: row.get(3)?,
+        clinvar_checksum: row.get(4)?,
+        library_version: row.get(5)?,
+        created_at: row.get(6)?,
+        rsids_searched: row.get(7)?,
+        matches_found: row.get(8)?,
+        genome_id: row.get(9)?,
+    })
+}
+
+/// List all stored runs, newest first
+pub fn list_analysis_runs(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+) -> Result<Vec<AnalysisRun>, BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM analysis_runs ORDER BY id DESC",
+        RUN_COLUMNS
+    ))?;
+    let run_iter = stmt.query_map([], run_from_row)?;
+
+    let mut runs = Vec::new();
+    for run in run_iter {
+        runs.push(run?);
+    }
+
+    Ok(runs)
+}
+
+/// Load a stored run and rebuild its analysis result
+pub fn load_analysis_run(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    run_id: i64,
+) -> Result<Option<StoredAnalysis>, BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+
+    let run = conn
+        .query_row(
+            &format!("SELECT {} FROM analysis_runs WHERE id = ?1", RUN_COLUMNS),
+            [run_id],
+            run_from_row,
+        )
+        .optional()?;
+
+    match run {
+        Some(run) => Ok(Some(stored_analysis(&conn, run)?)),
+        None => Ok(None),
+    }
+}
+
+/// Load the most recent run of the given analysis type on a genome together
+/// with its findings. Runs stored before genomes were tracked match any genome.
+pub fn latest_analysis_run(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    analysis_type: &str,
+    genome_id: i64,
+) -> Result<Option<StoredAnalysis>, BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+
+    let run = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM analysis_runs
+                 WHERE analysis_type = ?1 AND (genome_id = ?2 OR genome_id IS NULL)
+                 ORDER BY id DESC LIMIT 1",
+                RUN_COLUMNS
+            ),
+            params![analysis_type, genome_id],
+            run_from_row,
+        )
+        .optional()?;
+
+    match run {
+        Some(run) => Ok(Some(stored_analysis(&conn, run)?)),
+        None => Ok(None),
+    }
+}
+
+/// Delete a stored run and its findings. Returns false if no such run exists.
+pub fn delete_analysis_run(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    run_id: i64,
+) -> Result<bool, BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+
+    // Delete findings explicitly; foreign key enforcement is off by default
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM analysis_findings WHERE run_id = ?1", [run_id])?;
+    let deleted = tx.execute("DELETE FROM analysis_runs WHERE id = ?1", [run_id])?;
+    tx.commit()?;
+
+    Ok(deleted > 0)
+}
+
+/// Run the ClinVar analysis and store the result with its provenance.
+/// `genome_id` may be omitted if the database holds a single genome.
+pub fn analyze_and_store_clinvar(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    clinvar_db_path: &str,
+) -> Result<StoredAnalysis, BiovaultError> {
+    let genome_id = genomes::resolve_genome_id_at(user_db_path, user_db_key, genome_id)?;
+    let provenance = clinvar_provenance(clinvar_db_path, genome_id, serde_json::json!({}))?;
+    let result = analysis::analyze_clinvar_matches(
+        user_db_path,
+        user_db_key,
+        Some(genome_id),
+        clinvar_db_path,
+    )?;
+    let run_id = save_analysis_run(user_db_path, user_db_key, &provenance, &result)?;
+
+    load_analysis_run(user_db_path, user_db_key, run_id)?
+        .ok_or_else(|| BiovaultError::NotFound(format!("Stored analysis run {}", run_id)))
+}
+
+fn stored_analysis(conn: &Connection, run: AnalysisRun) -> Result<StoredAnalysis, BiovaultError> {
+    let matches = load_findings(conn, run.id)?;
+    let gene_groups = analysis::group_variants_by_gene(matches.clone());
+
+    let result = AnalysisResult {
+        matches,
+        gene_groups,
+        rsids_searched: run.rsids_searched,
+        matches_found: run.matches_found,
+    };
+
+    Ok(StoredAnalysis { run, result })
+}
+
+/// Load the findings stored for a run
+fn load_findings(conn: &Connection, run_id: i64) -> Result<Vec<ClinVarVariant>, BiovaultError> {
+    let mut stmt = conn.prepare(
+        "SELECT rsid, chrom, pos, ref_allele, alt_allele, gene, clnsig, clnrevstat, condition, user_genotype
+         FROM analysis_findings
+         WHERE run_id = ?1
+         ORDER BY id",
+    )?;
+
+    let finding_iter = stmt.query_map([run_id], |row| {
+        Ok(ClinVarVariant {
+            rsid: row.get(0)?,
+            chrom: row.get(1)?,
+            pos: row.get(2)?,
+            ref_allele: row.get(3)?,
+            alt_allele: row.get(4)?,
+            gene: row.get(5)?,
+            clnsig: row.get(6)?,
+            clnrevstat: row.get(7)?,
+            condition: row.get(8)?,
+            user_genotype: row.get(9)?,
+        })
+    })?;
+
+    let mut findings = Vec::new();
+    for finding in finding_iter {
+        findings.push(finding?);
+    }
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use std::fs;
+
+    /// A genome database with one genome of two calls, and a ClinVar
+    /// release matching one of them
+    fn fixtures(dir: &Path) -> (String, String) {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\n\
+             rs1\t1\t100\tAG\n\
+             rs2\t1\t200\tCC\n",
+        )
+        .unwrap();
+        let db_path = dir.join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        let clinvar_path = dir.join("clinvar_2024-05.sqlite");
+        let clinvar = Connection::open(&clinvar_path).unwrap();
+        clinvar
+            .execute_batch(
+                "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                     gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);
+                 INSERT INTO variants VALUES ('rs1', '1', 100, 'A', 'G', 'GENE1', 'Pathogenic',
+                     'criteria_provided,_single_submitter', 'Some_condition');",
+            )
+            .unwrap();
+
+        (
+            db_path.to_string_lossy().into_owned(),
+            clinvar_path.to_string_lossy().into_owned(),
+        )
+    }
+
+    fn finding(rsid: &str, gene: &str) -> ClinVarVariant {
+        ClinVarVariant {
+            rsid: rsid.to_string(),
+            chrom: "1".to_string(),
+            pos: 100,
+            ref_allele: "A".to_string(),
+            alt_allele: "G".to_string(),
+            gene: gene.to_string(),
+            clnsig: "Pathogenic".to_string(),
+            clnrevstat: "reviewed_by_expert_panel".to_string(),
+            condition: "Some_condition".to_string(),
+            user_genotype: Some("AG".to_string()),
+        }
+    }
+
+    fn provenance(analysis_type: &str, genome_id: Option<i64>) -> AnalysisProvenance {
+        AnalysisProvenance {
+            analysis_type: analysis_type.to_string(),
+            genome_id,
+            parameters: serde_json::json!({ "min_stars": 2 }),
+            clinvar_release: "clinvar_2024-05".to_string(),
+            clinvar_checksum: "abc".to_string(),
+            library_version: LIBRARY_VERSION.to_string(),
+        }
+    }
+
+    fn result(matches: Vec<ClinVarVariant>) -> AnalysisResult {
+        AnalysisResult {
+            gene_groups: analysis::group_variants_by_gene(matches.clone()),
+            matches_found: matches.len() as i32,
+            rsids_searched: 2,
+            matches,
+        }
+    }
+
+    #[test]
+    fn checksums_and_release_labels() {
+        assert_eq!(
+            bytes_checksum(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clinvar_2024-05.sqlite");
+        fs::write(&path, b"abc").unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(file_checksum(path).unwrap(), bytes_checksum(b"abc"));
+        assert_eq!(
+            clinvar_release_label(path, &bytes_checksum(b"abc")),
+            "clinvar_2024-05@ba7816bf8f01"
+        );
+        assert_eq!(clinvar_release_label(path, "abc"), "clinvar_2024-05@abc");
+        assert!(file_checksum(dir.path().join("missing").to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn saved_runs_load_back_with_their_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = fixtures(dir.path());
+
+        let saved = result(vec![finding("rs1", "GENE1"), finding("rs2", "GENE2")]);
+        let first =
+            save_analysis_run(&db, None, &provenance(CLINVAR_ANALYSIS, Some(1)), &saved).unwrap();
+        let second =
+            save_analysis_run(&db, None, &provenance("prs", Some(1)), &result(vec![])).unwrap();
+
+        let runs = list_analysis_runs(&db, None).unwrap();
+        assert_eq!(
+            runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+            [second, first]
+        );
+
+        let stored = load_analysis_run(&db, None, first).unwrap().unwrap();
+        assert_eq!(stored.run.analysis_type, CLINVAR_ANALYSIS);
+        assert_eq!(stored.run.genome_id, Some(1));
+        assert_eq!(stored.run.parameters, serde_json::json!({ "min_stars": 2 }));
+        assert_eq!(stored.run.clinvar_checksum, "abc");
+        assert_eq!(stored.run.library_version, LIBRARY_VERSION);
+        assert_eq!(
+            (stored.run.rsids_searched, stored.run.matches_found),
+            (2, 2)
+        );
+        assert_eq!(
+            stored
+                .result
+                .matches
+                .iter()
+                .map(|m| m.rsid.as_str())
+                .collect::<Vec<_>>(),
+            ["rs1", "rs2"]
+        );
+        assert_eq!(
+            stored.result.matches[0].user_genotype.as_deref(),
+            Some("AG")
+        );
+        assert_eq!(stored.result.gene_groups.len(), 2);
+
+        assert!(load_analysis_run(&db, None, 99).unwrap().is_none());
+    }
+
+    #[test]
+    fn latest_run_matches_type_and_genome() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = fixtures(dir.path());
+        let empty = result(vec![]);
+
+        assert!(
+            latest_analysis_run(&db, None, CLINVAR_ANALYSIS, 1)
+                .unwrap()
+                .is_none()
+        );
+
+        // Runs from before genomes were tracked match any genome
+        let legacy =
+            save_analysis_run(&db, None, &provenance(CLINVAR_ANALYSIS, None), &empty).unwrap();
+        let latest = latest_analysis_run(&db, None, CLINVAR_ANALYSIS, 2).unwrap();
+        assert_eq!(latest.unwrap().run.id, legacy);
+
+        let genome_one =
+            save_analysis_run(&db, None, &provenance(CLINVAR_ANALYSIS, Some(1)), &empty).unwrap();
+        save_analysis_run(&db, None, &provenance(CLINVAR_ANALYSIS, Some(2)), &empty).unwrap();
+        save_analysis_run(&db, None, &provenance("prs", Some(1)), &empty).unwrap();
+
+        let latest = latest_analysis_run(&db, None, CLINVAR_ANALYSIS, 1).unwrap();
+        assert_eq!(latest.unwrap().run.id, genome_one);
+    }
+
+    #[test]
+    fn deleting_a_run_removes_its_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = fixtures(dir.path());
+        let run = save_analysis_run(
+            &db,
+            None,
+            &provenance(CLINVAR_ANALYSIS, Some(1)),
+            &result(vec![finding("rs1", "GENE1")]),
+        )
+        .unwrap();
+
+        assert!(delete_analysis_run(&db, None, run).unwrap());
+        assert!(!delete_analysis_run(&db, None, run).unwrap());
+        assert!(list_analysis_runs(&db, None).unwrap().is_empty());
+
+        let conn = Connection::open(&db).unwrap();
+        let findings: i64 = conn
+            .query_row("SELECT COUNT(*) FROM analysis_findings", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(findings, 0);
+    }
+
+    #[test]
+    fn analyze_and_store_records_the_clinvar_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, clinvar) = fixtures(dir.path());
+
+        let stored = analyze_and_store_clinvar(&db, None, None, &clinvar).unwrap();
+        assert_eq!(stored.run.genome_id, Some(1));
+        let checksum = file_checksum(&clinvar).unwrap();
+        assert_eq!(
+            stored.run.clinvar_release,
+            format!("clinvar_2024-05@{}", &checksum[..12])
+        );
+        assert_eq!(stored.run.clinvar_checksum, checksum);
+        assert_eq!(stored.run.rsids_searched, 2);
+        assert_eq!(stored.result.matches.len(), 1);
+        assert_eq!(
+            stored.result.matches[0].user_genotype.as_deref(),
+            Some("AG")
+        );
+
+        let latest = latest_analysis_run(&db, None, CLINVAR_ANALYSIS, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.run.id, stored.run.id);
+    }
+}
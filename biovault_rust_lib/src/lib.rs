@@ -1,80 +1,143 @@
+mod access_log;
 mod analysis;
+#[cfg(not(target_family = "wasm"))]
+mod batch;
+mod bindings;
+#[cfg(not(target_family = "wasm"))]
+mod bundle;
+mod clinical;
+mod cohort;
+mod consent;
 mod database;
+#[cfg(not(target_family = "wasm"))]
+mod datasite;
+#[cfg(not(target_family = "wasm"))]
+mod deletion;
+mod diff;
+mod encoding;
+mod error;
+mod export;
+mod genomes;
+mod history;
+mod logging;
+mod migrations;
 mod parsers;
+#[cfg(not(target_family = "wasm"))]
+mod privacy;
+mod progress;
+mod query;
+mod report;
+mod storage;
+#[cfg(target_family = "wasm")]
+mod wasm;
 
-use database::create_genome_database;
-use parsers::twenty_three_and_me;
-use std::ffi::{CStr, CString};
+uniffi::setup_scaffolding!();
+
+pub use access_log::{AccessEvent, AccessLogEntry, AccessLogVerification, set_access_caller};
+pub use analysis::{
+    AnalysisResult, ClinVarFilter, ClinVarVariant, GeneGroup, SIGNIFICANCE_LABELS, Zygosity,
+};
+#[cfg(not(target_family = "wasm"))]
+pub use batch::{BatchEntry, BatchItem, BatchSummary, BatchTarget};
+#[cfg(not(target_family = "wasm"))]
+pub use bundle::BundleManifest;
+pub use clinical::{ClinicalFormat, ReportContext};
+pub use cohort::{Cohort, CohortOptions, CohortSample, CohortSummary, MarkerStats};
+pub use consent::{
+    ConsentDecision, ConsentGrant, GrantPurpose, Granularity, OWNER as CONSENT_OWNER, Purpose,
+};
+#[cfg(not(target_family = "wasm"))]
+pub use datasite::{
+    AllowList, AllowRule, DatasiteConfig, JobOutcome, JobQuery, JobResponse, JobStatus, ResearchJob,
+};
+#[cfg(not(target_family = "wasm"))]
+pub use deletion::{DeletionReport, Locus, RedactionSummary};
+pub use diff::{ClinVarDiff, SignificanceChange, SignificanceDirection};
+pub use error::{BiovaultError, ErrorReport, codes as error_codes};
+pub use export::{ExportFormat, ExportSummary};
+pub use genomes::{DuplicatePolicy, GenomeFile, GenomeRecord, ImportOutcome, ImportStatus};
+pub use history::{AnalysisProvenance, AnalysisRun, StoredAnalysis};
+pub use logging::{LogLevel, LogSink, set_log_sink};
+pub use migrations::SCHEMA_VERSION;
+#[cfg(not(target_family = "wasm"))]
+pub use privacy::{DpQuery, DpRelease, Mechanism, NoisyValue, PrivacyBudget, PrsWeight};
+pub use progress::{CancellationToken, Phase, Progress, ProgressUpdate};
+pub use query::{
+    DEFAULT_PAGE_SIZE, GeneLocation, MAX_PAGE_SIZE, VariantPage, VariantQuery, VariantRow,
+    gene_location, gene_panel,
+};
+pub use report::{FindingsReport, ReportFormat, SampleQc};
+pub use storage::Storage;
+#[cfg(target_family = "wasm")]
+pub use storage::install_opfs;
+
+use std::ffi::{CStr, CString, c_void};
 use std::os::raw::c_char;
 use std::path::Path;
+use tracing::{debug, error, info};
 
 /// Process a 23andMe file and create an SQLite database.
 ///
-/// Returns a newly-allocated C string containing the full path to the
-/// created database file on success, or a null pointer on error.
+/// Returns a result envelope (see `ffi_result`) whose `data` is the full path
+/// to the created database file.
 ///
 /// # Safety
-/// - `input_path`, `custom_name`, and `output_dir` must be valid pointers to
-///   NUL-terminated UTF-8 strings and remain valid for the duration of the call.
+/// - `input_path`, `custom_name`, and `output_dir` must be null or valid
+///   pointers to NUL-terminated strings that remain valid for the duration of
+///   the call. Null or non-UTF-8 arguments produce an `INVALID_ARGUMENT` error.
 /// - The returned pointer must be freed by calling `free_string` exactly once.
-/// - Passing null or invalid pointers, or freeing the returned pointer by any
-///   other means is undefined behavior.
+/// - Freeing the returned pointer by any other means is undefined behavior.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn process_23andme_file(
     input_path: *const c_char,
     custom_name: *const c_char,
     output_dir: *const c_char,
 ) -> *mut c_char {
-    let input_path = unsafe {
-        match CStr::from_ptr(input_path).to_str() {
-            Ok(s) => s,
-            Err(_) => return std::ptr::null_mut(),
-        }
-    };
+    ffi_result("processing", || {
+        let input_path = unsafe { c_str(input_path, "input_path") }?;
+        let custom_name = unsafe { c_str(custom_name, "custom_name") }?;
+        let output_dir = unsafe { c_str(output_dir, "output_dir") }?;
 
-    let custom_name = unsafe {
-        match CStr::from_ptr(custom_name).to_str() {
-            Ok(s) => s,
-            Err(_) => return std::ptr::null_mut(),
-        }
-    };
+        process_file_internal(input_path, custom_name, output_dir, None)
+    })
+}
 
-    let output_dir = unsafe {
-        match CStr::from_ptr(output_dir).to_str() {
-            Ok(s) => s,
-            Err(_) => return std::ptr::null_mut(),
-        }
-    };
+/// Process a 23andMe file into an SQLite database encrypted with `key`.
+///
+/// The key should come from the platform keystore (iOS Keychain / Android
+/// Keystore) and must be supplied again to open the database.
+///
+/// Returns a result envelope whose `data` is the full path to the created
+/// database file.
+///
+/// # Safety
+/// - `input_path`, `custom_name`, `output_dir` and `key` must be null or valid
+///   pointers to NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn process_23andme_file_encrypted(
+    input_path: *const c_char,
+    custom_name: *const c_char,
+    output_dir: *const c_char,
+    key: *const c_char,
+) -> *mut c_char {
+    ffi_result("processing", || {
+        let input_path = unsafe { c_str(input_path, "input_path") }?;
+        let custom_name = unsafe { c_str(custom_name, "custom_name") }?;
+        let output_dir = unsafe { c_str(output_dir, "output_dir") }?;
+        let key = unsafe { c_str(key, "key") }?;
 
-    match process_file_internal(input_path, custom_name, output_dir) {
-        Ok(db_name) => match CString::new(db_name) {
-            Ok(c_string) => c_string.into_raw(),
-            Err(e) => {
-                eprintln!("Failed to create CString: {}", e);
-                std::ptr::null_mut()
-            }
-        },
-        Err(e) => {
-            eprintln!("Rust processing failed: {}", e);
-            std::ptr::null_mut()
-        }
-    }
+        process_file_internal(input_path, custom_name, output_dir, Some(key))
+    })
 }
 
 fn process_file_internal(
     input_path: &str,
     custom_name: &str,
     output_dir: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    eprintln!("Rust: Starting to process file: {}", input_path);
-
-    // Parse the 23andMe file
-    let parse_result = twenty_three_and_me::parse_23andme_file(Path::new(input_path))?;
-
-    eprintln!(
-        "Rust: Parsed {} variants, {} with rsIDs",
-        parse_result.metadata.total_variants, parse_result.metadata.rsid_count
-    );
+    key: Option<&str>,
+) -> Result<String, BiovaultError> {
+    debug!("processing {}", input_path);
 
     // Create output SQLite file path in SQLite subdirectory
     // expo-sqlite expects databases to be in Documents/SQLite/
@@ -85,26 +148,31 @@ fn process_file_internal(
     let sqlite_dir = Path::new(output_dir).join("SQLite");
     if !sqlite_dir.exists() {
         std::fs::create_dir_all(&sqlite_dir)?;
-        eprintln!("Rust: Created SQLite directory at: {:?}", sqlite_dir);
+        debug!("created SQLite directory at {:?}", sqlite_dir);
     }
 
     let output_path = sqlite_dir.join(&db_filename);
 
-    eprintln!("Rust: Creating database at: {:?}", output_path);
+    // Create SQLite database holding just this genome
+    genomes::import_genome(
+        input_path,
+        &output_path,
+        custom_name,
+        None,
+        key,
+        DuplicatePolicy::Skip,
+    )?;
 
-    // Create SQLite database
-    let _db_name = create_genome_database(parse_result, &output_path, custom_name)?;
-
-    eprintln!("Rust: Database created successfully");
+    info!("created database {:?}", output_path);
 
     // Return the full path to the created database
     Ok(output_path.to_string_lossy().to_string())
 }
 
-/// Free memory allocated by `process_23andme_file`.
+/// Free a string returned by any of the functions above.
 ///
 /// # Safety
-/// - `ptr` must be a pointer previously returned by `process_23andme_file`.
+/// - `ptr` must be null or a pointer previously returned by this library.
 /// - It must not have been freed already.
 /// - Passing any other pointer, or double-freeing, is undefined behavior.
 #[unsafe(no_mangle)]
@@ -119,19 +187,176 @@ pub unsafe extern "C" fn free_string(ptr: *mut c_char) {
 // Keep the existing add function for testing
 #[unsafe(no_mangle)]
 pub extern "C" fn rust_add(a: i32, b: i32) -> i32 {
-    a + b
+    // Wrap like release builds do instead of panicking on overflow
+    a.wrapping_add(b)
 }
 
 /// cbindgen:ignore
 #[cfg(target_os = "android")]
 pub mod android {
     use crate::analysis;
+    use crate::clinical;
+    use crate::database;
+    use crate::diff;
+    use crate::error::BiovaultError;
+    use crate::export;
+    use crate::genomes;
+    use crate::history;
+    use crate::logging::{self, LogLevel, LogSink, set_log_sink};
+    use crate::migrations;
     use crate::process_file_internal;
-    use jni::JNIEnv;
-    use jni::objects::JClass;
+    use crate::progress::{CancellationToken, Progress, ProgressUpdate};
+    use crate::report;
+    use jni::objects::{GlobalRef, JClass, JObject, JString, JThrowable, JValue};
     use jni::sys;
+    use jni::{JNIEnv, JavaVM};
+    use std::path::Path;
+
+    /// Java class thrown for every library error
+    const EXCEPTION_CLASS: &str = "expo/modules/biovault/BiovaultException";
+
+    /// Throw a `BiovaultException(code, message, contextJson)` for `error`.
+    /// Falls back to a plain `RuntimeException` if the class cannot be built.
+    fn throw_error(env: &mut JNIEnv, operation: &str, error: &BiovaultError) {
+        logging::log_failure(operation, error);
+
+        let report = error.report();
+        let thrown = (|| -> jni::errors::Result<()> {
+            let code = env.new_string(report.code)?;
+            let message = env.new_string(&report.message)?;
+            let context = match &report.context {
+                Some(context) => JObject::from(env.new_string(context.to_string())?),
+                None => JObject::null(),
+            };
+            let exception = env.new_object(
+                EXCEPTION_CLASS,
+                "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                &[
+                    JValue::Object(&code),
+                    JValue::Object(&message),
+                    JValue::Object(&context),
+                ],
+            )?;
+            env.throw(JThrowable::from(exception))
+        })();
+
+        if thrown.is_err() {
+            let _ = env.exception_clear();
+            let _ = env.throw_new(
+                "java/lang/RuntimeException",
+                format!("{}: {}", report.code, report.message),
+            );
+        }
+    }
+
+    /// Run the body of a JNI entrypoint. Errors and panics never cross into
+    /// the JVM; they are thrown as `BiovaultException` and `None` is returned.
+    fn catch_jni<'local, T>(
+        env: &mut JNIEnv<'local>,
+        operation: &str,
+        f: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, BiovaultError>,
+    ) -> Option<T> {
+        match BiovaultError::catch_panic(|| f(env)) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                throw_error(env, operation, &e);
+                None
+            }
+        }
+    }
+
+    /// Run a JNI entrypoint that returns a string; null after throwing
+    fn string_call<'local>(
+        env: &mut JNIEnv<'local>,
+        operation: &str,
+        f: impl FnOnce(&mut JNIEnv<'local>) -> Result<String, BiovaultError>,
+    ) -> JString<'local> {
+        catch_jni(env, operation, |env| {
+            let value = f(env)?;
+            env.new_string(value)
+                .map_err(|e| BiovaultError::Other(format!("Cannot create Java string: {}", e)))
+        })
+        .unwrap_or_default()
+    }
+
+    /// Run a JNI entrypoint that returns its result as JSON
+    fn json_call<'local, T: serde::Serialize>(
+        env: &mut JNIEnv<'local>,
+        operation: &str,
+        f: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, BiovaultError>,
+    ) -> JString<'local> {
+        string_call(env, operation, |env| Ok(serde_json::to_string(&f(env)?)?))
+    }
+
+    /// Run a JNI entrypoint that returns an integer; -1 after throwing
+    fn int_call<'local>(
+        env: &mut JNIEnv<'local>,
+        operation: &str,
+        f: impl FnOnce(&mut JNIEnv<'local>) -> Result<sys::jint, BiovaultError>,
+    ) -> sys::jint {
+        catch_jni(env, operation, f).unwrap_or(-1)
+    }
+
+    /// Read a required Java string argument
+    fn java_string(env: &mut JNIEnv, value: &JString, name: &str) -> Result<String, BiovaultError> {
+        if value.is_null() {
+            return Err(BiovaultError::InvalidArgument(format!("{} is null", name)));
+        }
+        env.get_string(value)
+            .map(Into::into)
+            .map_err(|e| BiovaultError::InvalidArgument(format!("{}: {}", name, e)))
+    }
+
+    /// Read a nullable Java string argument
+    fn optional_java_string(
+        env: &mut JNIEnv,
+        value: &JString,
+        name: &str,
+    ) -> Result<Option<String>, BiovaultError> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            java_string(env, value, name).map(Some)
+        }
+    }
+
+    /// Report progress to a nullable `ProgressListener`. The listener is
+    /// polled through `isCancelled()` at every report; a listener that
+    /// throws also cancels the operation.
+    fn java_progress<'a, 'local>(
+        env: &'a mut JNIEnv<'local>,
+        listener: &'a JObject<'local>,
+    ) -> Progress<'a> {
+        if listener.is_null() {
+            return Progress::none();
+        }
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        Progress::new(move |update: ProgressUpdate| {
+            let cancelled = (|| -> jni::errors::Result<bool> {
+                let phase = env.new_string(update.phase.name())?;
+                env.call_method(
+                    listener,
+                    "onProgress",
+                    "(Ljava/lang/String;JJ)V",
+                    &[
+                        JValue::Object(&phase),
+                        JValue::Long(update.processed as i64),
+                        JValue::Long(update.total as i64),
+                    ],
+                )?;
+                env.delete_local_ref(phase)?;
+                env.call_method(listener, "isCancelled", "()Z", &[])?.z()
+            })();
+            if cancelled.unwrap_or_else(|_| env.exception_clear().is_ok()) {
+                cancel.cancel();
+            }
+        })
+        .with_cancellation(token)
+    }
 
     /// JNI entrypoint used by the Android module to process a genome file.
+    /// Returns the database path; failures throw `BiovaultException`.
     ///
     /// # Safety
     /// - Called by the JVM with valid JNI references and strings.
@@ -142,18 +367,112 @@ pub mod android {
     >(
         mut env: JNIEnv<'local>,
         _class: JClass<'local>,
-        input_path: jni::objects::JString<'local>,
-        custom_name: jni::objects::JString<'local>,
-        output_dir: jni::objects::JString<'local>,
-    ) -> jni::objects::JString<'local> {
-        let input_path_str: String = env.get_string(&input_path).unwrap().into();
-        let custom_name_str: String = env.get_string(&custom_name).unwrap().into();
-        let output_dir_str: String = env.get_string(&output_dir).unwrap().into();
+        input_path: JString<'local>,
+        custom_name: JString<'local>,
+        output_dir: JString<'local>,
+    ) -> JString<'local> {
+        string_call(&mut env, "processing", |env| {
+            let input_path = java_string(env, &input_path, "inputPath")?;
+            let custom_name = java_string(env, &custom_name, "customName")?;
+            let output_dir = java_string(env, &output_dir, "outputDir")?;
 
-        match process_file_internal(&input_path_str, &custom_name_str, &output_dir_str) {
-            Ok(result) => env.new_string(result).unwrap(),
-            Err(_) => env.new_string("ERROR").unwrap(),
-        }
+            process_file_internal(&input_path, &custom_name, &output_dir, None)
+        })
+    }
+
+    /// JNI entrypoint to process a genome file into an encrypted database.
+    /// The key comes from the Android Keystore on the Kotlin side.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_processGenomeFileEncrypted<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        input_path: JString<'local>,
+        custom_name: JString<'local>,
+        output_dir: JString<'local>,
+        key: JString<'local>,
+    ) -> JString<'local> {
+        string_call(&mut env, "processing", |env| {
+            let input_path = java_string(env, &input_path, "inputPath")?;
+            let custom_name = java_string(env, &custom_name, "customName")?;
+            let output_dir = java_string(env, &output_dir, "outputDir")?;
+            let key = java_string(env, &key, "key")?;
+
+            process_file_internal(&input_path, &custom_name, &output_dir, Some(&key))
+        })
+    }
+
+    /// JNI entrypoint for ClinVar analysis of an encrypted genome database
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_analyzeClinVarEncrypted<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        user_db_key: JString<'local>,
+        clinvar_db_path: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "analysis", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let user_db_key = java_string(env, &user_db_key, "userDbKey")?;
+            let clinvar_db = java_string(env, &clinvar_db_path, "clinvarDbPath")?;
+
+            analysis::analyze_clinvar_matches(&user_db, Some(&user_db_key), None, &clinvar_db)
+        })
+    }
+
+    /// JNI entrypoint to change the encryption key of a genome database.
+    /// Null keys mean plaintext. Returns 0 on success.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_rekeyGenomeDatabase<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        db_path: JString<'local>,
+        old_key: JString<'local>,
+        new_key: JString<'local>,
+    ) -> sys::jint {
+        int_call(&mut env, "rekey", |env| {
+            let db_path = java_string(env, &db_path, "dbPath")?;
+            let old_key = optional_java_string(env, &old_key, "oldKey")?;
+            let new_key = optional_java_string(env, &new_key, "newKey")?;
+
+            database::rekey_genome_database(
+                Path::new(&db_path),
+                old_key.as_deref(),
+                new_key.as_deref(),
+            )?;
+            Ok(0)
+        })
+    }
+
+    /// JNI entrypoint to write a decrypted copy of an encrypted genome database.
+    /// Returns 0 on success.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_exportDecryptedGenomeDatabase<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        db_path: JString<'local>,
+        key: JString<'local>,
+        output_path: JString<'local>,
+    ) -> sys::jint {
+        int_call(&mut env, "decrypted export", |env| {
+            let db_path = java_string(env, &db_path, "dbPath")?;
+            let key = java_string(env, &key, "key")?;
+            let output_path = java_string(env, &output_path, "outputPath")?;
+
+            database::export_decrypted_genome_database(
+                Path::new(&db_path),
+                &key,
+                Path::new(&output_path),
+            )?;
+            Ok(0)
+        })
     }
 
     /// JNI entrypoint for the rust_add function.
@@ -174,80 +493,1635 @@ pub mod android {
     >(
         mut env: JNIEnv<'local>,
         _class: JClass<'local>,
-        user_db_path: jni::objects::JString<'local>,
-        clinvar_db_path: jni::objects::JString<'local>,
-    ) -> jni::objects::JString<'local> {
-        let user_db_str: String = env.get_string(&user_db_path).unwrap().into();
-        let clinvar_db_str: String = env.get_string(&clinvar_db_path).unwrap().into();
+        user_db_path: JString<'local>,
+        clinvar_db_path: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "analysis", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let clinvar_db = java_string(env, &clinvar_db_path, "clinvarDbPath")?;
+
+            analysis::analyze_clinvar_matches(&user_db, None, None, &clinvar_db)
+        })
+    }
+
+    /// JNI entrypoint for ClinVar re-analysis against the last stored run
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_reanalyzeClinVar<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        clinvar_db_path: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "re-analysis", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let clinvar_db = java_string(env, &clinvar_db_path, "clinvarDbPath")?;
+
+            diff::reanalyze_clinvar(&user_db, None, None, &clinvar_db)
+        })
+    }
+
+    /// JNI entrypoint for ClinVar analysis that stores the run in the user database
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_analyzeAndStoreClinVar<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        clinvar_db_path: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "analysis", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let clinvar_db = java_string(env, &clinvar_db_path, "clinvarDbPath")?;
+
+            history::analyze_and_store_clinvar(&user_db, None, None, &clinvar_db)
+        })
+    }
+
+    /// JNI entrypoint to list stored analysis runs
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_listAnalysisRuns<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "listing analysis runs", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+
+            history::list_analysis_runs(&user_db, None)
+        })
+    }
+
+    /// JNI entrypoint to load a stored analysis run. Returns JSON `null` if the
+    /// run does not exist.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_loadAnalysisRun<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        run_id: sys::jlong,
+    ) -> JString<'local> {
+        json_call(&mut env, "loading analysis run", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+
+            history::load_analysis_run(&user_db, None, run_id)
+        })
+    }
+
+    /// JNI entrypoint to validate and migrate a user genome database.
+    /// Returns the schema version.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_migrateGenomeDatabase<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        db_path: JString<'local>,
+    ) -> sys::jint {
+        int_call(&mut env, "migration", |env| {
+            let db_path = java_string(env, &db_path, "dbPath")?;
+
+            let conn = database::open_genome_database(Path::new(&db_path), None)?;
+            Ok(migrations::schema_version(&conn)?)
+        })
+    }
+
+    /// JNI entrypoint to import a genome file into a (possibly shared) genome
+    /// database. Null `person` / `key` mean no label / plaintext.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_importGenomeFile<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        input_path: JString<'local>,
+        db_path: JString<'local>,
+        custom_name: JString<'local>,
+        person: JString<'local>,
+        key: JString<'local>,
+        replace: sys::jboolean,
+        listener: JObject<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "genome import", |env| {
+            let input_path = java_string(env, &input_path, "inputPath")?;
+            let db_path = java_string(env, &db_path, "dbPath")?;
+            let custom_name = java_string(env, &custom_name, "customName")?;
+            let person = optional_java_string(env, &person, "person")?;
+            let key = optional_java_string(env, &key, "key")?;
+            let policy = if replace != 0 {
+                genomes::DuplicatePolicy::Replace
+            } else {
+                genomes::DuplicatePolicy::Skip
+            };
+
+            genomes::import_genome_with_progress(
+                &input_path,
+                Path::new(&db_path),
+                &custom_name,
+                person.as_deref(),
+                key.as_deref(),
+                policy,
+                &mut java_progress(env, &listener),
+            )
+        })
+    }
+
+    /// JNI entrypoint to list the genomes in a genome database
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_listGenomes<'local>(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        db_path: JString<'local>,
+        key: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "listing genomes", |env| {
+            let db_path = java_string(env, &db_path, "dbPath")?;
+            let key = optional_java_string(env, &key, "key")?;
+
+            genomes::list_genomes(&db_path, key.as_deref())
+        })
+    }
+
+    /// JNI entrypoint for ClinVar analysis of one genome in a shared database
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_analyzeClinVarGenome<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        user_db_key: JString<'local>,
+        genome_id: sys::jlong,
+        clinvar_db_path: JString<'local>,
+        listener: JObject<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "analysis", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let user_db_key = optional_java_string(env, &user_db_key, "userDbKey")?;
+            let clinvar_db = java_string(env, &clinvar_db_path, "clinvarDbPath")?;
+
+            analysis::analyze_clinvar_matches_with_progress(
+                &user_db,
+                user_db_key.as_deref(),
+                Some(genome_id),
+                &clinvar_db,
+                &mut java_progress(env, &listener),
+            )
+        })
+    }
+
+    /// JNI entrypoint to export a genome as VCF, 23andMe TSV or PLINK.
+    /// A `genomeId` of 0 selects the only genome; null strings are optional.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_exportGenome<'local>(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        db_path: JString<'local>,
+        key: JString<'local>,
+        genome_id: sys::jlong,
+        format: JString<'local>,
+        output_path: JString<'local>,
+        reference_db_path: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "export", |env| {
+            let db_path = java_string(env, &db_path, "dbPath")?;
+            let key = optional_java_string(env, &key, "key")?;
+            let format = java_string(env, &format, "format")?;
+            let format = export::ExportFormat::from_name(&format).ok_or_else(|| {
+                BiovaultError::InvalidArgument(format!("unknown export format {}", format))
+            })?;
+            let output_path = java_string(env, &output_path, "outputPath")?;
+            let reference_db = optional_java_string(env, &reference_db_path, "referenceDbPath")?;
+
+            export::export_genome(
+                &db_path,
+                key.as_deref(),
+                crate::optional_id(genome_id),
+                format,
+                &output_path,
+                reference_db.as_deref(),
+            )
+        })
+    }
+
+    /// JNI entrypoint to export a stored analysis run as a Phenopacket or
+    /// FHIR bundle. `key` may be null.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_exportClinicalReport<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        key: JString<'local>,
+        run_id: sys::jlong,
+        format: JString<'local>,
+    ) -> JString<'local> {
+        json_call(&mut env, "clinical export", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let key = optional_java_string(env, &key, "key")?;
+            let format = java_string(env, &format, "format")?;
+            let format = clinical::ClinicalFormat::from_name(&format).ok_or_else(|| {
+                BiovaultError::InvalidArgument(format!("unknown clinical format {}", format))
+            })?;
+
+            clinical::export_analysis_run(&user_db, key.as_deref(), run_id, format)
+        })
+    }
+
+    /// JNI entrypoint to write an HTML or PDF findings report. `genomeId` and
+    /// `runId` of 0 select the only genome and its latest run; `key` may be null.
+    /// Returns 0 on success.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_generateReport<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        key: JString<'local>,
+        genome_id: sys::jlong,
+        run_id: sys::jlong,
+        format: JString<'local>,
+        output_path: JString<'local>,
+    ) -> sys::jint {
+        int_call(&mut env, "report", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+            let key = optional_java_string(env, &key, "key")?;
+            let format = java_string(env, &format, "format")?;
+            let format = report::ReportFormat::from_name(&format).ok_or_else(|| {
+                BiovaultError::InvalidArgument(format!("unknown report format {}", format))
+            })?;
+            let output_path = java_string(env, &output_path, "outputPath")?;
+
+            report::write_report(
+                &user_db,
+                key.as_deref(),
+                crate::optional_id(genome_id),
+                crate::optional_id(run_id),
+                format,
+                &output_path,
+            )?;
+            Ok(0)
+        })
+    }
+
+    /// JNI entrypoint to delete a stored analysis run. Returns 1 if the run
+    /// was deleted and 0 if it did not exist.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_deleteAnalysisRun<
+        'local,
+    >(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        user_db_path: JString<'local>,
+        run_id: sys::jlong,
+    ) -> sys::jint {
+        int_call(&mut env, "deleting analysis run", |env| {
+            let user_db = java_string(env, &user_db_path, "userDbPath")?;
+
+            let deleted = history::delete_analysis_run(&user_db, None, run_id)?;
+            Ok(sys::jint::from(deleted))
+        })
+    }
+
+    /// Forwards log events to a Kotlin `LogSink`
+    struct JavaLogSink {
+        vm: JavaVM,
+        sink: GlobalRef,
+    }
 
-        match analysis::analyze_clinvar_matches(&user_db_str, &clinvar_db_str) {
-            Ok(result) => {
-                match serde_json::to_string(&result) {
-                    Ok(json) => env.new_string(json).unwrap(),
-                    Err(_) => env.new_string("ERROR_SERIALIZATION").unwrap(),
-                }
+    impl LogSink for JavaLogSink {
+        fn log(&self, level: LogLevel, target: &str, message: &str) {
+            let Ok(mut env) = self.vm.attach_current_thread_as_daemon() else {
+                return;
+            };
+            let logged = (|| -> jni::errors::Result<()> {
+                let target = env.new_string(target)?;
+                let message = env.new_string(message)?;
+                env.call_method(
+                    &self.sink,
+                    "onLog",
+                    "(ILjava/lang/String;Ljava/lang/String;)V",
+                    &[
+                        JValue::Int(level as i32),
+                        JValue::Object(&target),
+                        JValue::Object(&message),
+                    ],
+                )?;
+                env.delete_local_ref(target)?;
+                env.delete_local_ref(message)
+            })();
+            if logged.is_err() {
+                let _ = env.exception_clear();
             }
-            Err(_) => env.new_string("ERROR_ANALYSIS").unwrap(),
         }
     }
+
+    /// JNI entrypoint to register a `LogSink` receiving log events up to
+    /// `maxLevel` (1 = error ... 5 = trace). A null sink turns logging off.
+    ///
+    /// # Safety
+    /// - Called by the JVM with valid JNI references.
+    /// - Follows standard JNI safety rules; misuse on the caller side is UB.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn Java_expo_modules_biovault_ExpoBiovaultModule_setLogSink<'local>(
+        mut env: JNIEnv<'local>,
+        _class: JClass<'local>,
+        sink: JObject<'local>,
+        max_level: sys::jint,
+    ) {
+        catch_jni(&mut env, "registering log sink", |env| {
+            let sink = if sink.is_null() {
+                None
+            } else {
+                let jni_error = |e: jni::errors::Error| {
+                    BiovaultError::Other(format!("Cannot keep log sink: {}", e))
+                };
+                let vm = env.get_java_vm().map_err(jni_error)?;
+                let sink = env.new_global_ref(&sink).map_err(jni_error)?;
+                Some(Box::new(JavaLogSink { vm, sink }) as Box<dyn LogSink>)
+            };
+            set_log_sink(sink, LogLevel::from_raw(max_level));
+            Ok(())
+        });
+    }
 }
 /// Analyze user genome against ClinVar database
+///
+/// Returns a result envelope whose `data` is the analysis result.
+///
+/// # Safety
+/// - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+///   NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn analyze_clinvar(
     user_db_path: *const c_char,
     clinvar_db_path: *const c_char,
 ) -> *mut c_char {
-    let user_db_path = unsafe {
-        match CStr::from_ptr(user_db_path).to_str() {
-            Ok(s) => s,
-            Err(_) => return std::ptr::null_mut(),
-        }
-    };
+    ffi_result("analysis", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let clinvar_db_path = unsafe { c_str(clinvar_db_path, "clinvar_db_path") }?;
 
-    let clinvar_db_path = unsafe {
-        match CStr::from_ptr(clinvar_db_path).to_str() {
-            Ok(s) => s,
-            Err(_) => return std::ptr::null_mut(),
-        }
-    };
+        analysis::analyze_clinvar_matches(user_db_path, None, None, clinvar_db_path)
+    })
+}
 
-    match analysis::analyze_clinvar_matches(user_db_path, clinvar_db_path) {
-        Ok(result) => {
-            match serde_json::to_string(&result) {
-                Ok(json) => match CString::new(json) {
-                    Ok(c_string) => c_string.into_raw(),
-                    Err(e) => {
-                        eprintln!("Failed to create CString: {}", e);
-                        std::ptr::null_mut()
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to serialize result: {}", e);
-                    std::ptr::null_mut()
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("Rust analysis failed: {}", e);
-            std::ptr::null_mut()
-        }
-    }
+/// Re-run ClinVar analysis and diff it against the last run stored in the
+/// user database. The new run is stored for the next comparison.
+///
+/// Returns a result envelope whose `data` is the diff, or `null` if there
+/// was no previous run.
+///
+/// # Safety
+/// - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+///   NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn reanalyze_clinvar(
+    user_db_path: *const c_char,
+    clinvar_db_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("re-analysis", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let clinvar_db_path = unsafe { c_str(clinvar_db_path, "clinvar_db_path") }?;
+
+        diff::reanalyze_clinvar(user_db_path, None, None, clinvar_db_path)
+    })
 }
 
-/// Public, safe Rust API to process a 23andMe file and create an SQLite DB.
-/// Returns the full path to the created database file.
-pub fn process_23andme(
-    input_path: &str,
-    custom_name: &str,
-    output_dir: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    process_file_internal(input_path, custom_name, output_dir)
+/// Diff the ClinVar analysis of a genome between two ClinVar database releases.
+///
+/// Returns a result envelope whose `data` is the diff.
+///
+/// # Safety
+/// - `user_db_path`, `old_clinvar_db_path` and `new_clinvar_db_path` must be
+///   null or valid pointers to NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn diff_clinvar_releases(
+    user_db_path: *const c_char,
+    old_clinvar_db_path: *const c_char,
+    new_clinvar_db_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("ClinVar diff", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let old_clinvar_db_path = unsafe { c_str(old_clinvar_db_path, "old_clinvar_db_path") }?;
+        let new_clinvar_db_path = unsafe { c_str(new_clinvar_db_path, "new_clinvar_db_path") }?;
+
+        diff::diff_clinvar_releases(
+            user_db_path,
+            None,
+            None,
+            old_clinvar_db_path,
+            new_clinvar_db_path,
+        )
+    })
 }
 
-/// Public, safe Rust API for ClinVar analysis
-pub fn analyze_clinvar_safe(
-    user_db_path: &str,
-    clinvar_db_path: &str,
-) -> Result<analysis::AnalysisResult, Box<dyn std::error::Error>> {
-    analysis::analyze_clinvar_matches(user_db_path, clinvar_db_path)
+/// Run ClinVar analysis and store the result and its provenance in the user
+/// database.
+///
+/// Returns a result envelope whose `data` is the stored run.
+///
+/// # Safety
+/// - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+///   NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn analyze_and_store_clinvar(
+    user_db_path: *const c_char,
+    clinvar_db_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("analysis", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let clinvar_db_path = unsafe { c_str(clinvar_db_path, "clinvar_db_path") }?;
+
+        history::analyze_and_store_clinvar(user_db_path, None, None, clinvar_db_path)
+    })
+}
+
+/// List the analysis runs stored in a user database, newest first.
+///
+/// Returns a result envelope whose `data` is an array of runs.
+///
+/// # Safety
+/// - `user_db_path` must be null or a valid pointer to a NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn list_analysis_runs(user_db_path: *const c_char) -> *mut c_char {
+    ffi_result("listing analysis runs", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+
+        history::list_analysis_runs(user_db_path, None)
+    })
+}
+
+/// Load a stored analysis run with its findings.
+///
+/// Returns a result envelope whose `data` is the run, or `null` if the run
+/// does not exist.
+///
+/// # Safety
+/// - `user_db_path` must be null or a valid pointer to a NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn load_analysis_run(
+    user_db_path: *const c_char,
+    run_id: i64,
+) -> *mut c_char {
+    ffi_result("loading analysis run", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+
+        history::load_analysis_run(user_db_path, None, run_id)
+    })
+}
+
+/// Delete a stored analysis run and its findings.
+///
+/// Returns a result envelope whose `data` is `true` if the run was deleted
+/// and `false` if it did not exist.
+///
+/// # Safety
+/// - `user_db_path` must be null or a valid pointer to a NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn delete_analysis_run(
+    user_db_path: *const c_char,
+    run_id: i64,
+) -> *mut c_char {
+    ffi_result("deleting analysis run", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+
+        history::delete_analysis_run(user_db_path, None, run_id)
+    })
+}
+
+/// Open a user genome database, validate it and migrate its schema to the
+/// current version.
+///
+/// Returns a result envelope whose `data` is the schema version. Files that
+/// are not genome databases fail with `NOT_A_GENOME_DATABASE`.
+///
+/// # Safety
+/// - `db_path` must be null or a valid pointer to a NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn migrate_genome_database(db_path: *const c_char) -> *mut c_char {
+    ffi_result("migration", || {
+        let db_path = unsafe { c_str(db_path, "db_path") }?;
+
+        let conn = database::open_genome_database(Path::new(db_path), None)?;
+        Ok(migrations::schema_version(&conn)?)
+    })
+}
+
+/// Analyze an encrypted user genome against the (unencrypted) ClinVar database
+///
+/// Returns a result envelope whose `data` is the analysis result. A wrong key
+/// fails with `WRONG_KEY`.
+///
+/// # Safety
+/// - `user_db_path`, `user_db_key` and `clinvar_db_path` must be null or valid
+///   pointers to NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn analyze_clinvar_encrypted(
+    user_db_path: *const c_char,
+    user_db_key: *const c_char,
+    clinvar_db_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("analysis", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let user_db_key = unsafe { c_str(user_db_key, "user_db_key") }?;
+        let clinvar_db_path = unsafe { c_str(clinvar_db_path, "clinvar_db_path") }?;
+
+        analysis::analyze_clinvar_matches(user_db_path, Some(user_db_key), None, clinvar_db_path)
+    })
+}
+
+/// Change the encryption key of a genome database in place.
+///
+/// A null `old_key` means the database is currently plaintext; a null
+/// `new_key` removes encryption. Returns a result envelope whose `data` is
+/// `null` on success.
+///
+/// # Safety
+/// - `db_path` must be null or a valid pointer to a NUL-terminated string.
+/// - `old_key` and `new_key` must each be null or a valid pointer to a
+///   NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rekey_genome_database(
+    db_path: *const c_char,
+    old_key: *const c_char,
+    new_key: *const c_char,
+) -> *mut c_char {
+    ffi_result("rekey", || {
+        let db_path = unsafe { c_str(db_path, "db_path") }?;
+        let old_key = unsafe { optional_c_str(old_key, "old_key") }?;
+        let new_key = unsafe { optional_c_str(new_key, "new_key") }?;
+
+        database::rekey_genome_database(Path::new(db_path), old_key, new_key)
+    })
+}
+
+/// Write a decrypted copy of an encrypted genome database to `output_path`,
+/// e.g. for export to other tools. Returns a result envelope whose `data` is
+/// `null` on success.
+///
+/// # Safety
+/// - `db_path`, `key` and `output_path` must be null or valid pointers to
+///   NUL-terminated strings.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn export_decrypted_genome_database(
+    db_path: *const c_char,
+    key: *const c_char,
+    output_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("decrypted export", || {
+        let db_path = unsafe { c_str(db_path, "db_path") }?;
+        let key = unsafe { c_str(key, "key") }?;
+        let output_path = unsafe { c_str(output_path, "output_path") }?;
+
+        database::export_decrypted_genome_database(Path::new(db_path), key, Path::new(output_path))
+    })
+}
+
+/// Import a genome file into a genome database, creating the database if it
+/// does not exist. Several genomes (e.g. a household) can share one database.
+///
+/// The input file is content-hashed; if it was imported before, a non-zero
+/// `replace` re-imports it in place, otherwise it is skipped. `person` and
+/// `key` may be null (no label / plaintext database).
+///
+/// `progress` may be null; otherwise it is called with `context` while the
+/// file is parsed and inserted, and can cancel the import (see
+/// `BiovaultProgressCallback`). A cancelled import leaves the database as it was.
+///
+/// Returns a result envelope whose `data` describes the outcome.
+///
+/// # Safety
+/// - `input_path`, `db_path` and `custom_name` must be null or valid pointers
+///   to NUL-terminated strings.
+/// - `person` and `key` must each be null or a valid pointer to a
+///   NUL-terminated string.
+/// - `progress` is called on the calling thread and must be safe to call with
+///   `context` until this function returns.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn import_genome_file(
+    input_path: *const c_char,
+    db_path: *const c_char,
+    custom_name: *const c_char,
+    person: *const c_char,
+    key: *const c_char,
+    replace: i32,
+    progress: BiovaultProgressCallback,
+    context: *mut c_void,
+) -> *mut c_char {
+    ffi_result("genome import", || {
+        let input_path = unsafe { c_str(input_path, "input_path") }?;
+        let db_path = unsafe { c_str(db_path, "db_path") }?;
+        let custom_name = unsafe { c_str(custom_name, "custom_name") }?;
+        let person = unsafe { optional_c_str(person, "person") }?;
+        let key = unsafe { optional_c_str(key, "key") }?;
+
+        let policy = if replace != 0 {
+            DuplicatePolicy::Replace
+        } else {
+            DuplicatePolicy::Skip
+        };
+
+        genomes::import_genome_with_progress(
+            input_path,
+            Path::new(db_path),
+            custom_name,
+            person,
+            key,
+            policy,
+            &mut c_progress(progress, context),
+        )
+    })
+}
+
+/// List the genomes stored in a genome database. `key` may be null for
+/// plaintext databases.
+///
+/// Returns a result envelope whose `data` is an array of genomes.
+///
+/// # Safety
+/// - `db_path` must be null or a valid pointer to a NUL-terminated string.
+/// - `key` must be null or a valid pointer to a NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn list_genomes(db_path: *const c_char, key: *const c_char) -> *mut c_char {
+    ffi_result("listing genomes", || {
+        let db_path = unsafe { c_str(db_path, "db_path") }?;
+        let key = unsafe { optional_c_str(key, "key") }?;
+
+        genomes::list_genomes(db_path, key)
+    })
+}
+
+/// Analyze one genome of a shared genome database against ClinVar.
+/// `user_db_key` may be null for plaintext databases. `progress` may be null;
+/// otherwise it is called with `context` per ClinVar lookup chunk and can
+/// cancel the analysis (see `BiovaultProgressCallback`).
+///
+/// Returns a result envelope whose `data` is the analysis result.
+///
+/// # Safety
+/// - `user_db_path` and `clinvar_db_path` must be null or valid pointers to
+///   NUL-terminated strings.
+/// - `user_db_key` must be null or a valid pointer to a NUL-terminated string.
+/// - `progress` is called on the calling thread and must be safe to call with
+///   `context` until this function returns.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn analyze_clinvar_genome(
+    user_db_path: *const c_char,
+    user_db_key: *const c_char,
+    genome_id: i64,
+    clinvar_db_path: *const c_char,
+    progress: BiovaultProgressCallback,
+    context: *mut c_void,
+) -> *mut c_char {
+    ffi_result("analysis", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let user_db_key = unsafe { optional_c_str(user_db_key, "user_db_key") }?;
+        let clinvar_db_path = unsafe { c_str(clinvar_db_path, "clinvar_db_path") }?;
+
+        analysis::analyze_clinvar_matches_with_progress(
+            user_db_path,
+            user_db_key,
+            Some(genome_id),
+            clinvar_db_path,
+            &mut c_progress(progress, context),
+        )
+    })
+}
+
+/// Export a genome to a standard file format.
+///
+/// `format` is one of `vcf`, `23andme` or `plink`. For PLINK, `output_path`
+/// is the prefix of the `.bed`/`.bim`/`.fam` files. `genome_id` of 0 selects
+/// the only genome in the database. `reference_db_path` points to a local
+/// reference allele database; it is required for VCF and optional for PLINK.
+/// `key` and `reference_db_path` may be null.
+///
+/// Returns a result envelope whose `data` is the export summary.
+///
+/// # Safety
+/// - `db_path`, `format` and `output_path` must be null or valid pointers to
+///   NUL-terminated strings.
+/// - `key` and `reference_db_path` must each be null or a valid pointer to a
+///   NUL-terminated string.
+/// - The returned pointer must be freed by calling `free_string` exactly once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn export_genome(
+    db_path: *const c_char,
+    key: *const c_char,
+    genome_id: i64,
+    format: *const c_char,
+    output_path: *const c_char,
+    reference_db_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("export", || {
+        let db_path = unsafe { c_str(db_path, "db_path") }?;
+        let key = unsafe { optional_c_str(key, "key") }?;
+        let format = unsafe { c_str(format, "format") }?;
+        let format = ExportFormat::from_name(format).ok_or_else(|| {
+            BiovaultError::InvalidArgument(format!("unknown export format {}", format))
+        })?;
+        let output_path = unsafe { c_str(output_path, "output_path") }?;
+        let reference_db_path = unsafe { optional_c_str(reference_db_path, "reference_db_path") }?;
+
+        export::export_genome(
+            db_path,
+            key,
+            optional_id(genome_id),
+            format,
+            output_path,
+            reference_db_path,
+        )
+    })
+}
+
+/// Export a stored analysis run as a GA4GH Phenopacket v2 document
+/// (`format` "phenopacket") or FHIR Genomics Reporting bundle ("fhir").
+/// Returns a result envelope whose `data` is the JSON document.
+///
+/// # Safety
+/// `user_db_path` and `format` must be null or valid, NUL-terminated C
+/// strings; `key` must be null or a valid C string. The returned string must
+/// be released with `free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn export_clinical_report(
+    user_db_path: *const c_char,
+    key: *const c_char,
+    run_id: i64,
+    format: *const c_char,
+) -> *mut c_char {
+    ffi_result("clinical export", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let key = unsafe { optional_c_str(key, "key") }?;
+        let format = unsafe { c_str(format, "format") }?;
+        let format = ClinicalFormat::from_name(format).ok_or_else(|| {
+            BiovaultError::InvalidArgument(format!("unknown clinical format {}", format))
+        })?;
+
+        clinical::export_analysis_run(user_db_path, key, run_id, format)
+    })
+}
+
+/// Write an offline findings report (`format` "html" or "pdf") for a genome
+/// from its stored ClinVar analysis. `genome_id` 0 selects the only genome and
+/// `run_id` 0 the genome's most recent run. Returns a result envelope whose
+/// `data` is `null` on success.
+///
+/// # Safety
+/// `user_db_path`, `format` and `output_path` must be null or valid,
+/// NUL-terminated C strings; `key` must be null or a valid C string. The
+/// returned string must be released with `free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn generate_report(
+    user_db_path: *const c_char,
+    key: *const c_char,
+    genome_id: i64,
+    run_id: i64,
+    format: *const c_char,
+    output_path: *const c_char,
+) -> *mut c_char {
+    ffi_result("report", || {
+        let user_db_path = unsafe { c_str(user_db_path, "user_db_path") }?;
+        let key = unsafe { optional_c_str(key, "key") }?;
+        let format = unsafe { c_str(format, "format") }?;
+        let format = ReportFormat::from_name(format).ok_or_else(|| {
+            BiovaultError::InvalidArgument(format!("unknown report format {}", format))
+        })?;
+        let output_path = unsafe { c_str(output_path, "output_path") }?;
+
+        report::write_report(
+            user_db_path,
+            key,
+            optional_id(genome_id),
+            optional_id(run_id),
+            format,
+            output_path,
+        )
+    })
+}
+
+/// Log callback for C hosts, e.g. forwarding to `os_log`. `level` is 1
+/// (error) to 5 (trace); `target` and `message` are NUL-terminated and only
+/// valid during the call. May be called from any thread.
+pub type BiovaultLogCallback = Option<
+    extern "C" fn(context: *mut c_void, level: i32, target: *const c_char, message: *const c_char),
+>;
+
+/// Forwards log events to a C callback
+struct CLogSink {
+    callback: extern "C" fn(*mut c_void, i32, *const c_char, *const c_char),
+    context: *mut c_void,
+}
+
+// The caller of `set_log_callback` guarantees `context` may be used from any thread
+unsafe impl Send for CLogSink {}
+unsafe impl Sync for CLogSink {}
+
+impl LogSink for CLogSink {
+    fn log(&self, level: LogLevel, target: &str, message: &str) {
+        let target = CString::new(target.replace('\0', "")).unwrap_or_default();
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        (self.callback)(
+            self.context,
+            level as i32,
+            target.as_ptr(),
+            message.as_ptr(),
+        );
+    }
+}
+
+/// Register a callback receiving log events up to `max_level` (1 = error,
+/// 5 = trace; 3 = info is a good default), replacing any previous one. A null
+/// callback turns logging off. Log events up to debug never contain rsIDs or
+/// genotypes.
+///
+/// # Safety
+/// `context` is passed back to `callback` as is, from any thread, until the
+/// callback is replaced; it must stay valid for that long.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn set_log_callback(
+    callback: BiovaultLogCallback,
+    context: *mut c_void,
+    max_level: i32,
+) {
+    let sink =
+        callback.map(|callback| Box::new(CLogSink { callback, context }) as Box<dyn LogSink>);
+    set_log_sink(sink, LogLevel::from_raw(max_level));
+}
+
+/// Borrow a required string argument. Null pointers and invalid UTF-8 are
+/// reported as `INVALID_ARGUMENT` naming the parameter.
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string that outlives `'a`.
+unsafe fn c_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, BiovaultError> {
+    if ptr.is_null() {
+        return Err(BiovaultError::InvalidArgument(format!("{} is null", name)));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| BiovaultError::InvalidArgument(format!("{} is not valid UTF-8", name)))
+}
+
+/// Borrow an optional string argument; null means `None`
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string that outlives `'a`.
+unsafe fn optional_c_str<'a>(
+    ptr: *const c_char,
+    name: &str,
+) -> Result<Option<&'a str>, BiovaultError> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        unsafe { c_str(ptr, name) }.map(Some)
+    }
+}
+
+/// Progress callback for long-running C calls. `phase` is a static,
+/// NUL-terminated phase name such as `parsing` or `clinvar_lookup`, and
+/// `total` is an estimate that is 0 when unknown. Returning non-zero cancels
+/// the operation, which then fails with `CANCELLED`. May be null.
+pub type BiovaultProgressCallback = Option<
+    extern "C" fn(context: *mut c_void, phase: *const c_char, processed: u64, total: u64) -> i32,
+>;
+
+/// Adapt an optional C progress callback to `Progress`
+fn c_progress<'a>(callback: BiovaultProgressCallback, context: *mut c_void) -> Progress<'a> {
+    let Some(callback) = callback else {
+        return Progress::none();
+    };
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    Progress::new(move |update: ProgressUpdate| {
+        let phase = update.phase.c_name().as_ptr();
+        if callback(context, phase, update.processed, update.total) != 0 {
+            cancel.cancel();
+        }
+    })
+    .with_cancellation(token)
+}
+
+/// Ids of 0 (or less) select the default genome or run
+fn optional_id(id: i64) -> Option<i64> {
+    if id > 0 { Some(id) } else { None }
+}
+
+/// Run an FFI operation and hand its outcome to C as a JSON result envelope:
+///
+/// - `{"ok": true, "data": ...}` on success
+/// - `{"ok": false, "error": {"code": ..., "message": ..., "context": ...}}`
+///   on failure, where `code` is one of the stable `error_codes`
+///
+/// The envelope is always returned, so callers never see a null pointer.
+/// Panics are caught here and reported as `INTERNAL_ERROR` instead of
+/// unwinding into the caller, which would abort the app.
+fn ffi_result<T: serde::Serialize>(
+    operation: &str,
+    f: impl FnOnce() -> Result<T, BiovaultError>,
+) -> *mut c_char {
+    let envelope =
+        match BiovaultError::catch_panic(f).and_then(|data| Ok(serde_json::to_value(data)?)) {
+            Ok(data) => serde_json::json!({ "ok": true, "data": data }),
+            Err(e) => {
+                logging::log_failure(operation, &e);
+                serde_json::json!({ "ok": false, "error": e.report() })
+            }
+        };
+    json_to_c_string(&envelope)
+}
+
+/// Serialize a value to JSON and hand it to C as a newly-allocated string
+fn json_to_c_string<T: serde::Serialize>(value: &T) -> *mut c_char {
+    match serde_json::to_string(value) {
+        Ok(json) => match CString::new(json) {
+            Ok(c_string) => c_string.into_raw(),
+            Err(e) => {
+                error!("failed to create CString: {}", e);
+                std::ptr::null_mut()
+            }
+        },
+        Err(e) => {
+            error!("failed to serialize result: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Public, safe Rust API to process a 23andMe file and create an SQLite DB.
+/// Returns the full path to the created database file.
+pub fn process_23andme(
+    input_path: &str,
+    custom_name: &str,
+    output_dir: &str,
+) -> Result<String, BiovaultError> {
+    process_file_internal(input_path, custom_name, output_dir, None)
+}
+
+/// Public, safe Rust API to process a 23andMe file into an SQLite database
+/// encrypted with `key`. Returns the full path to the created database file.
+pub fn process_23andme_encrypted(
+    input_path: &str,
+    custom_name: &str,
+    output_dir: &str,
+    key: &str,
+) -> Result<String, BiovaultError> {
+    process_file_internal(input_path, custom_name, output_dir, Some(key))
+}
+
+/// Public, safe Rust API for ClinVar analysis
+pub fn analyze_clinvar_safe(
+    user_db_path: &str,
+    clinvar_db_path: &str,
+) -> Result<analysis::AnalysisResult, BiovaultError> {
+    analysis::analyze_clinvar_matches(user_db_path, None, None, clinvar_db_path)
+}
+
+/// Public, safe Rust API to diff ClinVar analysis results between two
+/// ClinVar database releases
+pub fn diff_clinvar_releases_safe(
+    user_db_path: &str,
+    old_clinvar_db_path: &str,
+    new_clinvar_db_path: &str,
+) -> Result<ClinVarDiff, BiovaultError> {
+    diff::diff_clinvar_releases(
+        user_db_path,
+        None,
+        None,
+        old_clinvar_db_path,
+        new_clinvar_db_path,
+    )
+}
+
+/// Public, safe Rust API to re-run ClinVar analysis and diff it against the
+/// last stored run. Returns `None` on the first run.
+pub fn reanalyze_clinvar_safe(
+    user_db_path: &str,
+    clinvar_db_path: &str,
+) -> Result<Option<ClinVarDiff>, BiovaultError> {
+    diff::reanalyze_clinvar(user_db_path, None, None, clinvar_db_path)
+}
+
+/// Public, safe Rust API to run ClinVar analysis and store the result with
+/// its provenance in the user database
+pub fn analyze_and_store_clinvar_safe(
+    user_db_path: &str,
+    clinvar_db_path: &str,
+) -> Result<StoredAnalysis, BiovaultError> {
+    history::analyze_and_store_clinvar(user_db_path, None, None, clinvar_db_path)
+}
+
+/// Public, safe Rust API to list stored analysis runs, newest first
+pub fn list_analysis_runs_safe(user_db_path: &str) -> Result<Vec<AnalysisRun>, BiovaultError> {
+    history::list_analysis_runs(user_db_path, None)
+}
+
+/// Public, safe Rust API to load a stored analysis run
+pub fn load_analysis_run_safe(
+    user_db_path: &str,
+    run_id: i64,
+) -> Result<Option<StoredAnalysis>, BiovaultError> {
+    history::load_analysis_run(user_db_path, None, run_id)
+}
+
+/// Public, safe Rust API to delete a stored analysis run.
+/// Returns false if the run did not exist.
+pub fn delete_analysis_run_safe(user_db_path: &str, run_id: i64) -> Result<bool, BiovaultError> {
+    history::delete_analysis_run(user_db_path, None, run_id)
+}
+
+/// Public, safe Rust API to open a user genome database. The file is checked
+/// to be a genome database and migrated to the current schema version.
+/// `key` unlocks encrypted databases.
+pub fn open_genome_database(
+    db_path: &str,
+    key: Option<&str>,
+) -> Result<rusqlite::Connection, BiovaultError> {
+    database::open_genome_database(Path::new(db_path), key)
+}
+
+/// Public, safe Rust API for ClinVar analysis of an encrypted genome database.
+/// The ClinVar database itself is never encrypted.
+pub fn analyze_clinvar_encrypted_safe(
+    user_db_path: &str,
+    user_db_key: &str,
+    clinvar_db_path: &str,
+) -> Result<analysis::AnalysisResult, BiovaultError> {
+    analysis::analyze_clinvar_matches(user_db_path, Some(user_db_key), None, clinvar_db_path)
+}
+
+/// Public, safe Rust API to change the encryption of a genome database in
+/// place. `None` means plaintext for either key.
+pub fn rekey_genome_database_safe(
+    db_path: &str,
+    old_key: Option<&str>,
+    new_key: Option<&str>,
+) -> Result<(), BiovaultError> {
+    database::rekey_genome_database(Path::new(db_path), old_key, new_key)
+}
+
+/// Public, safe Rust API to write a decrypted copy of an encrypted genome
+/// database to `output_path`
+pub fn export_decrypted_genome_database_safe(
+    db_path: &str,
+    key: &str,
+    output_path: &str,
+) -> Result<(), BiovaultError> {
+    database::export_decrypted_genome_database(Path::new(db_path), key, Path::new(output_path))
+}
+
+/// Public, safe Rust API to import a genome file into a (possibly shared)
+/// genome database, creating it if needed. Re-imports of the same file are
+/// skipped or replaced according to `policy`.
+pub fn import_genome_safe(
+    input_path: &str,
+    db_path: &str,
+    custom_name: &str,
+    person: Option<&str>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+) -> Result<ImportOutcome, BiovaultError> {
+    genomes::import_genome(
+        input_path,
+        Path::new(db_path),
+        custom_name,
+        person,
+        key,
+        policy,
+    )
+}
+
+/// `import_genome_safe` with progress reporting and cancellation. A
+/// cancelled import leaves the database as it was.
+pub fn import_genome_with_progress_safe(
+    input_path: &str,
+    db_path: &str,
+    custom_name: &str,
+    person: Option<&str>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+    progress: &mut Progress,
+) -> Result<ImportOutcome, BiovaultError> {
+    genomes::import_genome_with_progress(
+        input_path,
+        Path::new(db_path),
+        custom_name,
+        person,
+        key,
+        policy,
+        progress,
+    )
+}
+
+/// Public, safe Rust API to import a genome file held in memory into a
+/// database in any `Storage`, creating it if needed
+pub fn import_genome_bytes_safe(
+    file: GenomeFile,
+    storage: &Storage,
+    custom_name: &str,
+    person: Option<&str>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+    progress: &mut Progress,
+) -> Result<ImportOutcome, BiovaultError> {
+    genomes::import_genome_bytes(file, storage, custom_name, person, key, policy, progress)
+}
+
+/// Public, safe Rust API to import many genome files in parallel, into one
+/// database each or into a shared cohort database. `workers` of `None` uses
+/// one thread per CPU.
+#[cfg(not(target_family = "wasm"))]
+pub fn import_batch_safe(
+    entries: &[BatchEntry],
+    target: &BatchTarget,
+    workers: Option<usize>,
+    key: Option<&str>,
+    policy: DuplicatePolicy,
+) -> Result<BatchSummary, BiovaultError> {
+    let workers = workers.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+    batch::import_batch(entries, target, workers, key, policy)
+}
+
+/// Public, safe Rust API to list the genome files in a directory for
+/// `import_batch_safe`
+#[cfg(not(target_family = "wasm"))]
+pub fn batch_entries_from_directory_safe(dir: &str) -> Result<Vec<BatchEntry>, BiovaultError> {
+    batch::entries_from_directory(Path::new(dir))
+}
+
+/// Public, safe Rust API to read a `path,name,format` CSV manifest for
+/// `import_batch_safe`
+#[cfg(not(target_family = "wasm"))]
+pub fn batch_entries_from_manifest_safe(
+    manifest_path: &str,
+) -> Result<Vec<BatchEntry>, BiovaultError> {
+    batch::entries_from_manifest(Path::new(manifest_path))
+}
+
+/// Public, safe Rust API to align every genome in `db_paths` into one cohort
+/// genotype matrix with per-marker allele frequencies, missingness and
+/// Hardy-Weinberg p-values
+pub fn load_cohort_safe(
+    db_paths: &[&str],
+    key: Option<&str>,
+    options: &CohortOptions,
+) -> Result<Cohort, BiovaultError> {
+    let db_paths: Vec<_> = db_paths.iter().map(std::path::PathBuf::from).collect();
+    cohort::load_cohort(&db_paths, key, options)
+}
+
+/// Public, safe Rust API to set the differential privacy budget of a
+/// genome database
+#[cfg(not(target_family = "wasm"))]
+pub fn set_privacy_budget_safe(
+    db_path: &str,
+    key: Option<&str>,
+    epsilon: f64,
+    delta: f64,
+) -> Result<PrivacyBudget, BiovaultError> {
+    privacy::set_privacy_budget(Path::new(db_path), key, epsilon, delta)
+}
+
+/// Public, safe Rust API to read a genome database's privacy budget and spend
+#[cfg(not(target_family = "wasm"))]
+pub fn privacy_budget_safe(
+    db_path: &str,
+    key: Option<&str>,
+) -> Result<PrivacyBudget, BiovaultError> {
+    privacy::privacy_budget(Path::new(db_path), key)
+}
+
+/// Public, safe Rust API for a differentially private aggregate query over
+/// every genome in a database, released to `recipient`. Fails with
+/// `CONSENT_REQUIRED` unless the owner granted `recipient` aggregate access,
+/// and with `PRIVACY_BUDGET_EXHAUSTED` once the database's budget cannot cover
+/// `epsilon`.
+#[cfg(not(target_family = "wasm"))]
+pub fn run_dp_query_safe(
+    db_path: &str,
+    key: Option<&str>,
+    recipient: &str,
+    query: &DpQuery,
+    mechanism: Mechanism,
+    epsilon: f64,
+) -> Result<DpRelease, BiovaultError> {
+    privacy::run_dp_query(
+        Path::new(db_path),
+        key,
+        recipient,
+        query,
+        mechanism,
+        epsilon,
+    )
+}
+
+/// Public, safe Rust API to answer the research jobs waiting in a SyftBox
+/// datasite's inbox. `allow_list_path` is a JSON `AllowList`; keep it outside
+/// the synced folder. Each job's requester is the inbox folder it arrived in,
+/// which only that requester can write to.
+#[cfg(not(target_family = "wasm"))]
+pub fn process_datasite_jobs_safe(
+    config: &DatasiteConfig,
+    allow_list_path: &str,
+) -> Result<Vec<JobOutcome>, BiovaultError> {
+    let allow_list = datasite::load_allow_list(Path::new(allow_list_path))?;
+    datasite::process_jobs(config, &allow_list)
+}
+
+/// Public, safe Rust API to send a research job to the requester's folder of
+/// another datasite's inbox
+#[cfg(not(target_family = "wasm"))]
+pub fn submit_datasite_job_safe(
+    syftbox_dir: &str,
+    owner_email: &str,
+    job: &ResearchJob,
+) -> Result<String, BiovaultError> {
+    datasite::submit_job(Path::new(syftbox_dir), owner_email, job)
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Public, safe Rust API to add a consent grant to a genome database.
+/// `purpose` of `None` covers every purpose; `expires_at` is RFC 3339.
+pub fn grant_consent_safe(
+    db_path: &str,
+    key: Option<&str>,
+    purpose: Option<Purpose>,
+    recipient: &str,
+    granularity: Granularity,
+    expires_at: Option<&str>,
+) -> Result<ConsentGrant, BiovaultError> {
+    consent::grant_consent(
+        Path::new(db_path),
+        key,
+        purpose,
+        recipient,
+        granularity,
+        expires_at,
+    )
+}
+
+/// Public, safe Rust API to revoke a consent grant. Returns false if it does
+/// not exist or was already revoked.
+pub fn revoke_consent_safe(
+    db_path: &str,
+    key: Option<&str>,
+    grant_id: i64,
+) -> Result<bool, BiovaultError> {
+    consent::revoke_consent(Path::new(db_path), key, grant_id)
+}
+
+/// Public, safe Rust API to list a genome database's consent grants
+pub fn list_consent_grants_safe(
+    db_path: &str,
+    key: Option<&str>,
+) -> Result<Vec<ConsentGrant>, BiovaultError> {
+    consent::list_consent_grants(Path::new(db_path), key)
+}
+
+/// Public, safe Rust API to list recorded consent decisions, newest first
+pub fn list_consent_audit_safe(
+    db_path: &str,
+    key: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<ConsentDecision>, BiovaultError> {
+    consent::list_consent_audit(Path::new(db_path), key, limit)
+}
+
+/// Public, safe Rust API to list a genome database's access log, newest first
+pub fn list_access_log_safe(
+    db_path: &str,
+    key: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<AccessLogEntry>, BiovaultError> {
+    access_log::list_access_log(Path::new(db_path), key, limit)
+}
+
+/// Public, safe Rust API to check a genome database's access log for edited
+/// or removed entries. `anchor` is a head hash from an earlier verification.
+pub fn verify_access_log_safe(
+    db_path: &str,
+    key: Option<&str>,
+    anchor: Option<&str>,
+) -> Result<AccessLogVerification, BiovaultError> {
+    access_log::verify_access_log(Path::new(db_path), key, anchor)
+}
+
+/// Public, safe Rust API to securely delete a genome. Removes the database
+/// file itself when it was the last genome; fails with `DELETION_INCOMPLETE`
+/// if any of its data can still be found.
+#[cfg(not(target_family = "wasm"))]
+pub fn delete_genome_safe(
+    db_path: &str,
+    key: Option<&str>,
+    genome_id: Option<i64>,
+) -> Result<DeletionReport, BiovaultError> {
+    deletion::delete_genome(Path::new(db_path), key, genome_id)
+}
+
+/// Public, safe Rust API to strip loci from a genome database, in place or
+/// into a copy at `output_path`. Gene loci need `clinvar_db_path`.
+#[cfg(not(target_family = "wasm"))]
+pub fn redact_loci_safe(
+    db_path: &str,
+    key: Option<&str>,
+    loci: &[Locus],
+    clinvar_db_path: Option<&str>,
+    output_path: Option<&str>,
+) -> Result<RedactionSummary, BiovaultError> {
+    deletion::redact_loci(
+        Path::new(db_path),
+        key,
+        loci,
+        clinvar_db_path.map(Path::new),
+        output_path.map(Path::new),
+    )
+}
+
+/// Public, safe Rust API to write a genome database, with its analysis
+/// history, into a bundle encrypted with `passphrase`
+#[cfg(not(target_family = "wasm"))]
+pub fn export_bundle_safe(
+    db_path: &str,
+    key: Option<&str>,
+    output_path: &str,
+    passphrase: &str,
+) -> Result<BundleManifest, BiovaultError> {
+    bundle::export_bundle(Path::new(db_path), key, Path::new(output_path), passphrase)
+}
+
+/// Public, safe Rust API to restore a bundle into a new genome database,
+/// encrypted with `key` if given. Fails with `WRONG_KEY` for a wrong
+/// passphrase or a modified file.
+#[cfg(not(target_family = "wasm"))]
+pub fn import_bundle_safe(
+    bundle_path: &str,
+    passphrase: &str,
+    db_path: &str,
+    key: Option<&str>,
+) -> Result<BundleManifest, BiovaultError> {
+    bundle::import_bundle(Path::new(bundle_path), passphrase, Path::new(db_path), key)
+}
+
+/// Public, safe Rust API to page through the stored variants of a genome
+/// database by rsID, region, gene or genotype
+pub fn query_variants_safe(
+    db_path: &str,
+    key: Option<&str>,
+    query: &VariantQuery,
+) -> Result<VariantPage, BiovaultError> {
+    query::query_variants(Path::new(db_path), key, query)
+}
+
+/// Public, safe Rust API to list the genomes stored in a genome database
+pub fn list_genomes_safe(
+    db_path: &str,
+    key: Option<&str>,
+) -> Result<Vec<GenomeRecord>, BiovaultError> {
+    genomes::list_genomes(db_path, key)
+}
+
+/// Public, safe Rust API for ClinVar analysis of one genome in a shared
+/// genome database
+pub fn analyze_clinvar_genome_safe(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: i64,
+    clinvar_db_path: &str,
+) -> Result<analysis::AnalysisResult, BiovaultError> {
+    analysis::analyze_clinvar_matches(user_db_path, user_db_key, Some(genome_id), clinvar_db_path)
+}
+
+/// `analyze_clinvar_genome_safe` with progress reporting and cancellation
+pub fn analyze_clinvar_genome_with_progress_safe(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: i64,
+    clinvar_db_path: &str,
+    progress: &mut Progress,
+) -> Result<analysis::AnalysisResult, BiovaultError> {
+    analysis::analyze_clinvar_matches_with_progress(
+        user_db_path,
+        user_db_key,
+        Some(genome_id),
+        clinvar_db_path,
+        progress,
+    )
+}
+
+/// Public, safe Rust API for ClinVar analysis of databases in any `Storage`
+pub fn analyze_clinvar_storage_safe(
+    user_db: &Storage,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    clinvar_db: &Storage,
+    progress: &mut Progress,
+) -> Result<analysis::AnalysisResult, BiovaultError> {
+    analysis::analyze_clinvar_storage(user_db, user_db_key, genome_id, clinvar_db, progress)
+}
+
+/// Public, safe Rust API to export a genome as VCF, 23andMe TSV or PLINK.
+/// For PLINK `output_path` is the fileset prefix.
+pub fn export_genome_safe(
+    db_path: &str,
+    key: Option<&str>,
+    genome_id: Option<i64>,
+    format: ExportFormat,
+    output_path: &str,
+    reference_db_path: Option<&str>,
+) -> Result<ExportSummary, BiovaultError> {
+    export::export_genome(
+        db_path,
+        key,
+        genome_id,
+        format,
+        output_path,
+        reference_db_path,
+    )
+}
+
+/// Public, safe Rust API to export a stored analysis run as a GA4GH
+/// Phenopacket v2 document or FHIR Genomics Reporting bundle
+pub fn export_clinical_report_safe(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    run_id: i64,
+    format: ClinicalFormat,
+) -> Result<serde_json::Value, BiovaultError> {
+    clinical::export_analysis_run(user_db_path, user_db_key, run_id, format)
+}
+
+/// Public, safe Rust API to write an HTML or PDF findings report from a
+/// genome's stored ClinVar analysis (the latest one unless `run_id` is given)
+pub fn generate_report_safe(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    run_id: Option<i64>,
+    format: ReportFormat,
+    output_path: &str,
+) -> Result<(), BiovaultError> {
+    report::write_report(
+        user_db_path,
+        user_db_key,
+        genome_id,
+        run_id,
+        format,
+        output_path,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Take ownership of a string this library returned and parse it
+    fn envelope(ptr: *mut c_char) -> serde_json::Value {
+        assert!(!ptr.is_null());
+        let json = unsafe { CString::from_raw(ptr) }.into_string().unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn c_str_rejects_null_and_invalid_utf8() {
+        let error = unsafe { c_str(std::ptr::null(), "db_path") }.unwrap_err();
+        assert_eq!(error.code(), error_codes::INVALID_ARGUMENT);
+        assert_eq!(error.to_string(), "Invalid argument: db_path is null");
+
+        let invalid = c"\xff\xfe";
+        let error = unsafe { c_str(invalid.as_ptr(), "key") }.unwrap_err();
+        assert_eq!(error.code(), error_codes::INVALID_ARGUMENT);
+        assert!(error.to_string().contains("key is not valid UTF-8"));
+
+        let valid = c"genome.sqlite";
+        assert_eq!(
+            unsafe { c_str(valid.as_ptr(), "db_path") }.unwrap(),
+            "genome.sqlite"
+        );
+        assert_eq!(
+            unsafe { optional_c_str(std::ptr::null(), "key") }.unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn ffi_result_wraps_data_and_errors() {
+        let ok = envelope(ffi_result("adding", || Ok(rust_add(2, 3))));
+        assert_eq!(ok, serde_json::json!({ "ok": true, "data": 5 }));
+
+        let failed = envelope(ffi_result::<()>("failing", || {
+            Err(BiovaultError::NotFound("Genome 7".to_string()))
+        }));
+        assert_eq!(failed["ok"], false);
+        assert_eq!(failed["error"]["code"], error_codes::NOT_FOUND);
+    }
+
+    /// Keeps the events of operations whose name contains `operation`
+    struct CapturingSink {
+        operation: &'static str,
+        events: std::sync::Arc<std::sync::Mutex<Vec<(LogLevel, String)>>>,
+    }
+
+    impl LogSink for CapturingSink {
+        fn log(&self, level: LogLevel, _target: &str, message: &str) {
+            if message.contains(self.operation) {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push((level, message.to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn ffi_failures_log_user_data_only_at_trace() {
+        let events = std::sync::Arc::default();
+        set_log_sink(
+            Some(Box::new(CapturingSink {
+                operation: "redacting loci",
+                events: std::sync::Arc::clone(&events),
+            })),
+            LogLevel::Trace,
+        );
+        envelope(ffi_result::<()>("redacting loci", || {
+            Err(BiovaultError::InvalidArgument(
+                "'rs429358 CT' is not a locus".to_string(),
+            ))
+        }));
+        set_log_sink(None, LogLevel::Info);
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|(level, _)| *level <= LogLevel::Debug)
+                .map(|(level, message)| (*level, message.as_str()))
+                .collect::<Vec<_>>(),
+            [(LogLevel::Warn, "redacting loci failed: INVALID_ARGUMENT")]
+        );
+        assert!(
+            events
+                .iter()
+                .any(|(level, message)| *level == LogLevel::Trace && message.contains("rs429358"))
+        );
+    }
+
+    #[test]
+    fn ffi_result_reports_panics_instead_of_unwinding() {
+        let panicked = envelope(ffi_result::<()>("panicking", || panic!("boom")));
+        assert_eq!(panicked["ok"], false);
+        assert_eq!(panicked["error"]["code"], error_codes::INTERNAL_ERROR);
+        assert!(
+            panicked["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("boom")
+        );
+
+        let formatted = BiovaultError::catch_panic::<()>(|| panic!("chunk {} of {}", 3, 4));
+        assert!(
+            matches!(formatted, Err(BiovaultError::Panic(message)) if message == "chunk 3 of 4")
+        );
+    }
+
+    #[test]
+    fn c_entrypoints_report_bad_arguments() {
+        let null_path = envelope(unsafe { list_genomes(std::ptr::null(), std::ptr::null()) });
+        assert_eq!(null_path["error"]["code"], error_codes::INVALID_ARGUMENT);
+
+        let invalid = c"\xc3\x28";
+        let bad_key =
+            envelope(unsafe { list_genomes(c"genome.sqlite".as_ptr(), invalid.as_ptr()) });
+        assert_eq!(bad_key["error"]["code"], error_codes::INVALID_ARGUMENT);
+        assert!(
+            bad_key["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("key")
+        );
+    }
 }
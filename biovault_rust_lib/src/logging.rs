@@ -0,0 +1,195 @@
+//! Logging facade.
+//!
+//! The library logs through `tracing`, with a span around each import and
+//! analysis. Events go to the `LogSink` the host registers with
+//! `set_log_sink` (or `set_log_callback` / `setLogSink` over FFI); nothing is
+//! logged until one is registered. Rust hosts that install their own
+//! `tracing` subscriber first receive the events there instead.
+//!
+//! Events carry counts, ids and file names, never rsIDs or genotypes, up to
+//! `Debug`. Error messages can quote input such as genotype lines, rsIDs or
+//! gene names, so failures are logged with their error code only and the
+//! message is left to `Trace`.
+
+use crate::error::BiovaultError;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Once, RwLock};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+/// Severity of a log event. The C and JNI APIs use the numeric values.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, uniffi::Enum,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    /// Level for a numeric value, clamped to `Error..=Trace`
+    pub fn from_raw(level: i32) -> LogLevel {
+        match level {
+            i32::MIN..=1 => LogLevel::Error,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Info,
+            4 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+
+    /// Level for a name such as `info`, as used by `BIOVAULT_LOG`
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+
+    fn from_tracing(level: &Level) -> LogLevel {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            Level::TRACE => LogLevel::Trace,
+        }
+    }
+}
+
+/// Receives the library's log events. `target` is the Rust module path and
+/// `message` is prefixed with the enclosing spans, e.g.
+/// `import{genome_id=2}: parsed 612000 variants`.
+pub trait LogSink: Send + Sync {
+    fn log(&self, level: LogLevel, target: &str, message: &str);
+}
+
+static SINK: RwLock<Option<Box<dyn LogSink>>> = RwLock::new(None);
+static MAX_LEVEL: AtomicI32 = AtomicI32::new(LogLevel::Info as i32);
+static INSTALL: Once = Once::new();
+
+/// Register the sink receiving log events up to `max_level`, replacing any
+/// previous one. `None` turns logging off.
+pub fn set_log_sink(sink: Option<Box<dyn LogSink>>, max_level: LogLevel) {
+    INSTALL.call_once(|| {
+        let _ = tracing::subscriber::set_global_default(Registry::default().with(SinkLayer));
+    });
+
+    MAX_LEVEL.store(max_level as i32, Ordering::Relaxed);
+    match SINK.write() {
+        Ok(mut current) => *current = sink,
+        Err(poisoned) => *poisoned.into_inner() = sink,
+    }
+    // Callsites cache whether they are enabled; make them ask again
+    tracing::callsite::rebuild_interest_cache();
+}
+
+/// Log that `operation` failed: its error code at `Warn`, the message, which
+/// may quote user data, at `Trace`
+pub(crate) fn log_failure(operation: &str, error: &BiovaultError) {
+    tracing::warn!("{} failed: {}", operation, error.code());
+    tracing::trace!("{} failed: {}", operation, error);
+}
+
+/// Forwards events to the registered `LogSink`
+struct SinkLayer;
+
+/// Formatted fields of a span, kept in its extensions
+struct SpanFields(String);
+
+impl<S> Layer<S> for SinkLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        LogLevel::from_tracing(metadata.level()) as i32 <= MAX_LEVEL.load(Ordering::Relaxed)
+            && SINK.read().is_ok_and(|sink| sink.is_some())
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = FieldFormatter::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields.fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut message = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                message.push_str(span.name());
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>()
+                    && !fields.is_empty()
+                {
+                    let _ = write!(message, "{{{}}}", fields);
+                }
+                message.push_str(": ");
+            }
+        }
+
+        let mut fields = FieldFormatter::default();
+        event.record(&mut fields);
+        message.push_str(&fields.message);
+        if !fields.fields.is_empty() {
+            if !fields.message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(&fields.fields);
+        }
+
+        let metadata = event.metadata();
+        if let Ok(sink) = SINK.read()
+            && let Some(sink) = sink.as_ref()
+        {
+            sink.log(
+                LogLevel::from_tracing(metadata.level()),
+                metadata.target(),
+                &message,
+            );
+        }
+    }
+}
+
+/// Formats a span's or event's fields as `message key=value ...`
+#[derive(Default)]
+struct FieldFormatter {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldFormatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &format_args!("{}", value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+            return;
+        }
+        if !self.fields.is_empty() {
+            self.fields.push(' ');
+        }
+        let _ = write!(self.fields, "{}={:?}", field.name(), value);
+    }
+}
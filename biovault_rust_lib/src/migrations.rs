@@ -0,0 +1,758 @@
+use crate::encoding::SEEDED_GENOTYPES;
+use crate::error::BiovaultError;
+use rusqlite::{Connection, params};
+use tracing::info;
+
+/// Schema version written by this library into `PRAGMA user_version`
+pub const SCHEMA_VERSION: i32 = 10;
+
+/// First version with the compact `variants` layout
+const COMPACT_LAYOUT_VERSION: i32 = 3;
+
+/// SQL for the text chromosome `v.chromosome` without case or `chr` prefix
+const CHROMOSOME_NAME_SQL: &str = "replace(upper(trim(v.chromosome)), 'CHR', '')";
+
+/// SQL for the code of the text chromosome `v.chromosome`: the mapping of
+/// `encoding::chromosome_code`, with 0 for anything it does not recognise
+fn chromosome_code_sql() -> String {
+    format!(
+        "CASE {name}
+             WHEN 'X' THEN 23
+             WHEN 'Y' THEN 24
+             WHEN 'XY' THEN 25
+             WHEN 'PAR' THEN 25
+             WHEN 'MT' THEN 26
+             WHEN 'M' THEN 26
+             ELSE CASE
+                 WHEN {name} NOT GLOB '*[^0-9]*'
+                      AND CAST({name} AS INTEGER) BETWEEN 1 AND 26
+                 THEN CAST({name} AS INTEGER)
+                 ELSE 0
+             END
+         END",
+        name = CHROMOSOME_NAME_SQL
+    )
+}
+
+/// A single forward-only schema upgrade
+struct Migration {
+    version: i32,
+    description: &'static str,
+    up: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Ordered list of migrations. Never edit a released migration; add a new one.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "genome metadata and variants",
+        up: migrate_v1,
+    },
+    Migration {
+        version: 2,
+        description: "analysis runs and findings",
+        up: migrate_v2,
+    },
+    Migration {
+        version: 3,
+        description: "compact variant storage",
+        up: migrate_v3,
+    },
+    Migration {
+        version: 4,
+        description: "multiple genomes per database",
+        up: migrate_v4,
+    },
+    Migration {
+        version: 5,
+        description: "privacy budget ledger",
+        up: migrate_v5,
+    },
+    Migration {
+        version: 6,
+        description: "consent manifest and audit",
+        up: migrate_v6,
+    },
+    Migration {
+        version: 7,
+        description: "hash-chained access log",
+        up: migrate_v7,
+    },
+    Migration {
+        version: 8,
+        description: "variants indexed by genome",
+        up: migrate_v8,
+    },
+    Migration {
+        version: 9,
+        description: "23andMe internal IDs in the decoded view",
+        up: migrate_v9,
+    },
+    Migration {
+        version: 10,
+        description: "access log head guards",
+        up: migrate_v10,
+    },
+];
+
+/// Version 1 is the layout written by the first app releases, which did not
+/// set `user_version`. `IF NOT EXISTS` lets those files pass through unchanged.
+fn migrate_v1(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS genome_metadata (
+            id INTEGER PRIMARY KEY,
+            file_name TEXT NOT NULL,
+            source_format TEXT NOT NULL,
+            total_variants INTEGER NOT NULL,
+            rsid_count INTEGER NOT NULL,
+            assembly TEXT,
+            upload_date TEXT NOT NULL,
+            db_name TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS variants (
+            id INTEGER PRIMARY KEY,
+            file_id INTEGER NOT NULL,
+            rsid TEXT,
+            chromosome TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            genotype TEXT NOT NULL,
+            source_format TEXT NOT NULL,
+            FOREIGN KEY(file_id) REFERENCES genome_metadata(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_variants_rsid ON variants (rsid);
+        CREATE INDEX IF NOT EXISTS idx_variants_chr_pos ON variants (chromosome, position);",
+    )
+}
+
+/// Persisted analysis history. Databases that already have the early
+/// `analysis_runs` layout get the provenance columns added.
+fn migrate_v2(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS analysis_runs (
+            id INTEGER PRIMARY KEY,
+            analysis_type TEXT NOT NULL,
+            parameters TEXT NOT NULL,
+            clinvar_release TEXT NOT NULL,
+            clinvar_checksum TEXT NOT NULL,
+            library_version TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            rsids_searched INTEGER NOT NULL,
+            matches_found INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS analysis_findings (
+            id INTEGER PRIMARY KEY,
+            run_id INTEGER NOT NULL,
+            rsid TEXT NOT NULL,
+            chrom TEXT NOT NULL,
+            pos INTEGER NOT NULL,
+            ref_allele TEXT NOT NULL,
+            alt_allele TEXT NOT NULL,
+            gene TEXT NOT NULL,
+            clnsig TEXT NOT NULL,
+            clnrevstat TEXT NOT NULL,
+            condition TEXT NOT NULL,
+            user_genotype TEXT,
+            FOREIGN KEY(run_id) REFERENCES analysis_runs(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_analysis_findings_run ON analysis_findings (run_id);",
+    )?;
+
+    add_column_if_missing(
+        conn,
+        "analysis_runs",
+        "parameters",
+        "TEXT NOT NULL DEFAULT '{}'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "analysis_runs",
+        "clinvar_checksum",
+        "TEXT NOT NULL DEFAULT ''",
+    )?;
+    add_column_if_missing(
+        conn,
+        "analysis_runs",
+        "library_version",
+        "TEXT NOT NULL DEFAULT ''",
+    )?;
+
+    Ok(())
+}
+
+/// Rebuild `variants` as a `WITHOUT ROWID` table clustered on
+/// (chromosome, position). Chromosomes use the codes from `encoding`, rsIDs
+/// are stored as integers (NULL when the probe has none) and genotypes are
+/// small-int codes into `genotype_codes`. Per-row source format is dropped;
+/// it is already recorded in `genome_metadata`. `migrate` refuses to run this
+/// on databases with chromosomes that have no code, so none are lost.
+fn migrate_v3(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE genotype_codes (
+            code INTEGER PRIMARY KEY,
+            genotype TEXT NOT NULL UNIQUE
+        )",
+        [],
+    )?;
+
+    {
+        let mut stmt =
+            conn.prepare("INSERT INTO genotype_codes (code, genotype) VALUES (?1, ?2)")?;
+        for (code, genotype) in SEEDED_GENOTYPES.iter().enumerate() {
+            stmt.execute(params![code as i64, genotype])?;
+        }
+    }
+
+    conn.execute_batch(&format!(
+        "INSERT OR IGNORE INTO genotype_codes (genotype)
+         SELECT DISTINCT genotype FROM variants;
+
+        CREATE TABLE variants_compact (
+            chromosome INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            file_id INTEGER NOT NULL,
+            probe INTEGER NOT NULL DEFAULT 0,
+            rsid INTEGER,
+            genotype INTEGER NOT NULL,
+            PRIMARY KEY (chromosome, position, file_id, probe)
+        ) WITHOUT ROWID;
+
+        INSERT INTO variants_compact (chromosome, position, file_id, probe, rsid, genotype)
+        SELECT chrom_code, position, file_id,
+               ROW_NUMBER() OVER (PARTITION BY chrom_code, position, file_id ORDER BY id) - 1,
+               rsid_number, genotype_code
+        FROM (
+            SELECT v.id, v.file_id, v.position, g.code AS genotype_code,
+                   {} AS chrom_code,
+                   CASE
+                       WHEN v.rsid GLOB 'rs[0-9]*' THEN CAST(substr(v.rsid, 3) AS INTEGER)
+                   END AS rsid_number
+            FROM variants v
+            JOIN genotype_codes g ON g.genotype = v.genotype
+        );
+
+        DROP TABLE variants;
+        ALTER TABLE variants_compact RENAME TO variants;
+        CREATE INDEX idx_variants_rsid ON variants (rsid);
+
+        CREATE VIEW variants_decoded AS
+        SELECT v.file_id,
+               'rs' || v.rsid AS rsid,
+               CASE v.chromosome
+                   WHEN 23 THEN 'X'
+                   WHEN 24 THEN 'Y'
+                   WHEN 25 THEN 'XY'
+                   WHEN 26 THEN 'MT'
+                   ELSE CAST(v.chromosome AS TEXT)
+               END AS chromosome,
+               v.position,
+               g.genotype,
+               m.source_format
+        FROM variants v
+        JOIN genotype_codes g ON g.code = v.genotype
+        JOIN genome_metadata m ON m.id = v.file_id;",
+        chromosome_code_sql()
+    ))
+}
+
+/// Chromosome names in a text-layout `variants` table that would be stored
+/// as unknown (code 0) by `migrate_v3`, other than `0` itself
+fn unmapped_chromosomes(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT v.chromosome FROM variants v
+         WHERE {} = 0 AND {} <> '0'
+         ORDER BY v.chromosome
+         LIMIT 10",
+        chromosome_code_sql(),
+        CHROMOSOME_NAME_SQL
+    ))?;
+    let names = stmt.query_map([], |row| row.get(0))?;
+    names.collect()
+}
+
+/// Several genomes can share one database. Each import records a hash of its
+/// input file so re-imports can be detected, and analysis runs record which
+/// genome they were run on (NULL for runs from single-genome databases).
+fn migrate_v4(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "genome_metadata", "person", "TEXT")?;
+    add_column_if_missing(conn, "genome_metadata", "content_hash", "TEXT")?;
+    add_column_if_missing(conn, "analysis_runs", "genome_id", "INTEGER")?;
+
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_genome_metadata_content_hash
+             ON genome_metadata (content_hash);",
+    )
+}
+
+/// Differentially private queries spend from a per-database budget. The
+/// single `privacy_budget` row holds the totals the owner allows; every
+/// answered query is recorded in `privacy_ledger`.
+fn migrate_v5(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS privacy_budget (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            epsilon REAL NOT NULL,
+            delta REAL NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS privacy_ledger (
+            id INTEGER PRIMARY KEY,
+            query TEXT NOT NULL,
+            parameters TEXT NOT NULL,
+            mechanism TEXT NOT NULL,
+            epsilon REAL NOT NULL,
+            delta REAL NOT NULL,
+            created_at TEXT NOT NULL
+        );",
+    )
+}
+
+/// Consent grants and the audit of every consent check. No grant is created
+/// here; the owner's first grant is added when they first use the data.
+fn migrate_v6(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS consent_grants (
+            id INTEGER PRIMARY KEY,
+            purpose TEXT NOT NULL,
+            recipient TEXT NOT NULL,
+            granularity TEXT NOT NULL,
+            expires_at TEXT,
+            created_at TEXT NOT NULL,
+            revoked_at TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS consent_audit (
+            id INTEGER PRIMARY KEY,
+            purpose TEXT NOT NULL,
+            recipient TEXT NOT NULL,
+            granularity TEXT NOT NULL,
+            allowed INTEGER NOT NULL,
+            grant_id INTEGER,
+            created_at TEXT NOT NULL
+        );",
+    )
+}
+
+/// Append-only access log. The triggers make accidental edits fail loudly;
+/// the hash chain is what detects deliberate ones.
+fn migrate_v7(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS access_log (
+            id INTEGER PRIMARY KEY,
+            event TEXT NOT NULL,
+            caller TEXT NOT NULL,
+            purpose TEXT,
+            rows INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            prev_hash TEXT NOT NULL,
+            hash TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS access_log_head (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            entries INTEGER NOT NULL,
+            hash TEXT NOT NULL
+        );
+
+        CREATE TRIGGER IF NOT EXISTS access_log_no_update
+        BEFORE UPDATE ON access_log
+        BEGIN
+            SELECT RAISE(ABORT, 'access_log is append-only');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS access_log_no_delete
+        BEFORE DELETE ON access_log
+        BEGIN
+            SELECT RAISE(ABORT, 'access_log is append-only');
+        END;",
+    )
+}
+
+/// Deleting or replacing one genome finds its rows without a table scan
+fn migrate_v8(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_variants_file ON variants (file_id);")
+}
+
+/// 23andMe's internal `i` IDs are stored as negative numbers; name them as
+/// such in the decoded view instead of `rs-4000`
+fn migrate_v9(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "DROP VIEW IF EXISTS variants_decoded;
+
+        CREATE VIEW variants_decoded AS
+        SELECT v.file_id,
+               CASE
+                   WHEN v.rsid < 0 THEN 'i' || -v.rsid
+                   ELSE 'rs' || v.rsid
+               END AS rsid,
+               CASE v.chromosome
+                   WHEN 23 THEN 'X'
+                   WHEN 24 THEN 'Y'
+                   WHEN 25 THEN 'XY'
+                   WHEN 26 THEN 'MT'
+                   ELSE CAST(v.chromosome AS TEXT)
+               END AS chromosome,
+               v.position,
+               g.genotype,
+               m.source_format
+        FROM variants v
+        JOIN genotype_codes g ON g.code = v.genotype
+        JOIN genome_metadata m ON m.id = v.file_id;",
+    )
+}
+
+/// The head may only advance one entry at a time to the newest row, so
+/// cutting rows from the end cannot be hidden by rewinding it
+fn migrate_v10(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS access_log_head_follows_log
+        BEFORE UPDATE ON access_log_head
+        WHEN NEW.id IS NOT OLD.id
+            OR NEW.entries IS NOT OLD.entries + 1
+            OR NEW.hash IS NOT (SELECT hash FROM access_log ORDER BY id DESC LIMIT 1)
+        BEGIN
+            SELECT RAISE(ABORT, 'access_log_head only follows the log');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS access_log_head_no_delete
+        BEFORE DELETE ON access_log_head
+        BEGIN
+            SELECT RAISE(ABORT, 'access_log_head only follows the log');
+        END;",
+    )
+}
+
+/// Add a column unless the table already has it
+pub(crate) fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    declaration: &str,
+) -> rusqlite::Result<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute(
+            &format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, declaration
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Check whether a table exists in the database
+pub(crate) fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Read `PRAGMA user_version`
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<i32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Bring the database up to `SCHEMA_VERSION`, running each pending migration
+/// in its own transaction. Returns the version the database started at.
+pub fn migrate(conn: &Connection) -> Result<i32, BiovaultError> {
+    let start_version = schema_version(conn)?;
+
+    if start_version > SCHEMA_VERSION {
+        return Err(BiovaultError::UnsupportedSchema {
+            found: start_version as i64,
+            supported: SCHEMA_VERSION as i64,
+        });
+    }
+
+    // The compact layout has no room for chromosome names without a code
+    if start_version < COMPACT_LAYOUT_VERSION && table_exists(conn, "variants")? {
+        let unmapped = unmapped_chromosomes(conn)?;
+        if !unmapped.is_empty() {
+            return Err(BiovaultError::Other(format!(
+                "cannot upgrade: variants on unrecognised chromosomes ({}) would lose \
+                 their chromosome; remove them or re-import the genome file",
+                unmapped.join(", ")
+            )));
+        }
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > start_version) {
+        info!(
+            "migrating schema to version {} ({})",
+            migration.version, migration.description
+        );
+
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    // Rebuilding the old text layout leaves free pages behind; reclaim them
+    if start_version < COMPACT_LAYOUT_VERSION {
+        conn.execute_batch("VACUUM")?;
+    }
+
+    Ok(start_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An unversioned database in the first app releases' layout, holding
+    /// one genome with calls on `chromosomes`
+    fn baseline(chromosomes: &[&str]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate_v1(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO genome_metadata
+                 (id, file_name, source_format, total_variants, rsid_count, upload_date, db_name)
+             VALUES (1, 'genome.txt', '23andMe', ?1, ?1, '2024-05-01', 'genome')",
+            [chromosomes.len() as i64],
+        )
+        .unwrap();
+        for (i, chromosome) in chromosomes.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO variants (file_id, rsid, chromosome, position, genotype, source_format)
+                 VALUES (1, ?1, ?2, ?3, 'AG', '23andMe')",
+                params![format!("rs{}", i + 1), chromosome, (i as i64 + 1) * 100],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn chromosome_codes(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT chromosome FROM variants ORDER BY rsid")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn names(conn: &Connection, kind: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT name FROM sqlite_master
+                 WHERE type = ?1 AND name NOT LIKE 'sqlite_%'
+                 ORDER BY name",
+            )
+            .unwrap();
+        stmt.query_map([kind], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn baseline_database_upgrades_to_the_current_schema() {
+        let conn = baseline(&["1", "chr2", "X"]);
+        // A probe without an rsID, a second probe at the same position and a
+        // genotype outside the seeded dictionary
+        conn.execute_batch(
+            "INSERT INTO variants (file_id, rsid, chromosome, position, genotype, source_format)
+             VALUES (1, 'i4000', 'MT', 400, 'G', '23andMe'),
+                    (1, 'rs5', '1', 100, 'CC', '23andMe'),
+                    (1, 'rs6', '3', 600, 'AGT', '23andMe');",
+        )
+        .unwrap();
+        // The early analysis history, before the provenance columns
+        conn.execute_batch(
+            "CREATE TABLE analysis_runs (
+                 id INTEGER PRIMARY KEY,
+                 analysis_type TEXT NOT NULL,
+                 clinvar_release TEXT NOT NULL,
+                 created_at TEXT NOT NULL,
+                 rsids_searched INTEGER NOT NULL,
+                 matches_found INTEGER NOT NULL
+             );
+             INSERT INTO analysis_runs VALUES (1, 'clinvar', '2024-04', '2024-05-02', 3, 0);",
+        )
+        .unwrap();
+
+        assert_eq!(migrate(&conn).unwrap(), 0);
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        assert_eq!(
+            names(&conn, "table"),
+            [
+                "access_log",
+                "access_log_head",
+                "analysis_findings",
+                "analysis_runs",
+                "consent_audit",
+                "consent_grants",
+                "genome_metadata",
+                "genotype_codes",
+                "privacy_budget",
+                "privacy_ledger",
+                "variants",
+            ]
+        );
+        assert_eq!(
+            names(&conn, "index"),
+            [
+                "idx_analysis_findings_run",
+                "idx_genome_metadata_content_hash",
+                "idx_variants_file",
+                "idx_variants_rsid",
+            ]
+        );
+        assert_eq!(names(&conn, "view"), ["variants_decoded"]);
+        assert_eq!(
+            names(&conn, "trigger"),
+            [
+                "access_log_head_follows_log",
+                "access_log_head_no_delete",
+                "access_log_no_delete",
+                "access_log_no_update",
+            ]
+        );
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT rsid, chromosome, position, genotype, source_format
+                 FROM variants_decoded ORDER BY position, rsid",
+            )
+            .unwrap();
+        let rows: Vec<(Option<String>, String, i64, String, String)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let row = |rsid: Option<&str>, chromosome: &str, position, genotype: &str| {
+            (
+                rsid.map(str::to_string),
+                chromosome.to_string(),
+                position,
+                genotype.to_string(),
+                "23andMe".to_string(),
+            )
+        };
+        assert_eq!(
+            rows,
+            [
+                row(Some("rs1"), "1", 100, "AG"),
+                row(Some("rs5"), "1", 100, "CC"),
+                row(Some("rs2"), "2", 200, "AG"),
+                row(Some("rs3"), "X", 300, "AG"),
+                row(None, "MT", 400, "G"),
+                row(Some("rs6"), "3", 600, "AGT"),
+            ]
+        );
+
+        // Both probes at 1:100 are kept apart
+        let probes: Vec<i64> = conn
+            .prepare("SELECT probe FROM variants WHERE chromosome = 1 ORDER BY probe")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(probes, [0, 1]);
+
+        let run: (String, String, String, Option<i64>) = conn
+            .query_row(
+                "SELECT parameters, clinvar_checksum, library_version, genome_id
+                 FROM analysis_runs WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(run, ("{}".to_string(), String::new(), String::new(), None));
+
+        let (person, hash): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT person, content_hash FROM genome_metadata WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((person, hash), (None, None));
+
+        // No consent is granted by the upgrade
+        let grants: i64 = conn
+            .query_row("SELECT COUNT(*) FROM consent_grants", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(grants, 0);
+
+        // Upgrading again is a no-op
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn text_chromosomes_get_their_codes() {
+        let conn = baseline(&["1", "chr22", "Chr7", "X", "chrY", "XY", "MT", "M", "0"]);
+        migrate(&conn).unwrap();
+        assert_eq!(
+            chromosome_codes(&conn),
+            vec![1, 22, 7, 23, 24, 25, 26, 26, 0]
+        );
+    }
+
+    #[test]
+    fn unrecognised_chromosomes_stop_the_upgrade() {
+        let conn = baseline(&["1", "GL000192.1", "chrUn", "1a"]);
+        let error = migrate(&conn).unwrap_err();
+        assert!(
+            error.to_string().contains("1a, GL000192.1, chrUn"),
+            "{}",
+            error
+        );
+
+        // Nothing was changed
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        let chromosome: String = conn
+            .query_row(
+                "SELECT chromosome FROM variants WHERE rsid = 'rs2'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(chromosome, "GL000192.1");
+    }
+
+    #[test]
+    fn variants_are_indexed_by_genome() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        let plan: String = conn
+            .query_row(
+                "EXPLAIN QUERY PLAN DELETE FROM variants WHERE file_id = 1",
+                [],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("idx_variants_file"), "{}", plan);
+    }
+}
@@ -1,4 +1,4 @@
-use std::error::Error;
+use crate::error::BiovaultError;
 use std::path::Path;
 
 pub mod twenty_three_and_me;
@@ -10,7 +10,6 @@ pub struct Variant {
     pub chromosome: String,
     pub position: u64,
     pub genotype: String,
-    pub source_format: String,
 }
 
 /// Metadata about the parsed genome file
@@ -28,12 +27,21 @@ pub struct ParseResult {
     pub variants: Vec<Variant>,
 }
 
-/// Extract first matching file from ZIP
-pub fn extract_from_zip(zip_path: &Path, pattern: &str) -> Result<String, Box<dyn Error>> {
+/// Whether a genome file is a ZIP archive, by extension or signature
+pub fn is_zip(file_name: &Path, bytes: &[u8]) -> bool {
+    file_name.extension().and_then(|s| s.to_str()) == Some("zip")
+        || bytes.starts_with(b"PK\x03\x04")
+}
+
+/// Extract first matching file from a ZIP archive held in memory
+pub fn extract_from_zip_bytes(
+    zip_name: &Path,
+    bytes: &[u8],
+    pattern: &str,
+) -> Result<String, BiovaultError> {
     use std::io::Read;
 
-    let file = std::fs::File::open(zip_path)?;
-    let mut archive = zip::ZipArchive::new(file)?;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
 
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
@@ -44,5 +52,8 @@ pub fn extract_from_zip(zip_path: &Path, pattern: &str) -> Result<String, Box<dy
         }
     }
 
-    Err(format!("No file matching '{}' found in ZIP", pattern).into())
+    Err(BiovaultError::Parse {
+        path: zip_name.to_path_buf(),
+        message: format!("no file matching '{}' in the archive", pattern),
+    })
 }
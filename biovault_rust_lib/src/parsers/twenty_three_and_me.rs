@@ -1,21 +1,35 @@
-use super::{GenomeMetadata, ParseResult, Variant, extract_from_zip};
-use std::error::Error;
+use super::{GenomeMetadata, ParseResult, Variant, extract_from_zip_bytes};
+use crate::error::BiovaultError;
+use crate::progress::{Phase, Progress};
+use std::borrow::Cow;
 use std::path::Path;
 
-pub fn parse_23andme_file(file_path: &Path) -> Result<ParseResult, Box<dyn Error>> {
-    let content = if file_path.extension().and_then(|s| s.to_str()) == Some("zip") {
+/// Parse a 23andMe export held in memory, either the raw text or the ZIP
+/// archive it is downloaded as. `file_name` is the original file name.
+pub fn parse_23andme_bytes(
+    file_name: &Path,
+    bytes: &[u8],
+    progress: &mut Progress,
+) -> Result<ParseResult, BiovaultError> {
+    let content = if super::is_zip(file_name, bytes) {
         // Extract from ZIP
-        extract_from_zip(file_path, "genome_")?
+        Cow::Owned(extract_from_zip_bytes(file_name, bytes, "genome_")?)
     } else {
-        // Read directly
-        std::fs::read_to_string(file_path)?
+        Cow::Borrowed(
+            std::str::from_utf8(bytes).map_err(|_| BiovaultError::Parse {
+                path: file_name.to_path_buf(),
+                message: "not a text file".to_string(),
+            })?,
+        )
     };
 
     let mut variants = Vec::new();
     // Skipping collection of parse errors for now.
     let mut rsid_count = 0;
+    let total_lines = count_lines(&content);
 
-    for line in content.lines() {
+    for (line_number, line) in content.lines().enumerate() {
+        progress.update(Phase::Parsing, line_number as u64, total_lines)?;
         let line = line.trim();
 
         // Skip comments and headers
@@ -58,22 +72,26 @@ pub fn parse_23andme_file(file_path: &Path) -> Result<ParseResult, Box<dyn Error
             rsid_count += 1;
         }
 
-        // Create variant
+        // Create variant; internal IDs such as i4000 are kept as well
         let variant = Variant {
-            rsid: if rsid.starts_with("rs") {
-                Some(rsid.to_string())
-            } else {
-                None
-            },
+            rsid: Some(rsid.to_string()),
             chromosome: chromosome.to_string(),
             position,
             genotype: genotype.to_string(),
-            source_format: "23andMe".to_string(),
         };
 
         variants.push(variant);
     }
 
+    progress.update(Phase::Parsing, total_lines, total_lines)?;
+
+    if variants.is_empty() {
+        return Err(BiovaultError::Parse {
+            path: file_name.to_path_buf(),
+            message: "no genotype calls found".to_string(),
+        });
+    }
+
     // parse_errors is collected above but not currently surfaced; consider persisting later.
     let metadata = GenomeMetadata {
         source_format: "23andMe".to_string(),
@@ -83,3 +101,8 @@ pub fn parse_23andme_file(file_path: &Path) -> Result<ParseResult, Box<dyn Error
 
     Ok(ParseResult { metadata, variants })
 }
+
+/// Number of lines, used as the progress total
+fn count_lines(content: &str) -> u64 {
+    content.bytes().filter(|&b| b == b'\n').count() as u64
+}
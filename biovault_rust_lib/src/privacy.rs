@@ -0,0 +1,609 @@
+//! Differentially private aggregate queries over a genome database.
+//!
+//! Researchers get noisy aggregates over all genomes in a database (allele
+//! counts, ClinVar carrier counts per gene, polygenic score histograms) and
+//! never the genotypes themselves. Neighbouring datasets differ by adding or
+//! removing one genome; the noise is calibrated to each query's sensitivity
+//! under that definition.
+//!
+//! Every answered query spends epsilon (and delta, for the Gaussian
+//! mechanism) from the database's budget under basic composition, and the
+//! spend is recorded before the answer is released. Queries that would
+//! overspend the budget are refused.
+
+use crate::analysis::{self, ClinVarFilter, Zygosity};
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::open_genome_database;
+use crate::encoding;
+use crate::error::BiovaultError;
+use crate::export::snp_alleles;
+use crate::genomes;
+use crate::progress::Progress;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, TransactionBehavior, params};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{info, info_span};
+
+/// Slack for comparing sums of floating point budget spends
+const BUDGET_TOLERANCE: f64 = 1e-9;
+
+/// Noise added to a query's true answer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Mechanism {
+    /// Laplace noise with scale `l1_sensitivity / epsilon`; pure epsilon-DP
+    Laplace,
+    /// Gaussian noise with sigma `l2_sensitivity * sqrt(2 ln(1.25 / delta)) /
+    /// epsilon`; (epsilon, delta)-DP for epsilon below 1
+    Gaussian { delta: f64 },
+}
+
+impl Mechanism {
+    fn name(self) -> &'static str {
+        match self {
+            Mechanism::Laplace => "laplace",
+            Mechanism::Gaussian { .. } => "gaussian",
+        }
+    }
+
+    fn delta(self) -> f64 {
+        match self {
+            Mechanism::Laplace => 0.0,
+            Mechanism::Gaussian { delta } => delta,
+        }
+    }
+
+    /// Laplace scale or Gaussian sigma for a query with L1 and L2
+    /// sensitivity `l1` and `l2`, answered with `epsilon`
+    fn noise_scale(self, l1: f64, l2: f64, epsilon: f64) -> f64 {
+        match self {
+            Mechanism::Laplace => l1 / epsilon,
+            Mechanism::Gaussian { delta } => l2 * (2.0 * (1.25 / delta).ln()).sqrt() / epsilon,
+        }
+    }
+}
+
+/// One weighted marker of a polygenic score
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrsWeight {
+    pub rsid: String,
+    pub effect_allele: String,
+    pub weight: f64,
+}
+
+/// An aggregate query over every genome in a database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub enum DpQuery {
+    /// Copies of each base at each rsID
+    AlleleCounts { rsids: Vec<String> },
+    /// Genomes carrying at least one pathogenic or likely pathogenic ClinVar
+    /// variant in each gene
+    CarrierCounts {
+        genes: Vec<String>,
+        clinvar_db_path: String,
+    },
+    /// Genomes per bin of a polygenic score. Scores outside `[min, max)` are
+    /// counted in the first or last bin.
+    PrsHistogram {
+        weights: Vec<PrsWeight>,
+        min: f64,
+        max: f64,
+        bins: usize,
+    },
+}
+
+impl DpQuery {
+    fn name(&self) -> &'static str {
+        match self {
+            DpQuery::AlleleCounts { .. } => "allele_counts",
+            DpQuery::CarrierCounts { .. } => "carrier_counts",
+            DpQuery::PrsHistogram { .. } => "prs_histogram",
+        }
+    }
+
+    /// L1 and L2 sensitivity when one genome is added or removed
+    fn sensitivity(&self) -> (f64, f64) {
+        match self {
+            // A genome adds two alleles at each rsID: at most 2 to one count
+            // or 1 to two counts
+            DpQuery::AlleleCounts { rsids } => {
+                let k = rsids.len() as f64;
+                (2.0 * k, 2.0 * k.sqrt())
+            }
+            // A genome is a carrier for every gene at most once
+            DpQuery::CarrierCounts { genes, .. } => {
+                let k = genes.len() as f64;
+                (k, k.sqrt())
+            }
+            // A genome falls in exactly one bin
+            DpQuery::PrsHistogram { .. } => (1.0, 1.0),
+        }
+    }
+
+    fn validate(&self) -> Result<(), BiovaultError> {
+        let invalid = |message: &str| Err(BiovaultError::InvalidArgument(message.to_string()));
+        match self {
+            DpQuery::AlleleCounts { rsids } if rsids.is_empty() => invalid("no rsIDs given"),
+            DpQuery::AlleleCounts { rsids }
+                if rsids.iter().any(|r| encoding::rsid_number(r).is_none()) =>
+            {
+                invalid("rsIDs must look like rs123")
+            }
+            DpQuery::CarrierCounts { genes, .. } if genes.is_empty() => invalid("no genes given"),
+            DpQuery::PrsHistogram { weights, .. } if weights.is_empty() => {
+                invalid("no score weights given")
+            }
+            DpQuery::PrsHistogram { min, max, bins, .. }
+                if *bins == 0 || !min.is_finite() || !max.is_finite() || min >= max =>
+            {
+                invalid("histogram needs at least one bin and min < max")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One noisy value of a query result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoisyValue {
+    /// e.g. `rs123:A`, a gene name or a bin like `[0.5, 1)`
+    pub label: String,
+    pub value: f64,
+}
+
+/// A differentially private answer
+#[derive(Debug, Clone, Serialize)]
+pub struct DpRelease {
+    pub query: &'static str,
+    pub mechanism: Mechanism,
+    pub epsilon: f64,
+    /// Laplace scale or Gaussian standard deviation of the added noise
+    pub noise_scale: f64,
+    pub values: Vec<NoisyValue>,
+    pub budget: PrivacyBudget,
+}
+
+/// A database's privacy budget and how much of it is spent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PrivacyBudget {
+    pub epsilon: f64,
+    pub delta: f64,
+    pub epsilon_spent: f64,
+    pub delta_spent: f64,
+}
+
+impl PrivacyBudget {
+    pub fn epsilon_remaining(&self) -> f64 {
+        (self.epsilon - self.epsilon_spent).max(0.0)
+    }
+
+    pub fn delta_remaining(&self) -> f64 {
+        (self.delta - self.delta_spent).max(0.0)
+    }
+}
+
+/// Set the total budget for a database. Spending so far is kept, so lowering
+/// the total below it refuses all further queries.
+pub fn set_privacy_budget(
+    db_path: &Path,
+    key: Option<&str>,
+    epsilon: f64,
+    delta: f64,
+) -> Result<PrivacyBudget, BiovaultError> {
+    if !epsilon.is_finite() || epsilon < 0.0 || !(0.0..1.0).contains(&delta) {
+        return Err(BiovaultError::InvalidArgument(
+            "epsilon must be non-negative and delta in [0, 1)".to_string(),
+        ));
+    }
+    let conn = open_genome_database(db_path, key)?;
+    conn.execute(
+        "INSERT INTO privacy_budget (id, epsilon, delta, updated_at) VALUES (1, ?1, ?2, ?3)
+         ON CONFLICT (id) DO UPDATE SET
+             epsilon = excluded.epsilon,
+             delta = excluded.delta,
+             updated_at = excluded.updated_at",
+        params![epsilon, delta, chrono::Utc::now().to_rfc3339()],
+    )?;
+    info!("privacy budget set to epsilon {}, delta {}", epsilon, delta);
+    Ok(load_budget(&conn)?)
+}
+
+/// The budget of a database. Databases without one have a budget of zero.
+pub fn privacy_budget(db_path: &Path, key: Option<&str>) -> Result<PrivacyBudget, BiovaultError> {
+    let conn = open_genome_database(db_path, key)?;
+    Ok(load_budget(&conn)?)
+}
+
+fn load_budget(conn: &Connection) -> rusqlite::Result<PrivacyBudget> {
+    let (epsilon, delta) = conn
+        .query_row(
+            "SELECT epsilon, delta FROM privacy_budget WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .unwrap_or((0.0, 0.0));
+    let (epsilon_spent, delta_spent) = conn.query_row(
+        "SELECT COALESCE(SUM(epsilon), 0.0), COALESCE(SUM(delta), 0.0) FROM privacy_ledger",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok(PrivacyBudget {
+        epsilon,
+        delta,
+        epsilon_spent,
+        delta_spent,
+    })
+}
+
+/// Answer `query` over every genome in the database with `mechanism` noise,
+/// spending `epsilon` (and the Gaussian delta) from its budget. `recipient`
+/// is who receives the release and needs an aggregate consent grant.
+pub fn run_dp_query(
+    db_path: &Path,
+    key: Option<&str>,
+    recipient: &str,
+    query: &DpQuery,
+    mechanism: Mechanism,
+    epsilon: f64,
+) -> Result<DpRelease, BiovaultError> {
+    query.validate()?;
+    if !epsilon.is_finite() || epsilon <= 0.0 {
+        return Err(BiovaultError::InvalidArgument(
+            "epsilon must be positive".to_string(),
+        ));
+    }
+    if let Mechanism::Gaussian { delta } = mechanism
+        && (epsilon >= 1.0 || !(delta > 0.0 && delta < 1.0))
+    {
+        return Err(BiovaultError::InvalidArgument(
+            "the Gaussian mechanism needs epsilon below 1 and delta in (0, 1)".to_string(),
+        ));
+    }
+
+    let _span = info_span!("dp_query", query = query.name()).entered();
+    let mut conn = open_genome_database(db_path, key)?;
+    consent::require_consent(&conn, Purpose::DpQuery, recipient, Granularity::Aggregate)?;
+
+    // Refuse early so an exhausted budget does not cost a full computation
+    let budget = load_budget(&conn)?;
+    check_budget(&budget, epsilon, mechanism.delta())?;
+
+    let true_values = match query {
+        DpQuery::AlleleCounts { rsids } => allele_counts(&conn, rsids)?,
+        DpQuery::CarrierCounts {
+            genes,
+            clinvar_db_path,
+        } => carrier_counts(&conn, genes, Path::new(clinvar_db_path))?,
+        DpQuery::PrsHistogram {
+            weights,
+            min,
+            max,
+            bins,
+        } => prs_histogram(&conn, weights, *min, *max, *bins)?,
+    };
+
+    // Record the spend before releasing anything; the immediate transaction
+    // keeps concurrent queries from overspending together
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let budget = load_budget(&tx)?;
+    check_budget(&budget, epsilon, mechanism.delta())?;
+    tx.execute(
+        "INSERT INTO privacy_ledger (query, parameters, mechanism, epsilon, delta, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            query.name(),
+            serde_json::to_string(query)?,
+            mechanism.name(),
+            epsilon,
+            mechanism.delta(),
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    let budget = load_budget(&tx)?;
+    tx.commit()?;
+
+    let (l1, l2) = query.sensitivity();
+    let noise_scale = mechanism.noise_scale(l1, l2, epsilon);
+    let values = true_values
+        .into_iter()
+        .map(|(label, value)| {
+            let noise = match mechanism {
+                Mechanism::Laplace => sample_laplace(noise_scale),
+                Mechanism::Gaussian { .. } => sample_gaussian(noise_scale),
+            }?;
+            Ok(NoisyValue {
+                label,
+                value: value + noise,
+            })
+        })
+        .collect::<Result<Vec<_>, BiovaultError>>()?;
+
+    info!(
+        "answered with epsilon {}; {} remains",
+        epsilon,
+        budget.epsilon_remaining()
+    );
+    Ok(DpRelease {
+        query: query.name(),
+        mechanism,
+        epsilon,
+        noise_scale,
+        values,
+        budget,
+    })
+}
+
+fn check_budget(budget: &PrivacyBudget, epsilon: f64, delta: f64) -> Result<(), BiovaultError> {
+    if budget.epsilon_spent + epsilon > budget.epsilon + BUDGET_TOLERANCE
+        || budget.delta_spent + delta > budget.delta + BUDGET_TOLERANCE
+    {
+        return Err(BiovaultError::PrivacyBudgetExhausted {
+            requested: epsilon,
+            remaining: budget.epsilon_remaining(),
+        });
+    }
+    Ok(())
+}
+
+/// Copies of A, C, G and T at each rsID, one call per genome. All four bases
+/// are always reported so the labels do not reveal which alleles occur.
+fn allele_counts(conn: &Connection, rsids: &[String]) -> Result<Vec<(String, f64)>, BiovaultError> {
+    let mut stmt = conn.prepare(
+        "SELECT v.file_id, g.genotype
+         FROM variants v
+         JOIN genotype_codes g ON g.code = v.genotype
+         WHERE v.rsid = ?1
+         ORDER BY v.file_id, v.probe",
+    )?;
+    let mut values = Vec::new();
+    for rsid in rsids {
+        let number = encoding::rsid_number(rsid).unwrap_or_default();
+        let mut counts = [0usize; 4];
+        let mut seen = HashSet::new();
+        let mut rows = stmt.query([number])?;
+        while let Some(row) = rows.next()? {
+            let genome: i64 = row.get(0)?;
+            let genotype: String = row.get(1)?;
+            if !seen.insert(genome) {
+                continue;
+            }
+            for allele in snp_alleles(&genotype).unwrap_or_default() {
+                if let Some(base) = "ACGT".find(allele) {
+                    counts[base] += 1;
+                }
+            }
+        }
+        for (base, count) in "ACGT".chars().zip(counts) {
+            values.push((format!("{}:{}", rsid, base), count as f64));
+        }
+    }
+    Ok(values)
+}
+
+/// Genomes with a pathogenic or likely pathogenic ClinVar variant in each gene
+fn carrier_counts(
+    conn: &Connection,
+    genes: &[String],
+    clinvar_db_path: &Path,
+) -> Result<Vec<(String, f64)>, BiovaultError> {
+    let clinvar_conn = Connection::open_with_flags(
+        clinvar_db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let filter = ClinVarFilter {
+        significance: vec!["Pathogenic".to_string(), "Likely_pathogenic".to_string()],
+        min_stars: 0,
+        genes: genes.to_vec(),
+        zygosity: vec![
+            Zygosity::Homozygous,
+            Zygosity::Heterozygous,
+            Zygosity::Hemizygous,
+        ],
+    };
+
+    let mut counts = vec![0usize; genes.len()];
+    for genome in genomes::genomes_in(conn)? {
+        let result =
+            analysis::analyze_genome(conn, Some(genome.id), &clinvar_conn, &mut Progress::none())?
+                .filtered(&filter);
+        for (gene, count) in genes.iter().zip(counts.iter_mut()) {
+            let single = ClinVarFilter {
+                genes: vec![gene.clone()],
+                ..filter.clone()
+            };
+            if result.matches.iter().any(|variant| single.matches(variant)) {
+                *count += 1;
+            }
+        }
+    }
+    Ok(genes
+        .iter()
+        .zip(counts)
+        .map(|(gene, count)| (gene.clone(), count as f64))
+        .collect())
+}
+
+/// Genomes per polygenic score bin. A score sums `weight` times the copies of
+/// the effect allele; markers a genome lacks contribute nothing.
+fn prs_histogram(
+    conn: &Connection,
+    weights: &[PrsWeight],
+    min: f64,
+    max: f64,
+    bins: usize,
+) -> Result<Vec<(String, f64)>, BiovaultError> {
+    let width = (max - min) / bins as f64;
+    let mut counts = vec![0usize; bins];
+    for genome in genomes::genomes_in(conn)? {
+        let genotypes =
+            analysis::get_rsids_and_genotypes_from_user_database(conn, Some(genome.id))?;
+        let score: f64 = weights
+            .iter()
+            .filter_map(|w| {
+                let genotype = genotypes.get(&w.rsid)?;
+                let dosage = snp_alleles(genotype)?
+                    .iter()
+                    .filter(|allele| w.effect_allele.eq_ignore_ascii_case(&allele.to_string()))
+                    .count();
+                Some(w.weight * dosage as f64)
+            })
+            .sum();
+        let bin = ((score - min) / width).floor();
+        let bin = if bin.is_nan() {
+            0
+        } else {
+            (bin.max(0.0) as usize).min(bins - 1)
+        };
+        counts[bin] += 1;
+    }
+    Ok(counts
+        .into_iter()
+        .enumerate()
+        .map(|(bin, count)| {
+            let lower = min + width * bin as f64;
+            (format!("[{}, {})", lower, lower + width), count as f64)
+        })
+        .collect())
+}
+
+/// Uniform sample in the open interval (0, 1) from OS entropy
+fn sample_uniform() -> Result<f64, BiovaultError> {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| BiovaultError::Other(format!("no secure randomness available: {}", e)))?;
+    // 53 random bits, shifted off zero
+    let bits = u64::from_le_bytes(bytes) >> 11;
+    Ok((bits as f64 + 0.5) / (1u64 << 53) as f64)
+}
+
+/// Laplace(0, scale) by inverting its CDF
+fn sample_laplace(scale: f64) -> Result<f64, BiovaultError> {
+    let u = sample_uniform()? - 0.5;
+    Ok(-scale * u.signum() * (1.0 - 2.0 * u.abs()).ln())
+}
+
+/// Normal(0, sigma) with the Box-Muller transform
+fn sample_gaussian(sigma: f64) -> Result<f64, BiovaultError> {
+    let (u1, u2) = (sample_uniform()?, sample_uniform()?);
+    Ok(sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::create_genome_storage;
+    use crate::storage::Storage;
+
+    const SAMPLES: usize = 200_000;
+
+    /// Sample mean and variance of `SAMPLES` draws
+    fn moments(mut sample: impl FnMut() -> f64) -> (f64, f64) {
+        let draws: Vec<f64> = (0..SAMPLES).map(|_| sample()).collect();
+        let mean = draws.iter().sum::<f64>() / SAMPLES as f64;
+        let variance = draws.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (SAMPLES - 1) as f64;
+        (mean, variance)
+    }
+
+    // Tolerances are at least six standard errors of the estimate, so these
+    // fail by chance far less than once in a million runs
+
+    #[test]
+    fn laplace_noise_has_mean_zero_and_variance_two_b_squared() {
+        let b = 4.0;
+        let (mean, variance) = moments(|| sample_laplace(b).unwrap());
+        // Standard error of the mean is b * sqrt(2 / N), about 0.013
+        assert!(mean.abs() < 0.1, "mean {}", mean);
+        // Standard error of the variance is b^2 * sqrt(20 / N), about 0.16
+        assert!(
+            (variance - 2.0 * b * b).abs() < 1.2,
+            "variance {}",
+            variance
+        );
+    }
+
+    #[test]
+    fn gaussian_noise_has_mean_zero_and_variance_sigma_squared() {
+        let sigma = 3.0;
+        let (mean, variance) = moments(|| sample_gaussian(sigma).unwrap());
+        // Standard error of the mean is sigma / sqrt(N), about 0.007
+        assert!(mean.abs() < 0.05, "mean {}", mean);
+        // Standard error of the variance is sigma^2 * sqrt(2 / N), about 0.03
+        assert!(
+            (variance - sigma * sigma).abs() < 0.2,
+            "variance {}",
+            variance
+        );
+    }
+
+    #[test]
+    fn noise_is_calibrated_to_epsilon_and_delta() {
+        // b = L1 / epsilon
+        assert_eq!(Mechanism::Laplace.noise_scale(2.0, 1.0, 0.5), 4.0);
+        assert_eq!(Mechanism::Laplace.noise_scale(2.0, 1.0, 0.25), 8.0);
+        // sigma = L2 * sqrt(2 ln(1.25 / delta)) / epsilon
+        let gaussian = Mechanism::Gaussian { delta: 1e-5 };
+        let sigma = gaussian.noise_scale(2.0, 1.0, 0.5);
+        assert!((sigma - 9.689_6).abs() < 1e-4, "sigma {}", sigma);
+        assert!((gaussian.noise_scale(2.0, 2.0, 0.5) - 2.0 * sigma).abs() < 1e-12);
+    }
+
+    #[test]
+    fn answers_spend_the_budget_until_it_is_gone() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        create_genome_storage(&Storage::from(db_path.as_path()), None).unwrap();
+        let query = DpQuery::AlleleCounts {
+            rsids: vec!["rs1".to_string()],
+        };
+        let run = |mechanism, epsilon| {
+            run_dp_query(&db_path, None, "alice@uni.edu", &query, mechanism, epsilon)
+        };
+
+        assert!(matches!(
+            run(Mechanism::Laplace, 0.5),
+            Err(BiovaultError::ConsentRequired { .. })
+        ));
+        consent::grant_consent(
+            &db_path,
+            None,
+            Some(Purpose::DpQuery),
+            "alice@uni.edu",
+            Granularity::Aggregate,
+            None,
+        )
+        .unwrap();
+        // Databases start with no budget
+        assert!(matches!(
+            run(Mechanism::Laplace, 0.5),
+            Err(BiovaultError::PrivacyBudgetExhausted { .. })
+        ));
+
+        set_privacy_budget(&db_path, None, 1.0, 0.0).unwrap();
+        let release = run(Mechanism::Laplace, 0.4).unwrap();
+        // One rsID: L1 sensitivity 2
+        assert_eq!(release.noise_scale, 5.0);
+        assert_eq!(release.values.len(), 4);
+        assert!((release.budget.epsilon_remaining() - 0.6).abs() < 1e-12);
+        run(Mechanism::Laplace, 0.6).unwrap();
+
+        match run(Mechanism::Laplace, 0.01) {
+            Err(BiovaultError::PrivacyBudgetExhausted { remaining, .. }) => {
+                assert!(remaining.abs() < 1e-12)
+            }
+            other => panic!("expected an exhausted budget, got {:?}", other.map(|_| ())),
+        }
+        // No delta was budgeted
+        set_privacy_budget(&db_path, None, 2.0, 0.0).unwrap();
+        assert!(matches!(
+            run(Mechanism::Gaussian { delta: 1e-6 }, 0.5),
+            Err(BiovaultError::PrivacyBudgetExhausted { .. })
+        ));
+
+        let budget = privacy_budget(&db_path, None).unwrap();
+        assert!((budget.epsilon_spent - 1.0).abs() < 1e-12);
+        assert_eq!(budget.delta_spent, 0.0);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::error::BiovaultError;
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Rows between two progress callbacks within one phase
+const REPORT_INTERVAL: u64 = 10_000;
+
+/// Stage of a long-running import or analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Reading the genome file line by line
+    Parsing,
+    /// Writing variants into the genome database
+    Inserting,
+    /// Refreshing query planner statistics after an import
+    Indexing,
+    /// Reading the genome's rsIDs and genotypes for analysis
+    LoadingGenotypes,
+    /// Looking up rsIDs in ClinVar, one chunk at a time
+    ClinvarLookup,
+    /// Grouping matches by gene
+    Grouping,
+}
+
+impl Phase {
+    /// Stable name used by the FFI, JNI and CLI
+    pub fn name(self) -> &'static str {
+        self.c_name().to_str().unwrap_or_default()
+    }
+
+    pub(crate) fn c_name(self) -> &'static CStr {
+        match self {
+            Phase::Parsing => c"parsing",
+            Phase::Inserting => c"inserting",
+            Phase::Indexing => c"indexing",
+            Phase::LoadingGenotypes => c"loading_genotypes",
+            Phase::ClinvarLookup => c"clinvar_lookup",
+            Phase::Grouping => c"grouping",
+        }
+    }
+}
+
+/// One progress report. `total` is an estimate and 0 when unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressUpdate {
+    pub phase: Phase,
+    pub processed: u64,
+    pub total: u64,
+}
+
+/// Flag to cancel a running import or analysis, e.g. from a UI thread.
+/// Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress sink and cancellation check threaded through long operations.
+/// Callbacks are throttled to one every `REPORT_INTERVAL` rows per phase.
+pub struct Progress<'a> {
+    callback: Option<Box<dyn FnMut(ProgressUpdate) + 'a>>,
+    cancel: Option<CancellationToken>,
+    last: Option<ProgressUpdate>,
+}
+
+impl<'a> Progress<'a> {
+    /// No reporting and no cancellation
+    pub fn none() -> Self {
+        Progress {
+            callback: None,
+            cancel: None,
+            last: None,
+        }
+    }
+
+    /// Report progress to `callback`
+    pub fn new(callback: impl FnMut(ProgressUpdate) + 'a) -> Self {
+        Progress {
+            callback: Some(Box::new(callback)),
+            ..Self::none()
+        }
+    }
+
+    /// Stop with `BiovaultError::Cancelled` once `token` is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Fail with `Cancelled` if cancellation was requested
+    pub fn check(&self) -> Result<(), BiovaultError> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(BiovaultError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record progress within a phase and fail with `Cancelled` if
+    /// cancellation was requested
+    pub fn update(
+        &mut self,
+        phase: Phase,
+        processed: u64,
+        total: u64,
+    ) -> Result<(), BiovaultError> {
+        self.report(phase, processed, total);
+        self.check()
+    }
+
+    /// Record progress without a cancellation point, for work that can no
+    /// longer be undone. The first and last update of a phase always reach
+    /// the callback; updates in between are throttled.
+    pub fn report(&mut self, phase: Phase, processed: u64, total: u64) {
+        let due = match self.last {
+            Some(last) if last.phase == phase => {
+                (total > 0 && processed >= total)
+                    || processed.saturating_sub(last.processed) >= REPORT_INTERVAL
+            }
+            _ => true,
+        };
+
+        if due {
+            let update = ProgressUpdate {
+                phase,
+                processed,
+                total,
+            };
+            self.last = Some(update);
+            if let Some(callback) = self.callback.as_mut() {
+                callback(update);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze_clinvar_matches_with_progress;
+    use crate::database::open_genome_database;
+    use crate::genomes::{DuplicatePolicy, import_genome, import_genome_with_progress};
+    use std::path::Path;
+
+    const HEADER: &str = "# rsid\tchromosome\tposition\tgenotype\n";
+
+    fn genome_file(dir: &Path, name: &str, first_position: i64) -> String {
+        let rows: String = (0..50)
+            .map(|i| format!("rs{}\t1\t{}\tAG\n", i + 1, first_position + i))
+            .collect();
+        let path = dir.join(name);
+        std::fs::write(&path, format!("{}{}", HEADER, rows)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Progress that cancels `token` at the first update of `phase`
+    fn cancel_at(phase: Phase, token: &CancellationToken) -> Progress<'static> {
+        let trigger = token.clone();
+        Progress::new(move |update| {
+            if update.phase == phase {
+                trigger.cancel();
+            }
+        })
+        .with_cancellation(token.clone())
+    }
+
+    fn count(db_path: &Path, sql: &str) -> i64 {
+        let conn = open_genome_database(db_path, None).unwrap();
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn cancelled_first_import_leaves_no_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        let genome = genome_file(dir.path(), "genome.txt", 100);
+
+        let token = CancellationToken::new();
+        let err = import_genome_with_progress(
+            &genome,
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+            &mut cancel_at(Phase::Inserting, &token),
+        )
+        .unwrap_err();
+        assert!(matches!(err, BiovaultError::Cancelled), "{:?}", err);
+        assert!(token.is_cancelled());
+
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("genome.sqlite"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+    }
+
+    #[test]
+    fn cancelled_import_keeps_existing_genomes_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        let first = genome_file(dir.path(), "first.txt", 100);
+        let second = genome_file(dir.path(), "second.txt", 1_000);
+        import_genome(&first, &db_path, "first", None, None, DuplicatePolicy::Skip).unwrap();
+
+        let token = CancellationToken::new();
+        let err = import_genome_with_progress(
+            &second,
+            &db_path,
+            "second",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+            &mut cancel_at(Phase::Inserting, &token),
+        )
+        .unwrap_err();
+        assert!(matches!(err, BiovaultError::Cancelled), "{:?}", err);
+
+        assert_eq!(count(&db_path, "SELECT COUNT(*) FROM genome_metadata"), 1);
+        assert_eq!(count(&db_path, "SELECT COUNT(*) FROM variants"), 50);
+        assert_eq!(
+            count(
+                &db_path,
+                "SELECT COUNT(*) FROM variants WHERE position >= 1000"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn cancelled_analysis_is_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("genome.sqlite");
+        let genome = genome_file(dir.path(), "genome.txt", 100);
+        import_genome(
+            &genome,
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        let clinvar_path = dir.path().join("clinvar.sqlite");
+        rusqlite::Connection::open(&clinvar_path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                     gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);
+                 INSERT INTO variants VALUES ('rs1', '1', 100, 'A', 'G', 'GENE1', 'Pathogenic',
+                     'criteria_provided,_single_submitter', 'Some_condition');",
+            )
+            .unwrap();
+
+        let token = CancellationToken::new();
+        let err = analyze_clinvar_matches_with_progress(
+            &db_path.to_string_lossy(),
+            None,
+            None,
+            &clinvar_path.to_string_lossy(),
+            &mut cancel_at(Phase::ClinvarLookup, &token),
+        )
+        .unwrap_err();
+        assert!(matches!(err, BiovaultError::Cancelled), "{:?}", err);
+        assert_eq!(
+            count(
+                &db_path,
+                "SELECT COUNT(*) FROM access_log WHERE event = 'analysis'"
+            ),
+            0
+        );
+    }
+}
@@ -0,0 +1,494 @@
+//! Browsing the stored variants of a genome database by rsID, region, gene or
+//! genotype, a page at a time.
+//!
+//! rsID filters use `idx_variants_rsid`; region and gene filters use the
+//! `(chromosome, position)` primary key of `variants`, which replaced
+//! `idx_variants_chr_pos` when the table was compacted. Genes are resolved
+//! through the panel of commonly screened genes in
+//! `data/gene_panel_grch37.tsv`; other symbols are not found.
+
+use crate::access_log::{self, AccessEvent};
+use crate::database::open_genome_database;
+use crate::encoding::{self, CHROM_X, CHROM_XY};
+use crate::error::BiovaultError;
+use crate::genomes;
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const GENE_PANEL: &str = include_str!("../data/gene_panel_grch37.tsv");
+
+/// Page size when the query does not set one
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+/// Larger page sizes are reduced to this
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Where a gene lies on GRCh37
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct GeneLocation {
+    pub symbol: String,
+    pub chromosome: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+/// Every gene in the bundled panel, in symbol order
+pub fn gene_panel() -> Vec<GeneLocation> {
+    GENE_PANEL
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(GeneLocation {
+                symbol: fields.next()?.to_string(),
+                chromosome: fields.next()?.to_string(),
+                start: fields.next()?.parse().ok()?,
+                end: fields.next()?.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Look up a gene symbol, case-insensitively, in the bundled panel
+pub fn gene_location(symbol: &str) -> Option<GeneLocation> {
+    let symbol = symbol.trim();
+    gene_panel()
+        .into_iter()
+        .find(|gene| gene.symbol.eq_ignore_ascii_case(symbol))
+}
+
+/// Which variants `query_variants` returns. Filters combine with AND; unset
+/// or empty filters match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, uniffi::Record)]
+pub struct VariantQuery {
+    /// Only this genome; `None` for every genome in the database
+    pub genome_id: Option<i64>,
+    /// Any of these rsIDs
+    pub rsids: Vec<String>,
+    pub chromosome: Option<String>,
+    /// First position on `chromosome`, inclusive
+    pub start: Option<i64>,
+    /// Last position on `chromosome`, inclusive
+    pub end: Option<i64>,
+    /// A gene symbol from the bundled panel (see `gene_panel`)
+    pub gene: Option<String>,
+    /// Genotype pattern where `?` stands for one allele and `*` for any
+    /// number, e.g. `A?` or `*T*`
+    pub genotype: Option<String>,
+    /// Rows to skip
+    pub offset: u32,
+    /// Rows to return: 0 for `DEFAULT_PAGE_SIZE`, at most `MAX_PAGE_SIZE`
+    pub limit: u32,
+}
+
+/// One stored genotype call
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct VariantRow {
+    pub genome_id: i64,
+    pub rsid: Option<String>,
+    pub chromosome: String,
+    pub position: i64,
+    pub genotype: String,
+}
+
+/// A page of `query_variants` results, ordered by chromosome and position
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct VariantPage {
+    pub variants: Vec<VariantRow>,
+    /// Offset to pass for the next page; `None` on the last page
+    pub next_offset: Option<u32>,
+    /// Span of the gene the query filtered on
+    pub gene: Option<GeneLocation>,
+}
+
+/// WHERE clauses and their parameters, built up filter by filter
+#[derive(Default)]
+struct Conditions {
+    clauses: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Conditions {
+    fn push(&mut self, clause: &str, values: impl IntoIterator<Item = Value>) {
+        self.clauses.push(clause.to_string());
+        self.values.extend(values);
+    }
+
+    /// Positions `start..=end` on a chromosome; pseudo-autosomal calls are
+    /// stored as XY at X coordinates
+    fn push_region(&mut self, chromosome: i64, start: i64, end: i64) {
+        let chromosomes = if chromosome == CHROM_X {
+            vec![CHROM_X, CHROM_XY]
+        } else {
+            vec![chromosome]
+        };
+        let placeholders = vec!["?"; chromosomes.len()].join(", ");
+        self.push(
+            &format!(
+                "v.chromosome IN ({}) AND v.position BETWEEN ? AND ?",
+                placeholders
+            ),
+            chromosomes
+                .into_iter()
+                .chain([start, end])
+                .map(Value::Integer),
+        );
+    }
+}
+
+/// Genotype text with only the alleles, `-` and wildcards, uppercased
+fn genotype_pattern(pattern: &str) -> Result<String, BiovaultError> {
+    let pattern = pattern.trim().to_ascii_uppercase();
+    if pattern.is_empty()
+        || !pattern
+            .chars()
+            .all(|c| matches!(c, 'A' | 'C' | 'G' | 'T' | 'I' | 'D' | '-' | '?' | '*'))
+    {
+        return Err(BiovaultError::InvalidArgument(format!(
+            "genotype pattern '{}' may only use A, C, G, T, I, D, -, ? and *",
+            pattern
+        )));
+    }
+    Ok(pattern)
+}
+
+fn conditions(
+    query: &VariantQuery,
+    gene: Option<&GeneLocation>,
+) -> Result<Conditions, BiovaultError> {
+    let mut conditions = Conditions::default();
+
+    if let Some(genome_id) = query.genome_id {
+        conditions.push("v.file_id = ?", [Value::Integer(genome_id)]);
+    }
+
+    if !query.rsids.is_empty() {
+        let numbers = query
+            .rsids
+            .iter()
+            .map(|rsid| {
+                encoding::rsid_number(rsid.trim()).ok_or_else(|| {
+                    BiovaultError::InvalidArgument(format!("'{}' is not an rsID", rsid))
+                })
+            })
+            .collect::<Result<Vec<i64>, _>>()?;
+        // One parameter however long the list is
+        conditions.push(
+            "v.rsid IN (SELECT value FROM json_each(?))",
+            [Value::Text(serde_json::to_string(&numbers)?)],
+        );
+    }
+
+    match (&query.chromosome, query.start, query.end) {
+        (Some(chromosome), start, end) => {
+            let code = encoding::chromosome_code(chromosome).ok_or_else(|| {
+                BiovaultError::InvalidArgument(format!("unknown chromosome '{}'", chromosome))
+            })?;
+            let (start, end) = (start.unwrap_or(0), end.unwrap_or(i64::MAX));
+            if start > end || start < 0 {
+                return Err(BiovaultError::InvalidArgument(format!(
+                    "region {}:{}-{} is empty",
+                    chromosome, start, end
+                )));
+            }
+            conditions.push_region(code, start, end);
+        }
+        (None, None, None) => {}
+        (None, ..) => {
+            return Err(BiovaultError::InvalidArgument(
+                "a start or end position needs a chromosome".to_string(),
+            ));
+        }
+    }
+
+    if let Some(gene) = gene {
+        let code = encoding::chromosome_code(&gene.chromosome).ok_or_else(|| {
+            BiovaultError::Other(format!("bad chromosome for gene {}", gene.symbol))
+        })?;
+        conditions.push_region(code, gene.start, gene.end);
+    }
+
+    if let Some(pattern) = &query.genotype {
+        conditions.push(
+            "g.genotype GLOB ?",
+            [Value::Text(genotype_pattern(pattern)?)],
+        );
+    }
+
+    Ok(conditions)
+}
+
+/// Find the stored variants matching `query`, one page at a time
+pub fn query_variants(
+    db_path: &Path,
+    key: Option<&str>,
+    query: &VariantQuery,
+) -> Result<VariantPage, BiovaultError> {
+    let gene = match query.gene.as_deref() {
+        Some(symbol) => Some(gene_location(symbol).ok_or_else(|| {
+            BiovaultError::NotFound(format!("Gene {} in the bundled gene panel", symbol))
+        })?),
+        None => None,
+    };
+    let mut conditions = conditions(query, gene.as_ref())?;
+    let limit = match query.limit {
+        0 => DEFAULT_PAGE_SIZE,
+        limit => limit.min(MAX_PAGE_SIZE),
+    };
+
+    let conn = open_genome_database(db_path, key)?;
+    if let Some(genome_id) = query.genome_id {
+        genomes::resolve_genome_id(&conn, Some(genome_id))?;
+    }
+
+    let filter = if conditions.clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.clauses.join(" AND "))
+    };
+    // CROSS JOIN keeps `variants` as the outer loop, so the filters above
+    // drive the index choice rather than the genotype dictionary
+    let sql = format!(
+        "SELECT v.file_id, v.rsid, v.chromosome, v.position, g.genotype
+         FROM variants v
+         CROSS JOIN genotype_codes g ON g.code = v.genotype
+         {}
+         ORDER BY v.chromosome, v.position, v.file_id, v.probe
+         LIMIT ? OFFSET ?",
+        filter
+    );
+    // One extra row tells whether there is another page
+    conditions.values.push(Value::Integer(limit as i64 + 1));
+    conditions.values.push(Value::Integer(query.offset as i64));
+
+    let mut variants = {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(
+            rusqlite::params_from_iter(conditions.values.iter()),
+            |row| {
+                Ok(VariantRow {
+                    genome_id: row.get(0)?,
+                    rsid: row.get::<_, Option<i64>>(1)?.map(encoding::rsid_string),
+                    chromosome: encoding::chromosome_name(row.get(2)?),
+                    position: row.get(3)?,
+                    genotype: row.get(4)?,
+                })
+            },
+        )?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+
+    let next_offset = if variants.len() > limit as usize {
+        variants.truncate(limit as usize);
+        Some(query.offset.saturating_add(limit))
+    } else {
+        None
+    };
+
+    access_log::record_access(&conn, AccessEvent::Query, None, variants.len() as i64)?;
+    Ok(VariantPage {
+        variants,
+        next_offset,
+        gene,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use std::fs;
+
+    /// A database holding one genome with calls in APOE, on X and on the
+    /// pseudo-autosomal XY
+    fn genome_db(dir: &Path) -> std::path::PathBuf {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\n\
+             rs1\t1\t100\tAG\n\
+             rs2\t1\t200\tCC\n\
+             rs3\t1\t300\tAA\n\
+             rs429358\t19\t45411941\tTC\n\
+             rs7412\t19\t45412079\tCC\n\
+             rs5\tX\t500\tA\n\
+             rs6\tXY\t600\tAG\n",
+        )
+        .unwrap();
+        let db_path = dir.join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db_path,
+            "genome",
+            None,
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+        db_path
+    }
+
+    fn rsids(page: &VariantPage) -> Vec<&str> {
+        page.variants
+            .iter()
+            .map(|v| v.rsid.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn panel_lists_every_gene_and_looks_up_case_insensitively() {
+        let panel = gene_panel();
+        assert_eq!(panel.len(), 25);
+        assert!(panel.windows(2).all(|w| w[0].symbol < w[1].symbol));
+        assert!(panel.iter().all(|gene| gene.start <= gene.end));
+
+        let brca1 = gene_location(" brca1 ").unwrap();
+        assert_eq!(
+            (brca1.chromosome.as_str(), brca1.start, brca1.end),
+            ("17", 41196312, 41277500)
+        );
+        assert!(gene_location("TTN").is_none());
+    }
+
+    #[test]
+    fn filters_by_rsid_region_gene_and_genotype() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = genome_db(dir.path());
+        let run = |query: VariantQuery| query_variants(&db, None, &query).unwrap();
+
+        let page = run(VariantQuery::default());
+        assert_eq!(page.variants.len(), 7);
+        assert_eq!(page.next_offset, None);
+
+        let page = run(VariantQuery {
+            rsids: vec!["rs3".to_string(), " rs1".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(rsids(&page), ["rs1", "rs3"]);
+
+        let page = run(VariantQuery {
+            chromosome: Some("chr1".to_string()),
+            start: Some(150),
+            end: Some(300),
+            ..Default::default()
+        });
+        assert_eq!(rsids(&page), ["rs2", "rs3"]);
+
+        // X includes the pseudo-autosomal calls stored as XY
+        let page = run(VariantQuery {
+            chromosome: Some("X".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(rsids(&page), ["rs5", "rs6"]);
+
+        let page = run(VariantQuery {
+            gene: Some("apoe".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(rsids(&page), ["rs429358", "rs7412"]);
+        assert_eq!(page.gene.unwrap().symbol, "APOE");
+
+        let page = run(VariantQuery {
+            genotype: Some("c?".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(rsids(&page), ["rs2", "rs7412"]);
+
+        // Filters combine
+        let page = run(VariantQuery {
+            gene: Some("APOE".to_string()),
+            genotype: Some("*T*".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(rsids(&page), ["rs429358"]);
+    }
+
+    #[test]
+    fn rejects_bad_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = genome_db(dir.path());
+        let run = |query: VariantQuery| query_variants(&db, None, &query);
+
+        assert!(matches!(
+            run(VariantQuery {
+                gene: Some("TTN".to_string()),
+                ..Default::default()
+            }),
+            Err(BiovaultError::NotFound(_))
+        ));
+        assert!(matches!(
+            run(VariantQuery {
+                start: Some(100),
+                ..Default::default()
+            }),
+            Err(BiovaultError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            run(VariantQuery {
+                chromosome: Some("1".to_string()),
+                start: Some(300),
+                end: Some(100),
+                ..Default::default()
+            }),
+            Err(BiovaultError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            run(VariantQuery {
+                rsids: vec!["1234".to_string()],
+                ..Default::default()
+            }),
+            Err(BiovaultError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            run(VariantQuery {
+                genotype: Some("A%".to_string()),
+                ..Default::default()
+            }),
+            Err(BiovaultError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn pages_follow_next_offset_to_the_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = genome_db(dir.path());
+
+        let mut seen = Vec::new();
+        let mut offsets = Vec::new();
+        let mut query = VariantQuery {
+            limit: 3,
+            ..Default::default()
+        };
+        loop {
+            let page = query_variants(&db, None, &query).unwrap();
+            assert!(page.variants.len() <= 3);
+            seen.extend(rsids(&page).into_iter().map(str::to_string));
+            match page.next_offset {
+                Some(next) => {
+                    offsets.push(next);
+                    query.offset = next;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(offsets, [3, 6]);
+        assert_eq!(
+            seen,
+            ["rs1", "rs2", "rs3", "rs429358", "rs7412", "rs5", "rs6"]
+        );
+
+        // An exactly full last page has no next page
+        let page = query_variants(
+            &db,
+            None,
+            &VariantQuery {
+                offset: 4,
+                limit: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(page.variants.len(), 3);
+        assert_eq!(page.next_offset, None);
+    }
+}
@@ -0,0 +1,949 @@
+use crate::analysis::{
+    AnalysisResult, ClinVarVariant, GeneGroup, Zygosity, get_significance_label,
+};
+use crate::consent::{self, Granularity, Purpose};
+use crate::database::open_genome_database;
+use crate::encoding::{CHROM_X, CHROM_Y};
+use crate::error::BiovaultError;
+use crate::genomes::{self, GenomeRecord};
+use crate::history::{self, AnalysisRun, CLINVAR_ANALYSIS, LIBRARY_VERSION};
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use tracing::info;
+
+/// Significance categories in report order, with their display labels
+const SIGNIFICANCE_ORDER: [(&str, &str); 5] = [
+    ("Pathogenic", "Pathogenic"),
+    ("Likely_pathogenic", "Likely pathogenic"),
+    ("Uncertain_significance", "Uncertain significance"),
+    ("Conflicting", "Conflicting interpretations"),
+    ("Benign", "Benign / likely benign"),
+];
+
+const DISCLAIMERS: [&str; 4] = [
+    "This report is for research and educational use. It is not a diagnostic test and has not \
+     been reviewed by a clinician.",
+    "Consumer genotyping arrays have a meaningful false-positive rate for rare variants. Confirm \
+     any finding of concern with clinical-grade testing before acting on it.",
+    "Variants are matched to ClinVar by rsID only. ClinVar classifications change over time and \
+     reflect the submitted evidence, not your personal risk.",
+    "Not finding a variant does not mean you do not carry one: arrays test a small fraction of \
+     the genome.",
+];
+
+/// Output formats of the findings report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    /// Single self-contained HTML file with inline styles
+    Html,
+    /// PDF using the standard Helvetica fonts
+    Pdf,
+}
+
+impl ReportFormat {
+    /// Parse the format names used by the CLI and FFI
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "html" | "htm" => Some(ReportFormat::Html),
+            "pdf" => Some(ReportFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from an output file extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_name)
+    }
+}
+
+/// Call-level quality metrics of one genome
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
+pub struct SampleQc {
+    pub total_calls: i64,
+    pub no_calls: i64,
+    pub call_rate: f64,
+    pub rsid_coverage: f64,
+    pub chromosomes: i64,
+    pub autosomal_heterozygosity: f64,
+    pub x_heterozygosity: Option<f64>,
+    pub y_calls: i64,
+    pub indel_calls: i64,
+    /// "male", "female" or "undetermined", from X heterozygosity and Y calls
+    pub inferred_sex: String,
+}
+
+/// Everything a findings report shows
+#[derive(Debug, Clone)]
+pub struct FindingsReport {
+    pub genome: GenomeRecord,
+    pub run: AnalysisRun,
+    pub result: AnalysisResult,
+    pub qc: SampleQc,
+    pub generated_at: String,
+}
+
+/// Gather a genome, one of its stored ClinVar runs and QC metrics.
+/// Without `run_id` the most recent ClinVar run of the genome is used.
+pub fn build_report(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    run_id: Option<i64>,
+) -> Result<FindingsReport, BiovaultError> {
+    let conn = open_genome_database(Path::new(user_db_path), user_db_key)?;
+    consent::require_consent(
+        &conn,
+        Purpose::Report,
+        consent::OWNER,
+        Granularity::Individual,
+    )?;
+    let stored = match run_id {
+        Some(run_id) => history::load_analysis_run(user_db_path, user_db_key, run_id)?
+            .ok_or_else(|| BiovaultError::NotFound(format!("Analysis run {}", run_id)))?,
+        None => {
+            let genome_id = genomes::resolve_genome_id_at(user_db_path, user_db_key, genome_id)?;
+            history::latest_analysis_run(user_db_path, user_db_key, CLINVAR_ANALYSIS, genome_id)?
+                .ok_or_else(|| {
+                    BiovaultError::NotFound(format!(
+                        "Stored ClinVar analysis for genome {}",
+                        genome_id
+                    ))
+                })?
+        }
+    };
+
+    if let (Some(requested), Some(analysed)) = (genome_id, stored.run.genome_id)
+        && requested != analysed
+    {
+        return Err(BiovaultError::InvalidArgument(format!(
+            "Analysis run {} belongs to genome {}, not {}",
+            stored.run.id, analysed, requested
+        )));
+    }
+
+    let genome = genomes::load_genome(&conn, stored.run.genome_id.or(genome_id))?;
+    let qc = sample_qc(&conn, &genome)?;
+
+    Ok(FindingsReport {
+        genome,
+        run: stored.run,
+        result: stored.result,
+        qc,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Build a report and write it to `output_path`, which must not exist yet
+pub fn write_report(
+    user_db_path: &str,
+    user_db_key: Option<&str>,
+    genome_id: Option<i64>,
+    run_id: Option<i64>,
+    format: ReportFormat,
+    output_path: &str,
+) -> Result<(), BiovaultError> {
+    let path = Path::new(output_path);
+    if path.exists() {
+        return Err(BiovaultError::AlreadyExists(path.to_path_buf()));
+    }
+
+    let report = build_report(user_db_path, user_db_key, genome_id, run_id)?;
+    let bytes = match format {
+        ReportFormat::Html => render_html(&report).into_bytes(),
+        ReportFormat::Pdf => render_pdf(&report),
+    };
+
+    let mut file = File::create(path)?;
+    file.write_all(&bytes)?;
+
+    info!("wrote {:?} report to {:?}", format, path);
+    Ok(())
+}
+
+fn sample_qc(
+    conn: &rusqlite::Connection,
+    genome: &GenomeRecord,
+) -> Result<SampleQc, BiovaultError> {
+    let mut stmt = conn.prepare(
+        "SELECT v.chromosome, g.genotype, COUNT(*)
+         FROM variants v
+         JOIN genotype_codes g ON g.code = v.genotype
+         WHERE v.file_id = ?1
+         GROUP BY v.chromosome, v.genotype",
+    )?;
+    let rows = stmt.query_map([genome.id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+
+    let mut total_calls = 0;
+    let mut no_calls = 0;
+    let mut indel_calls = 0;
+    let mut y_calls = 0;
+    // (heterozygous, homozygous) diploid SNP calls
+    let mut autosomal = (0i64, 0i64);
+    let mut x = (0i64, 0i64);
+    let mut chromosomes = HashSet::new();
+
+    for row in rows {
+        let (chromosome, genotype, count) = row?;
+        total_calls += count;
+        chromosomes.insert(chromosome);
+
+        if genotype == "--" {
+            no_calls += count;
+            continue;
+        }
+        if genotype.contains('I') || genotype.contains('D') {
+            indel_calls += count;
+            continue;
+        }
+        if chromosome == CHROM_Y {
+            y_calls += count;
+        }
+
+        let alleles: Vec<char> = genotype.chars().collect();
+        if alleles.len() != 2 {
+            continue;
+        }
+        let tally = match chromosome {
+            1..=22 => &mut autosomal,
+            CHROM_X => &mut x,
+            _ => continue,
+        };
+        if alleles[0] == alleles[1] {
+            tally.1 += count;
+        } else {
+            tally.0 += count;
+        }
+    }
+
+    let ratio = |part: i64, whole: i64| {
+        if whole > 0 {
+            part as f64 / whole as f64
+        } else {
+            0.0
+        }
+    };
+    let x_heterozygosity = (x.0 + x.1 > 0).then(|| ratio(x.0, x.0 + x.1));
+    // Males have (almost) no heterozygous X calls outside the PAR and do have Y calls
+    let inferred_sex = match x_heterozygosity {
+        Some(het) if het < 0.03 && y_calls > 0 => "male",
+        Some(het) if het > 0.1 && y_calls == 0 => "female",
+        _ => "undetermined",
+    };
+
+    Ok(SampleQc {
+        total_calls,
+        no_calls,
+        call_rate: ratio(total_calls - no_calls, total_calls),
+        rsid_coverage: ratio(genome.rsid_count, genome.total_variants),
+        chromosomes: chromosomes.len() as i64,
+        autosomal_heterozygosity: ratio(autosomal.0, autosomal.0 + autosomal.1),
+        x_heterozygosity,
+        y_calls,
+        indel_calls,
+        inferred_sex: inferred_sex.to_string(),
+    })
+}
+
+/// Matches and carried matches per significance category
+fn significance_breakdown(result: &AnalysisResult) -> Vec<(&'static str, usize, usize)> {
+    SIGNIFICANCE_ORDER
+        .iter()
+        .map(|(category, label)| {
+            let in_category: Vec<&ClinVarVariant> = result
+                .matches
+                .iter()
+                .filter(|v| get_significance_label(&v.clnsig) == *category)
+                .collect();
+            let carried = in_category.iter().filter(|v| is_carried(v)).count();
+            (*label, in_category.len(), carried)
+        })
+        .collect()
+}
+
+fn is_carried(variant: &ClinVarVariant) -> bool {
+    matches!(
+        variant.zygosity(),
+        Zygosity::Homozygous | Zygosity::Heterozygous | Zygosity::Hemizygous
+    )
+}
+
+fn zygosity_label(zygosity: Zygosity) -> &'static str {
+    match zygosity {
+        Zygosity::Homozygous => "homozygous",
+        Zygosity::Heterozygous => "heterozygous",
+        Zygosity::Hemizygous => "hemizygous",
+        Zygosity::NonCarrier => "not carried",
+        Zygosity::Unknown => "unknown",
+    }
+}
+
+fn percent(value: f64) -> String {
+    format!("{:.1}%", value * 100.0)
+}
+
+fn subject_name(genome: &GenomeRecord) -> &str {
+    genome.person.as_deref().unwrap_or(&genome.name)
+}
+
+fn summary_rows(report: &FindingsReport) -> Vec<(&'static str, String)> {
+    let genome = &report.genome;
+    let run = &report.run;
+    vec![
+        ("Subject", subject_name(genome).to_string()),
+        ("Genome", format!("{} (id {})", genome.name, genome.id)),
+        ("Source format", genome.source_format.clone()),
+        (
+            "Assembly",
+            genome
+                .assembly
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+        ),
+        ("Imported", genome.upload_date.clone()),
+        ("ClinVar release", run.clinvar_release.clone()),
+        (
+            "ClinVar checksum",
+            run.clinvar_checksum.chars().take(16).collect(),
+        ),
+        ("Analysis run", format!("{} ({})", run.id, run.created_at)),
+        (
+            "rsIDs searched / matched",
+            format!(
+                "{} / {}",
+                report.result.rsids_searched, report.result.matches_found
+            ),
+        ),
+        (
+            "Generated",
+            format!("{} by biovault {}", report.generated_at, LIBRARY_VERSION),
+        ),
+    ]
+}
+
+fn qc_rows(qc: &SampleQc) -> Vec<(&'static str, String)> {
+    vec![
+        ("Total calls", qc.total_calls.to_string()),
+        ("Call rate", percent(qc.call_rate)),
+        ("No-calls", qc.no_calls.to_string()),
+        ("Calls with rsID", percent(qc.rsid_coverage)),
+        ("Chromosomes", qc.chromosomes.to_string()),
+        (
+            "Autosomal heterozygosity",
+            percent(qc.autosomal_heterozygosity),
+        ),
+        (
+            "X heterozygosity",
+            qc.x_heterozygosity
+                .map(percent)
+                .unwrap_or_else(|| "no X calls".to_string()),
+        ),
+        ("Y calls", qc.y_calls.to_string()),
+        ("Indel calls", qc.indel_calls.to_string()),
+        ("Inferred sex", qc.inferred_sex.clone()),
+    ]
+}
+
+fn gene_summary(group: &GeneGroup) -> String {
+    let mut summary = format!(
+        "{} variant(s), most significant: {}",
+        group.total_variants,
+        group.most_significant.replace('_', " ")
+    );
+    if !group.conditions.is_empty() {
+        let _ = write!(summary, "; {}", group.conditions.join(", "));
+    }
+    summary
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,Helvetica,Arial,sans-serif;\
+max-width:960px;margin:2em auto;padding:0 1em;color:#222}\
+h1{font-size:1.6em}h2{border-bottom:1px solid #ccc;padding-bottom:.2em;margin-top:1.6em}\
+table{border-collapse:collapse;width:100%;margin:.5em 0;font-size:.9em}\
+th,td{text-align:left;padding:.3em .5em;border-bottom:1px solid #eee}th{background:#f5f5f5}\
+.bar{background:#c0392b;height:.8em;display:inline-block}\
+.sig-1{color:#c0392b;font-weight:bold}.sig-2{color:#d35400}.sig-3{color:#7f8c8d}\
+.disclaimer{background:#fff8e1;border:1px solid #f0c36d;padding:.8em 1em;font-size:.9em}\
+.gene{margin-top:1.2em}";
+
+/// Render the report as one self-contained HTML document
+pub fn render_html(report: &FindingsReport) -> String {
+    let mut html = String::new();
+    let subject = html_escape(subject_name(&report.genome));
+
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>BioVault findings report: {}</title>\n<style>{}</style>\n</head>\n<body>\n\
+         <h1>BioVault findings report</h1>\n",
+        subject, HTML_STYLE
+    );
+    let _ = writeln!(
+        html,
+        "<p class=\"disclaimer\"><strong>Not a diagnosis.</strong> {}</p>",
+        html_escape(DISCLAIMERS[0])
+    );
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    for (label, value) in summary_rows(report) {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            label,
+            html_escape(&value)
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<h2>Significance breakdown</h2>\n<table>\n\
+         <tr><th>Classification</th><th>ClinVar matches</th><th>Carried</th><th></th></tr>\n",
+    );
+    let breakdown = significance_breakdown(&report.result);
+    let widest = breakdown
+        .iter()
+        .map(|(_, n, _)| *n)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (label, matches, carried) in &breakdown {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td>\
+             <td><span class=\"bar\" style=\"width:{}px\"></span></td></tr>",
+            label,
+            matches,
+            carried,
+            matches * 200 / widest
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Sample QC</h2>\n<table>\n");
+    for (label, value) in qc_rows(&report.qc) {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            label,
+            html_escape(&value)
+        );
+    }
+    html.push_str("</table>\n");
+
+    let _ = writeln!(
+        html,
+        "<h2>Findings by gene</h2>\n<p>{} gene(s) with ClinVar matches.</p>",
+        report.result.gene_groups.len()
+    );
+    for group in &report.result.gene_groups {
+        let _ = write!(
+            html,
+            "<div class=\"gene\">\n<h3 class=\"sig-{}\">{}</h3>\n<p>{}</p>\n<table>\n\
+             <tr><th>rsID</th><th>Position</th><th>Change</th><th>Genotype</th>\
+             <th>Zygosity</th><th>Significance</th><th>Review status</th><th>Condition</th></tr>\n",
+            group.significance_score.min(3),
+            html_escape(&group.gene),
+            html_escape(&gene_summary(group))
+        );
+        for variant in &group.variants {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}:{}</td><td>{}&gt;{}</td><td>{}</td><td>{}</td>\
+                 <td>{}</td><td>{}</td><td>{}</td></tr>",
+                html_escape(&variant.rsid),
+                html_escape(&variant.chrom),
+                variant.pos,
+                html_escape(&variant.ref_allele),
+                html_escape(&variant.alt_allele),
+                html_escape(variant.user_genotype.as_deref().unwrap_or("")),
+                zygosity_label(variant.zygosity()),
+                html_escape(&variant.clnsig.replace('_', " ")),
+                html_escape(&variant.clnrevstat.replace('_', " ")),
+                html_escape(&variant.condition.replace('_', " "))
+            );
+        }
+        html.push_str("</table>\n</div>\n");
+    }
+
+    html.push_str("<h2>Limitations</h2>\n<div class=\"disclaimer\">\n<ul>\n");
+    for disclaimer in DISCLAIMERS {
+        let _ = writeln!(html, "<li>{}</li>", html_escape(disclaimer));
+    }
+    html.push_str("</ul>\n</div>\n</body>\n</html>\n");
+
+    html
+}
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+/// Minimal top-to-bottom text layout over A4 pages
+struct PdfLayout {
+    pages: Vec<Content>,
+    y: f32,
+}
+
+impl PdfLayout {
+    fn new() -> Self {
+        let mut layout = PdfLayout {
+            pages: Vec::new(),
+            y: 0.0,
+        };
+        layout.new_page();
+        layout
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Content::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn page(&mut self) -> &mut Content {
+        self.pages.last_mut().expect("layout always has a page")
+    }
+
+    /// Move down by `height`, starting a new page if it does not fit
+    fn advance(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+        self.y -= height;
+    }
+
+    fn text_at(&mut self, x: f32, font: Name, size: f32, text: &str) {
+        let y = self.y;
+        let bytes = pdf_text(text);
+        self.page()
+            .begin_text()
+            .set_font(font, size)
+            .next_line(x, y)
+            .show(Str(&bytes))
+            .end_text();
+    }
+
+    fn heading(&mut self, text: &str, size: f32) {
+        self.advance(size * 1.8);
+        self.text_at(MARGIN, BOLD, size, text);
+    }
+
+    /// Wrapped text; Helvetica averages about half an em per character
+    fn paragraph(&mut self, text: &str, size: f32) {
+        let max_chars = ((PAGE_WIDTH - 2.0 * MARGIN) / (size * 0.5)) as usize;
+        for line in wrap(text, max_chars) {
+            self.advance(size * 1.4);
+            self.text_at(MARGIN, REGULAR, size, &line);
+        }
+    }
+
+    /// One table row; each cell is clipped to the space before the next column
+    fn row(&mut self, font: Name, size: f32, cells: &[(f32, &str)]) {
+        self.advance(size * 1.5);
+        for (index, (x, text)) in cells.iter().enumerate() {
+            let end = cells
+                .get(index + 1)
+                .map(|(next, _)| *next)
+                .unwrap_or(PAGE_WIDTH - MARGIN);
+            let max_chars = ((end - x - 4.0) / (size * 0.5)).max(1.0) as usize;
+            let clipped: String = if text.chars().count() > max_chars {
+                let mut s: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+                s.push('~');
+                s
+            } else {
+                text.to_string()
+            };
+            self.text_at(MARGIN + x, font, size, &clipped);
+        }
+    }
+
+    /// Horizontal bar on the current row
+    fn bar(&mut self, x: f32, width: f32, height: f32) {
+        let y = self.y;
+        self.page()
+            .set_fill_rgb(0.75, 0.22, 0.17)
+            .rect(MARGIN + x, y, width, height)
+            .fill_nonzero()
+            .set_fill_rgb(0.0, 0.0, 0.0);
+    }
+
+    fn finish(mut self, title: &str) -> Vec<u8> {
+        let catalog_id = Ref::new(1);
+        let tree_id = Ref::new(2);
+        let regular_id = Ref::new(3);
+        let bold_id = Ref::new(4);
+        let info_id = Ref::new(5);
+
+        let page_count = self.pages.len();
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(tree_id);
+        pdf.document_info(info_id)
+            .title(TextStr(title))
+            .producer(TextStr("biovault"));
+        pdf.type1_font(regular_id)
+            .base_font(Name(b"Helvetica"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.type1_font(bold_id)
+            .base_font(Name(b"Helvetica-Bold"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+        let page_ids: Vec<Ref> = (0..page_count)
+            .map(|i| Ref::new(6 + 2 * i as i32))
+            .collect();
+        pdf.pages(tree_id)
+            .kids(page_ids.iter().copied())
+            .count(page_count as i32);
+
+        for (index, mut content) in self.pages.drain(..).enumerate() {
+            let footer = pdf_text(&format!(
+                "Page {} of {} - not for clinical use",
+                index + 1,
+                page_count
+            ));
+            content
+                .begin_text()
+                .set_font(REGULAR, 8.0)
+                .next_line(MARGIN, MARGIN / 2.0)
+                .show(Str(&footer))
+                .end_text();
+
+            let page_id = page_ids[index];
+            let content_id = Ref::new(page_id.get() + 1);
+            let mut page = pdf.page(page_id);
+            page.parent(tree_id)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(content_id);
+            page.resources()
+                .fonts()
+                .pair(REGULAR, regular_id)
+                .pair(BOLD, bold_id);
+            drop(page);
+            pdf.stream(content_id, &content.finish());
+        }
+
+        pdf.finish()
+    }
+}
+
+/// Encode text for the WinAnsi standard fonts; other characters become '?'
+fn pdf_text(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Render the report as a PDF document
+pub fn render_pdf(report: &FindingsReport) -> Vec<u8> {
+    let mut layout = PdfLayout::new();
+    let title = format!("BioVault findings report: {}", subject_name(&report.genome));
+
+    layout.heading("BioVault findings report", 18.0);
+    layout.paragraph(&format!("Not a diagnosis. {}", DISCLAIMERS[0]), 9.0);
+
+    layout.heading("Summary", 13.0);
+    for (label, value) in summary_rows(report) {
+        layout.row(REGULAR, 9.0, &[(0.0, label), (140.0, &value)]);
+    }
+
+    layout.heading("Significance breakdown", 13.0);
+    layout.row(
+        BOLD,
+        9.0,
+        &[
+            (0.0, "Classification"),
+            (150.0, "Matches"),
+            (210.0, "Carried"),
+        ],
+    );
+    let breakdown = significance_breakdown(&report.result);
+    let widest = breakdown
+        .iter()
+        .map(|(_, n, _)| *n)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (label, matches, carried) in &breakdown {
+        layout.row(
+            REGULAR,
+            9.0,
+            &[
+                (0.0, label),
+                (150.0, &matches.to_string()),
+                (210.0, &carried.to_string()),
+            ],
+        );
+        let width = *matches as f32 * 200.0 / widest as f32;
+        if width > 0.0 {
+            layout.bar(270.0, width, 7.0);
+        }
+    }
+
+    layout.heading("Sample QC", 13.0);
+    for (label, value) in qc_rows(&report.qc) {
+        layout.row(REGULAR, 9.0, &[(0.0, label), (140.0, &value)]);
+    }
+
+    layout.heading("Findings by gene", 13.0);
+    layout.paragraph(
+        &format!(
+            "{} gene(s) with ClinVar matches.",
+            report.result.gene_groups.len()
+        ),
+        9.0,
+    );
+    let columns = [0.0, 60.0, 135.0, 175.0, 215.0, 285.0, 385.0];
+    for group in &report.result.gene_groups {
+        layout.heading(&group.gene, 11.0);
+        layout.paragraph(&gene_summary(group), 8.0);
+        let header = [
+            "rsID",
+            "Position",
+            "Change",
+            "Genotype",
+            "Zygosity",
+            "Significance",
+            "Condition",
+        ];
+        let cells: Vec<(f32, &str)> = columns.iter().copied().zip(header).collect();
+        layout.row(BOLD, 8.0, &cells);
+        for variant in &group.variants {
+            let values = [
+                variant.rsid.clone(),
+                format!("{}:{}", variant.chrom, variant.pos),
+                format!("{}>{}", variant.ref_allele, variant.alt_allele),
+                variant.user_genotype.clone().unwrap_or_default(),
+                zygosity_label(variant.zygosity()).to_string(),
+                variant.clnsig.replace('_', " "),
+                variant.condition.replace('_', " "),
+            ];
+            let cells: Vec<(f32, &str)> = columns
+                .iter()
+                .copied()
+                .zip(values.iter().map(String::as_str))
+                .collect();
+            layout.row(REGULAR, 8.0, &cells);
+        }
+    }
+
+    layout.heading("Limitations", 13.0);
+    for disclaimer in DISCLAIMERS {
+        layout.paragraph(&format!("- {}", disclaimer), 9.0);
+    }
+
+    layout.finish(&title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genomes::{DuplicatePolicy, import_genome};
+    use rusqlite::Connection;
+    use std::fs;
+
+    /// A genome with a stored ClinVar run whose gene and condition names
+    /// need escaping
+    fn stored_run(dir: &Path) -> (String, String) {
+        let input = dir.join("genome.txt");
+        fs::write(
+            &input,
+            "# rsid\tchromosome\tposition\tgenotype\n\
+             rs1\t1\t100\tAG\n\
+             rs2\t2\t200\tCC\n\
+             rs3\tX\t300\tA\n",
+        )
+        .unwrap();
+        let db = dir.join("genome.sqlite");
+        import_genome(
+            input.to_str().unwrap(),
+            &db,
+            "genome",
+            Some("Ann & Bo"),
+            None,
+            DuplicatePolicy::Skip,
+        )
+        .unwrap();
+
+        let clinvar = dir.join("clinvar_2024-05.sqlite");
+        Connection::open(&clinvar)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE variants (rsid TEXT, chrom TEXT, pos INTEGER, ref TEXT, alt TEXT,
+                     gene TEXT, clnsig TEXT, clnrevstat TEXT, condition TEXT);
+                 INSERT INTO variants VALUES ('rs1', '1', 100, 'A', 'G', '<b>GENE1</b>',
+                     'Pathogenic', 'reviewed_by_expert_panel', 'Breast_&_\"ovarian\"_cancer');
+                 INSERT INTO variants VALUES ('rs2', '2', 200, 'C', 'T', 'GENE2',
+                     'Benign', 'criteria_provided,_single_submitter', 'not_provided');",
+            )
+            .unwrap();
+        let stored = history::analyze_and_store_clinvar(
+            db.to_str().unwrap(),
+            None,
+            None,
+            clinvar.to_str().unwrap(),
+        )
+        .unwrap();
+        (
+            db.to_string_lossy().into_owned(),
+            stored.run.clinvar_release,
+        )
+    }
+
+    fn contains(bytes: &[u8], text: &str) -> bool {
+        bytes
+            .windows(text.len())
+            .any(|window| window == text.as_bytes())
+    }
+
+    #[test]
+    fn html_reports_show_the_run_and_escape_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, release) = stored_run(dir.path());
+        let output = dir.path().join("report.html");
+        write_report(
+            &db,
+            None,
+            None,
+            None,
+            ReportFormat::Html,
+            output.to_str().unwrap(),
+        )
+        .unwrap();
+        let html = fs::read_to_string(&output).unwrap();
+
+        assert!(html.contains(&format!(
+            "<tr><th>ClinVar release</th><td>{}</td></tr>",
+            release
+        )));
+        assert!(html.contains("<tr><th>Total calls</th><td>3</td></tr>"));
+        assert!(html.contains("<tr><th>Inferred sex</th>"));
+        assert!(html.contains("<tr><td>Pathogenic</td><td>1</td><td>1</td>"));
+        assert!(html.contains("<tr><td>Benign / likely benign</td><td>1</td><td>0</td>"));
+        for disclaimer in DISCLAIMERS {
+            assert!(html.contains(disclaimer), "{}", disclaimer);
+        }
+
+        assert!(html.contains("<title>BioVault findings report: Ann &amp; Bo</title>"));
+        assert!(html.contains("&lt;b&gt;GENE1&lt;/b&gt;"));
+        assert!(html.contains("Breast &amp; &quot;ovarian&quot; cancer"));
+        assert!(!html.contains("<b>"));
+
+        let err = write_report(
+            &db,
+            None,
+            None,
+            None,
+            ReportFormat::Html,
+            output.to_str().unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), crate::error::codes::ALREADY_EXISTS);
+    }
+
+    #[test]
+    fn pdf_reports_show_the_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, release) = stored_run(dir.path());
+        let output = dir.path().join("report.pdf");
+        write_report(
+            &db,
+            None,
+            Some(1),
+            None,
+            ReportFormat::Pdf,
+            output.to_str().unwrap(),
+        )
+        .unwrap();
+        let pdf = fs::read(&output).unwrap();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        for text in [
+            "(ClinVar release)",
+            release.as_str(),
+            "(Significance breakdown)",
+            "(Pathogenic)",
+            "(Sample QC)",
+            "(Call rate)",
+            "(<b>GENE1</b>)",
+            "Page 1 of",
+        ] {
+            assert!(contains(&pdf, text), "{}", text);
+        }
+        for disclaimer in DISCLAIMERS {
+            // Paragraphs are wrapped, so look for the start of each
+            assert!(contains(&pdf, &disclaimer[..40]), "{}", disclaimer);
+        }
+    }
+
+    #[test]
+    fn reports_need_a_stored_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, _) = stored_run(dir.path());
+        let output = dir.path().join("report.html");
+
+        let err = write_report(
+            &db,
+            None,
+            None,
+            Some(99),
+            ReportFormat::Html,
+            output.to_str().unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), crate::error::codes::NOT_FOUND);
+        assert!(!output.exists());
+        assert_eq!(ReportFormat::from_path("out.PDF"), Some(ReportFormat::Pdf));
+        assert_eq!(ReportFormat::from_path("out.txt"), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! Where genome databases live.
+//!
+//! Native builds keep databases in files. The WebAssembly build has no file
+//! system, so its databases live in memory or in the browser's origin private
+//! file system (OPFS) and are moved in and out as bytes.
+
+use crate::error::BiovaultError;
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+#[cfg(not(target_family = "wasm"))]
+use std::path::PathBuf;
+
+#[cfg(target_family = "wasm")]
+use sqlite_wasm_rs::{MemVfsUtil, WasmOsCallback};
+#[cfg(target_family = "wasm")]
+use sqlite_wasm_vfs::sahpool::{self, OpfsSAHPoolCfg, OpfsSAHPoolUtil};
+
+/// Location of a SQLite database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// A database file on disk
+    #[cfg(not(target_family = "wasm"))]
+    File(PathBuf),
+    /// An in-memory database, kept under its name until it is removed
+    #[cfg(target_family = "wasm")]
+    Memory(String),
+    /// A database persisted in OPFS. Needs `install_opfs` first.
+    #[cfg(target_family = "wasm")]
+    Opfs(String),
+}
+
+/// Name of the VFS `install_opfs` registers
+#[cfg(target_family = "wasm")]
+const OPFS_VFS: &str = "opfs-sahpool";
+
+#[cfg(target_family = "wasm")]
+thread_local! {
+    static OPFS: std::cell::OnceCell<OpfsSAHPoolUtil> = const { std::cell::OnceCell::new() };
+}
+
+/// Register the OPFS VFS so `Storage::Opfs` can be used. OPFS access handles
+/// are only available in a dedicated Web Worker.
+#[cfg(target_family = "wasm")]
+pub async fn install_opfs() -> Result<(), BiovaultError> {
+    if OPFS.with(|opfs| opfs.get().is_some()) {
+        return Ok(());
+    }
+    let config = OpfsSAHPoolCfg {
+        vfs_name: OPFS_VFS.to_string(),
+        ..OpfsSAHPoolCfg::default()
+    };
+    let util = sahpool::install::<WasmOsCallback>(&config, false)
+        .await
+        .map_err(|e| vfs_error(Path::new(OPFS_VFS), e))?;
+    OPFS.with(|opfs| {
+        let _ = opfs.set(util);
+    });
+    Ok(())
+}
+
+#[cfg(target_family = "wasm")]
+fn vfs_error(path: &Path, error: impl std::fmt::Display) -> BiovaultError {
+    BiovaultError::io(path, std::io::Error::other(error.to_string()))
+}
+
+#[cfg(target_family = "wasm")]
+fn memory_vfs() -> MemVfsUtil<WasmOsCallback> {
+    MemVfsUtil::new()
+}
+
+#[cfg(target_family = "wasm")]
+fn with_opfs<T>(
+    name: &str,
+    f: impl FnOnce(&OpfsSAHPoolUtil) -> Result<T, sahpool::OpfsSAHError>,
+) -> Result<T, BiovaultError> {
+    OPFS.with(|opfs| {
+        let util = opfs.get().ok_or_else(|| {
+            BiovaultError::InvalidArgument("OPFS storage is not installed".into())
+        })?;
+        f(util).map_err(|e| vfs_error(Path::new(name), e))
+    })
+}
+
+impl Storage {
+    /// Path or name of the database, as shown in errors and results
+    pub fn path(&self) -> &Path {
+        match self {
+            #[cfg(not(target_family = "wasm"))]
+            Storage::File(path) => path,
+            #[cfg(target_family = "wasm")]
+            Storage::Memory(name) | Storage::Opfs(name) => Path::new(name),
+        }
+    }
+
+    /// Whether the database has been created
+    pub fn exists(&self) -> Result<bool, BiovaultError> {
+        match self {
+            #[cfg(not(target_family = "wasm"))]
+            Storage::File(path) => Ok(path.exists()),
+            #[cfg(target_family = "wasm")]
+            Storage::Memory(name) => Ok(memory_vfs().exists(name)),
+            #[cfg(target_family = "wasm")]
+            Storage::Opfs(name) => with_opfs(name, |opfs| opfs.exists(name)),
+        }
+    }
+
+    /// Open a connection to the database
+    pub(crate) fn open(&self, flags: OpenFlags) -> Result<Connection, BiovaultError> {
+        let conn = match self {
+            #[cfg(not(target_family = "wasm"))]
+            Storage::File(path) => Connection::open_with_flags(path, flags)?,
+            #[cfg(target_family = "wasm")]
+            Storage::Memory(name) => Connection::open_with_flags(name, flags)?,
+            #[cfg(target_family = "wasm")]
+            Storage::Opfs(name) => {
+                with_opfs(name, |_| Ok(()))?;
+                Connection::open_with_flags_and_vfs(name, flags, OPFS_VFS)?
+            }
+        };
+        Ok(conn)
+    }
+
+    /// Delete the database. All connections to it must be closed.
+    pub fn remove(&self) -> Result<(), BiovaultError> {
+        match self {
+            #[cfg(not(target_family = "wasm"))]
+            Storage::File(path) => {
+                std::fs::remove_file(path).map_err(|e| BiovaultError::io(path, e))
+            }
+            #[cfg(target_family = "wasm")]
+            Storage::Memory(name) => {
+                memory_vfs().delete_db(name);
+                Ok(())
+            }
+            #[cfg(target_family = "wasm")]
+            Storage::Opfs(name) => with_opfs(name, |opfs| opfs.delete_db(name)).map(|_| ()),
+        }
+    }
+
+    /// Create the database from the bytes of a SQLite file
+    #[cfg(target_family = "wasm")]
+    pub fn import_bytes(&self, bytes: &[u8]) -> Result<(), BiovaultError> {
+        if self.exists()? {
+            return Err(BiovaultError::AlreadyExists(self.path().to_path_buf()));
+        }
+        match self {
+            Storage::Memory(name) => memory_vfs()
+                .import_db(name, bytes)
+                .map_err(|e| vfs_error(self.path(), e)),
+            Storage::Opfs(name) => with_opfs(name, |opfs| opfs.import_db(name, bytes)),
+        }
+    }
+
+    /// The database as the bytes of a SQLite file
+    #[cfg(target_family = "wasm")]
+    pub fn export_bytes(&self) -> Result<Vec<u8>, BiovaultError> {
+        match self {
+            Storage::Memory(name) => memory_vfs()
+                .export_db(name)
+                .map_err(|e| vfs_error(self.path(), e)),
+            Storage::Opfs(name) => with_opfs(name, |opfs| opfs.export_db(name)),
+        }
+    }
+}
+
+/// Paths name files natively and in-memory databases in the WebAssembly build
+impl From<&Path> for Storage {
+    fn from(path: &Path) -> Self {
+        #[cfg(not(target_family = "wasm"))]
+        return Storage::File(path.to_path_buf());
+        #[cfg(target_family = "wasm")]
+        return Storage::Memory(path.to_string_lossy().into_owned());
+    }
+}
@@ -0,0 +1,146 @@
+//! Browser API generated with wasm-bindgen.
+//!
+//! Mirrors `process_23andme` and `analyze_clinvar_safe`, but files are passed
+//! as bytes and databases live in memory or OPFS (see `Storage`). Errors are
+//! thrown as JS `Error`s with the stable error `code` and `context` attached,
+//! like the result envelopes of the mobile API.
+
+use crate::analysis;
+use crate::error::BiovaultError;
+use crate::genomes::{self, DuplicatePolicy, GenomeFile};
+use crate::progress::Progress;
+use crate::storage::{self, Storage};
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+/// Where `process23andme` creates its database
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    /// Kept in memory until deleted or the page is closed
+    Memory,
+    /// Persisted in OPFS; needs `installOpfs()` first
+    Opfs,
+}
+
+/// Handle to a SQLite database in memory or OPFS
+#[wasm_bindgen]
+pub struct Database {
+    storage: Storage,
+}
+
+#[wasm_bindgen]
+impl Database {
+    /// An in-memory database called `name`
+    #[wasm_bindgen(js_name = inMemory)]
+    pub fn in_memory(name: String) -> Database {
+        Database {
+            storage: Storage::Memory(name),
+        }
+    }
+
+    /// A database called `name` persisted in OPFS
+    pub fn opfs(name: String) -> Database {
+        Database {
+            storage: Storage::Opfs(name),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.storage.path().to_string_lossy().into_owned()
+    }
+
+    pub fn exists(&self) -> Result<bool, JsValue> {
+        self.storage.exists().map_err(js_error)
+    }
+
+    /// Create the database from the bytes of a SQLite file, e.g. a
+    /// downloaded ClinVar database
+    #[wasm_bindgen(js_name = importBytes)]
+    pub fn import_bytes(&self, bytes: &[u8]) -> Result<(), JsValue> {
+        self.storage.import_bytes(bytes).map_err(js_error)
+    }
+
+    /// The database as the bytes of a SQLite file, e.g. to download it
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        self.storage.export_bytes().map_err(js_error)
+    }
+
+    /// Delete the database
+    pub fn delete(&self) -> Result<(), JsValue> {
+        self.storage.remove().map_err(js_error)
+    }
+}
+
+/// Register the OPFS storage. Only works in a dedicated Web Worker.
+#[wasm_bindgen(js_name = installOpfs)]
+pub async fn install_opfs() -> Result<(), JsValue> {
+    storage::install_opfs().await.map_err(js_error)
+}
+
+/// Process a 23andMe file (text or ZIP) into a new genome database named
+/// like the native `process_23andme` output. Returns the created database.
+#[wasm_bindgen(js_name = process23andme)]
+pub fn process_23andme(
+    file_name: &str,
+    bytes: &[u8],
+    custom_name: &str,
+    storage: StorageKind,
+) -> Result<Database, JsValue> {
+    let timestamp = chrono::Utc::now().timestamp();
+    let db_name = format!("{}_{}.sqlite", custom_name.replace(' ', "_"), timestamp);
+    let storage = match storage {
+        StorageKind::Memory => Storage::Memory(db_name),
+        StorageKind::Opfs => Storage::Opfs(db_name),
+    };
+
+    genomes::import_genome_bytes(
+        GenomeFile {
+            name: file_name,
+            bytes,
+        },
+        &storage,
+        custom_name,
+        None,
+        None,
+        DuplicatePolicy::Skip,
+        &mut Progress::none(),
+    )
+    .map_err(js_error)?;
+
+    Ok(Database { storage })
+}
+
+/// Analyze a single-genome database against a ClinVar database. Returns the
+/// `AnalysisResult` as a plain object.
+#[wasm_bindgen(js_name = analyzeClinvar)]
+pub fn analyze_clinvar(user_db: &Database, clinvar_db: &Database) -> Result<JsValue, JsValue> {
+    let result = analysis::analyze_clinvar_storage(
+        &user_db.storage,
+        None,
+        None,
+        &clinvar_db.storage,
+        &mut Progress::none(),
+    )
+    .map_err(js_error)?;
+    to_js(&result)
+}
+
+fn to_js<T: serde::Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(JsValue::from)
+}
+
+/// A JS `Error` carrying the stable error code and context
+fn js_error(error: BiovaultError) -> JsValue {
+    let report = error.report();
+    let js_error = js_sys::Error::new(&report.message);
+    let _ = Reflect::set(&js_error, &"code".into(), &report.code.into());
+    if let Some(context) = report.context.as_ref().and_then(|c| to_js(c).ok()) {
+        let _ = Reflect::set(&js_error, &"context".into(), &context);
+    }
+    js_error.into()
+}
@@ -15,7 +15,9 @@ export interface UserGenomeDatabase {
 }
 
 /**
- * Check if a SQLite database is a valid genome database created by our Rust code
+ * Check if a SQLite database is a valid genome database created by our Rust code.
+ * Versioned databases carry a non-zero `PRAGMA user_version`; files from older app
+ * versions are unversioned and detected by their columns (Rust migrates them on open).
  */
 async function isGenomeDatabase(db: SQLite.SQLiteDatabase): Promise<boolean> {
 	try {
@@ -27,7 +29,10 @@ async function isGenomeDatabase(db: SQLite.SQLiteDatabase): Promise<boolean> {
 
 		if (!schemaCheck || schemaCheck.count < 2) return false
 
-		// Check for required columns in genome_metadata table
+		const version = await db.getFirstAsync<{ user_version: number }>('PRAGMA user_version')
+		if (version && version.user_version > 0) return true
+
+		// Legacy unversioned database: check for required columns in genome_metadata table
 		const metadataColumns = await db.getAllAsync<{ name: string }>(
 			`PRAGMA table_info(genome_metadata)`
 		)
@@ -0,0 +1,14 @@
+package expo.modules.biovault
+
+import expo.modules.kotlin.exception.CodedException
+
+/**
+ * Thrown by the Rust library for every failure. `code` is one of the stable
+ * error codes (e.g. `WRONG_KEY`, `DISK_FULL`); `context` is a JSON object
+ * with details such as the file involved, or null.
+ */
+class BiovaultException(
+  code: String,
+  message: String,
+  val context: String?
+) : CodedException(code, message, null)
@@ -3,6 +3,7 @@ package expo.modules.biovault
 import expo.modules.kotlin.modules.Module
 import expo.modules.kotlin.modules.ModuleDefinition
 import java.net.URL
+import java.util.concurrent.atomic.AtomicBoolean
 
 class ExpoBiovaultModule : Module() {
   companion object {
@@ -13,11 +14,49 @@ class ExpoBiovaultModule : Module() {
 
   external fun processGenomeFile(inputPath: String, customName: String, outputDir: String): String
   external fun analyzeClinVar(userDbPath: String, clinvarDbPath: String): String
+  external fun reanalyzeClinVar(userDbPath: String, clinvarDbPath: String): String
+  external fun analyzeAndStoreClinVar(userDbPath: String, clinvarDbPath: String): String
+  external fun listAnalysisRuns(userDbPath: String): String
+  external fun loadAnalysisRun(userDbPath: String, runId: Long): String
+  external fun deleteAnalysisRun(userDbPath: String, runId: Long): Int
+  external fun migrateGenomeDatabase(dbPath: String): Int
+  external fun processGenomeFileEncrypted(inputPath: String, customName: String, outputDir: String, key: String): String
+  external fun analyzeClinVarEncrypted(userDbPath: String, userDbKey: String, clinvarDbPath: String): String
+  external fun rekeyGenomeDatabase(dbPath: String, oldKey: String?, newKey: String?): Int
+  external fun exportDecryptedGenomeDatabase(dbPath: String, key: String, outputPath: String): Int
+  external fun importGenomeFile(inputPath: String, dbPath: String, customName: String, person: String?, key: String?, replace: Boolean, listener: ProgressListener?): String
+  external fun listGenomes(dbPath: String, key: String?): String
+  external fun analyzeClinVarGenome(userDbPath: String, userDbKey: String?, genomeId: Long, clinvarDbPath: String, listener: ProgressListener?): String
+  external fun exportGenome(dbPath: String, key: String?, genomeId: Long, format: String, outputPath: String, referenceDbPath: String?): String
+  external fun exportClinicalReport(userDbPath: String, key: String?, runId: Long, format: String): String
+  external fun generateReport(userDbPath: String, key: String?, genomeId: Long, runId: Long, format: String, outputPath: String): Int
+  external fun setLogSink(sink: LogSink?, maxLevel: Int)
   external fun rustAdd(a: Int, b: Int): Int
 
+  private val cancelRequested = AtomicBoolean(false)
+
+  // Forwards progress as `onProgress` events and polls `cancelOperations`
+  private fun progressListener(): ProgressListener {
+    cancelRequested.set(false)
+    return object : ProgressListener {
+      override fun onProgress(phase: String, processed: Long, total: Long) {
+        sendEvent("onProgress", mapOf("phase" to phase, "processed" to processed, "total" to total))
+      }
+
+      override fun isCancelled(): Boolean = cancelRequested.get()
+    }
+  }
+
   override fun definition() = ModuleDefinition {
     Name("ExpoBiovault")
 
+    Events("onProgress")
+
+    OnCreate {
+      // Info and above; Rust never logs rsIDs or genotypes
+      setLogSink(LogcatSink, 3)
+    }
+
     AsyncFunction("processGenomeFile") { inputPath: String, customName: String, outputDir: String ->
       processGenomeFile(inputPath, customName, outputDir)
     }
@@ -26,6 +65,74 @@ class ExpoBiovaultModule : Module() {
       analyzeClinVar(userDbPath, clinvarDbPath)
     }
 
+    AsyncFunction("reanalyzeClinVar") { userDbPath: String, clinvarDbPath: String ->
+      reanalyzeClinVar(userDbPath, clinvarDbPath)
+    }
+
+    AsyncFunction("analyzeAndStoreClinVar") { userDbPath: String, clinvarDbPath: String ->
+      analyzeAndStoreClinVar(userDbPath, clinvarDbPath)
+    }
+
+    AsyncFunction("listAnalysisRuns") { userDbPath: String ->
+      listAnalysisRuns(userDbPath)
+    }
+
+    AsyncFunction("loadAnalysisRun") { userDbPath: String, runId: Long ->
+      loadAnalysisRun(userDbPath, runId)
+    }
+
+    AsyncFunction("deleteAnalysisRun") { userDbPath: String, runId: Long ->
+      deleteAnalysisRun(userDbPath, runId)
+    }
+
+    AsyncFunction("migrateGenomeDatabase") { dbPath: String ->
+      migrateGenomeDatabase(dbPath)
+    }
+
+    AsyncFunction("processGenomeFileEncrypted") { inputPath: String, customName: String, outputDir: String, key: String ->
+      processGenomeFileEncrypted(inputPath, customName, outputDir, key)
+    }
+
+    AsyncFunction("analyzeClinVarMatchesEncrypted") { userDbPath: String, userDbKey: String, clinvarDbPath: String ->
+      analyzeClinVarEncrypted(userDbPath, userDbKey, clinvarDbPath)
+    }
+
+    AsyncFunction("rekeyGenomeDatabase") { dbPath: String, oldKey: String?, newKey: String? ->
+      rekeyGenomeDatabase(dbPath, oldKey, newKey)
+    }
+
+    AsyncFunction("exportDecryptedGenomeDatabase") { dbPath: String, key: String, outputPath: String ->
+      exportDecryptedGenomeDatabase(dbPath, key, outputPath)
+    }
+
+    AsyncFunction("importGenomeFile") { inputPath: String, dbPath: String, customName: String, person: String?, key: String?, replace: Boolean ->
+      importGenomeFile(inputPath, dbPath, customName, person, key, replace, progressListener())
+    }
+
+    AsyncFunction("listGenomes") { dbPath: String, key: String? ->
+      listGenomes(dbPath, key)
+    }
+
+    AsyncFunction("analyzeClinVarGenome") { userDbPath: String, userDbKey: String?, genomeId: Long, clinvarDbPath: String ->
+      analyzeClinVarGenome(userDbPath, userDbKey, genomeId, clinvarDbPath, progressListener())
+    }
+
+    AsyncFunction("exportGenome") { dbPath: String, key: String?, genomeId: Long, format: String, outputPath: String, referenceDbPath: String? ->
+      exportGenome(dbPath, key, genomeId, format, outputPath, referenceDbPath)
+    }
+
+    AsyncFunction("exportClinicalReport") { userDbPath: String, key: String?, runId: Long, format: String ->
+      exportClinicalReport(userDbPath, key, runId, format)
+    }
+
+    AsyncFunction("generateReport") { userDbPath: String, key: String?, genomeId: Long, runId: Long, format: String, outputPath: String ->
+      generateReport(userDbPath, key, genomeId, runId, format, outputPath)
+    }
+
+    Function("cancelOperations") {
+      cancelRequested.set(true)
+    }
+
     Function("rust_add") { a: Int, b: Int ->
       rustAdd(a, b)
     }
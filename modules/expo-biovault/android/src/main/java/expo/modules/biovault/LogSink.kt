@@ -0,0 +1,28 @@
+package expo.modules.biovault
+
+import android.util.Log
+
+/**
+ * Receives log events from the Rust library. `level` is 1 (error) to
+ * 5 (trace); `target` is the Rust module. Events never contain rsIDs or
+ * genotypes. May be called from any thread.
+ */
+interface LogSink {
+  fun onLog(level: Int, target: String, message: String)
+}
+
+/** Forwards Rust log events to Logcat */
+object LogcatSink : LogSink {
+  private const val TAG = "BiovaultRust"
+
+  override fun onLog(level: Int, target: String, message: String) {
+    val priority = when (level) {
+      1 -> Log.ERROR
+      2 -> Log.WARN
+      3 -> Log.INFO
+      4 -> Log.DEBUG
+      else -> Log.VERBOSE
+    }
+    Log.println(priority, TAG, message)
+  }
+}
@@ -0,0 +1,11 @@
+package expo.modules.biovault
+
+/**
+ * Receives progress from long-running Rust calls. `phase` is a stable name
+ * such as `parsing` or `clinvar_lookup`; `total` is 0 when unknown.
+ * Returning true from `isCancelled` stops the call with a `CANCELLED` error.
+ */
+interface ProgressListener {
+  fun onProgress(phase: String, processed: Long, total: Long)
+  fun isCancelled(): Boolean
+}
@@ -1,4 +1,17 @@
-import ExpoBiovaultModule, { type AnalysisResult } from './src/ExpoBiovaultModule'
+import type { EventSubscription } from 'expo'
+import ExpoBiovaultModule, {
+	type AnalysisResult,
+	type AnalysisRun,
+	type ClinVarDiff,
+	type ClinicalFormat,
+	type ExportFormat,
+	type ExportSummary,
+	type GenomeRecord,
+	type ImportOutcome,
+	type ProgressEvent,
+	type ReportFormat,
+	type StoredAnalysis,
+} from './src/ExpoBiovaultModule'
 
 // ts-prune-ignore-next
 export async function processGenomeFile(
@@ -18,6 +31,232 @@ export async function analyzeClinVarMatches(
 	return JSON.parse(resultJson)
 }
 
+// ts-prune-ignore-next
+export async function reanalyzeClinVar(
+	userDbPath: string,
+	clinvarDbPath: string
+): Promise<ClinVarDiff | null> {
+	const resultJson = await ExpoBiovaultModule.reanalyzeClinVar(userDbPath, clinvarDbPath)
+	return JSON.parse(resultJson)
+}
+
+// ts-prune-ignore-next
+export async function analyzeAndStoreClinVar(
+	userDbPath: string,
+	clinvarDbPath: string
+): Promise<StoredAnalysis> {
+	const resultJson = await ExpoBiovaultModule.analyzeAndStoreClinVar(userDbPath, clinvarDbPath)
+	return JSON.parse(resultJson)
+}
+
+// ts-prune-ignore-next
+export async function listAnalysisRuns(userDbPath: string): Promise<AnalysisRun[]> {
+	const resultJson = await ExpoBiovaultModule.listAnalysisRuns(userDbPath)
+	return JSON.parse(resultJson)
+}
+
+// ts-prune-ignore-next
+export async function loadAnalysisRun(
+	userDbPath: string,
+	runId: number
+): Promise<StoredAnalysis | null> {
+	const resultJson = await ExpoBiovaultModule.loadAnalysisRun(userDbPath, runId)
+	return JSON.parse(resultJson)
+}
+
+// ts-prune-ignore-next
+export async function deleteAnalysisRun(userDbPath: string, runId: number): Promise<boolean> {
+	return (await ExpoBiovaultModule.deleteAnalysisRun(userDbPath, runId)) === 1
+}
+
+/**
+ * Validate a genome database and upgrade its schema in place.
+ * Returns the schema version; throws `NOT_A_GENOME_DATABASE` for other files.
+ */
+// ts-prune-ignore-next
+export async function migrateGenomeDatabase(dbPath: string): Promise<number> {
+	return await ExpoBiovaultModule.migrateGenomeDatabase(dbPath)
+}
+
+/**
+ * Process a genome file into a database encrypted with `key`.
+ * The key should be kept in the iOS Keychain / Android Keystore.
+ */
+// ts-prune-ignore-next
+export async function processGenomeFileEncrypted(
+	inputPath: string,
+	customName: string,
+	outputDir: string,
+	key: string
+): Promise<string> {
+	return await ExpoBiovaultModule.processGenomeFileEncrypted(inputPath, customName, outputDir, key)
+}
+
+// ts-prune-ignore-next
+export async function analyzeClinVarMatchesEncrypted(
+	userDbPath: string,
+	userDbKey: string,
+	clinvarDbPath: string
+): Promise<AnalysisResult> {
+	const resultJson = await ExpoBiovaultModule.analyzeClinVarMatchesEncrypted(
+		userDbPath,
+		userDbKey,
+		clinvarDbPath
+	)
+	return JSON.parse(resultJson)
+}
+
+/**
+ * Change the encryption of a genome database in place.
+ * `null` means plaintext, so this can also encrypt or decrypt a database.
+ */
+// ts-prune-ignore-next
+export async function rekeyGenomeDatabase(
+	dbPath: string,
+	oldKey: string | null,
+	newKey: string | null
+): Promise<boolean> {
+	return (await ExpoBiovaultModule.rekeyGenomeDatabase(dbPath, oldKey, newKey)) === 0
+}
+
+// ts-prune-ignore-next
+export async function exportDecryptedGenomeDatabase(
+	dbPath: string,
+	key: string,
+	outputPath: string
+): Promise<boolean> {
+	return (await ExpoBiovaultModule.exportDecryptedGenomeDatabase(dbPath, key, outputPath)) === 0
+}
+
+/**
+ * Import a genome file into a database that can hold several genomes
+ * (e.g. a household), creating it if needed. Re-importing the same file is
+ * skipped unless `replace` is set. Reports progress through
+ * `addProgressListener` and can be stopped with `cancelOperations`.
+ */
+// ts-prune-ignore-next
+export async function importGenomeFile(
+	inputPath: string,
+	dbPath: string,
+	customName: string,
+	options: { person?: string; key?: string; replace?: boolean } = {}
+): Promise<ImportOutcome> {
+	const resultJson = await ExpoBiovaultModule.importGenomeFile(
+		inputPath,
+		dbPath,
+		customName,
+		options.person ?? null,
+		options.key ?? null,
+		options.replace ?? false
+	)
+	return JSON.parse(resultJson)
+}
+
+// ts-prune-ignore-next
+export async function listGenomes(dbPath: string, key: string | null = null): Promise<GenomeRecord[]> {
+	const resultJson = await ExpoBiovaultModule.listGenomes(dbPath, key)
+	return JSON.parse(resultJson)
+}
+
+/**
+ * Analyze one genome against ClinVar. Reports progress through
+ * `addProgressListener` and can be stopped with `cancelOperations`.
+ */
+// ts-prune-ignore-next
+export async function analyzeClinVarGenome(
+	userDbPath: string,
+	genomeId: number,
+	clinvarDbPath: string,
+	userDbKey: string | null = null
+): Promise<AnalysisResult> {
+	const resultJson = await ExpoBiovaultModule.analyzeClinVarGenome(
+		userDbPath,
+		userDbKey,
+		genomeId,
+		clinvarDbPath
+	)
+	return JSON.parse(resultJson)
+}
+
+/**
+ * Export a genome as VCF, 23andMe TSV or PLINK (.bed/.bim/.fam, `outputPath`
+ * is the prefix). VCF needs a local reference allele database.
+ */
+// ts-prune-ignore-next
+export async function exportGenome(
+	dbPath: string,
+	format: ExportFormat,
+	outputPath: string,
+	options: { genomeId?: number; key?: string; referenceDbPath?: string } = {}
+): Promise<ExportSummary> {
+	const resultJson = await ExpoBiovaultModule.exportGenome(
+		dbPath,
+		options.key ?? null,
+		options.genomeId ?? 0,
+		format,
+		outputPath,
+		options.referenceDbPath ?? null
+	)
+	return JSON.parse(resultJson)
+}
+
+/**
+ * Export a stored analysis run as a GA4GH Phenopacket v2 document or an HL7
+ * FHIR Genomics Reporting bundle, ready to share with a clinician.
+ */
+// ts-prune-ignore-next
+export async function exportClinicalReport(
+	userDbPath: string,
+	runId: number,
+	format: ClinicalFormat,
+	key?: string
+): Promise<object> {
+	const resultJson = await ExpoBiovaultModule.exportClinicalReport(
+		userDbPath,
+		key ?? null,
+		runId,
+		format
+	)
+	return JSON.parse(resultJson)
+}
+
+/**
+ * Write an offline HTML or PDF findings report for a genome from its stored
+ * ClinVar analysis (the most recent one unless `runId` is given).
+ */
+// ts-prune-ignore-next
+export async function generateReport(
+	userDbPath: string,
+	format: ReportFormat,
+	outputPath: string,
+	options: { genomeId?: number; runId?: number; key?: string } = {}
+): Promise<boolean> {
+	const result = await ExpoBiovaultModule.generateReport(
+		userDbPath,
+		options.key ?? null,
+		options.genomeId ?? 0,
+		options.runId ?? 0,
+		format,
+		outputPath
+	)
+	return result === 0
+}
+
+/** Listen for progress of `importGenomeFile` and `analyzeClinVarGenome` */
+// ts-prune-ignore-next
+export function addProgressListener(listener: (event: ProgressEvent) => void): EventSubscription {
+	return ExpoBiovaultModule.addListener('onProgress', listener)
+}
+
+/**
+ * Cancel the running import or analysis. It rejects with a `CANCELLED`
+ * error and a cancelled import leaves the database unchanged.
+ */
+// ts-prune-ignore-next
+export function cancelOperations(): void {
+	ExpoBiovaultModule.cancelOperations()
+}
+
 // ts-prune-ignore-next
 export function rust_add(a: number, b: number): number {
 	return ExpoBiovaultModule.rust_add(a, b)
@@ -25,4 +264,21 @@ export function rust_add(a: number, b: number): number {
 
 // Export types
 // ts-prune-ignore-next
-export type { AnalysisResult, ClinVarVariant, GeneGroup } from './src/ExpoBiovaultModule'
+export type {
+	AnalysisResult,
+	AnalysisRun,
+	BiovaultErrorCode,
+	ClinVarDiff,
+	ClinVarVariant,
+	ClinicalFormat,
+	ExportFormat,
+	ExportSummary,
+	GeneGroup,
+	GenomeRecord,
+	ImportOutcome,
+	ProgressEvent,
+	ProgressPhase,
+	ReportFormat,
+	SignificanceChange,
+	StoredAnalysis,
+} from './src/ExpoBiovaultModule'
@@ -1,4 +1,5 @@
 import ExpoModulesCore
+import os
 
 // Declare the external Rust functions
 @_silgen_name("rust_add")
@@ -10,38 +11,300 @@ func process_23andme_file(_ inputPath: UnsafePointer<CChar>, _ customName: Unsaf
 @_silgen_name("analyze_clinvar")
 func analyze_clinvar(_ userDbPath: UnsafePointer<CChar>, _ clinvarDbPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
 
+@_silgen_name("reanalyze_clinvar")
+func reanalyze_clinvar(_ userDbPath: UnsafePointer<CChar>, _ clinvarDbPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("analyze_and_store_clinvar")
+func analyze_and_store_clinvar(_ userDbPath: UnsafePointer<CChar>, _ clinvarDbPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("list_analysis_runs")
+func list_analysis_runs(_ userDbPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("load_analysis_run")
+func load_analysis_run(_ userDbPath: UnsafePointer<CChar>, _ runId: Int64) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("delete_analysis_run")
+func delete_analysis_run(_ userDbPath: UnsafePointer<CChar>, _ runId: Int64) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("migrate_genome_database")
+func migrate_genome_database(_ dbPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("process_23andme_file_encrypted")
+func process_23andme_file_encrypted(_ inputPath: UnsafePointer<CChar>, _ customName: UnsafePointer<CChar>, _ outputDir: UnsafePointer<CChar>, _ key: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("analyze_clinvar_encrypted")
+func analyze_clinvar_encrypted(_ userDbPath: UnsafePointer<CChar>, _ userDbKey: UnsafePointer<CChar>, _ clinvarDbPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("rekey_genome_database")
+func rekey_genome_database(_ dbPath: UnsafePointer<CChar>, _ oldKey: UnsafePointer<CChar>?, _ newKey: UnsafePointer<CChar>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("export_decrypted_genome_database")
+func export_decrypted_genome_database(_ dbPath: UnsafePointer<CChar>, _ key: UnsafePointer<CChar>, _ outputPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+/// Called with the context pointer, phase name, rows processed and estimated
+/// total (0 if unknown). Returning non-zero cancels the operation.
+typealias BiovaultProgressCallback = @convention(c) (UnsafeMutableRawPointer?, UnsafePointer<CChar>?, UInt64, UInt64) -> Int32
+
+@_silgen_name("import_genome_file")
+func import_genome_file(_ inputPath: UnsafePointer<CChar>, _ dbPath: UnsafePointer<CChar>, _ customName: UnsafePointer<CChar>, _ person: UnsafePointer<CChar>?, _ key: UnsafePointer<CChar>?, _ replace: Int32, _ progress: BiovaultProgressCallback?, _ context: UnsafeMutableRawPointer?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("list_genomes")
+func list_genomes(_ dbPath: UnsafePointer<CChar>, _ key: UnsafePointer<CChar>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("analyze_clinvar_genome")
+func analyze_clinvar_genome(_ userDbPath: UnsafePointer<CChar>, _ userDbKey: UnsafePointer<CChar>?, _ genomeId: Int64, _ clinvarDbPath: UnsafePointer<CChar>, _ progress: BiovaultProgressCallback?, _ context: UnsafeMutableRawPointer?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("export_genome")
+func export_genome(_ dbPath: UnsafePointer<CChar>, _ key: UnsafePointer<CChar>?, _ genomeId: Int64, _ format: UnsafePointer<CChar>, _ outputPath: UnsafePointer<CChar>, _ referenceDbPath: UnsafePointer<CChar>?) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("export_clinical_report")
+func export_clinical_report(_ userDbPath: UnsafePointer<CChar>, _ key: UnsafePointer<CChar>?, _ runId: Int64, _ format: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("generate_report")
+func generate_report(_ userDbPath: UnsafePointer<CChar>, _ key: UnsafePointer<CChar>?, _ genomeId: Int64, _ runId: Int64, _ format: UnsafePointer<CChar>, _ outputPath: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>?
+
+/// Called with the context pointer, level (1 = error ... 5 = trace), Rust
+/// module and message. May be called from any thread.
+typealias BiovaultLogCallback = @convention(c) (UnsafeMutableRawPointer?, Int32, UnsafePointer<CChar>?, UnsafePointer<CChar>?) -> Void
+
+@_silgen_name("set_log_callback")
+func set_log_callback(_ callback: BiovaultLogCallback?, _ context: UnsafeMutableRawPointer?, _ maxLevel: Int32)
+
 @_silgen_name("free_string")
 func free_string(_ ptr: UnsafeMutablePointer<CChar>)
 
+/// Unwrap the JSON result envelope returned by the Rust library and free it.
+/// Failures are thrown with the library's stable error code.
+func rustResult(_ ptr: UnsafeMutablePointer<CChar>?) throws -> Any {
+  guard let ptr = ptr else {
+    throw Exception(name: "BiovaultError", description: "Rust library returned no result", code: "INTERNAL_ERROR")
+  }
+  let json = String(cString: ptr)
+  free_string(ptr)
+
+  guard let envelope = try? JSONSerialization.jsonObject(with: Data(json.utf8)) as? [String: Any] else {
+    throw Exception(name: "BiovaultError", description: "Malformed result from Rust library", code: "INTERNAL_ERROR")
+  }
+  if envelope["ok"] as? Bool == true {
+    return envelope["data"] ?? NSNull()
+  }
+
+  let error = envelope["error"] as? [String: Any]
+  throw Exception(
+    name: "BiovaultError",
+    description: error?["message"] as? String ?? "Unknown error",
+    code: error?["code"] as? String ?? "INTERNAL_ERROR"
+  )
+}
+
+/// Result data re-encoded as a JSON string for the JS side
+func rustJSON(_ ptr: UnsafeMutablePointer<CChar>?) throws -> String {
+  let data = try JSONSerialization.data(withJSONObject: try rustResult(ptr), options: [.fragmentsAllowed])
+  return String(decoding: data, as: UTF8.self)
+}
+
+/// Result data that is a plain string, e.g. a database path
+func rustString(_ ptr: UnsafeMutablePointer<CChar>?) throws -> String {
+  return try rustResult(ptr) as? String ?? ""
+}
+
+/// Forwards Rust progress to JS as `onProgress` events. `context` is the
+/// unretained module, which outlives the synchronous Rust call.
+let progressCallback: BiovaultProgressCallback = { context, phase, processed, total in
+  guard let context = context, let phase = phase else { return 0 }
+  let module = Unmanaged<ExpoBiovaultModule>.fromOpaque(context).takeUnretainedValue()
+  module.sendEvent("onProgress", ["phase": String(cString: phase), "processed": processed, "total": total])
+  return module.isCancelRequested ? 1 : 0
+}
+
+let rustLog = OSLog(subsystem: "expo.modules.biovault", category: "rust")
+
+/// Forwards Rust log events to os_log. Rust never logs rsIDs or genotypes.
+let logCallback: BiovaultLogCallback = { _, level, _, message in
+  guard let message = message else { return }
+  let type: OSLogType
+  switch level {
+  case 1: type = .fault
+  case 2: type = .error
+  case 3: type = .info
+  default: type = .debug
+  }
+  os_log("%{public}s", log: rustLog, type: type, String(cString: message))
+}
+
 public class ExpoBiovaultModule: Module {
+  private let cancelLock = NSLock()
+  private var cancelRequested = false
+
+  var isCancelRequested: Bool {
+    cancelLock.lock()
+    defer { cancelLock.unlock() }
+    return cancelRequested
+  }
+
+  private func setCancelRequested(_ value: Bool) {
+    cancelLock.lock()
+    cancelRequested = value
+    cancelLock.unlock()
+  }
+
+  /// Context for `progressCallback`; also clears an earlier cancel request
+  private func progressContext() -> UnsafeMutableRawPointer {
+    setCancelRequested(false)
+    return Unmanaged.passUnretained(self).toOpaque()
+  }
+
   public func definition() -> ModuleDefinition {
     Name("ExpoBiovault")
 
+    Events("onProgress")
+
+    OnCreate {
+      // Info and above
+      set_log_callback(logCallback, nil, 3)
+    }
+
     AsyncFunction("processGenomeFile") { (inputPath: String, customName: String, outputDir: String) -> String in
       let inputCString = inputPath.cString(using: .utf8)!
       let nameCString = customName.cString(using: .utf8)!
       let outputCString = outputDir.cString(using: .utf8)!
       
-      guard let resultPtr = process_23andme_file(inputCString, nameCString, outputCString) else {
-        throw Exception(name: "ProcessingError", description: "Failed to process genome file")
-      }
-      
-      let result = String(cString: resultPtr)
-      free_string(resultPtr)
-      return result
+      return try rustString(process_23andme_file(inputCString, nameCString, outputCString))
     }
 
     AsyncFunction("analyzeClinVarMatches") { (userDbPath: String, clinvarDbPath: String) -> String in
       let userDbCString = userDbPath.cString(using: .utf8)!
       let clinvarDbCString = clinvarDbPath.cString(using: .utf8)!
       
-      guard let resultPtr = analyze_clinvar(userDbCString, clinvarDbCString) else {
-        throw Exception(name: "AnalysisError", description: "Failed to analyze ClinVar matches")
-      }
-      
-      let result = String(cString: resultPtr)
-      free_string(resultPtr)
-      return result
+      return try rustJSON(analyze_clinvar(userDbCString, clinvarDbCString))
+    }
+
+    AsyncFunction("reanalyzeClinVar") { (userDbPath: String, clinvarDbPath: String) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      let clinvarDbCString = clinvarDbPath.cString(using: .utf8)!
+
+      return try rustJSON(reanalyze_clinvar(userDbCString, clinvarDbCString))
+    }
+
+    AsyncFunction("analyzeAndStoreClinVar") { (userDbPath: String, clinvarDbPath: String) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      let clinvarDbCString = clinvarDbPath.cString(using: .utf8)!
+
+      return try rustJSON(analyze_and_store_clinvar(userDbCString, clinvarDbCString))
+    }
+
+    AsyncFunction("listAnalysisRuns") { (userDbPath: String) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+
+      return try rustJSON(list_analysis_runs(userDbCString))
+    }
+
+    AsyncFunction("loadAnalysisRun") { (userDbPath: String, runId: Int64) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+
+      return try rustJSON(load_analysis_run(userDbCString, runId))
+    }
+
+    AsyncFunction("deleteAnalysisRun") { (userDbPath: String, runId: Int64) -> Int32 in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      return try rustResult(delete_analysis_run(userDbCString, runId)) as? Bool == true ? 1 : 0
+    }
+
+    AsyncFunction("migrateGenomeDatabase") { (dbPath: String) -> Int32 in
+      let dbCString = dbPath.cString(using: .utf8)!
+      return Int32(try rustResult(migrate_genome_database(dbCString)) as? Int ?? 0)
+    }
+
+    AsyncFunction("processGenomeFileEncrypted") { (inputPath: String, customName: String, outputDir: String, key: String) -> String in
+      let inputCString = inputPath.cString(using: .utf8)!
+      let nameCString = customName.cString(using: .utf8)!
+      let outputCString = outputDir.cString(using: .utf8)!
+      let keyCString = key.cString(using: .utf8)!
+
+      return try rustString(process_23andme_file_encrypted(inputCString, nameCString, outputCString, keyCString))
+    }
+
+    AsyncFunction("analyzeClinVarMatchesEncrypted") { (userDbPath: String, userDbKey: String, clinvarDbPath: String) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      let keyCString = userDbKey.cString(using: .utf8)!
+      let clinvarDbCString = clinvarDbPath.cString(using: .utf8)!
+
+      return try rustJSON(analyze_clinvar_encrypted(userDbCString, keyCString, clinvarDbCString))
+    }
+
+    AsyncFunction("rekeyGenomeDatabase") { (dbPath: String, oldKey: String?, newKey: String?) -> Int32 in
+      let dbCString = dbPath.cString(using: .utf8)!
+      let oldKeyCString = oldKey?.cString(using: .utf8)
+      let newKeyCString = newKey?.cString(using: .utf8)
+
+      _ = try rustResult(rekey_genome_database(dbCString, oldKeyCString, newKeyCString))
+      return 0
+    }
+
+    AsyncFunction("exportDecryptedGenomeDatabase") { (dbPath: String, key: String, outputPath: String) -> Int32 in
+      let dbCString = dbPath.cString(using: .utf8)!
+      let keyCString = key.cString(using: .utf8)!
+      let outputCString = outputPath.cString(using: .utf8)!
+
+      _ = try rustResult(export_decrypted_genome_database(dbCString, keyCString, outputCString))
+      return 0
+    }
+
+    AsyncFunction("importGenomeFile") { (inputPath: String, dbPath: String, customName: String, person: String?, key: String?, replace: Bool) -> String in
+      let inputCString = inputPath.cString(using: .utf8)!
+      let dbCString = dbPath.cString(using: .utf8)!
+      let nameCString = customName.cString(using: .utf8)!
+      let personCString = person?.cString(using: .utf8)
+      let keyCString = key?.cString(using: .utf8)
+
+      return try rustJSON(import_genome_file(inputCString, dbCString, nameCString, personCString, keyCString, replace ? 1 : 0, progressCallback, self.progressContext()))
+    }
+
+    AsyncFunction("listGenomes") { (dbPath: String, key: String?) -> String in
+      let dbCString = dbPath.cString(using: .utf8)!
+      let keyCString = key?.cString(using: .utf8)
+
+      return try rustJSON(list_genomes(dbCString, keyCString))
+    }
+
+    AsyncFunction("analyzeClinVarGenome") { (userDbPath: String, userDbKey: String?, genomeId: Int64, clinvarDbPath: String) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      let keyCString = userDbKey?.cString(using: .utf8)
+      let clinvarDbCString = clinvarDbPath.cString(using: .utf8)!
+
+      return try rustJSON(analyze_clinvar_genome(userDbCString, keyCString, genomeId, clinvarDbCString, progressCallback, self.progressContext()))
+    }
+
+    AsyncFunction("exportGenome") { (dbPath: String, key: String?, genomeId: Int64, format: String, outputPath: String, referenceDbPath: String?) -> String in
+      let dbCString = dbPath.cString(using: .utf8)!
+      let keyCString = key?.cString(using: .utf8)
+      let formatCString = format.cString(using: .utf8)!
+      let outputCString = outputPath.cString(using: .utf8)!
+      let referenceCString = referenceDbPath?.cString(using: .utf8)
+
+      return try rustJSON(export_genome(dbCString, keyCString, genomeId, formatCString, outputCString, referenceCString))
+    }
+
+    AsyncFunction("exportClinicalReport") { (userDbPath: String, key: String?, runId: Int64, format: String) -> String in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      let keyCString = key?.cString(using: .utf8)
+      let formatCString = format.cString(using: .utf8)!
+
+      return try rustJSON(export_clinical_report(userDbCString, keyCString, runId, formatCString))
+    }
+
+    AsyncFunction("generateReport") { (userDbPath: String, key: String?, genomeId: Int64, runId: Int64, format: String, outputPath: String) -> Int32 in
+      let userDbCString = userDbPath.cString(using: .utf8)!
+      let keyCString = key?.cString(using: .utf8)
+      let formatCString = format.cString(using: .utf8)!
+      let outputCString = outputPath.cString(using: .utf8)!
+
+      _ = try rustResult(generate_report(userDbCString, keyCString, genomeId, runId, formatCString, outputCString))
+      return 0
+    }
+
+    Function("cancelOperations") {
+      self.setCancelRequested(true)
     }
 
     Function("rust_add") { (a: Int32, b: Int32) -> Int32 in
@@ -34,9 +34,187 @@ export interface GeneGroup {
 	conditions: string[]
 }
 
-declare class ExpoBiovaultModule extends NativeModule {
+export interface SignificanceChange {
+	rsid: string
+	gene: string
+	old_clnsig: string
+	new_clnsig: string
+	old_label: string
+	new_label: string
+	direction: 'upgrade' | 'downgrade'
+	user_genotype?: string
+}
+
+export interface ClinVarDiff {
+	old_release: string
+	new_release: string
+	new_matches: ClinVarVariant[]
+	dropped_matches: ClinVarVariant[]
+	upgrades: SignificanceChange[]
+	downgrades: SignificanceChange[]
+	unchanged_count: number
+}
+
+export interface AnalysisRun {
+	id: number
+	analysis_type: string
+	parameters: Record<string, unknown>
+	clinvar_release: string
+	clinvar_checksum: string
+	library_version: string
+	created_at: string
+	rsids_searched: number
+	matches_found: number
+	genome_id?: number
+}
+
+export interface StoredAnalysis {
+	run: AnalysisRun
+	result: AnalysisResult
+}
+
+export interface GenomeRecord {
+	id: number
+	name: string
+	person?: string
+	source_format: string
+	total_variants: number
+	rsid_count: number
+	assembly?: string
+	upload_date: string
+	content_hash?: string
+}
+
+export interface ImportOutcome {
+	db_path: string
+	genome_id: number
+	status: 'imported' | 'skipped' | 'replaced'
+	content_hash: string
+}
+
+export type ExportFormat = 'vcf' | '23andme' | 'plink'
+
+export interface ExportSummary {
+	format: ExportFormat
+	genome_id: number
+	output_files: string[]
+	variants_written: number
+	variants_skipped: number
+}
+
+export type ClinicalFormat = 'phenopacket' | 'fhir'
+
+export type ReportFormat = 'html' | 'pdf'
+
+/**
+ * Stable codes carried by errors thrown from the native module (`error.code`).
+ * Match on these rather than on the message.
+ */
+export type BiovaultErrorCode =
+	| 'INVALID_ARGUMENT'
+	| 'FILE_NOT_FOUND'
+	| 'PERMISSION_DENIED'
+	| 'DISK_FULL'
+	| 'IO_ERROR'
+	| 'CORRUPT_ARCHIVE'
+	| 'PARSE_ERROR'
+	| 'DATABASE_ERROR'
+	| 'DATABASE_CORRUPT'
+	| 'DATABASE_BUSY'
+	| 'NOT_A_GENOME_DATABASE'
+	| 'WRONG_KEY'
+	| 'NOT_FOUND'
+	| 'ALREADY_EXISTS'
+	| 'GENOME_AMBIGUOUS'
+	| 'NO_GENOMES'
+	| 'UNSUPPORTED_SCHEMA'
+	| 'SERIALIZATION_ERROR'
+	| 'CONSENT_REQUIRED'
+	| 'DELETION_INCOMPLETE'
+	| 'INVALID_BUNDLE'
+	| 'PRIVACY_BUDGET_EXHAUSTED'
+	| 'CANCELLED'
+	| 'INTERNAL_ERROR'
+
+export type ProgressPhase =
+	| 'parsing'
+	| 'inserting'
+	| 'indexing'
+	| 'loading_genotypes'
+	| 'clinvar_lookup'
+	| 'grouping'
+
+/** Progress of a running import or analysis; `total` is 0 when unknown */
+export type ProgressEvent = {
+	phase: ProgressPhase
+	processed: number
+	total: number
+}
+
+type ExpoBiovaultModuleEvents = {
+	onProgress(event: ProgressEvent): void
+}
+
+declare class ExpoBiovaultModule extends NativeModule<ExpoBiovaultModuleEvents> {
 	processGenomeFile(inputPath: string, customName: string, outputDir: string): Promise<string>
 	analyzeClinVarMatches(userDbPath: string, clinvarDbPath: string): Promise<string>
+	reanalyzeClinVar(userDbPath: string, clinvarDbPath: string): Promise<string>
+	analyzeAndStoreClinVar(userDbPath: string, clinvarDbPath: string): Promise<string>
+	listAnalysisRuns(userDbPath: string): Promise<string>
+	loadAnalysisRun(userDbPath: string, runId: number): Promise<string>
+	deleteAnalysisRun(userDbPath: string, runId: number): Promise<number>
+	migrateGenomeDatabase(dbPath: string): Promise<number>
+	processGenomeFileEncrypted(
+		inputPath: string,
+		customName: string,
+		outputDir: string,
+		key: string
+	): Promise<string>
+	analyzeClinVarMatchesEncrypted(
+		userDbPath: string,
+		userDbKey: string,
+		clinvarDbPath: string
+	): Promise<string>
+	rekeyGenomeDatabase(dbPath: string, oldKey: string | null, newKey: string | null): Promise<number>
+	exportDecryptedGenomeDatabase(dbPath: string, key: string, outputPath: string): Promise<number>
+	importGenomeFile(
+		inputPath: string,
+		dbPath: string,
+		customName: string,
+		person: string | null,
+		key: string | null,
+		replace: boolean
+	): Promise<string>
+	listGenomes(dbPath: string, key: string | null): Promise<string>
+	analyzeClinVarGenome(
+		userDbPath: string,
+		userDbKey: string | null,
+		genomeId: number,
+		clinvarDbPath: string
+	): Promise<string>
+	exportGenome(
+		dbPath: string,
+		key: string | null,
+		genomeId: number,
+		format: ExportFormat,
+		outputPath: string,
+		referenceDbPath: string | null
+	): Promise<string>
+	exportClinicalReport(
+		userDbPath: string,
+		key: string | null,
+		runId: number,
+		format: ClinicalFormat
+	): Promise<string>
+	generateReport(
+		userDbPath: string,
+		key: string | null,
+		genomeId: number,
+		runId: number,
+		format: ReportFormat,
+		outputPath: string
+	): Promise<number>
+	cancelOperations(): void
 	rust_add(a: number, b: number): number
 }
 
@@ -15,6 +15,31 @@ function build(target: string) {
 	})
 }
 
+// Kotlin bindings for the UniFFI API, generated from the built library
+function generateKotlinBindings(target: string) {
+	spawnSync(
+		'cargo',
+		[
+			'run',
+			'--features',
+			'uniffi-cli',
+			'--bin',
+			'uniffi-bindgen',
+			'--',
+			'generate',
+			'--library',
+			path.join('target', target, 'release', 'libbiovault_rust_lib.so'),
+			'--language',
+			'kotlin',
+			'--out-dir',
+			path.join('..', 'modules', 'expo-biovault', 'android', 'src', 'main', 'java'),
+		],
+		{
+			stdio: 'inherit',
+		}
+	)
+}
+
 function main() {
 	console.log('Building rust library for android')
 
@@ -22,6 +47,9 @@ function main() {
 
 	Object.keys(TARGET_TO_DESTINATION).forEach(build)
 
+	console.log('Generating bindings for android')
+	generateKotlinBindings('aarch64-linux-android')
+
 	process.chdir('..')
 
 	Object.entries(TARGET_TO_DESTINATION).forEach(([target, architecture]) => {
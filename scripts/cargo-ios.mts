@@ -15,6 +15,31 @@ function cargoBuild(target: string) {
 	})
 }
 
+// Swift bindings for the UniFFI API, generated from the built library
+function generateSwiftBindings(target: string) {
+	spawnSync(
+		'cargo',
+		[
+			'run',
+			'--features',
+			'uniffi-cli',
+			'--bin',
+			'uniffi-bindgen',
+			'--',
+			'generate',
+			'--library',
+			path.join('target', target, 'release', 'libbiovault_rust_lib.a'),
+			'--language',
+			'swift',
+			'--out-dir',
+			path.join('..', 'modules', 'expo-biovault', 'ios', 'uniffi'),
+		],
+		{
+			stdio: 'inherit',
+		}
+	)
+}
+
 function getTarget() {
 	const args = process.argv.slice(2)
 	const target = (args[0] ?? '').replace('--target=', '')
@@ -53,6 +78,7 @@ function main() {
 				stdio: 'inherit',
 			}
 		)
+		generateSwiftBindings(TARGETS.ios)
 
 		process.chdir('..')
 
@@ -106,6 +132,7 @@ function main() {
 				stdio: 'inherit',
 			}
 		)
+		generateSwiftBindings(target)
 
 		process.chdir('..')
 